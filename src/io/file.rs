@@ -1,9 +1,8 @@
-use std::fs::{File, OpenOptions};
-use std::io::{Result, Read, Write};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Error, ErrorKind, Result, Read, Write};
 
 pub fn read_file_to_bytes(path: &str) -> Result<Vec<u8>> {
-    let file = File::open(path).unwrap_or_else(|_| panic!("Failed to read spv file {:?}", path));
-    Ok(file.bytes().filter_map(|b| b.ok()).collect())
+    fs::read(path)
 }
 
 pub fn read_file_to_str(path: &str) -> Result<String> {
@@ -19,7 +18,111 @@ pub fn write_file(path: &str, contents: &str) -> Result<bool> {
     Ok(true)
 }
 
+pub fn write_bytes(path: &str, contents: &[u8]) -> Result<()> {
+    fs::write(path, contents)
+}
+
 pub fn append_file(path: &str, contents: &str) -> Result<()>{
     let mut file = OpenOptions::new().append(true).open(path).expect("Failed to open file");
     file.write_all(contents.as_bytes())
-}
\ No newline at end of file
+}
+
+pub fn read_png(path: &str) -> Result<(u32, u32, Vec<u8>)> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    // Textures are uploaded as 8-bit RGBA, so 16-bit channels are narrowed while decoding.
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (info, mut reader) = decoder
+        .read_info()
+        .map_err(Error::other)?;
+    let mut pixels = vec![0_u8; info.buffer_size()];
+    reader
+        .next_frame(&mut pixels)
+        .map_err(Error::other)?;
+
+    let rgba = match info.color_type {
+        png::ColorType::RGBA => pixels,
+        png::ColorType::RGB => pixels
+            .chunks_exact(3)
+            .flat_map(|x| vec![x[0], x[1], x[2], 255])
+            .collect(),
+        color_type => {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Unsupported PNG color type {:?}", color_type),
+            ))
+        }
+    };
+    Ok((info.width, info.height, rgba))
+}
+
+pub fn write_png(path: &str, width: u32, height: u32, rgba: &[u8]) -> Result<()> {
+    let mut png_bytes = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png_bytes, width, height);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder
+            .write_header()
+            .map_err(Error::other)?;
+        writer
+            .write_image_data(rgba)
+            .map_err(Error::other)?;
+    }
+    let mut file = File::create(path)?;
+    file.write_all(&png_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("potato-io-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
+
+    #[test]
+    fn read_missing_file_returns_err() {
+        let path = temp_path("missing.bin");
+        assert!(read_file_to_bytes(path.to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let path = temp_path("round-trip.bin");
+        let path = path.to_str().unwrap();
+        let contents: Vec<u8> = (0..=255).collect();
+        write_bytes(path, &contents).unwrap();
+        assert_eq!(read_file_to_bytes(path).unwrap(), contents);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let path = temp_path("round-trip.png");
+        let path = path.to_str().unwrap();
+        let rgba = [255, 0, 0, 255, 0, 255, 0, 128];
+        write_png(path, 2, 1, &rgba).unwrap();
+        assert_eq!(read_png(path).unwrap(), (2, 1, rgba.to_vec()));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn sixteen_bit_png_is_read_as_eight_bit() {
+        let path = temp_path("sixteen-bit.png");
+        let path = path.to_str().unwrap();
+        let mut png_bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut png_bytes, 1, 1);
+            encoder.set_color(png::ColorType::RGBA);
+            encoder.set_depth(png::BitDepth::Sixteen);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[0xFF, 0x00, 0x80, 0x00, 0x00, 0x00, 0xFF, 0xFF]).unwrap();
+        }
+        write_bytes(path, &png_bytes).unwrap();
+        assert_eq!(read_png(path).unwrap(), (1, 1, vec![0xFF, 0x80, 0x00, 0xFF]));
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,10 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) out vec2 fragUv;
+
+void main() {
+    int index = int(gl_VertexIndex);
+    fragUv = vec2(float((index << 1) & 2), float(index & 2));
+    gl_Position = vec4(fragUv * 2.0 - 1.0, 0.0, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,22 @@
+#version 450
+
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+    vec4 lightDirection;
+} ubo;
+
+layout(location = 0) in vec3 fragColor;
+layout(location = 1) in vec3 fragNormal;
+
+layout(location = 0) out vec4 outColor;
+
+const float AMBIENT = 0.1;
+
+void main(){
+    float diffuse = max(dot(normalize(fragNormal), -normalize(ubo.lightDirection.xyz)), 0.0);
+    outColor = vec4(fragColor * (AMBIENT + (1.0 - AMBIENT) * diffuse), 1.0);
+}
\ No newline at end of file
@@ -5,18 +5,27 @@ layout(set = 0, binding = 0) uniform UniformBufferObject {
     mat4 model;
     mat4 view;
     mat4 proj;
+    vec4 lightDirection;
 } ubo;
 
+layout(push_constant) uniform PushConstants {
+    mat4 transform;
+} push;
+
 layout (location = 0) in vec2 inPosition;
 layout (location = 1) in vec3 inColor;
+layout (location = 2) in vec3 inNormal;
 
 out gl_PerVertex {
     vec4 gl_Position;
 };
 
 layout(location = 0) out vec3 fragColor;
+layout(location = 1) out vec3 fragNormal;
 
 void main() {
-    gl_Position = ubo.proj * ubo.view  * ubo.model * vec4(inPosition, 0.0, 1.0);
+    mat4 model = ubo.model * push.transform;
+    gl_Position = ubo.proj * ubo.view * model * vec4(inPosition, 0.0, 1.0);
     fragColor = inColor;
+    fragNormal = mat3(model) * inNormal;
 }
\ No newline at end of file
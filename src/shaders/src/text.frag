@@ -0,0 +1,13 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform texture2D fontAtlas;
+layout(set = 0, binding = 1) uniform sampler fontSampler;
+
+layout(location = 0) in vec2 fragUv;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = texture(sampler2D(fontAtlas, fontSampler), fragUv);
+}
\ No newline at end of file
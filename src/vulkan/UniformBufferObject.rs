@@ -1,23 +1,63 @@
-use super::buffer::create_buffer;
-use super::swapchain::PotatoSwapChain;
+use super::buffer::PotatoBuffer;
+use super::constants::LIGHT_DIRECTION;
+use super::descriptor_allocator::DescriptorAllocator;
 use ash::version::DeviceV1_0;
 use ash::vk::{
-    Buffer, BufferUsageFlags, DescriptorBufferInfo, DescriptorPool, DescriptorPoolCreateFlags,
-    DescriptorPoolCreateInfo, DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo,
-    DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateFlags,
-    DescriptorSetLayoutCreateInfo, DescriptorType, DeviceMemory, MemoryMapFlags,
-    MemoryPropertyFlags, PhysicalDeviceMemoryProperties, ShaderStageFlags, StructureType,
-    WriteDescriptorSet,
+    BufferUsageFlags, DescriptorBufferInfo, DescriptorSet, DescriptorSetLayout,
+    DescriptorSetLayoutBinding, DescriptorSetLayoutCreateFlags,
+    DescriptorSetLayoutCreateInfo, DescriptorType, DeviceSize, Extent2D, MemoryPropertyFlags,
+    PhysicalDeviceMemoryProperties, ShaderStageFlags, StructureType, WriteDescriptorSet,
 };
 use ash::Device;
-use cgmath::{perspective, Deg, Matrix4, Point3, Vector3};
+use cgmath::{perspective, Deg, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
+use std::rc::Rc;
 
 #[repr(C)]
 #[derive(Clone, Debug, Copy)]
 pub struct UniformBufferObject {
-    model: Matrix4<f32>,
-    view: Matrix4<f32>,
-    proj: Matrix4<f32>,
+    pub model: Matrix4<f32>,
+    pub view: Matrix4<f32>,
+    pub proj: Matrix4<f32>,
+    pub light_direction: Vector4<f32>,
+}
+
+impl UniformBufferObject {
+    pub fn demo_rotation(
+        proj: Matrix4<f32>,
+        delta_time: f32,
+        is_animated: bool,
+    ) -> UniformBufferObject {
+        UniformBufferObject {
+            model: demo_model(delta_time, is_animated),
+            view: Matrix4::look_at(
+                Point3::new(2.0, 2.0, 2.0),
+                Point3::new(0.0, 0.0, 0.0),
+                Vector3::new(0.0, 0.0, 1.0),
+            ),
+            proj,
+            light_direction: LIGHT_DIRECTION.into(),
+        }
+    }
+}
+
+pub fn demo_model(delta_time: f32, is_animated: bool) -> Matrix4<f32> {
+    if is_animated {
+        Matrix4::from_angle_z(Deg(90.0 * delta_time))
+    } else {
+        Matrix4::identity()
+    }
+}
+
+pub fn aspect_ratio(extent: Extent2D) -> f32 {
+    if extent.height == 0 {
+        1.0
+    } else {
+        extent.width as f32 / extent.height as f32
+    }
+}
+
+pub fn create_projection(extent: Extent2D) -> Matrix4<f32> {
+    perspective(Deg(45.0), aspect_ratio(extent), 0.1, 10.0)
 }
 
 pub fn create_descriptor_set_layout(device: &Device) -> DescriptorSetLayout {
@@ -25,7 +65,7 @@ pub fn create_descriptor_set_layout(device: &Device) -> DescriptorSetLayout {
         binding: 0,
         descriptor_type: DescriptorType::UNIFORM_BUFFER,
         descriptor_count: 1,
-        stage_flags: ShaderStageFlags::VERTEX,
+        stage_flags: ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT,
         p_immutable_samplers: std::ptr::null(),
     }];
 
@@ -45,120 +85,62 @@ pub fn create_descriptor_set_layout(device: &Device) -> DescriptorSetLayout {
 }
 
 pub fn create_uniform_buffers(
-    device: &Device,
+    device: &Rc<Device>,
     device_memory_properties: &PhysicalDeviceMemoryProperties,
-    swapchain_image_count: usize,
-) -> (Vec<Buffer>, Vec<DeviceMemory>) {
+    frames_in_flight: usize,
+) -> Vec<PotatoBuffer> {
     let buffer_size = std::mem::size_of::<UniformBufferObject>();
-    let mut uniform_buffers = vec![];
-    let mut uniform_buffers_memory = vec![];
-
-    for _ in 0..swapchain_image_count {
-        let (uniform_buffer, uniform_buffer_memory) = create_buffer(
-            device,
-            buffer_size as u64,
-            BufferUsageFlags::UNIFORM_BUFFER,
-            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
-            device_memory_properties,
-        );
-        uniform_buffers.push(uniform_buffer);
-        uniform_buffers_memory.push(uniform_buffer_memory);
-    }
 
-    (uniform_buffers, uniform_buffers_memory)
+    (0..frames_in_flight)
+        .map(|_| {
+            PotatoBuffer::new(
+                device,
+                buffer_size as u64,
+                BufferUsageFlags::UNIFORM_BUFFER,
+                MemoryPropertyFlags::HOST_VISIBLE,
+                MemoryPropertyFlags::HOST_COHERENT | MemoryPropertyFlags::DEVICE_LOCAL,
+                device_memory_properties,
+            )
+        })
+        .collect()
 }
 
 pub fn update_uniform_buffer(
-    swapchain: &PotatoSwapChain,
-    device: &Device,
-    current_image: usize,
-    delta_time: f32,
-    uniform_buffers_memory: &Vec<DeviceMemory>,
+    ubo: &UniformBufferObject,
+    current_frame: usize,
+    uniform_buffers: &[PotatoBuffer],
+    non_coherent_atom_size: DeviceSize,
 ) {
-    let ubos = [UniformBufferObject {
-        model: Matrix4::from_angle_z(Deg(90.0 * delta_time)),
-        view: Matrix4::look_at(
-            Point3::new(2.0, 2.0, 2.0),
-            Point3::new(0.0, 0.0, 0.0),
-            Vector3::new(0.0, 0.0, 1.0),
-        ),
-        proj: perspective(
-            Deg(45.0),
-            swapchain.swapchain_extent.width as f32 / swapchain.swapchain_extent.height as f32,
-            0.1,
-            10.0,
-        ),
-    }];
-
-    let buffer_size = (std::mem::size_of::<UniformBufferObject>() * ubos.len()) as u64;
+    let ubos = [*ubo];
+    let uniform_buffer = &uniform_buffers[current_frame];
 
     unsafe {
-        let data_ptr = device
-            .map_memory(
-                uniform_buffers_memory[current_image],
-                0,
-                buffer_size,
-                MemoryMapFlags::empty(),
-            )
-            .expect("Failed to map memory") as *mut UniformBufferObject;
+        let data_ptr = uniform_buffer.map::<UniformBufferObject>();
 
         data_ptr.copy_from_nonoverlapping(ubos.as_ptr(), ubos.len());
 
-        device.unmap_memory(uniform_buffers_memory[current_image]);
-    }
-}
-
-pub fn create_descriptor_pool(device: &Device, swapchain_images_size: usize) -> DescriptorPool {
-    let pool_sizes = [DescriptorPoolSize {
-        ty: DescriptorType::UNIFORM_BUFFER,
-        descriptor_count: swapchain_images_size as u32,
-    }];
-
-    let descriptor_pool_create_info = DescriptorPoolCreateInfo {
-        s_type: StructureType::DESCRIPTOR_POOL_CREATE_INFO,
-        p_next: std::ptr::null(),
-        flags: DescriptorPoolCreateFlags::empty(),
-        max_sets: swapchain_images_size as u32,
-        pool_size_count: pool_sizes.len() as u32,
-        p_pool_sizes: pool_sizes.as_ptr(),
-    };
-
-    unsafe {
-        device
-            .create_descriptor_pool(&descriptor_pool_create_info, None)
-            .expect("Failed to create descriptor pool")
+        uniform_buffer.flush(
+            0,
+            std::mem::size_of_val(&ubos) as DeviceSize,
+            non_coherent_atom_size,
+        );
+        uniform_buffer.unmap();
     }
 }
 
 pub fn create_descriptor_sets(
     device: &Device,
-    descriptor_pool: DescriptorPool,
+    descriptor_allocator: &mut DescriptorAllocator,
     descriptor_set_layout: DescriptorSetLayout,
-    uniform_buffers: &Vec<Buffer>,
-    swapchain_images_size: usize,
+    uniform_buffers: &[PotatoBuffer],
 ) -> Vec<DescriptorSet> {
-    let mut layouts: Vec<DescriptorSetLayout> = vec![];
-    for _ in 0..swapchain_images_size {
-        layouts.push(descriptor_set_layout);
-    }
-
-    let descriptor_set_allocate_info = DescriptorSetAllocateInfo {
-        s_type: StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
-        p_next: std::ptr::null(),
-        descriptor_pool,
-        descriptor_set_count: swapchain_images_size as u32,
-        p_set_layouts: layouts.as_ptr(),
-    };
-
-    let descriptor_sets = unsafe {
-        device
-            .allocate_descriptor_sets(&descriptor_set_allocate_info)
-            .expect("Failed to allocate descriptor sets")
-    };
+    let descriptor_sets = descriptor_allocator
+        .allocate_many(device, descriptor_set_layout, uniform_buffers.len())
+        .expect("Failed to allocate descriptor sets");
 
     descriptor_sets.iter().enumerate().for_each(|(i, x)| {
         let descriptor_buffer_info = [DescriptorBufferInfo {
-            buffer: uniform_buffers[i],
+            buffer: uniform_buffers[i].buffer,
             offset: 0,
             range: std::mem::size_of::<UniformBufferObject>() as u64,
         }];
@@ -183,3 +165,95 @@ pub fn create_descriptor_sets(
 
     descriptor_sets
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::constants::MAX_FRAMES_IN_FLIGHT;
+    use crate::vulkan::fake_device;
+    use ash::vk::Handle;
+
+    #[test]
+    fn demo_model_is_identity_when_not_animated() {
+        assert_eq!(demo_model(0.0, false), Matrix4::identity());
+        assert_eq!(demo_model(1.5, false), Matrix4::identity());
+    }
+
+    #[test]
+    fn demo_rotation_keeps_identity_model_when_not_animated() {
+        let proj = create_projection(Extent2D {
+            width: 800,
+            height: 600,
+        });
+        let ubo = UniformBufferObject::demo_rotation(proj, 2.0, false);
+        assert_eq!(ubo.model, Matrix4::identity());
+        assert_eq!(ubo.proj, proj);
+    }
+
+    #[test]
+    fn demo_model_rotates_when_animated() {
+        assert_ne!(demo_model(1.0, true), Matrix4::identity());
+    }
+
+    #[test]
+    fn aspect_ratio_follows_the_extent() {
+        let wide = Extent2D {
+            width: 1920,
+            height: 1080,
+        };
+        let tall = Extent2D {
+            width: 600,
+            height: 800,
+        };
+        assert_eq!(aspect_ratio(wide), 1920.0 / 1080.0);
+        assert_eq!(aspect_ratio(tall), 600.0 / 800.0);
+        let proj = create_projection(tall);
+        assert!((proj.y.y / proj.x.x - aspect_ratio(tall)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn zero_height_extent_keeps_a_square_aspect_ratio() {
+        let minimized = Extent2D {
+            width: 800,
+            height: 0,
+        };
+        assert_eq!(aspect_ratio(minimized), 1.0);
+        assert!(create_projection(minimized).x.x.is_finite());
+    }
+
+    #[test]
+    fn each_frame_in_flight_gets_a_uniform_buffer_and_descriptor_set() {
+        let device = Rc::new(fake_device::device());
+        let mut memory_properties = PhysicalDeviceMemoryProperties {
+            memory_type_count: 1,
+            ..Default::default()
+        };
+        memory_properties.memory_types[0].property_flags =
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT;
+        let uniform_buffers =
+            create_uniform_buffers(&device, &memory_properties, MAX_FRAMES_IN_FLIGHT);
+        let mut descriptor_allocator =
+            DescriptorAllocator::new(1, &[(DescriptorType::UNIFORM_BUFFER, 1)]);
+        let descriptor_sets = create_descriptor_sets(
+            &device,
+            &mut descriptor_allocator,
+            DescriptorSetLayout::from_raw(1),
+            &uniform_buffers,
+        );
+
+        assert_eq!(uniform_buffers.len(), MAX_FRAMES_IN_FLIGHT);
+        assert_eq!(descriptor_sets.len(), MAX_FRAMES_IN_FLIGHT);
+        let written_buffers: Vec<_> = fake_device::take_calls()
+            .into_iter()
+            .filter(|(name, _)| *name == "vkUpdateDescriptorSets")
+            .map(|(_, buffer)| buffer)
+            .collect();
+        assert_eq!(
+            written_buffers,
+            uniform_buffers
+                .iter()
+                .map(|x| x.buffer.as_raw())
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use super::buffer::find_memory_type;
+use ash::version::{DeviceV1_0, InstanceV1_0};
+use ash::vk::{
+    ComponentMapping, ComponentSwizzle, DeviceMemory, Extent2D, Extent3D, Format,
+    FormatFeatureFlags, Image, ImageAspectFlags, ImageCreateFlags, ImageCreateInfo, ImageLayout,
+    ImageSubresourceRange, ImageTiling, ImageType, ImageUsageFlags, ImageView,
+    ImageViewCreateFlags, ImageViewCreateInfo, ImageViewType, MemoryAllocateInfo,
+    MemoryPropertyFlags, PhysicalDevice, PhysicalDeviceLimits, PhysicalDeviceMemoryProperties,
+    SampleCountFlags,
+    SharingMode, StructureType,
+};
+use ash::{Device, Instance};
+
+const SAMPLE_COUNTS_DESCENDING: [SampleCountFlags; 7] = [
+    SampleCountFlags::TYPE_64,
+    SampleCountFlags::TYPE_32,
+    SampleCountFlags::TYPE_16,
+    SampleCountFlags::TYPE_8,
+    SampleCountFlags::TYPE_4,
+    SampleCountFlags::TYPE_2,
+    SampleCountFlags::TYPE_1,
+];
+
+const DEPTH_FORMAT_CANDIDATES: [Format; 3] = [
+    Format::D32_SFLOAT,
+    Format::D32_SFLOAT_S8_UINT,
+    Format::D24_UNORM_S8_UINT,
+];
+const DEPTH_STENCIL_FORMAT_CANDIDATES: [Format; 2] =
+    [Format::D24_UNORM_S8_UINT, Format::D32_SFLOAT_S8_UINT];
+
+pub struct AttachmentImage {
+    pub image: Image,
+    pub memory: DeviceMemory,
+    pub view: ImageView,
+    pub format: Format,
+    pub samples: SampleCountFlags,
+}
+
+impl AttachmentImage {
+    pub fn new(
+        device: &Device,
+        device_memory_properties: &PhysicalDeviceMemoryProperties,
+        extent: Extent2D,
+        format: Format,
+        samples: SampleCountFlags,
+        usage: ImageUsageFlags,
+        aspect_mask: ImageAspectFlags,
+    ) -> AttachmentImage {
+        let image_create_info = ImageCreateInfo {
+            s_type: StructureType::IMAGE_CREATE_INFO,
+            p_next: std::ptr::null(),
+            flags: ImageCreateFlags::empty(),
+            image_type: ImageType::TYPE_2D,
+            format,
+            extent: Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            },
+            mip_levels: 1,
+            array_layers: 1,
+            samples,
+            tiling: ImageTiling::OPTIMAL,
+            usage,
+            sharing_mode: SharingMode::EXCLUSIVE,
+            queue_family_index_count: 0,
+            p_queue_family_indices: std::ptr::null(),
+            initial_layout: ImageLayout::UNDEFINED,
+        };
+
+        let image = unsafe {
+            device
+                .create_image(&image_create_info, None)
+                .expect("Failed to create attachment image")
+        };
+
+        let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
+        let allocate_info = MemoryAllocateInfo {
+            s_type: StructureType::MEMORY_ALLOCATE_INFO,
+            p_next: std::ptr::null(),
+            allocation_size: mem_requirements.size,
+            memory_type_index: find_memory_type(
+                mem_requirements.memory_type_bits,
+                MemoryPropertyFlags::DEVICE_LOCAL,
+                MemoryPropertyFlags::empty(),
+                device_memory_properties,
+            )
+            .expect("Failed to find attachment image memory type"),
+        };
+
+        let memory = unsafe {
+            device
+                .allocate_memory(&allocate_info, None)
+                .expect("Failed to allocate attachment image memory")
+        };
+
+        unsafe {
+            device
+                .bind_image_memory(image, memory, 0)
+                .expect("Failed to bind attachment image memory");
+        }
+
+        let image_view_create_info = ImageViewCreateInfo {
+            s_type: StructureType::IMAGE_VIEW_CREATE_INFO,
+            p_next: std::ptr::null(),
+            flags: ImageViewCreateFlags::empty(),
+            view_type: ImageViewType::TYPE_2D,
+            format,
+            components: ComponentMapping {
+                r: ComponentSwizzle::IDENTITY,
+                g: ComponentSwizzle::IDENTITY,
+                b: ComponentSwizzle::IDENTITY,
+                a: ComponentSwizzle::IDENTITY,
+            },
+            subresource_range: ImageSubresourceRange {
+                aspect_mask,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image,
+        };
+
+        let view = unsafe {
+            device
+                .create_image_view(&image_view_create_info, None)
+                .expect("Failed to create attachment image view")
+        };
+
+        AttachmentImage {
+            image,
+            memory,
+            view,
+            format,
+            samples,
+        }
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        unsafe {
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+            device.free_memory(self.memory, None);
+        }
+    }
+}
+
+pub fn create_depth_attachment(
+    device: &Device,
+    device_memory_properties: &PhysicalDeviceMemoryProperties,
+    extent: Extent2D,
+    format: Format,
+    samples: SampleCountFlags,
+) -> AttachmentImage {
+    let aspect_mask = if has_stencil_component(format) {
+        ImageAspectFlags::DEPTH | ImageAspectFlags::STENCIL
+    } else {
+        ImageAspectFlags::DEPTH
+    };
+
+    AttachmentImage::new(
+        device,
+        device_memory_properties,
+        extent,
+        format,
+        samples,
+        ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+        aspect_mask,
+    )
+}
+
+pub fn create_msaa_color_attachment(
+    device: &Device,
+    device_memory_properties: &PhysicalDeviceMemoryProperties,
+    extent: Extent2D,
+    format: Format,
+    samples: SampleCountFlags,
+) -> AttachmentImage {
+    AttachmentImage::new(
+        device,
+        device_memory_properties,
+        extent,
+        format,
+        samples,
+        ImageUsageFlags::TRANSIENT_ATTACHMENT | ImageUsageFlags::COLOR_ATTACHMENT,
+        ImageAspectFlags::COLOR,
+    )
+}
+
+pub fn find_depth_format(
+    instance: &Instance,
+    physical_device: PhysicalDevice,
+    is_stencil_required: bool,
+) -> Format {
+    depth_format_candidates(is_stencil_required)
+        .iter()
+        .find(|x| {
+            let format_properties =
+                unsafe { instance.get_physical_device_format_properties(physical_device, **x) };
+            format_properties
+                .optimal_tiling_features
+                .contains(FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+        })
+        .copied()
+        .expect("Failed to find a supported depth format")
+}
+
+pub fn depth_format_candidates(is_stencil_required: bool) -> &'static [Format] {
+    if is_stencil_required {
+        &DEPTH_STENCIL_FORMAT_CANDIDATES
+    } else {
+        &DEPTH_FORMAT_CANDIDATES
+    }
+}
+
+pub fn has_stencil_component(format: Format) -> bool {
+    format == Format::D32_SFLOAT_S8_UINT || format == Format::D24_UNORM_S8_UINT
+}
+
+pub fn find_supported_sample_counts(limits: &PhysicalDeviceLimits) -> SampleCountFlags {
+    limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts
+}
+
+pub fn decode_sample_counts(sample_counts: SampleCountFlags) -> Vec<SampleCountFlags> {
+    SAMPLE_COUNTS_DESCENDING
+        .iter()
+        .copied()
+        .filter(|x| sample_counts.contains(*x))
+        .collect()
+}
+
+pub fn choose_sample_count(
+    supported: SampleCountFlags,
+    requested: SampleCountFlags,
+) -> SampleCountFlags {
+    decode_sample_counts(supported)
+        .into_iter()
+        .find(|x| x.as_raw() <= requested.as_raw())
+        .unwrap_or(SampleCountFlags::TYPE_1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::fake_device;
+    use ash::vk::Handle;
+
+    #[test]
+    fn sample_counts_decode_highest_first() {
+        let supported =
+            SampleCountFlags::TYPE_1 | SampleCountFlags::TYPE_4 | SampleCountFlags::TYPE_8;
+        assert_eq!(
+            decode_sample_counts(supported),
+            vec![SampleCountFlags::TYPE_8, SampleCountFlags::TYPE_4, SampleCountFlags::TYPE_1]
+        );
+    }
+
+    #[test]
+    fn sample_count_is_clamped_to_request() {
+        let supported =
+            SampleCountFlags::TYPE_1 | SampleCountFlags::TYPE_2 | SampleCountFlags::TYPE_8;
+        let choose = |requested| choose_sample_count(supported, requested);
+        assert_eq!(choose(SampleCountFlags::TYPE_64), SampleCountFlags::TYPE_8);
+        assert_eq!(choose(SampleCountFlags::TYPE_8), SampleCountFlags::TYPE_8);
+        assert_eq!(choose(SampleCountFlags::TYPE_4), SampleCountFlags::TYPE_2);
+        assert_eq!(choose(SampleCountFlags::TYPE_1), SampleCountFlags::TYPE_1);
+    }
+
+    #[test]
+    fn stencil_requirement_picks_a_stencil_capable_depth_format() {
+        let instance = fake_device::instance();
+        let physical_device = PhysicalDevice::from_raw(1);
+        let depth_format = find_depth_format(&instance, physical_device, true);
+        assert_eq!(depth_format, Format::D32_SFLOAT_S8_UINT);
+        assert!(has_stencil_component(depth_format));
+        assert_eq!(find_depth_format(&instance, physical_device, false), Format::D32_SFLOAT);
+    }
+}
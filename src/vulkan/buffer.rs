@@ -1,19 +1,281 @@
-use ash::version::DeviceV1_0;
+use super::error::PotatoError;
+use super::submit_batch::submit_checked;
+use ash::version::{DeviceV1_0, InstanceV1_0};
 use ash::vk::{
-    Buffer, BufferCopy, BufferCreateFlags, BufferCreateInfo, BufferUsageFlags,
-    CommandBufferAllocateInfo, CommandBufferBeginInfo, CommandBufferLevel, CommandBufferUsageFlags,
-    CommandPool, DeviceMemory, DeviceSize, Fence, MemoryAllocateInfo, MemoryPropertyFlags,
-    PhysicalDeviceMemoryProperties, Queue, SharingMode, StructureType, SubmitInfo,
+    AccessFlags, Buffer, BufferCopy, BufferCreateFlags, BufferCreateInfo, BufferMemoryBarrier,
+    BufferUsageFlags, CommandBuffer, CommandBufferAllocateInfo, CommandBufferBeginInfo,
+    CommandBufferLevel, CommandBufferUsageFlags, CommandPool, DependencyFlags, DeviceMemory,
+    DeviceSize, FenceCreateFlags, FenceCreateInfo, MappedMemoryRange, MemoryAllocateInfo,
+    MemoryMapFlags, MemoryPropertyFlags, PhysicalDevice, PhysicalDeviceMemoryProperties,
+    PipelineStageFlags, Queue, SharingMode, StructureType, SubmitInfo, WHOLE_SIZE,
 };
-use ash::Device;
+use ash::{Device, Instance};
+use std::rc::Rc;
+
+pub struct PotatoBuffer {
+    pub buffer: Buffer,
+    pub memory: DeviceMemory,
+    pub size: DeviceSize,
+    allocation_size: DeviceSize,
+    is_coherent: bool,
+    device: Rc<Device>,
+}
+
+impl PotatoBuffer {
+    pub fn new(
+        device: &Rc<Device>,
+        size: DeviceSize,
+        usage: BufferUsageFlags,
+        required_memory_properties: MemoryPropertyFlags,
+        preferred_memory_properties: MemoryPropertyFlags,
+        device_memory_properties: &PhysicalDeviceMemoryProperties,
+    ) -> PotatoBuffer {
+        let allocation = allocate_buffer(
+            device,
+            size,
+            usage,
+            required_memory_properties,
+            preferred_memory_properties,
+            device_memory_properties,
+        );
+
+        PotatoBuffer {
+            buffer: allocation.buffer,
+            memory: allocation.memory,
+            size,
+            allocation_size: allocation.allocation_size,
+            is_coherent: allocation
+                .memory_properties
+                .contains(MemoryPropertyFlags::HOST_COHERENT),
+            device: Rc::clone(device),
+        }
+    }
+
+    pub fn is_coherent(&self) -> bool {
+        self.is_coherent
+    }
+
+    pub fn map<T>(&self) -> *mut T {
+        unsafe {
+            self.device
+                .map_memory(self.memory, 0, self.size, MemoryMapFlags::empty())
+                .expect("Failed to map memory") as *mut T
+        }
+    }
+
+    pub fn unmap(&self) {
+        unsafe {
+            self.device.unmap_memory(self.memory);
+        }
+    }
+
+    pub fn flush(&self, offset: DeviceSize, size: DeviceSize, non_coherent_atom_size: DeviceSize) {
+        if self.is_coherent {
+            return;
+        }
+        let (offset, size) =
+            align_flush_range(offset, size, non_coherent_atom_size, self.allocation_size);
+        let ranges = [MappedMemoryRange {
+            s_type: StructureType::MAPPED_MEMORY_RANGE,
+            p_next: std::ptr::null(),
+            memory: self.memory,
+            offset,
+            size,
+        }];
+        unsafe {
+            self.device
+                .flush_mapped_memory_ranges(&ranges)
+                .expect("Failed to flush mapped memory");
+        }
+    }
+}
+
+pub fn align_flush_range(
+    offset: DeviceSize,
+    size: DeviceSize,
+    non_coherent_atom_size: DeviceSize,
+    allocation_size: DeviceSize,
+) -> (DeviceSize, DeviceSize) {
+    let atom_size = non_coherent_atom_size.max(1);
+    let aligned_offset = offset / atom_size * atom_size;
+    let end = offset + size;
+    let aligned_end = (end.div_ceil(atom_size) * atom_size).min(allocation_size);
+    (aligned_offset, aligned_end - aligned_offset)
+}
+
+#[cfg(test)]
+impl PotatoBuffer {
+    pub fn from_raw_parts(
+        buffer: Buffer,
+        memory: DeviceMemory,
+        size: DeviceSize,
+        device: &Rc<Device>,
+    ) -> PotatoBuffer {
+        PotatoBuffer {
+            buffer,
+            memory,
+            size,
+            allocation_size: size,
+            is_coherent: true,
+            device: Rc::clone(device),
+        }
+    }
+}
+
+impl Drop for PotatoBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_buffer(self.buffer, None);
+            self.device.free_memory(self.memory, None);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct UploadQueues {
+    pub transfer_queue: Queue,
+    pub transfer_command_pool: CommandPool,
+    pub transfer_family: u32,
+    pub graphics_queue: Queue,
+    pub graphics_command_pool: CommandPool,
+    pub graphics_family: u32,
+}
+
+impl UploadQueues {
+    pub fn is_dedicated_transfer(&self) -> bool {
+        self.transfer_family != self.graphics_family
+    }
+}
+
+pub struct StagingBuffer {
+    pub buffer: Buffer,
+    pub memory: DeviceMemory,
+    pub capacity: DeviceSize,
+}
+
+impl StagingBuffer {
+    pub fn new() -> StagingBuffer {
+        StagingBuffer {
+            buffer: Buffer::null(),
+            memory: DeviceMemory::null(),
+            capacity: 0,
+        }
+    }
+
+    // Uploads that fit reuse the current buffer, larger ones replace it.
+    pub fn needs_to_grow(&self, size: DeviceSize) -> bool {
+        size > self.capacity
+    }
+
+    pub fn upload<T>(
+        &mut self,
+        device: &Device,
+        device_memory_properties: &PhysicalDeviceMemoryProperties,
+        data: &[T],
+    ) -> DeviceSize {
+        let size = std::mem::size_of_val(data) as DeviceSize;
+        if self.needs_to_grow(size) {
+            self.destroy(device);
+            let (buffer, memory) = create_buffer(
+                device,
+                size,
+                BufferUsageFlags::TRANSFER_SRC,
+                MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+                MemoryPropertyFlags::empty(),
+                device_memory_properties,
+            );
+            self.buffer = buffer;
+            self.memory = memory;
+            self.capacity = size;
+        }
+
+        unsafe {
+            let data_ptr = device
+                .map_memory(self.memory, 0, size, MemoryMapFlags::empty())
+                .expect("Failed to map memory") as *mut T;
+
+            data_ptr.copy_from_nonoverlapping(data.as_ptr(), data.len());
+
+            device.unmap_memory(self.memory);
+        }
+
+        size
+    }
+
+    pub fn copy_to(
+        &self,
+        device: &Device,
+        upload_queues: &UploadQueues,
+        dst_buffer: Buffer,
+        size: DeviceSize,
+    ) {
+        if upload_queues.is_dedicated_transfer() {
+            copy_buffer_to_graphics_family(device, upload_queues, self.buffer, dst_buffer, size);
+        } else {
+            copy_buffer(
+                device,
+                upload_queues.graphics_queue,
+                upload_queues.graphics_command_pool,
+                self.buffer,
+                dst_buffer,
+                size,
+            );
+        }
+    }
+
+    pub fn destroy(&mut self, device: &Device) {
+        if self.buffer != Buffer::null() {
+            unsafe {
+                device.destroy_buffer(self.buffer, None);
+                device.free_memory(self.memory, None);
+            }
+        }
+        self.buffer = Buffer::null();
+        self.memory = DeviceMemory::null();
+        self.capacity = 0;
+    }
+}
+
+impl Default for StagingBuffer {
+    fn default() -> Self {
+        StagingBuffer::new()
+    }
+}
+
+pub struct BufferAllocation {
+    pub buffer: Buffer,
+    pub memory: DeviceMemory,
+    pub allocation_size: DeviceSize,
+    pub memory_properties: MemoryPropertyFlags,
+}
 
 pub fn create_buffer(
     device: &Device,
     size: DeviceSize,
     usage: BufferUsageFlags,
     required_memory_properties: MemoryPropertyFlags,
+    preferred_memory_properties: MemoryPropertyFlags,
     device_memory_properties: &PhysicalDeviceMemoryProperties,
 ) -> (Buffer, DeviceMemory) {
+    let allocation = allocate_buffer(
+        device,
+        size,
+        usage,
+        required_memory_properties,
+        preferred_memory_properties,
+        device_memory_properties,
+    );
+    (allocation.buffer, allocation.memory)
+}
+
+pub fn allocate_buffer(
+    device: &Device,
+    size: DeviceSize,
+    usage: BufferUsageFlags,
+    required_memory_properties: MemoryPropertyFlags,
+    preferred_memory_properties: MemoryPropertyFlags,
+    device_memory_properties: &PhysicalDeviceMemoryProperties,
+) -> BufferAllocation {
     let buffer_create_info = BufferCreateInfo {
         s_type: StructureType::BUFFER_CREATE_INFO,
         p_next: std::ptr::null(),
@@ -33,11 +295,13 @@ pub fn create_buffer(
 
     let mem_requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
 
-    let memory_type = find_mem_type(
+    let memory_type = find_memory_type(
         mem_requirements.memory_type_bits,
         required_memory_properties,
-        *device_memory_properties,
-    );
+        preferred_memory_properties,
+        device_memory_properties,
+    )
+    .expect("Failed to find buffer memory type");
 
     let allocate_info = MemoryAllocateInfo {
         s_type: StructureType::MEMORY_ALLOCATE_INFO,
@@ -58,22 +322,140 @@ pub fn create_buffer(
             .expect("Failed to bind buffer");
     }
 
+    BufferAllocation {
+        buffer,
+        memory: buffer_memory,
+        allocation_size: mem_requirements.size,
+        memory_properties: device_memory_properties.memory_types[memory_type as usize]
+            .property_flags,
+    }
+}
+
+pub fn create_device_local_buffer<T>(
+    device: &Device,
+    device_memory_properties: &PhysicalDeviceMemoryProperties,
+    upload_queues: &UploadQueues,
+    staging_buffer: &mut StagingBuffer,
+    data: &[T],
+    buffer_usage_flags: BufferUsageFlags,
+) -> (Buffer, DeviceMemory) {
+    let buffer_size = staging_buffer.upload(device, device_memory_properties, data);
+
+    let (buffer, buffer_memory) = create_buffer(
+        device,
+        buffer_size,
+        buffer_usage_flags,
+        MemoryPropertyFlags::DEVICE_LOCAL,
+        MemoryPropertyFlags::empty(),
+        device_memory_properties,
+    );
+
+    staging_buffer.copy_to(device, upload_queues, buffer, buffer_size);
+
     (buffer, buffer_memory)
 }
 
-fn find_mem_type(
+pub struct BufferUploader<'a> {
+    device: &'a Device,
+    device_memory_properties: PhysicalDeviceMemoryProperties,
+    upload_queues: &'a UploadQueues,
+    staging_buffer: &'a mut StagingBuffer,
+}
+
+impl<'a> BufferUploader<'a> {
+    pub fn new(
+        instance: &Instance,
+        device: &'a Device,
+        physical_device: PhysicalDevice,
+        upload_queues: &'a UploadQueues,
+        staging_buffer: &'a mut StagingBuffer,
+    ) -> BufferUploader<'a> {
+        let device_memory_properties =
+            unsafe { instance.get_physical_device_memory_properties(physical_device) };
+        BufferUploader {
+            device,
+            device_memory_properties,
+            upload_queues,
+            staging_buffer,
+        }
+    }
+
+    pub fn create_device_local_buffer<T>(
+        &mut self,
+        data: &[T],
+        buffer_usage_flags: BufferUsageFlags,
+    ) -> (Buffer, DeviceMemory) {
+        create_device_local_buffer(
+            self.device,
+            &self.device_memory_properties,
+            self.upload_queues,
+            self.staging_buffer,
+            data,
+            buffer_usage_flags,
+        )
+    }
+}
+
+pub fn upload_host_visible<T>(
+    device: &Rc<Device>,
+    device_memory_properties: &PhysicalDeviceMemoryProperties,
+    buffer: &mut Option<PotatoBuffer>,
+    usage: BufferUsageFlags,
+    data: &[T],
+) {
+    let size = std::mem::size_of_val(data) as u64;
+    let is_too_small = match buffer {
+        Some(buffer) => buffer.size < size,
+        None => true,
+    };
+    if is_too_small {
+        *buffer = Some(PotatoBuffer::new(
+            device,
+            size,
+            usage,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+            device_memory_properties,
+        ));
+    }
+
+    if let Some(buffer) = buffer {
+        unsafe {
+            let data_ptr = buffer.map::<T>();
+            data_ptr.copy_from_nonoverlapping(data.as_ptr(), data.len());
+        }
+        buffer.unmap();
+    }
+}
+
+pub fn find_memory_type(
     type_filter: u32,
     required_properties: MemoryPropertyFlags,
-    mem_properties: PhysicalDeviceMemoryProperties,
-) -> u32 {
-    mem_properties
-        .memory_types
+    preferred_properties: MemoryPropertyFlags,
+    mem_properties: &PhysicalDeviceMemoryProperties,
+) -> Result<u32, PotatoError> {
+    find_exact_memory_type(
+        type_filter,
+        required_properties | preferred_properties,
+        mem_properties,
+    )
+    .or_else(|| find_exact_memory_type(type_filter, required_properties, mem_properties))
+    .ok_or(PotatoError::NoSuitableMemoryType(
+        type_filter,
+        required_properties,
+    ))
+}
+
+fn find_exact_memory_type(
+    type_filter: u32,
+    properties: MemoryPropertyFlags,
+    mem_properties: &PhysicalDeviceMemoryProperties,
+) -> Option<u32> {
+    mem_properties.memory_types[..mem_properties.memory_type_count as usize]
         .iter()
         .enumerate()
-        .position(|(i, x)| {
-            type_filter & (1 << i) > 0 && x.property_flags.contains(required_properties)
-        })
-        .unwrap() as u32
+        .position(|(i, x)| type_filter & (1 << i) > 0 && x.property_flags.contains(properties))
+        .map(|i| i as u32)
 }
 
 pub fn copy_buffer(
@@ -83,6 +465,106 @@ pub fn copy_buffer(
     src_buffer: Buffer,
     dst_buffer: Buffer,
     size: DeviceSize,
+) {
+    single_time_commands(
+        device,
+        command_pool,
+        submit_queue,
+        |command_buffer| unsafe {
+            let copy_regions = [BufferCopy {
+                src_offset: 0,
+                dst_offset: 0,
+                size,
+            }];
+            device.cmd_copy_buffer(command_buffer, src_buffer, dst_buffer, &copy_regions);
+        },
+    );
+}
+
+fn copy_buffer_to_graphics_family(
+    device: &Device,
+    upload_queues: &UploadQueues,
+    src_buffer: Buffer,
+    dst_buffer: Buffer,
+    size: DeviceSize,
+) {
+    let release_barrier = create_ownership_barrier(
+        dst_buffer,
+        AccessFlags::TRANSFER_WRITE,
+        AccessFlags::empty(),
+        upload_queues,
+    );
+    let acquire_barrier = create_ownership_barrier(
+        dst_buffer,
+        AccessFlags::empty(),
+        AccessFlags::MEMORY_READ,
+        upload_queues,
+    );
+
+    single_time_commands(
+        device,
+        upload_queues.transfer_command_pool,
+        upload_queues.transfer_queue,
+        |command_buffer| unsafe {
+            let copy_regions = [BufferCopy {
+                src_offset: 0,
+                dst_offset: 0,
+                size,
+            }];
+            device.cmd_copy_buffer(command_buffer, src_buffer, dst_buffer, &copy_regions);
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                PipelineStageFlags::TRANSFER,
+                PipelineStageFlags::BOTTOM_OF_PIPE,
+                DependencyFlags::empty(),
+                &[],
+                &[release_barrier],
+                &[],
+            );
+        },
+    );
+    single_time_commands(
+        device,
+        upload_queues.graphics_command_pool,
+        upload_queues.graphics_queue,
+        |command_buffer| unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                PipelineStageFlags::TOP_OF_PIPE,
+                PipelineStageFlags::ALL_COMMANDS,
+                DependencyFlags::empty(),
+                &[],
+                &[acquire_barrier],
+                &[],
+            );
+        },
+    );
+}
+
+fn create_ownership_barrier(
+    buffer: Buffer,
+    src_access_mask: AccessFlags,
+    dst_access_mask: AccessFlags,
+    upload_queues: &UploadQueues,
+) -> BufferMemoryBarrier {
+    BufferMemoryBarrier {
+        s_type: StructureType::BUFFER_MEMORY_BARRIER,
+        p_next: std::ptr::null(),
+        src_access_mask,
+        dst_access_mask,
+        src_queue_family_index: upload_queues.transfer_family,
+        dst_queue_family_index: upload_queues.graphics_family,
+        buffer,
+        offset: 0,
+        size: WHOLE_SIZE,
+    }
+}
+
+pub fn single_time_commands<F: FnOnce(CommandBuffer)>(
+    device: &Device,
+    command_pool: CommandPool,
+    submit_queue: Queue,
+    record: F,
 ) {
     let allocate_info = CommandBufferAllocateInfo {
         s_type: StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
@@ -109,12 +591,9 @@ pub fn copy_buffer(
         device
             .begin_command_buffer(command_buffers[0], &begin_info)
             .expect("Failed to begin command buffer");
-        let copy_regions = [BufferCopy {
-            src_offset: 0,
-            dst_offset: 0,
-            size,
-        }];
-        device.cmd_copy_buffer(command_buffers[0], src_buffer, dst_buffer, &copy_regions);
+    }
+    record(command_buffers[0]);
+    unsafe {
         device
             .end_command_buffer(command_buffers[0])
             .expect("Failed to end command buffer");
@@ -132,14 +611,189 @@ pub fn copy_buffer(
         p_signal_semaphores: std::ptr::null(),
     }];
 
-    //TODO add logic for semaphore or fence for multiple submissions.
+    let fence_create_info = FenceCreateInfo {
+        s_type: StructureType::FENCE_CREATE_INFO,
+        p_next: std::ptr::null(),
+        flags: FenceCreateFlags::empty(),
+    };
+
     unsafe {
+        let fence = device
+            .create_fence(&fence_create_info, None)
+            .expect("Failed to create fence");
+        submit_checked(device, submit_queue, &submit_info, fence)
+            .unwrap_or_else(|e| panic!("Failed to submit queue: {}", e));
         device
-            .queue_submit(submit_queue, &submit_info, Fence::null())
-            .expect("Failed to submit queue");
-        device
-            .queue_wait_idle(submit_queue)
-            .expect("Failed to wait on queue");
+            .wait_for_fences(&[fence], true, u64::MAX)
+            .expect("Failed to wait for fence");
+        device.destroy_fence(fence, None);
         device.free_command_buffers(command_pool, &command_buffers);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::vk;
+    use ash::vk::{AllocationCallbacks, Handle, InstanceFnV1_0, MemoryType, PFN_vkVoidFunction};
+    use std::cell::RefCell;
+    use std::ffi::CStr;
+    use std::os::raw::{c_char, c_void};
+
+    fn memory_properties(types: &[MemoryPropertyFlags]) -> PhysicalDeviceMemoryProperties {
+        let mut mem_properties = PhysicalDeviceMemoryProperties {
+            memory_type_count: types.len() as u32,
+            ..Default::default()
+        };
+        for (memory_type, property_flags) in mem_properties.memory_types.iter_mut().zip(types) {
+            *memory_type = MemoryType {
+                property_flags: *property_flags,
+                heap_index: 0,
+            };
+        }
+        mem_properties
+    }
+
+    #[test]
+    fn memory_type_prefers_preferred_properties() {
+        let host_visible = MemoryPropertyFlags::HOST_VISIBLE;
+        let mem_properties = memory_properties(&[
+            MemoryPropertyFlags::DEVICE_LOCAL,
+            host_visible,
+            host_visible | MemoryPropertyFlags::HOST_COHERENT,
+        ]);
+        let coherent = MemoryPropertyFlags::HOST_COHERENT;
+        assert_eq!(find_memory_type(0b111, host_visible, coherent, &mem_properties).ok(), Some(2));
+        assert_eq!(find_memory_type(0b011, host_visible, coherent, &mem_properties).ok(), Some(1));
+    }
+
+    #[test]
+    fn memory_type_not_found_is_an_error() {
+        let mem_properties = memory_properties(&[MemoryPropertyFlags::DEVICE_LOCAL]);
+        let result = find_memory_type(
+            0b1,
+            MemoryPropertyFlags::HOST_VISIBLE,
+            MemoryPropertyFlags::empty(),
+            &mem_properties,
+        );
+        assert!(matches!(result, Err(PotatoError::NoSuitableMemoryType(0b1, _))));
+    }
+
+    #[test]
+    fn flush_range_keeps_aligned_size() {
+        assert_eq!(align_flush_range(0, 256, 64, 1024), (0, 256));
+        assert_eq!(align_flush_range(128, 64, 64, 1024), (128, 64));
+    }
+
+    #[test]
+    fn flush_range_rounds_up_to_next_atom() {
+        assert_eq!(align_flush_range(0, 65, 64, 1024), (0, 128));
+        assert_eq!(align_flush_range(70, 10, 64, 1024), (64, 64));
+        assert_eq!(align_flush_range(960, 65, 64, 1024), (960, 64));
+    }
+
+    #[test]
+    fn staging_buffer_grows_only_past_capacity() {
+        let mut staging_buffer = StagingBuffer::new();
+        assert!(staging_buffer.needs_to_grow(1));
+
+        staging_buffer.capacity = 256;
+        assert!(!staging_buffer.needs_to_grow(128));
+        assert!(!staging_buffer.needs_to_grow(256));
+        assert!(staging_buffer.needs_to_grow(257));
+    }
+
+    thread_local! {
+        static RELEASED: RefCell<Vec<(Buffer, DeviceMemory)>> =
+            const { RefCell::new(Vec::new()) };
+    }
+
+    extern "system" fn record_destroy_buffer(
+        _device: vk::Device,
+        buffer: Buffer,
+        _p_allocator: *const AllocationCallbacks,
+    ) {
+        RELEASED.with(|x| x.borrow_mut().push((buffer, DeviceMemory::null())));
+    }
+
+    extern "system" fn record_free_memory(
+        _device: vk::Device,
+        memory: DeviceMemory,
+        _p_allocator: *const AllocationCallbacks,
+    ) {
+        RELEASED.with(|x| x.borrow_mut().push((Buffer::null(), memory)));
+    }
+
+    extern "system" fn get_recording_device_proc_addr(
+        _device: vk::Device,
+        p_name: *const c_char,
+    ) -> PFN_vkVoidFunction {
+        let function = match unsafe { CStr::from_ptr(p_name) }.to_bytes() {
+            b"vkDestroyBuffer" => record_destroy_buffer as *const (),
+            b"vkFreeMemory" => record_free_memory as *const (),
+            _ => return None,
+        };
+        Some(unsafe {
+            std::mem::transmute::<*const (), unsafe extern "system" fn() -> c_void>(function)
+        })
+    }
+
+    fn recording_device() -> Rc<Device> {
+        let instance_fn = InstanceFnV1_0 {
+            get_device_proc_addr: get_recording_device_proc_addr,
+            ..InstanceFnV1_0::load(|_| std::ptr::null())
+        };
+        Rc::new(unsafe { Device::load(&instance_fn, vk::Device::from_raw(1)) })
+    }
+
+    #[test]
+    fn potato_buffer_frees_memory_exactly_once() {
+        let device = recording_device();
+        let potato_buffer = PotatoBuffer {
+            buffer: Buffer::from_raw(2),
+            memory: DeviceMemory::from_raw(3),
+            size: 64,
+            allocation_size: 64,
+            is_coherent: true,
+            device: Rc::clone(&device),
+        };
+        drop(potato_buffer);
+        drop(device);
+
+        RELEASED.with(|x| {
+            assert_eq!(
+                *x.borrow(),
+                vec![
+                    (Buffer::from_raw(2), DeviceMemory::null()),
+                    (Buffer::null(), DeviceMemory::from_raw(3)),
+                ]
+            )
+        });
+    }
+
+    #[test]
+    fn single_time_commands_submits_waits_and_frees() {
+        use crate::vulkan::fake_device::{self, COMMAND_BUFFER, FENCE};
+        let device = fake_device::device();
+        let mut recorded = None;
+        single_time_commands(&device, CommandPool::from_raw(2), Queue::from_raw(3), |x| {
+            recorded = Some(x);
+        });
+
+        assert_eq!(recorded, Some(CommandBuffer::from_raw(COMMAND_BUFFER)));
+        assert_eq!(
+            fake_device::take_calls(),
+            vec![
+                ("vkAllocateCommandBuffers", COMMAND_BUFFER),
+                ("vkBeginCommandBuffer", COMMAND_BUFFER),
+                ("vkEndCommandBuffer", COMMAND_BUFFER),
+                ("vkCreateFence", FENCE),
+                ("vkQueueSubmit", COMMAND_BUFFER),
+                ("vkQueueSubmit", FENCE),
+                ("vkWaitForFences", FENCE),
+                ("vkDestroyFence", FENCE),
+                ("vkFreeCommandBuffers", COMMAND_BUFFER),
+            ]
+        );
+    }
+}
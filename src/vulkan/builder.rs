@@ -0,0 +1,343 @@
+use super::color::ClearColorSpace;
+use super::error::PotatoError;
+use super::graphics_pipeline::{BlendMode, DepthConfig, PipelineConfig, StencilOpConfig};
+use super::instance::ApplicationConfig;
+use super::particles::Particle;
+use super::physical_device::DevicePreference;
+use super::shader::{ShaderSource, ShadingMode};
+use super::swapchain::{HdrColorSpace, SwapchainConfig};
+use super::text::BitmapFont;
+use super::texture::{SamplerOptions, SamplerPreset};
+use super::vertex::{Vertex, VertexLayout};
+use super::vulk_init::VulkanApiObjects;
+use crate::windowing::input::InputMap;
+use crate::windowing::window::CursorState;
+use ash::vk::{
+    make_version, CompareOp, CullModeFlags, FrontFace, ImageUsageFlags, PolygonMode,
+    PresentModeKHR, SampleCountFlags,
+};
+use winit::event_loop::EventLoop;
+
+pub struct VulkanApiObjectsBuilder {
+    pub(crate) application_config: ApplicationConfig,
+    pub(crate) instance_extensions: Vec<String>,
+    pub(crate) device_preference: DevicePreference,
+    pub(crate) sampler_options: SamplerOptions,
+    pub(crate) sampler_preset: SamplerPreset,
+    pub(crate) swapchain_config: SwapchainConfig,
+    pub(crate) pipeline_config: PipelineConfig,
+    pub(crate) clear_color: [f32; 4],
+    pub(crate) clear_color_space: ClearColorSpace,
+    pub(crate) input_map: InputMap,
+    pub(crate) cursor: CursorState,
+    pub(crate) shaders: (ShaderSource, ShaderSource),
+    pub(crate) vertex_layout: VertexLayout,
+    pub(crate) particles: Option<Vec<Particle>>,
+    pub(crate) particle_shader: ShaderSource,
+    pub(crate) font: Option<(String, String, BitmapFont)>,
+    pub(crate) texture: Option<(String, String)>,
+    pub(crate) validation_log_capacity: usize,
+    pub(crate) is_lenient_validation: bool,
+    pub(crate) is_validation_requested: bool,
+    pub(crate) is_external_memory_requested: bool,
+    pub(crate) is_animated: bool,
+    pub(crate) max_fps: u32,
+}
+
+impl VulkanApiObjectsBuilder {
+    pub fn new() -> VulkanApiObjectsBuilder {
+        VulkanApiObjectsBuilder {
+            application_config: ApplicationConfig::default(),
+            instance_extensions: vec![],
+            device_preference: DevicePreference::FirstSuitable,
+            sampler_options: SamplerOptions::default(),
+            sampler_preset: SamplerPreset::Anisotropic,
+            swapchain_config: SwapchainConfig::default(),
+            pipeline_config: PipelineConfig::default(),
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+            clear_color_space: ClearColorSpace::Linear,
+            input_map: InputMap::default(),
+            cursor: CursorState::default(),
+            shaders: (
+                ShaderSource::File("src/shaders/spv/shader-vert.spv"),
+                ShadingMode::Smooth.fragment_shader(),
+            ),
+            vertex_layout: Vertex::layout(),
+            particles: None,
+            particle_shader: ShaderSource::File("src/shaders/spv/particle-comp.spv"),
+            font: None,
+            texture: None,
+            validation_log_capacity: 64,
+            is_lenient_validation: true,
+            is_validation_requested: true,
+            is_external_memory_requested: false,
+            is_animated: true,
+            max_fps: 0,
+        }
+    }
+
+    pub fn app_name(mut self, name: &str) -> Self {
+        self.application_config.app_name = name.to_string();
+        self
+    }
+
+    pub fn app_version(mut self, major: u32, minor: u32, patch: u32) -> Self {
+        self.application_config.app_version = make_version(major, minor, patch);
+        self
+    }
+
+    pub fn engine_name(mut self, name: &str) -> Self {
+        self.application_config.engine_name = name.to_string();
+        self
+    }
+
+    pub fn engine_version(mut self, major: u32, minor: u32, patch: u32) -> Self {
+        self.application_config.engine_version = make_version(major, minor, patch);
+        self
+    }
+
+    pub fn additional_instance_extensions(mut self, extensions: &[&str]) -> Self {
+        self.instance_extensions.extend(extensions.iter().map(|x| x.to_string()));
+        self
+    }
+
+    pub fn api_version(mut self, major: u32, minor: u32, patch: u32) -> Self {
+        self.application_config.api_version = make_version(major, minor, patch);
+        self
+    }
+
+    pub fn prefer_discrete(mut self) -> Self {
+        self.device_preference = DevicePreference::PreferDiscrete;
+        self
+    }
+
+    pub fn device_index(mut self, index: usize) -> Self {
+        self.device_preference = DevicePreference::Index(index);
+        self
+    }
+
+    pub fn device_name(mut self, name: &str) -> Self {
+        self.device_preference = DevicePreference::Name(name.to_string());
+        self
+    }
+
+    pub fn anisotropy(mut self, level: f32) -> Self {
+        self.sampler_options.max_anisotropy = Some(level);
+        self
+    }
+
+    pub fn sampler_options(mut self, options: SamplerOptions) -> Self {
+        self.sampler_options = options;
+        self
+    }
+
+    pub fn sampler_preset(mut self, preset: SamplerPreset) -> Self {
+        self.sampler_preset = preset;
+        self
+    }
+
+    pub fn present_mode(mut self, present_mode: PresentModeKHR) -> Self {
+        self.swapchain_config.present_mode = present_mode;
+        self
+    }
+
+    pub fn linear_color(mut self) -> Self {
+        self.swapchain_config.is_srgb = false;
+        self
+    }
+
+    pub fn hdr(mut self, color_space: HdrColorSpace) -> Self {
+        self.swapchain_config.hdr = Some(color_space);
+        self
+    }
+
+    pub fn swapchain_usage(mut self, usage: ImageUsageFlags) -> Self {
+        self.swapchain_config.extra_usage = usage;
+        self
+    }
+
+    pub fn blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.pipeline_config.blend_mode = blend_mode;
+        self
+    }
+
+    pub fn polygon_mode(mut self, polygon_mode: PolygonMode) -> Self {
+        self.pipeline_config.polygon_mode = polygon_mode;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: CullModeFlags) -> Self {
+        self.pipeline_config.cull_mode = cull_mode;
+        self
+    }
+
+    pub fn front_face(mut self, front_face: FrontFace) -> Self {
+        self.pipeline_config.front_face = front_face;
+        self
+    }
+
+    pub fn max_fps(mut self, max_fps: u32) -> Self {
+        self.max_fps = max_fps;
+        self
+    }
+
+    pub fn stencil(mut self, stencil: StencilOpConfig) -> Self {
+        self.pipeline_config.stencil = Some(stencil);
+        self
+    }
+
+    pub fn depth_compare_op(mut self, compare_op: CompareOp) -> Self {
+        self.pipeline_config.depth.compare_op = compare_op;
+        self
+    }
+
+    pub fn depth_clear_value(mut self, clear_value: f32) -> Self {
+        self.pipeline_config.depth.clear_value = clear_value;
+        self
+    }
+
+    pub fn reverse_z(mut self) -> Self {
+        self.pipeline_config.depth = DepthConfig::reverse_z();
+        self
+    }
+
+    pub fn clear_color(mut self, clear_color: [f32; 4]) -> Self {
+        self.clear_color = clear_color;
+        self
+    }
+
+    pub fn srgb_clear_color(mut self) -> Self {
+        self.clear_color_space = ClearColorSpace::Srgb;
+        self
+    }
+
+    pub fn line_width(mut self, line_width: f32) -> Self {
+        self.pipeline_config.line_width = line_width;
+        self
+    }
+
+    pub fn msaa_samples(mut self, samples: SampleCountFlags) -> Self {
+        self.pipeline_config.samples = samples;
+        self
+    }
+
+    pub fn cursor_visible(mut self, is_visible: bool) -> Self {
+        self.cursor.is_visible = is_visible;
+        self
+    }
+
+    pub fn cursor_grabbed(mut self, is_grabbed: bool) -> Self {
+        self.cursor.is_grabbed = is_grabbed;
+        self
+    }
+
+    pub fn input_map(mut self, input_map: InputMap) -> Self {
+        self.input_map = input_map;
+        self
+    }
+
+    pub fn shaders(mut self, vert_shader: ShaderSource, frag_shader: ShaderSource) -> Self {
+        self.shaders = (vert_shader, frag_shader);
+        self
+    }
+
+    pub fn vertex_layout(mut self, vertex_layout: VertexLayout) -> Self {
+        self.vertex_layout = vertex_layout;
+        self
+    }
+
+    pub fn shading_mode(mut self, shading_mode: ShadingMode) -> Self {
+        self.shaders.1 = shading_mode.fragment_shader();
+        self
+    }
+
+    pub fn particles(mut self, particles: Vec<Particle>) -> Self {
+        self.particles = Some(particles);
+        self
+    }
+
+    pub fn particle_shader(mut self, compute_shader: ShaderSource) -> Self {
+        self.particle_shader = compute_shader;
+        self
+    }
+
+    pub fn font(mut self, path: &str, fallback_png_path: &str, font: BitmapFont) -> Self {
+        self.font = Some((path.to_string(), fallback_png_path.to_string(), font));
+        self
+    }
+
+    pub fn texture(mut self, path: &str, fallback_png_path: &str) -> Self {
+        self.texture = Some((path.to_string(), fallback_png_path.to_string()));
+        self
+    }
+
+    pub fn strict_validation(mut self) -> Self {
+        self.is_lenient_validation = false;
+        self
+    }
+
+    pub fn external_memory(mut self) -> Self {
+        self.is_external_memory_requested = true;
+        self
+    }
+
+    pub fn animate(mut self, is_animated: bool) -> Self {
+        self.is_animated = is_animated;
+        self
+    }
+
+    pub fn benchmark_mode(mut self) -> Self {
+        self.is_validation_requested = false;
+        self
+    }
+
+    pub fn validation_log_capacity(mut self, capacity: usize) -> Self {
+        self.validation_log_capacity = capacity;
+        self
+    }
+
+    pub fn build(self, event_loop: &EventLoop<()>) -> Result<VulkanApiObjects, PotatoError> {
+        VulkanApiObjects::init(event_loop, self)
+    }
+
+    pub fn run(self) -> Result<(), PotatoError> {
+        VulkanApiObjects::run(self)
+    }
+}
+
+impl Default for VulkanApiObjectsBuilder {
+    fn default() -> Self {
+        VulkanApiObjectsBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_config_is_built_without_an_event_loop() {
+        let builder = VulkanApiObjects::builder()
+            .app_name("headless")
+            .animate(false)
+            .max_fps(30)
+            .benchmark_mode();
+        assert_eq!(builder.application_config.app_name, "headless");
+        assert!(!builder.is_animated);
+        assert_eq!(builder.max_fps, 30);
+        assert!(!builder.is_validation_requested);
+    }
+
+    #[test]
+    fn shading_mode_swaps_only_the_fragment_shader() {
+        let builder = VulkanApiObjects::builder().shading_mode(ShadingMode::Flat);
+        assert_eq!(
+            builder.shaders,
+            (
+                ShaderSource::File("src/shaders/spv/shader-vert.spv"),
+                ShadingMode::Flat.fragment_shader(),
+            )
+        );
+        assert_ne!(builder.shaders.1, ShadingMode::Smooth.fragment_shader());
+    }
+}
@@ -0,0 +1,122 @@
+use ash::vk::{ClearColorValue, Format};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClearColorSpace {
+    Linear,
+    Srgb,
+}
+
+pub fn linear_to_srgb(channel: f32) -> f32 {
+    if channel <= 0.003_130_8 {
+        channel * 12.92
+    } else {
+        1.055 * channel.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub fn srgb_to_linear(channel: f32) -> f32 {
+    if channel <= 0.040_45 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb_clear(color: [f32; 4]) -> ClearColorValue {
+    ClearColorValue {
+        float32: map_rgb(color, linear_to_srgb),
+    }
+}
+
+// Which encoding a clear value must already be in for the attachment to store what was asked for.
+pub fn attachment_clear_space(format: Format) -> Option<ClearColorSpace> {
+    match format {
+        Format::B8G8R8A8_SRGB | Format::R8G8B8A8_SRGB | Format::A8B8G8R8_SRGB_PACK32 => {
+            Some(ClearColorSpace::Linear)
+        }
+        Format::B8G8R8A8_UNORM | Format::R8G8B8A8_UNORM | Format::A8B8G8R8_UNORM_PACK32 => {
+            Some(ClearColorSpace::Srgb)
+        }
+        _ => None,
+    }
+}
+
+pub fn resolve_clear_color(
+    color: [f32; 4],
+    color_space: ClearColorSpace,
+    format: Format,
+) -> ClearColorValue {
+    match (color_space, attachment_clear_space(format)) {
+        (ClearColorSpace::Linear, Some(ClearColorSpace::Srgb)) => linear_to_srgb_clear(color),
+        (ClearColorSpace::Srgb, Some(ClearColorSpace::Linear)) => ClearColorValue {
+            float32: map_rgb(color, srgb_to_linear),
+        },
+        _ => ClearColorValue { float32: color },
+    }
+}
+
+fn map_rgb(color: [f32; 4], transfer: fn(f32) -> f32) -> [f32; 4] {
+    [
+        transfer(color[0]),
+        transfer(color[1]),
+        transfer(color[2]),
+        color[3],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-5, "{} != {}", actual, expected);
+    }
+
+    fn float32(value: ClearColorValue) -> [f32; 4] {
+        unsafe { value.float32 }
+    }
+
+    #[test]
+    fn linear_to_srgb_matches_known_values() {
+        assert_close(linear_to_srgb(0.0), 0.0);
+        assert_close(linear_to_srgb(1.0), 1.0);
+        assert_close(linear_to_srgb(0.5), 0.735_356_9);
+    }
+
+    #[test]
+    fn linear_to_srgb_is_continuous_at_knee() {
+        let knee = 0.003_130_8;
+        assert_close(linear_to_srgb(knee), knee * 12.92);
+        assert_close(linear_to_srgb(knee + 1e-7), knee * 12.92);
+        assert_close(srgb_to_linear(linear_to_srgb(knee)), knee);
+    }
+
+    #[test]
+    fn clear_color_is_encoded_for_unorm_targets() {
+        let color = [0.5, 0.5, 0.5, 0.5];
+        let resolved = float32(resolve_clear_color(
+            color,
+            ClearColorSpace::Linear,
+            Format::B8G8R8A8_UNORM,
+        ));
+        assert_close(resolved[0], 0.735_356_9);
+        assert_close(resolved[3], 0.5);
+        assert_eq!(
+            float32(resolve_clear_color(
+                color,
+                ClearColorSpace::Linear,
+                Format::B8G8R8A8_SRGB
+            )),
+            color
+        );
+    }
+
+    #[test]
+    fn linear_clear_encodes_color_but_not_alpha() {
+        let clear = float32(linear_to_srgb_clear([0.0, 0.5, 1.0, 0.5]));
+        assert_close(clear[0], 0.0);
+        assert_close(clear[1], 0.735_356_9);
+        assert_close(clear[2], 1.0);
+        assert_close(clear[3], 0.5);
+    }
+}
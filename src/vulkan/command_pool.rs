@@ -1,21 +1,41 @@
-use super::constants::INDICES_DATA;
+use super::constants::MAX_FRAMES_IN_FLIGHT;
+use super::debug_draw::DebugLines;
+use super::indirect::indirect_command_stride;
+use super::overlay::Overlay2d;
+use super::particles::ParticleSystem;
+use super::pipeline_stats::PipelineStatsQuery;
 use super::queue_family::QueueFamily;
+use super::scene::{DrawMode, Mesh, Renderable};
+use super::screenshot::ScreenshotCapture;
+use super::text::TextRenderer;
+use super::timestamp::GpuTimer;
+use super::vulk_validation_layers::{begin_label, end_label};
+use ash::extensions::ext::DebugUtils;
 use ash::version::DeviceV1_0;
 use ash::vk::{
-    Buffer, ClearColorValue, ClearValue, CommandBuffer, CommandBufferAllocateInfo,
-    CommandBufferBeginInfo, CommandBufferLevel, CommandBufferUsageFlags, CommandPool,
-    CommandPoolCreateFlags, CommandPoolCreateInfo, DescriptorSet, Extent2D, Framebuffer, IndexType,
-    Offset2D, Pipeline, PipelineBindPoint, PipelineLayout, Rect2D, RenderPass, RenderPassBeginInfo,
-    StructureType, SubpassContents,
+    ClearColorValue, ClearDepthStencilValue, ClearValue, CommandBuffer, CommandBufferAllocateInfo,
+    CommandBufferBeginInfo, CommandBufferInheritanceInfo, CommandBufferLevel,
+    CommandBufferUsageFlags, CommandPool, CommandPoolCreateFlags, CommandPoolCreateInfo,
+    DescriptorSet, Extent2D, Framebuffer, IndexType, Offset2D, Pipeline, PipelineBindPoint,
+    PipelineLayout, QueryControlFlags, QueryPipelineStatisticFlags, Rect2D, RenderPass,
+    RenderPassBeginInfo, ShaderStageFlags, StructureType, SubpassContents, FALSE,
 };
 use ash::Device;
+use std::sync::Arc;
+use std::thread;
+
+pub type RecordChunk = Arc<dyn Fn(&Device, CommandBuffer) + Send + Sync>;
 
 pub fn create_command_pool(device: &Device, queue_familes: &QueueFamily) -> CommandPool {
+    create_command_pool_for_family(device, queue_familes.graphics_family.unwrap() as u32)
+}
+
+pub fn create_command_pool_for_family(device: &Device, queue_family_index: u32) -> CommandPool {
     let command_pool_create_info = CommandPoolCreateInfo {
         s_type: StructureType::COMMAND_POOL_CREATE_INFO,
         p_next: std::ptr::null(),
-        flags: CommandPoolCreateFlags::empty(),
-        queue_family_index: queue_familes.graphics_family.unwrap() as u32,
+        flags: CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+        queue_family_index,
     };
 
     unsafe {
@@ -25,126 +45,535 @@ pub fn create_command_pool(device: &Device, queue_familes: &QueueFamily) -> Comm
     }
 }
 
-//TODO Reduce number of arguments
+pub struct SceneDraws<'a> {
+    pub graphics_pipeline: (Pipeline, PipelineLayout),
+    pub renderables: &'a [Renderable],
+    pub particles: Option<(&'a ParticleSystem, Pipeline)>,
+    pub debug_lines: (&'a DebugLines, (Pipeline, PipelineLayout)),
+    pub overlay: (&'a Overlay2d, (Pipeline, PipelineLayout)),
+    pub text: Option<(&'a TextRenderer, (Pipeline, PipelineLayout))>,
+}
+
+pub struct FrameRecording<'a> {
+    pub frame_index: usize,
+    pub render_pass: RenderPass,
+    pub framebuffer: Framebuffer,
+    pub surface_extent: Extent2D,
+    pub clear_values: &'a [ClearValue],
+    pub particle_system: Option<&'a ParticleSystem>,
+    pub gpu_timer: Option<&'a GpuTimer>,
+    pub pipeline_stats_query: Option<&'a PipelineStatsQuery>,
+    pub secondary_command_buffers: &'a [CommandBuffer],
+    pub screenshot: Option<&'a ScreenshotCapture>,
+}
+
 pub fn create_command_buffers(
     device: &Device,
     command_pool: CommandPool,
-    graphics_pipeline: Pipeline,
-    framebuffers: &[Framebuffer],
     render_pass: RenderPass,
-    surface_extent: Extent2D,
-    vertex_buffer: Buffer,
-    index_buffer: Buffer,
-    pipeline_layout: PipelineLayout,
-    descriptor_sets: &Vec<DescriptorSet>,
-) -> Vec<CommandBuffer> {
-    let command_buffer_allocate_info = CommandBufferAllocateInfo {
-        s_type: StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
-        p_next: std::ptr::null(),
-        command_buffer_count: framebuffers.len() as u32,
+    descriptor_sets: &[DescriptorSet],
+    scene: &SceneDraws,
+    pipeline_statistics: QueryPipelineStatisticFlags,
+) -> (Vec<CommandBuffer>, Vec<CommandBuffer>) {
+    let command_buffers = allocate_command_buffers(
+        device,
         command_pool,
-        level: CommandBufferLevel::PRIMARY,
-    };
+        CommandBufferLevel::PRIMARY,
+        MAX_FRAMES_IN_FLIGHT as u32,
+    );
 
-    let command_buffers = unsafe {
-        device
-            .allocate_command_buffers(&command_buffer_allocate_info)
-            .expect("failed to create command buffers")
-    };
+    let scene_command_buffers = allocate_command_buffers(
+        device,
+        command_pool,
+        CommandBufferLevel::SECONDARY,
+        descriptor_sets.len() as u32,
+    );
 
-    command_buffers.iter().enumerate().for_each(|(i, x)| {
-        process_command_buffer(
-            i,
-            x,
-            render_pass,
-            framebuffers,
-            surface_extent,
-            device,
-            graphics_pipeline,
-            vertex_buffer,
-            index_buffer,
-            pipeline_layout,
-            descriptor_sets,
-        )
-    });
+    scene_command_buffers
+        .iter()
+        .enumerate()
+        .for_each(|(i, scene_command_buffer)| {
+            record_scene_command_buffer(
+                device,
+                *scene_command_buffer,
+                render_pass,
+                scene,
+                descriptor_sets[i],
+                pipeline_statistics,
+            );
+        });
 
-    command_buffers
+    (command_buffers, scene_command_buffers)
 }
 
-//TODO Reduce number of arguments
-fn process_command_buffer(
-    index: usize,
-    command_buffer: &CommandBuffer,
+pub fn record_scene_command_buffer(
+    device: &Device,
+    scene_command_buffer: CommandBuffer,
     render_pass: RenderPass,
-    framebuffers: &[Framebuffer],
-    surface_extent: Extent2D,
+    scene: &SceneDraws,
+    descriptor_set: DescriptorSet,
+    pipeline_statistics: QueryPipelineStatisticFlags,
+) {
+    let inheritance_info =
+        create_inheritance_info(render_pass, 0, Framebuffer::null(), pipeline_statistics);
+    begin_secondary_command_buffer(device, scene_command_buffer, &inheritance_info);
+    record_scene_draws(device, scene_command_buffer, scene, descriptor_set);
+    unsafe {
+        device
+            .end_command_buffer(scene_command_buffer)
+            .expect("Failed to record secondary command buffer ending");
+    }
+}
+
+pub fn create_clear_values(
+    clear_color: ClearColorValue,
+    depth_clear_value: f32,
+) -> [ClearValue; 2] {
+    [
+        ClearValue { color: clear_color },
+        ClearValue {
+            depth_stencil: ClearDepthStencilValue {
+                depth: depth_clear_value,
+                stencil: 0,
+            },
+        },
+    ]
+}
+
+pub fn record_frame_command_buffer(
     device: &Device,
-    graphics_pipeline: Pipeline,
-    vertex_buffer: Buffer,
-    index_buffer: Buffer,
-    pipeline_layout: PipelineLayout,
-    descriptor_sets: &Vec<DescriptorSet>,
+    command_buffer: CommandBuffer,
+    frame: &FrameRecording,
+    debug_utils: Option<&DebugUtils>,
 ) {
     let command_buffer_begin_info = CommandBufferBeginInfo {
         s_type: StructureType::COMMAND_BUFFER_BEGIN_INFO,
         p_next: std::ptr::null(),
         p_inheritance_info: std::ptr::null(),
-        flags: CommandBufferUsageFlags::SIMULTANEOUS_USE,
+        flags: CommandBufferUsageFlags::ONE_TIME_SUBMIT,
     };
 
     unsafe {
         device
-            .begin_command_buffer(*command_buffer, &command_buffer_begin_info)
+            .begin_command_buffer(command_buffer, &command_buffer_begin_info)
             .expect("Failed to begin recording Command Buffer at beginning!");
     }
 
-    let clear_values = [ClearValue {
-        color: ClearColorValue {
-            float32: [0.0, 0.0, 0.0, 1.0],
-        },
-    }];
+    if let Some(particle_system) = frame.particle_system {
+        begin_label(debug_utils, command_buffer, "Particle Update", [0.2, 0.6, 1.0, 1.0]);
+        particle_system.record_update(device, command_buffer);
+        end_label(debug_utils, command_buffer);
+    }
 
     let render_pass_begin_info = RenderPassBeginInfo {
         s_type: StructureType::RENDER_PASS_BEGIN_INFO,
         p_next: std::ptr::null(),
-        render_pass,
-        framebuffer: framebuffers[index],
+        render_pass: frame.render_pass,
+        framebuffer: frame.framebuffer,
         render_area: Rect2D {
             offset: Offset2D { x: 0, y: 0 },
-            extent: surface_extent,
+            extent: frame.surface_extent,
         },
-        clear_value_count: clear_values.len() as u32,
-        p_clear_values: clear_values.as_ptr(),
+        clear_value_count: frame.clear_values.len() as u32,
+        p_clear_values: frame.clear_values.as_ptr(),
     };
 
+    if let Some(gpu_timer) = frame.gpu_timer {
+        gpu_timer.record_start(device, command_buffer, frame.frame_index);
+    }
+    if let Some(pipeline_stats_query) = frame.pipeline_stats_query {
+        pipeline_stats_query.record_begin(device, command_buffer, frame.frame_index);
+    }
+
+    begin_label(debug_utils, command_buffer, "Geometry Pass", [1.0, 0.6, 0.2, 1.0]);
     unsafe {
         device.cmd_begin_render_pass(
-            *command_buffer,
+            command_buffer,
             &render_pass_begin_info,
-            SubpassContents::INLINE,
+            SubpassContents::SECONDARY_COMMAND_BUFFERS,
         );
-        device.cmd_bind_pipeline(
-            *command_buffer,
-            PipelineBindPoint::GRAPHICS,
-            graphics_pipeline,
+        device.cmd_execute_commands(command_buffer, frame.secondary_command_buffers);
+        device.cmd_end_render_pass(command_buffer);
+    }
+    end_label(debug_utils, command_buffer);
+    if let Some(screenshot) = frame.screenshot {
+        screenshot.record_copy(device, command_buffer);
+    }
+    if let Some(pipeline_stats_query) = frame.pipeline_stats_query {
+        pipeline_stats_query.record_end(device, command_buffer, frame.frame_index);
+    }
+    unsafe {
+        if let Some(gpu_timer) = frame.gpu_timer {
+            gpu_timer.record_end(device, command_buffer, frame.frame_index);
+        }
+        device
+            .end_command_buffer(command_buffer)
+            .expect("Failed to record command buffer ending");
+    }
+}
+
+fn record_scene_draws(
+    device: &Device,
+    command_buffer: CommandBuffer,
+    scene: &SceneDraws,
+    descriptor_set: DescriptorSet,
+) {
+    let (graphics_pipeline, pipeline_layout) = scene.graphics_pipeline;
+    let (debug_lines, debug_line_pipeline) = scene.debug_lines;
+    let (overlay, overlay_pipeline) = scene.overlay;
+    unsafe {
+        device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::GRAPHICS, graphics_pipeline);
+    }
+    for renderable in scene.renderables {
+        record_renderable(
+            device,
+            command_buffer,
+            renderable,
+            pipeline_layout,
+            descriptor_set,
         );
-        let vertex_buffers = [vertex_buffer];
-        let offsets = [0_u64];
-        let descriptor_sets_to_bind = [descriptor_sets[index]];
-        device.cmd_bind_vertex_buffers(*command_buffer, 0, &vertex_buffers, &offsets);
-        device.cmd_bind_index_buffer(*command_buffer, index_buffer, 0, IndexType::UINT32);
+    }
+    if let Some((particle_system, particle_pipeline)) = scene.particles {
+        particle_system.record_draw(device, command_buffer, particle_pipeline);
+    }
+    debug_lines.record_draw(device, command_buffer, debug_line_pipeline, descriptor_set);
+    overlay.record_draw(device, command_buffer, overlay_pipeline);
+    if let Some((text_renderer, text_pipeline)) = scene.text {
+        text_renderer.record_draw(device, command_buffer, text_pipeline);
+    }
+}
+
+pub fn record_renderable(
+    device: &Device,
+    command_buffer: CommandBuffer,
+    renderable: &Renderable,
+    pipeline_layout: PipelineLayout,
+    frame_descriptor_set: DescriptorSet,
+) {
+    let vertex_buffers = [renderable.mesh.vertex_buffer];
+    let offsets = [0_u64];
+    let transform: &[f32; 16] = renderable.transform.as_ref();
+    let transform_bytes = unsafe {
+        std::slice::from_raw_parts(
+            transform.as_ptr() as *const u8,
+            std::mem::size_of_val(transform),
+        )
+    };
+    // The frame set stays at set 0 and a material set, if any, sits next to it.
+    let descriptor_sets_to_bind = match renderable.descriptor_set {
+        Some(material_descriptor_set) => vec![frame_descriptor_set, material_descriptor_set],
+        None => vec![frame_descriptor_set],
+    };
+    unsafe {
+        device.cmd_bind_vertex_buffers(command_buffer, 0, &vertex_buffers, &offsets);
         device.cmd_bind_descriptor_sets(
-            *command_buffer,
+            command_buffer,
             PipelineBindPoint::GRAPHICS,
             pipeline_layout,
             0,
             &descriptor_sets_to_bind,
             &[],
         );
-        device.cmd_draw_indexed(*command_buffer, INDICES_DATA.len() as u32, 1, 0, 0, 0);
-        device.cmd_end_render_pass(*command_buffer);
+        device.cmd_push_constants(
+            command_buffer,
+            pipeline_layout,
+            ShaderStageFlags::VERTEX,
+            0,
+            transform_bytes,
+        );
+    }
+    record_mesh_draw(device, command_buffer, &renderable.mesh);
+}
+
+pub fn record_mesh_draw(device: &Device, command_buffer: CommandBuffer, mesh: &Mesh) {
+    unsafe {
+        match mesh.draw_mode {
+            DrawMode::Indexed { index_count } => {
+                device.cmd_bind_index_buffer(
+                    command_buffer,
+                    mesh.index_buffer,
+                    mesh.index_offset,
+                    IndexType::UINT32,
+                );
+                device.cmd_draw_indexed(command_buffer, index_count, 1, 0, 0, 0);
+            }
+            DrawMode::NonIndexed { vertex_count } => {
+                device.cmd_draw(command_buffer, vertex_count, 1, 0, 0);
+            }
+            DrawMode::IndexedIndirect { buffer, draw_count } => {
+                device.cmd_bind_index_buffer(
+                    command_buffer,
+                    mesh.index_buffer,
+                    mesh.index_offset,
+                    IndexType::UINT32,
+                );
+                device.cmd_draw_indexed_indirect(
+                    command_buffer,
+                    buffer,
+                    0,
+                    draw_count,
+                    indirect_command_stride(),
+                );
+            }
+        }
+    }
+}
+
+pub fn record_parallel(
+    device: &Device,
+    command_pools: &[CommandPool],
+    render_pass: RenderPass,
+    chunks: &[RecordChunk],
+    pipeline_statistics: QueryPipelineStatisticFlags,
+) -> Vec<CommandBuffer> {
+    let handles: Vec<_> = chunks
+        .iter()
+        .zip(command_pools)
+        .map(|(chunk, command_pool)| {
+            let device = device.clone();
+            let chunk = Arc::clone(chunk);
+            let command_pool = *command_pool;
+            thread::spawn(move || {
+                let command_buffer = allocate_command_buffers(
+                    &device,
+                    command_pool,
+                    CommandBufferLevel::SECONDARY,
+                    1,
+                )[0];
+                let inheritance_info = create_inheritance_info(
+                    render_pass,
+                    0,
+                    Framebuffer::null(),
+                    pipeline_statistics,
+                );
+                begin_secondary_command_buffer(&device, command_buffer, &inheritance_info);
+                chunk(&device, command_buffer);
+                unsafe {
+                    device
+                        .end_command_buffer(command_buffer)
+                        .expect("Failed to record secondary command buffer ending");
+                }
+                command_buffer
+            })
+        })
+        .collect();
+
+    handles
+        .into_iter()
+        .map(|x| x.join().expect("Command buffer recording thread panicked"))
+        .collect()
+}
+
+pub fn create_inheritance_info(
+    render_pass: RenderPass,
+    subpass: u32,
+    framebuffer: Framebuffer,
+    pipeline_statistics: QueryPipelineStatisticFlags,
+) -> CommandBufferInheritanceInfo {
+    CommandBufferInheritanceInfo {
+        s_type: StructureType::COMMAND_BUFFER_INHERITANCE_INFO,
+        p_next: std::ptr::null(),
+        render_pass,
+        subpass,
+        framebuffer,
+        occlusion_query_enable: FALSE,
+        query_flags: QueryControlFlags::empty(),
+        pipeline_statistics,
+    }
+}
+
+fn begin_secondary_command_buffer(
+    device: &Device,
+    command_buffer: CommandBuffer,
+    inheritance_info: &CommandBufferInheritanceInfo,
+) {
+    let command_buffer_begin_info = CommandBufferBeginInfo {
+        s_type: StructureType::COMMAND_BUFFER_BEGIN_INFO,
+        p_next: std::ptr::null(),
+        p_inheritance_info: inheritance_info,
+        flags: CommandBufferUsageFlags::RENDER_PASS_CONTINUE
+            | CommandBufferUsageFlags::SIMULTANEOUS_USE,
+    };
+
+    unsafe {
         device
-            .end_command_buffer(*command_buffer)
-            .expect("Failed to record command buffer ending");
+            .begin_command_buffer(command_buffer, &command_buffer_begin_info)
+            .expect("Failed to begin recording secondary command buffer");
+    }
+}
+
+fn allocate_command_buffers(
+    device: &Device,
+    command_pool: CommandPool,
+    level: CommandBufferLevel,
+    command_buffer_count: u32,
+) -> Vec<CommandBuffer> {
+    let command_buffer_allocate_info = CommandBufferAllocateInfo {
+        s_type: StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+        p_next: std::ptr::null(),
+        command_buffer_count,
+        command_pool,
+        level,
+    };
+
+    unsafe {
+        device
+            .allocate_command_buffers(&command_buffer_allocate_info)
+            .expect("failed to create command buffers")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::fake_device;
+    use ash::vk::{Buffer, DeviceMemory, Handle};
+    use std::sync::Mutex;
+
+    #[test]
+    fn inheritance_info_targets_the_render_pass_and_subpass() {
+        let render_pass = RenderPass::from_raw(4);
+        let inheritance_info = create_inheritance_info(
+            render_pass,
+            1,
+            Framebuffer::null(),
+            QueryPipelineStatisticFlags::empty(),
+        );
+        assert_eq!(inheritance_info.render_pass, render_pass);
+        assert_eq!(inheritance_info.subpass, 1);
+        assert_eq!(inheritance_info.framebuffer, Framebuffer::null());
+    }
+
+    #[test]
+    fn record_parallel_records_each_chunk_on_its_own_thread() {
+        let device = fake_device::device();
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let chunks: Vec<RecordChunk> = (0..3)
+            .map(|i| {
+                let recorded = Arc::clone(&recorded);
+                Arc::new(move |_: &Device, _: CommandBuffer| {
+                    recorded.lock().unwrap().push((i, thread::current().id()));
+                }) as RecordChunk
+            })
+            .collect();
+        let command_pools: Vec<_> = (1..=3).map(CommandPool::from_raw).collect();
+
+        let command_buffers = record_parallel(
+            &device,
+            &command_pools,
+            RenderPass::from_raw(4),
+            &chunks,
+            QueryPipelineStatisticFlags::empty(),
+        );
+
+        assert_eq!(command_buffers.len(), 3);
+        let mut recorded = recorded.lock().unwrap().clone();
+        recorded.sort_by_key(|x| x.0);
+        assert_eq!(
+            recorded.iter().map(|x| x.0).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+        let main_thread = thread::current().id();
+        assert!(recorded.iter().all(|x| x.1 != main_thread));
+        assert!(recorded[0].1 != recorded[1].1 && recorded[1].1 != recorded[2].1);
+        assert!(recorded[0].1 != recorded[2].1);
+    }
+
+    fn mesh(handle: u64, draw_mode: DrawMode) -> Mesh {
+        Mesh {
+            vertex_buffer: Buffer::from_raw(handle),
+            vertex_buffer_memory: DeviceMemory::from_raw(handle),
+            index_buffer: Buffer::from_raw(handle + 1),
+            index_buffer_memory: DeviceMemory::from_raw(handle + 1),
+            index_offset: 0,
+            draw_mode,
+        }
+    }
+
+    #[test]
+    fn two_renderables_record_two_indexed_draws() {
+        let device = fake_device::device();
+        let renderables = [
+            Renderable::new(mesh(10, DrawMode::Indexed { index_count: 36 })),
+            Renderable::new(mesh(20, DrawMode::Indexed { index_count: 6 })),
+        ];
+        for renderable in &renderables {
+            record_renderable(
+                &device,
+                CommandBuffer::from_raw(1),
+                renderable,
+                PipelineLayout::from_raw(2),
+                DescriptorSet::from_raw(3),
+            );
+        }
+
+        let draws: Vec<_> = fake_device::take_calls()
+            .into_iter()
+            .filter(|x| x.0 == "vkCmdDrawIndexed")
+            .collect();
+        assert_eq!(
+            draws,
+            vec![("vkCmdDrawIndexed", 36), ("vkCmdDrawIndexed", 6)]
+        );
+    }
+
+    #[test]
+    fn consecutive_frames_clear_to_their_own_color_without_re_recording() {
+        let device = fake_device::device();
+        let (primary, secondary) = (CommandBuffer::from_raw(1), [CommandBuffer::from_raw(2)]);
+        for red in [0.25_f32, 0.75] {
+            let clear_color = ClearColorValue {
+                float32: [red, 0.0, 0.0, 1.0],
+            };
+            let clear_values = create_clear_values(clear_color, 1.0);
+            let frame = FrameRecording {
+                frame_index: 0,
+                render_pass: RenderPass::from_raw(3),
+                framebuffer: Framebuffer::from_raw(4),
+                surface_extent: Extent2D {
+                    width: 800,
+                    height: 600,
+                },
+                clear_values: &clear_values,
+                particle_system: None,
+                gpu_timer: None,
+                pipeline_stats_query: None,
+                secondary_command_buffers: &secondary,
+                screenshot: None,
+            };
+            record_frame_command_buffer(&device, primary, &frame, None);
+        }
+
+        let calls = fake_device::take_calls();
+        let clears: Vec<_> = calls
+            .iter()
+            .filter(|x| x.0 == "vkCmdBeginRenderPass")
+            .collect();
+        assert_eq!(
+            clears,
+            vec![
+                &("vkCmdBeginRenderPass", u64::from(0.25_f32.to_bits())),
+                &("vkCmdBeginRenderPass", u64::from(0.75_f32.to_bits())),
+            ]
+        );
+        assert!(calls
+            .iter()
+            .all(|x| x.0 != "vkBeginCommandBuffer" || x.1 == primary.as_raw()));
+        assert_eq!(
+            calls
+                .iter()
+                .filter(|x| **x == ("vkCmdExecuteCommands", 2))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn non_indexed_mesh_draws_without_an_index_buffer() {
+        let device = fake_device::device();
+        let mesh = mesh(10, DrawMode::NonIndexed { vertex_count: 3 });
+        record_mesh_draw(&device, CommandBuffer::from_raw(1), &mesh);
+
+        assert_eq!(fake_device::take_calls(), vec![("vkCmdDraw", 3)]);
     }
 }
@@ -1,4 +1,5 @@
 use super::vertex::Vertex;
+use ash::vk::{Extent2D, Format};
 
 pub struct ValidationInfo {
     pub is_enable: bool,
@@ -20,22 +21,37 @@ pub const DEVICE_EXTENSTIONS: DeviceExtension = DeviceExtension {
 
 pub const MAX_FRAMES_IN_FLIGHT: usize = 2;
 
+pub const DESCRIPTOR_SETS_PER_POOL: u32 = 16;
+
+pub const BENCHMARK_EXTENT: Extent2D = Extent2D {
+    width: 800,
+    height: 600,
+};
+
+pub const BENCHMARK_FORMAT: Format = Format::R8G8B8A8_UNORM;
+
+pub const LIGHT_DIRECTION: [f32; 4] = [-0.4, -0.3, -1.0, 0.0];
+
 pub const VERTICES_DATA: [Vertex; 4] = [
     Vertex {
         pos: [-0.5, -0.5],
         color: [0.0, 0.2, 0.2],
+        normal: [0.0, 0.0, 1.0],
     },
     Vertex {
         pos: [0.5, -0.5],
         color: [1.0, 1.0, 1.0],
+        normal: [0.0, 0.0, 1.0],
     },
     Vertex {
         pos: [0.5, 0.5],
         color: [1.0, 1.0, 1.0],
+        normal: [0.0, 0.0, 1.0],
     },
     Vertex {
         pos: [-0.5, 0.5],
         color: [0.0, 0.2, 0.2],
+        normal: [0.0, 0.0, 1.0],
     },
 ];
 
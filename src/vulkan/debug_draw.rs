@@ -0,0 +1,101 @@
+use super::buffer::PotatoBuffer;
+use super::vertex::Vertex;
+use ash::version::DeviceV1_0;
+use ash::vk::{
+    BufferUsageFlags, CommandBuffer, DescriptorSet, MemoryPropertyFlags,
+    PhysicalDeviceMemoryProperties, Pipeline, PipelineBindPoint, PipelineLayout,
+};
+use ash::Device;
+use std::rc::Rc;
+
+pub struct DebugLines {
+    buffer: Option<PotatoBuffer>,
+    pub vertex_count: u32,
+}
+
+impl DebugLines {
+    pub fn new() -> DebugLines {
+        DebugLines {
+            buffer: None,
+            vertex_count: 0,
+        }
+    }
+
+    pub fn upload(
+        &mut self,
+        device: &Rc<Device>,
+        device_memory_properties: &PhysicalDeviceMemoryProperties,
+        segments: &[(Vertex, Vertex)],
+    ) {
+        let vertices: Vec<Vertex> = segments
+            .iter()
+            .flat_map(|(start, end)| vec![start.clone(), end.clone()])
+            .collect();
+        self.vertex_count = vertices.len() as u32;
+        if vertices.is_empty() {
+            return;
+        }
+
+        let size = std::mem::size_of_val(vertices.as_slice()) as u64;
+        let is_too_small = match &self.buffer {
+            Some(buffer) => buffer.size < size,
+            None => true,
+        };
+        if is_too_small {
+            self.buffer = Some(PotatoBuffer::new(
+                device,
+                size,
+                BufferUsageFlags::VERTEX_BUFFER,
+                MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+                MemoryPropertyFlags::DEVICE_LOCAL,
+                device_memory_properties,
+            ));
+        }
+
+        if let Some(buffer) = &self.buffer {
+            unsafe {
+                let data_ptr = buffer.map::<Vertex>();
+                data_ptr.copy_from_nonoverlapping(vertices.as_ptr(), vertices.len());
+            }
+            buffer.unmap();
+        }
+    }
+
+    pub fn release(&mut self) {
+        self.buffer = None;
+        self.vertex_count = 0;
+    }
+
+    pub fn record_draw(
+        &self,
+        device: &Device,
+        command_buffer: CommandBuffer,
+        (pipeline, pipeline_layout): (Pipeline, PipelineLayout),
+        descriptor_set: DescriptorSet,
+    ) {
+        let buffer = match &self.buffer {
+            Some(buffer) if self.vertex_count > 0 => buffer,
+            _ => return,
+        };
+
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::GRAPHICS, pipeline);
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                PipelineBindPoint::GRAPHICS,
+                pipeline_layout,
+                0,
+                &[descriptor_set],
+                &[],
+            );
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[buffer.buffer], &[0]);
+            device.cmd_draw(command_buffer, self.vertex_count, 1, 0, 0);
+        }
+    }
+}
+
+impl Default for DebugLines {
+    fn default() -> Self {
+        DebugLines::new()
+    }
+}
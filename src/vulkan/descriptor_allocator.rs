@@ -0,0 +1,137 @@
+use super::error::PotatoError;
+use ash::version::DeviceV1_0;
+use ash::vk::{
+    DescriptorPool, DescriptorPoolCreateFlags, DescriptorPoolCreateInfo, DescriptorPoolSize,
+    DescriptorSet, DescriptorSetAllocateInfo, DescriptorSetLayout, DescriptorType, Result,
+    StructureType,
+};
+use ash::Device;
+use log::debug;
+
+pub struct DescriptorAllocator {
+    pools: Vec<DescriptorPool>,
+    sets_per_pool: u32,
+    pool_sizes: Vec<DescriptorPoolSize>,
+}
+
+impl DescriptorAllocator {
+    pub fn new(sets_per_pool: u32, descriptors_per_set: &[(DescriptorType, u32)]) -> Self {
+        DescriptorAllocator {
+            pools: vec![],
+            sets_per_pool,
+            pool_sizes: descriptors_per_set
+                .iter()
+                .map(|(ty, count)| DescriptorPoolSize {
+                    ty: *ty,
+                    descriptor_count: count * sets_per_pool,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn pool_count(&self) -> usize {
+        self.pools.len()
+    }
+
+    pub fn allocate(
+        &mut self,
+        device: &Device,
+        layout: DescriptorSetLayout,
+    ) -> std::result::Result<DescriptorSet, PotatoError> {
+        if let Some(pool) = self.pools.last() {
+            match allocate_descriptor_set(device, *pool, layout) {
+                Err(Result::ERROR_OUT_OF_POOL_MEMORY) | Err(Result::ERROR_FRAGMENTED_POOL) => {}
+                result => return result.map_err(PotatoError::from),
+            }
+        }
+
+        let pool = self.create_pool(device)?;
+        Ok(allocate_descriptor_set(device, pool, layout)?)
+    }
+
+    pub fn allocate_many(
+        &mut self,
+        device: &Device,
+        layout: DescriptorSetLayout,
+        count: usize,
+    ) -> std::result::Result<Vec<DescriptorSet>, PotatoError> {
+        (0..count).map(|_| self.allocate(device, layout)).collect()
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        for pool in &self.pools {
+            unsafe {
+                device.destroy_descriptor_pool(*pool, None);
+            }
+        }
+    }
+
+    fn create_pool(&mut self, device: &Device) -> std::result::Result<DescriptorPool, PotatoError> {
+        let descriptor_pool_create_info = DescriptorPoolCreateInfo {
+            s_type: StructureType::DESCRIPTOR_POOL_CREATE_INFO,
+            p_next: std::ptr::null(),
+            flags: DescriptorPoolCreateFlags::empty(),
+            max_sets: self.sets_per_pool,
+            pool_size_count: self.pool_sizes.len() as u32,
+            p_pool_sizes: self.pool_sizes.as_ptr(),
+        };
+
+        let pool = unsafe { device.create_descriptor_pool(&descriptor_pool_create_info, None)? };
+        self.pools.push(pool);
+        debug!("Created descriptor pool {}", self.pools.len());
+        Ok(pool)
+    }
+}
+
+fn allocate_descriptor_set(
+    device: &Device,
+    descriptor_pool: DescriptorPool,
+    layout: DescriptorSetLayout,
+) -> std::result::Result<DescriptorSet, Result> {
+    let layouts = [layout];
+    let descriptor_set_allocate_info = DescriptorSetAllocateInfo {
+        s_type: StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
+        p_next: std::ptr::null(),
+        descriptor_pool,
+        descriptor_set_count: layouts.len() as u32,
+        p_set_layouts: layouts.as_ptr(),
+    };
+
+    unsafe { Ok(device.allocate_descriptor_sets(&descriptor_set_allocate_info)?[0]) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::fake_device::{self, DESCRIPTOR_POOL};
+    use ash::vk::Handle;
+
+    #[test]
+    fn allocating_past_a_full_pool_creates_another() {
+        let device = fake_device::device();
+        let mut descriptor_allocator =
+            DescriptorAllocator::new(2, &[(DescriptorType::UNIFORM_BUFFER, 1)]);
+        let descriptor_sets = descriptor_allocator
+            .allocate_many(&device, DescriptorSetLayout::from_raw(1), 3)
+            .unwrap();
+
+        assert_eq!(
+            descriptor_sets
+                .iter()
+                .map(|x| x.as_raw())
+                .collect::<Vec<_>>(),
+            vec![DESCRIPTOR_POOL, DESCRIPTOR_POOL, DESCRIPTOR_POOL + 1]
+        );
+        assert_eq!(descriptor_allocator.pool_count(), 2);
+        assert_eq!(
+            fake_device::take_calls(),
+            vec![
+                ("vkCreateDescriptorPool", DESCRIPTOR_POOL),
+                ("vkAllocateDescriptorSets", DESCRIPTOR_POOL),
+                ("vkAllocateDescriptorSets", DESCRIPTOR_POOL),
+                ("vkCreateDescriptorPool", DESCRIPTOR_POOL + 1),
+                ("vkAllocateDescriptorSets", DESCRIPTOR_POOL + 1),
+            ]
+        );
+    }
+}
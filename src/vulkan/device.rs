@@ -2,45 +2,82 @@ use ash::Device;
 use ash::Instance;
 use ash::version::InstanceV1_0;
 use ash::version::DeviceV1_0;
-use ash::vk::{PhysicalDevice, StructureType, DeviceQueueCreateFlags, DeviceQueueCreateInfo, PhysicalDeviceFeatures, DeviceCreateInfo, DeviceCreateFlags};
+use ash::vk::{PhysicalDevice, StructureType, DeviceQueueCreateFlags, DeviceQueueCreateInfo, PhysicalDeviceFeatures, DeviceCreateInfo, DeviceCreateFlags, PolygonMode, FALSE, TRUE};
 use ash::extensions::khr::Swapchain;
 use super::queue_family::{find_graphical_queue_family, QueueFamily};
 use super::utilities::conver_str_vec_to_c_str_ptr_vec;
 use super::constants::VALIDATION;
+use super::error::PotatoError;
+use super::graphics_pipeline::PipelineConfig;
 use super::surface::PotatoSurface;
+use super::physical_device::find_available_extensions;
+use super::portability::append_portability_subset;
 use log::debug;
+use std::ffi::CStr;
 
-pub fn create_logical_device(instance: &Instance, physical_device: PhysicalDevice, surface: &PotatoSurface) -> (Device, QueueFamily){
+pub fn check_pipeline_features(pipeline_config: &PipelineConfig, supported_features: &PhysicalDeviceFeatures) -> Result<(), PotatoError> {
+    if pipeline_config.polygon_mode != PolygonMode::FILL && supported_features.fill_mode_non_solid != TRUE {
+        return Err(PotatoError::MissingDeviceFeature("fillModeNonSolid".to_string()));
+    }
+
+    if pipeline_config.line_width != 1.0 && supported_features.wide_lines != TRUE {
+        return Err(PotatoError::MissingDeviceFeature("wideLines".to_string()));
+    }
+
+    Ok(())
+}
+
+pub fn create_logical_device(instance: &Instance, physical_device: PhysicalDevice, surface: &PotatoSurface, pipeline_config: &PipelineConfig, optional_extensions: &[&CStr]) -> Result<(Device, QueueFamily, PhysicalDeviceFeatures), PotatoError>{
     let queue_family = find_graphical_queue_family(instance, physical_device, surface);
 
     let queue_priorities = [1.0_f32];
 
-    let queue_create_info = DeviceQueueCreateInfo {
-        s_type: StructureType::DEVICE_QUEUE_CREATE_INFO,
-        p_next: std::ptr::null(),
-        flags: DeviceQueueCreateFlags::empty(),
-        queue_family_index: queue_family.graphics_family.unwrap() as u32, 
-        p_queue_priorities: queue_priorities.as_ptr(),
-        queue_count: queue_priorities.len() as u32,
-    };
+    let queue_create_infos: Vec<DeviceQueueCreateInfo> = queue_family
+        .unique_families()
+        .into_iter()
+        .map(|x| DeviceQueueCreateInfo {
+            s_type: StructureType::DEVICE_QUEUE_CREATE_INFO,
+            p_next: std::ptr::null(),
+            flags: DeviceQueueCreateFlags::empty(),
+            queue_family_index: x,
+            p_queue_priorities: queue_priorities.as_ptr(),
+            queue_count: queue_priorities.len() as u32,
+        })
+        .collect();
+
+    let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
+
+    check_pipeline_features(pipeline_config, &supported_features)?;
+
+    // Stats queries span the secondary command buffers, so they need inherited queries too.
+    let is_pipeline_stats_supported = supported_features.pipeline_statistics_query == TRUE && supported_features.inherited_queries == TRUE;
 
     let physical_device_features = PhysicalDeviceFeatures {
+        sampler_anisotropy: supported_features.sampler_anisotropy,
+        fill_mode_non_solid: supported_features.fill_mode_non_solid,
+        wide_lines: if pipeline_config.line_width != 1.0 { supported_features.wide_lines } else { FALSE },
+        pipeline_statistics_query: if is_pipeline_stats_supported { TRUE } else { FALSE },
+        inherited_queries: if is_pipeline_stats_supported { TRUE } else { FALSE },
+        multi_draw_indirect: supported_features.multi_draw_indirect,
         ..Default::default()
     };
 
     let (cstring_vec, enable_layer_names) = conver_str_vec_to_c_str_ptr_vec(VALIDATION.required_validation_layers.to_vec());
     debug!("{:?}", cstring_vec);
 
-    let enable_extension_names = [
+    let mut enable_extension_names = vec![
         Swapchain::name().as_ptr(),
     ];
+    let mut optional_extensions = optional_extensions.to_vec();
+    append_portability_subset(&mut optional_extensions, &find_available_extensions(instance, physical_device));
+    enable_extension_names.extend(optional_extensions.iter().map(|x| x.as_ptr()));
 
     let device_create_info = DeviceCreateInfo {
         s_type: StructureType::DEVICE_CREATE_INFO,
         p_next: std::ptr::null(),
         flags: DeviceCreateFlags::empty(),
-        queue_create_info_count: 1,
-        p_queue_create_infos: &queue_create_info,
+        queue_create_info_count: queue_create_infos.len() as u32,
+        p_queue_create_infos: queue_create_infos.as_ptr(),
         enabled_layer_count: if VALIDATION.is_enable {
             enable_layer_names.len()
         } else {
@@ -64,5 +101,36 @@ pub fn create_logical_device(instance: &Instance, physical_device: PhysicalDevic
         device.get_device_queue(queue_family.graphics_family.unwrap() as u32, 0) 
     };
 
-    (device, queue_family)
-}
\ No newline at end of file
+    Ok((device, queue_family, physical_device_features))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wireframe_config() -> PipelineConfig {
+        PipelineConfig {
+            polygon_mode: PolygonMode::LINE,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn fill_mode_non_solid_is_only_needed_for_non_fill_modes() {
+        let unsupported = PhysicalDeviceFeatures::default();
+        assert!(check_pipeline_features(&PipelineConfig::default(), &unsupported).is_ok());
+        match check_pipeline_features(&wireframe_config(), &unsupported) {
+            Err(PotatoError::MissingDeviceFeature(feature)) => assert_eq!(feature, "fillModeNonSolid"),
+            result => panic!("Expected a missing feature error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn fill_mode_non_solid_allows_wireframe_when_supported() {
+        let supported = PhysicalDeviceFeatures {
+            fill_mode_non_solid: TRUE,
+            ..Default::default()
+        };
+        assert!(check_pipeline_features(&wireframe_config(), &supported).is_ok());
+    }
+}
@@ -0,0 +1,150 @@
+use ash::vk;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum PotatoError {
+    InvalidSpirv(String),
+    ShaderRead(String, std::io::Error),
+    ShaderCompile(String),
+    TextureRead(String, std::io::Error),
+    InvalidTexture(String),
+    NoSuitableDevice,
+    DeviceNotFound(String),
+    UnsuitableDevice(String),
+    MissingDeviceFeature(String),
+    MissingDeviceExtensions(Vec<String>),
+    MissingInstanceExtensions(Vec<String>),
+    MissingValidationLayer(String),
+    ResolveFormatMismatch(vk::Format, vk::Format),
+    SampleCountMismatch(vk::SampleCountFlags, vk::SampleCountFlags),
+    InvalidSubpassDependency(u32, u32),
+    InvalidRenderGraph(String),
+    UnsupportedLayoutTransition(vk::ImageLayout, vk::ImageLayout),
+    UnsupportedSwapchainUsage(vk::ImageUsageFlags),
+    NoSuitableMemoryType(u32, vk::MemoryPropertyFlags),
+    BenchmarkWithValidation,
+    DeviceLost,
+    ShutDown,
+    OutOfMemory(vk::Result),
+    Vulkan(vk::Result),
+}
+
+impl fmt::Display for PotatoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PotatoError::InvalidSpirv(reason) => write!(f, "Invalid SPIR-V: {}", reason),
+            PotatoError::ShaderRead(path, e) => write!(f, "Failed to read shader {}: {}", path, e),
+            PotatoError::ShaderCompile(reason) => write!(f, "Failed to compile shader: {}", reason),
+            PotatoError::TextureRead(path, e) => {
+                write!(f, "Failed to read texture {}: {}", path, e)
+            }
+            PotatoError::InvalidTexture(reason) => write!(f, "Invalid texture: {}", reason),
+            PotatoError::NoSuitableDevice => write!(f, "Failed to find a compatible device"),
+            PotatoError::DeviceNotFound(device) => write!(f, "No device matches {}", device),
+            PotatoError::UnsuitableDevice(name) => write!(
+                f,
+                "Requested device {} lacks the required queues, extensions or swapchain support",
+                name
+            ),
+            PotatoError::MissingDeviceFeature(feature) => {
+                write!(f, "Device does not support the {} feature", feature)
+            }
+            PotatoError::MissingDeviceExtensions(extensions) => write!(
+                f,
+                "Device is missing required extensions: {}",
+                extensions.join(", ")
+            ),
+            PotatoError::MissingInstanceExtensions(extensions) => write!(
+                f,
+                "Instance is missing requested extensions: {}",
+                extensions.join(", ")
+            ),
+            PotatoError::MissingValidationLayer(layer) => write!(
+                f,
+                "Validation layer {} is not installed (is the Vulkan SDK present?)",
+                layer
+            ),
+            PotatoError::ResolveFormatMismatch(color, resolve) => write!(
+                f,
+                "Resolve attachment format {:?} does not match color format {:?}",
+                resolve, color
+            ),
+            PotatoError::SampleCountMismatch(color, depth) => write!(
+                f,
+                "Depth attachment has {:?} samples but the color attachment has {:?}",
+                depth, color
+            ),
+            PotatoError::InvalidSubpassDependency(src, dst) => write!(
+                f,
+                "Subpass dependency {} -> {} references a subpass that does not exist",
+                src, dst
+            ),
+            PotatoError::InvalidRenderGraph(reason) => {
+                write!(f, "Invalid render graph: {}", reason)
+            }
+            PotatoError::UnsupportedLayoutTransition(old, new) => {
+                write!(f, "Unsupported image layout transition from {:?} to {:?}", old, new)
+            }
+            PotatoError::UnsupportedSwapchainUsage(usage) => {
+                write!(f, "Surface does not support swapchain usage {:?}", usage)
+            }
+            PotatoError::NoSuitableMemoryType(type_filter, required) => write!(
+                f,
+                "No memory type in {:#b} supports {:?}",
+                type_filter, required
+            ),
+            PotatoError::BenchmarkWithValidation => {
+                write!(f, "Benchmarking requires validation to be disabled with benchmark_mode()")
+            }
+            PotatoError::DeviceLost => write!(f, "The logical device was lost"),
+            PotatoError::ShutDown => write!(f, "The renderer has already been shut down"),
+            PotatoError::OutOfMemory(vk_result) => write!(f, "Out of memory: {}", vk_result),
+            PotatoError::Vulkan(vk_result) => write!(f, "Vulkan call failed: {}", vk_result),
+        }
+    }
+}
+
+impl std::error::Error for PotatoError {}
+
+impl From<vk::Result> for PotatoError {
+    fn from(vk_result: vk::Result) -> Self {
+        match vk_result {
+            vk::Result::ERROR_DEVICE_LOST => PotatoError::DeviceLost,
+            vk::Result::ERROR_OUT_OF_HOST_MEMORY | vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => {
+                PotatoError::OutOfMemory(vk_result)
+            }
+            _ => PotatoError::Vulkan(vk_result),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_lost_converts_to_device_lost() {
+        let error: PotatoError = vk::Result::ERROR_DEVICE_LOST.into();
+        assert!(matches!(error, PotatoError::DeviceLost));
+    }
+
+    #[test]
+    fn out_of_memory_results_convert_to_out_of_memory() {
+        for vk_result in &[
+            vk::Result::ERROR_OUT_OF_HOST_MEMORY,
+            vk::Result::ERROR_OUT_OF_DEVICE_MEMORY,
+        ] {
+            let error: PotatoError = (*vk_result).into();
+            assert!(matches!(error, PotatoError::OutOfMemory(x) if x == *vk_result));
+        }
+    }
+
+    #[test]
+    fn other_results_convert_to_vulkan() {
+        let error: PotatoError = vk::Result::ERROR_INITIALIZATION_FAILED.into();
+        assert!(matches!(
+            error,
+            PotatoError::Vulkan(vk::Result::ERROR_INITIALIZATION_FAILED)
+        ));
+    }
+}
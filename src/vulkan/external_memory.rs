@@ -0,0 +1,215 @@
+use super::buffer::find_memory_type;
+use super::error::PotatoError;
+use super::physical_device::{find_available_extensions, find_missing_extensions};
+#[cfg(unix)]
+use ash::extensions::khr::ExternalMemoryFd;
+use ash::version::DeviceV1_0;
+#[cfg(windows)]
+use ash::version::InstanceV1_0;
+#[cfg(unix)]
+use ash::vk::MemoryGetFdInfoKHR;
+use ash::vk::{
+    version_minor, Buffer, BufferCreateFlags, BufferCreateInfo, BufferUsageFlags, DeviceMemory,
+    DeviceSize, ExportMemoryAllocateInfo, ExternalMemoryBufferCreateInfo,
+    ExternalMemoryHandleTypeFlags, KhrExternalMemoryFn, MemoryAllocateInfo, MemoryPropertyFlags,
+    PhysicalDevice, PhysicalDeviceMemoryProperties, SharingMode, StructureType,
+};
+#[cfg(windows)]
+use ash::vk::{KhrExternalMemoryWin32Fn, MemoryGetWin32HandleInfoKHR, HANDLE};
+use ash::{Device, Instance};
+use std::ffi::CStr;
+use std::os::raw::c_void;
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+
+#[cfg(unix)]
+pub const EXTERNAL_MEMORY_HANDLE_TYPE: ExternalMemoryHandleTypeFlags =
+    ExternalMemoryHandleTypeFlags::EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD;
+#[cfg(windows)]
+pub const EXTERNAL_MEMORY_HANDLE_TYPE: ExternalMemoryHandleTypeFlags =
+    ExternalMemoryHandleTypeFlags::EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_WIN32;
+
+#[cfg(unix)]
+pub fn external_memory_extension_names() -> [&'static CStr; 2] {
+    [KhrExternalMemoryFn::name(), ExternalMemoryFd::name()]
+}
+
+#[cfg(windows)]
+pub fn external_memory_extension_names() -> [&'static CStr; 2] {
+    [
+        KhrExternalMemoryFn::name(),
+        KhrExternalMemoryWin32Fn::name(),
+    ]
+}
+
+// VK_KHR_external_memory_capabilities is core from 1.1, so older instances can't use this.
+pub fn is_external_memory_usable(
+    instance: &Instance,
+    physical_device: PhysicalDevice,
+    instance_api_version: u32,
+) -> bool {
+    let extension_names: Vec<String> = external_memory_extension_names()
+        .iter()
+        .map(|x| x.to_string_lossy().to_string())
+        .collect();
+    let extension_names: Vec<&str> = extension_names.iter().map(|x| x.as_str()).collect();
+    version_minor(instance_api_version) >= 1
+        && find_missing_extensions(
+            &extension_names,
+            &find_available_extensions(instance, physical_device),
+        )
+        .is_empty()
+}
+
+pub fn create_export_memory_allocate_info(
+    handle_types: ExternalMemoryHandleTypeFlags,
+) -> ExportMemoryAllocateInfo {
+    ExportMemoryAllocateInfo {
+        s_type: StructureType::EXPORT_MEMORY_ALLOCATE_INFO,
+        p_next: std::ptr::null(),
+        handle_types,
+    }
+}
+
+pub struct ExportableBuffer {
+    pub buffer: Buffer,
+    pub memory: DeviceMemory,
+    pub size: DeviceSize,
+    #[cfg(unix)]
+    external_memory_fd: ExternalMemoryFd,
+    #[cfg(windows)]
+    device: ash::vk::Device,
+    #[cfg(windows)]
+    external_memory_win32: KhrExternalMemoryWin32Fn,
+}
+
+impl ExportableBuffer {
+    pub fn new(
+        instance: &Instance,
+        device: &Device,
+        size: DeviceSize,
+        usage: BufferUsageFlags,
+        device_memory_properties: &PhysicalDeviceMemoryProperties,
+    ) -> Result<ExportableBuffer, PotatoError> {
+        let external_memory_buffer_create_info = ExternalMemoryBufferCreateInfo {
+            s_type: StructureType::EXTERNAL_MEMORY_BUFFER_CREATE_INFO,
+            p_next: std::ptr::null(),
+            handle_types: EXTERNAL_MEMORY_HANDLE_TYPE,
+        };
+        let buffer_create_info = BufferCreateInfo {
+            s_type: StructureType::BUFFER_CREATE_INFO,
+            p_next: &external_memory_buffer_create_info as *const ExternalMemoryBufferCreateInfo
+                as *const c_void,
+            flags: BufferCreateFlags::empty(),
+            size,
+            usage,
+            sharing_mode: SharingMode::EXCLUSIVE,
+            queue_family_index_count: 0,
+            p_queue_family_indices: std::ptr::null(),
+        };
+
+        let buffer = unsafe { device.create_buffer(&buffer_create_info, None)? };
+        let mem_requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+        let memory_type = find_memory_type(
+            mem_requirements.memory_type_bits,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+            MemoryPropertyFlags::empty(),
+            device_memory_properties,
+        )?;
+
+        let export_memory_allocate_info =
+            create_export_memory_allocate_info(EXTERNAL_MEMORY_HANDLE_TYPE);
+        let allocate_info = MemoryAllocateInfo {
+            s_type: StructureType::MEMORY_ALLOCATE_INFO,
+            p_next: &export_memory_allocate_info as *const ExportMemoryAllocateInfo
+                as *const c_void,
+            allocation_size: mem_requirements.size,
+            memory_type_index: memory_type,
+        };
+
+        let memory = unsafe { device.allocate_memory(&allocate_info, None)? };
+        unsafe { device.bind_buffer_memory(buffer, memory, 0)? };
+
+        Ok(ExportableBuffer {
+            buffer,
+            memory,
+            size: mem_requirements.size,
+            #[cfg(unix)]
+            external_memory_fd: ExternalMemoryFd::new(instance, device),
+            #[cfg(windows)]
+            device: device.handle(),
+            #[cfg(windows)]
+            external_memory_win32: KhrExternalMemoryWin32Fn::load(|name| unsafe {
+                std::mem::transmute(instance.get_device_proc_addr(device.handle(), name.as_ptr()))
+            }),
+        })
+    }
+
+    // The caller owns the returned fd and must close it or hand it to the importing API.
+    #[cfg(unix)]
+    pub fn export_memory_fd(&self) -> Result<RawFd, PotatoError> {
+        let get_fd_info = MemoryGetFdInfoKHR {
+            s_type: StructureType::MEMORY_GET_FD_INFO_KHR,
+            p_next: std::ptr::null(),
+            memory: self.memory,
+            handle_type: EXTERNAL_MEMORY_HANDLE_TYPE,
+        };
+        Ok(unsafe { self.external_memory_fd.get_memory_fd(&get_fd_info)? })
+    }
+
+    // The caller owns the returned handle and must close it with CloseHandle.
+    #[cfg(windows)]
+    pub fn export_memory_win32_handle(&self) -> Result<HANDLE, PotatoError> {
+        let get_handle_info = MemoryGetWin32HandleInfoKHR {
+            s_type: StructureType::MEMORY_GET_WIN32_HANDLE_INFO_KHR,
+            p_next: std::ptr::null(),
+            memory: self.memory,
+            handle_type: EXTERNAL_MEMORY_HANDLE_TYPE,
+        };
+        let mut handle = std::ptr::null_mut();
+        match self.external_memory_win32.get_memory_win32_handle_khr(
+            self.device,
+            &get_handle_info,
+            &mut handle,
+        ) {
+            ash::vk::Result::SUCCESS => Ok(handle),
+            result => Err(result.into()),
+        }
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        unsafe {
+            device.destroy_buffer(self.buffer, None);
+            device.free_memory(self.memory, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_allocate_info_carries_the_handle_types() {
+        let info = create_export_memory_allocate_info(EXTERNAL_MEMORY_HANDLE_TYPE);
+        assert_eq!(info.s_type, StructureType::EXPORT_MEMORY_ALLOCATE_INFO);
+        assert_eq!(info.handle_types, EXTERNAL_MEMORY_HANDLE_TYPE);
+        assert!(info.p_next.is_null());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_exports_opaque_fds() {
+        assert_eq!(
+            EXTERNAL_MEMORY_HANDLE_TYPE,
+            ExternalMemoryHandleTypeFlags::EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD
+        );
+        assert_eq!(
+            external_memory_extension_names(),
+            [
+                CStr::from_bytes_with_nul(b"VK_KHR_external_memory\0").unwrap(),
+                CStr::from_bytes_with_nul(b"VK_KHR_external_memory_fd\0").unwrap(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,685 @@
+//! Function tables for tests that drive real code paths without a driver. The entry points
+//! stubbed here append their name and the handle they were given to a per-thread log; every
+//! other entry point keeps ash's panicking placeholder.
+
+use ash::vk;
+use ash::vk::{AllocationCallbacks, Handle, InstanceFnV1_0, PFN_vkVoidFunction, StaticFn};
+use ash::{Device, EntryCustom, Instance};
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+
+thread_local! {
+    static CALLS: RefCell<Vec<(&'static str, u64)>> = const { RefCell::new(Vec::new()) };
+    // Sets each created descriptor pool can still hand out, indexed from DESCRIPTOR_POOL.
+    static DESCRIPTOR_POOLS: RefCell<Vec<u32>> = const { RefCell::new(Vec::new()) };
+}
+
+fn record(name: &'static str, handle: u64) {
+    CALLS.with(|x| x.borrow_mut().push((name, handle)));
+}
+
+pub fn take_calls() -> Vec<(&'static str, u64)> {
+    CALLS.with(|x| std::mem::take(&mut *x.borrow_mut()))
+}
+
+// Every vkDestroy* and vkFreeMemory share the (device, handle, allocator) shape.
+macro_rules! destroy_stubs {
+    ($($name:literal => $stub:ident),* $(,)?) => {
+        $(
+            extern "system" fn $stub(
+                _device: vk::Device,
+                handle: u64,
+                _p_allocator: *const AllocationCallbacks,
+            ) {
+                record($name, handle);
+            }
+        )*
+
+        fn destroy_stub(name: &[u8]) -> Option<*const ()> {
+            match name {
+                $(x if x == $name.as_bytes() => Some($stub as *const ()),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+destroy_stubs! {
+    "vkDestroyBuffer" => destroy_buffer,
+    "vkFreeMemory" => free_memory,
+    "vkDestroyImage" => destroy_image,
+    "vkDestroyImageView" => destroy_image_view,
+    "vkDestroyFramebuffer" => destroy_framebuffer,
+    "vkDestroyPipeline" => destroy_pipeline,
+    "vkDestroyPipelineLayout" => destroy_pipeline_layout,
+    "vkDestroyRenderPass" => destroy_render_pass,
+    "vkDestroyQueryPool" => destroy_query_pool,
+    "vkDestroyDescriptorPool" => destroy_descriptor_pool,
+    "vkDestroySwapchainKHR" => destroy_swapchain,
+    "vkDestroyFence" => destroy_fence,
+    "vkDestroySemaphore" => destroy_semaphore,
+    "vkDestroyCommandPool" => destroy_command_pool,
+}
+
+// Handles handed out by the creating stubs, so tests can follow them to their destroy call.
+pub const COMMAND_BUFFER: u64 = 0x10;
+pub const FENCE: u64 = 0x20;
+pub const DESCRIPTOR_POOL: u64 = 0x30;
+pub const IMAGE: u64 = 0x40;
+pub const MEMORY: u64 = 0x50;
+pub const IMAGE_VIEW: u64 = 0x60;
+pub const SHADER_MODULE: u64 = 0x70;
+pub const SWAPCHAIN: u64 = 0x80;
+pub const BUFFER: u64 = 0x90;
+
+// The surface leaves the extent to the swapchain, within these bounds.
+pub const SURFACE_MIN_EXTENT: vk::Extent2D = vk::Extent2D {
+    width: 100,
+    height: 100,
+};
+pub const SURFACE_MAX_EXTENT: vk::Extent2D = vk::Extent2D {
+    width: 1920,
+    height: 1080,
+};
+
+extern "system" fn destroy_device(device: vk::Device, _p_allocator: *const AllocationCallbacks) {
+    record("vkDestroyDevice", device.as_raw());
+}
+
+extern "system" fn device_wait_idle(device: vk::Device) -> vk::Result {
+    record("vkDeviceWaitIdle", device.as_raw());
+    vk::Result::SUCCESS
+}
+
+extern "system" fn allocate_command_buffers(
+    _device: vk::Device,
+    p_allocate_info: *const vk::CommandBufferAllocateInfo,
+    p_command_buffers: *mut vk::CommandBuffer,
+) -> vk::Result {
+    let count = unsafe { (*p_allocate_info).command_buffer_count };
+    for i in 0..count {
+        let handle = COMMAND_BUFFER + u64::from(i);
+        unsafe { *p_command_buffers.add(i as usize) = vk::CommandBuffer::from_raw(handle) };
+        record("vkAllocateCommandBuffers", handle);
+    }
+    vk::Result::SUCCESS
+}
+
+extern "system" fn free_command_buffers(
+    _device: vk::Device,
+    _command_pool: vk::CommandPool,
+    command_buffer_count: u32,
+    p_command_buffers: *const vk::CommandBuffer,
+) {
+    for i in 0..command_buffer_count as usize {
+        record(
+            "vkFreeCommandBuffers",
+            unsafe { *p_command_buffers.add(i) }.as_raw(),
+        );
+    }
+}
+
+extern "system" fn begin_command_buffer(
+    command_buffer: vk::CommandBuffer,
+    _p_begin_info: *const vk::CommandBufferBeginInfo,
+) -> vk::Result {
+    record("vkBeginCommandBuffer", command_buffer.as_raw());
+    vk::Result::SUCCESS
+}
+
+extern "system" fn end_command_buffer(command_buffer: vk::CommandBuffer) -> vk::Result {
+    record("vkEndCommandBuffer", command_buffer.as_raw());
+    vk::Result::SUCCESS
+}
+
+extern "system" fn create_fence(
+    _device: vk::Device,
+    _p_create_info: *const vk::FenceCreateInfo,
+    _p_allocator: *const AllocationCallbacks,
+    p_fence: *mut vk::Fence,
+) -> vk::Result {
+    unsafe { *p_fence = vk::Fence::from_raw(FENCE) };
+    record("vkCreateFence", FENCE);
+    vk::Result::SUCCESS
+}
+
+extern "system" fn queue_submit(
+    _queue: vk::Queue,
+    submit_count: u32,
+    p_submits: *const vk::SubmitInfo,
+    fence: vk::Fence,
+) -> vk::Result {
+    for i in 0..submit_count as usize {
+        let submit_info = unsafe { &*p_submits.add(i) };
+        for j in 0..submit_info.command_buffer_count as usize {
+            let command_buffer = unsafe { *submit_info.p_command_buffers.add(j) };
+            record("vkQueueSubmit", command_buffer.as_raw());
+        }
+    }
+    record("vkQueueSubmit", fence.as_raw());
+    vk::Result::SUCCESS
+}
+
+extern "system" fn wait_for_fences(
+    _device: vk::Device,
+    fence_count: u32,
+    p_fences: *const vk::Fence,
+    _wait_all: vk::Bool32,
+    _timeout: u64,
+) -> vk::Result {
+    for i in 0..fence_count as usize {
+        record("vkWaitForFences", unsafe { *p_fences.add(i) }.as_raw());
+    }
+    vk::Result::SUCCESS
+}
+
+extern "system" fn cmd_bind_vertex_buffers(
+    _command_buffer: vk::CommandBuffer,
+    _first_binding: u32,
+    binding_count: u32,
+    p_buffers: *const vk::Buffer,
+    _p_offsets: *const vk::DeviceSize,
+) {
+    for i in 0..binding_count as usize {
+        record(
+            "vkCmdBindVertexBuffers",
+            unsafe { *p_buffers.add(i) }.as_raw(),
+        );
+    }
+}
+
+extern "system" fn cmd_bind_descriptor_sets(
+    _command_buffer: vk::CommandBuffer,
+    _pipeline_bind_point: vk::PipelineBindPoint,
+    _layout: vk::PipelineLayout,
+    _first_set: u32,
+    descriptor_set_count: u32,
+    p_descriptor_sets: *const vk::DescriptorSet,
+    _dynamic_offset_count: u32,
+    _p_dynamic_offsets: *const u32,
+) {
+    for i in 0..descriptor_set_count as usize {
+        record(
+            "vkCmdBindDescriptorSets",
+            unsafe { *p_descriptor_sets.add(i) }.as_raw(),
+        );
+    }
+}
+
+extern "system" fn cmd_push_constants(
+    _command_buffer: vk::CommandBuffer,
+    layout: vk::PipelineLayout,
+    _stage_flags: vk::ShaderStageFlags,
+    _offset: u32,
+    _size: u32,
+    _p_values: *const c_void,
+) {
+    record("vkCmdPushConstants", layout.as_raw());
+}
+
+extern "system" fn cmd_bind_index_buffer(
+    _command_buffer: vk::CommandBuffer,
+    buffer: vk::Buffer,
+    _offset: vk::DeviceSize,
+    _index_type: vk::IndexType,
+) {
+    record("vkCmdBindIndexBuffer", buffer.as_raw());
+}
+
+// Draws log their element count in place of a handle.
+extern "system" fn cmd_draw(
+    _command_buffer: vk::CommandBuffer,
+    vertex_count: u32,
+    _instance_count: u32,
+    _first_vertex: u32,
+    _first_instance: u32,
+) {
+    record("vkCmdDraw", u64::from(vertex_count));
+}
+
+extern "system" fn cmd_draw_indexed(
+    _command_buffer: vk::CommandBuffer,
+    index_count: u32,
+    _instance_count: u32,
+    _first_index: u32,
+    _vertex_offset: i32,
+    _first_instance: u32,
+) {
+    record("vkCmdDrawIndexed", u64::from(index_count));
+}
+
+// Logs the bits of the first clear value's red channel, so tests can tell frames apart.
+extern "system" fn cmd_begin_render_pass(
+    _command_buffer: vk::CommandBuffer,
+    p_render_pass_begin: *const vk::RenderPassBeginInfo,
+    _contents: vk::SubpassContents,
+) {
+    let red = unsafe { (*(*p_render_pass_begin).p_clear_values).color.float32[0] };
+    record("vkCmdBeginRenderPass", u64::from(red.to_bits()));
+}
+
+extern "system" fn cmd_execute_commands(
+    _command_buffer: vk::CommandBuffer,
+    command_buffer_count: u32,
+    p_command_buffers: *const vk::CommandBuffer,
+) {
+    for i in 0..command_buffer_count as usize {
+        record(
+            "vkCmdExecuteCommands",
+            unsafe { *p_command_buffers.add(i) }.as_raw(),
+        );
+    }
+}
+
+extern "system" fn cmd_end_render_pass(command_buffer: vk::CommandBuffer) {
+    record("vkCmdEndRenderPass", command_buffer.as_raw());
+}
+
+extern "system" fn create_descriptor_pool(
+    _device: vk::Device,
+    p_create_info: *const vk::DescriptorPoolCreateInfo,
+    _p_allocator: *const AllocationCallbacks,
+    p_descriptor_pool: *mut vk::DescriptorPool,
+) -> vk::Result {
+    let handle = DESCRIPTOR_POOLS.with(|x| {
+        let mut pools = x.borrow_mut();
+        pools.push(unsafe { (*p_create_info).max_sets });
+        DESCRIPTOR_POOL + pools.len() as u64 - 1
+    });
+    unsafe { *p_descriptor_pool = vk::DescriptorPool::from_raw(handle) };
+    record("vkCreateDescriptorPool", handle);
+    vk::Result::SUCCESS
+}
+
+extern "system" fn allocate_descriptor_sets(
+    _device: vk::Device,
+    p_allocate_info: *const vk::DescriptorSetAllocateInfo,
+    p_descriptor_sets: *mut vk::DescriptorSet,
+) -> vk::Result {
+    let allocate_info = unsafe { &*p_allocate_info };
+    let pool = allocate_info.descriptor_pool.as_raw();
+    let count = allocate_info.descriptor_set_count;
+    let has_room = DESCRIPTOR_POOLS.with(|x| {
+        let remaining = &mut x.borrow_mut()[(pool - DESCRIPTOR_POOL) as usize];
+        let has_room = *remaining >= count;
+        if has_room {
+            *remaining -= count;
+        }
+        has_room
+    });
+    if !has_room {
+        return vk::Result::ERROR_OUT_OF_POOL_MEMORY;
+    }
+    // Sets share their pool's handle, which is all tests need to tell pools apart.
+    for i in 0..count as usize {
+        unsafe { *p_descriptor_sets.add(i) = vk::DescriptorSet::from_raw(pool) };
+    }
+    record("vkAllocateDescriptorSets", pool);
+    vk::Result::SUCCESS
+}
+
+// Logs the requested usage flags in place of a handle, since that is what callers choose.
+// Each buffer gets its own handle, counted from BUFFER.
+extern "system" fn create_buffer(
+    _device: vk::Device,
+    _p_create_info: *const vk::BufferCreateInfo,
+    _p_allocator: *const AllocationCallbacks,
+    p_buffer: *mut vk::Buffer,
+) -> vk::Result {
+    let created = CALLS.with(|x| {
+        x.borrow()
+            .iter()
+            .filter(|(name, _)| *name == "vkCreateBuffer")
+            .count() as u64
+    });
+    unsafe { *p_buffer = vk::Buffer::from_raw(BUFFER + created) };
+    record("vkCreateBuffer", BUFFER + created);
+    vk::Result::SUCCESS
+}
+
+extern "system" fn get_buffer_memory_requirements(
+    _device: vk::Device,
+    buffer: vk::Buffer,
+    p_memory_requirements: *mut vk::MemoryRequirements,
+) {
+    unsafe {
+        *p_memory_requirements = vk::MemoryRequirements {
+            size: 256,
+            alignment: 1,
+            memory_type_bits: !0,
+        }
+    };
+    record("vkGetBufferMemoryRequirements", buffer.as_raw());
+}
+
+extern "system" fn bind_buffer_memory(
+    _device: vk::Device,
+    buffer: vk::Buffer,
+    _memory: vk::DeviceMemory,
+    _memory_offset: vk::DeviceSize,
+) -> vk::Result {
+    record("vkBindBufferMemory", buffer.as_raw());
+    vk::Result::SUCCESS
+}
+
+// Logs the buffer behind each buffer descriptor written.
+extern "system" fn update_descriptor_sets(
+    _device: vk::Device,
+    descriptor_write_count: u32,
+    p_descriptor_writes: *const vk::WriteDescriptorSet,
+    _descriptor_copy_count: u32,
+    _p_descriptor_copies: *const vk::CopyDescriptorSet,
+) {
+    let descriptor_writes =
+        unsafe { std::slice::from_raw_parts(p_descriptor_writes, descriptor_write_count as usize) };
+    descriptor_writes
+        .iter()
+        .filter(|x| !x.p_buffer_info.is_null())
+        .for_each(|x| {
+            record("vkUpdateDescriptorSets", unsafe {
+                (*x.p_buffer_info).buffer.as_raw()
+            })
+        });
+}
+
+extern "system" fn create_image(
+    _device: vk::Device,
+    p_create_info: *const vk::ImageCreateInfo,
+    _p_allocator: *const AllocationCallbacks,
+    p_image: *mut vk::Image,
+) -> vk::Result {
+    unsafe { *p_image = vk::Image::from_raw(IMAGE) };
+    record(
+        "vkCreateImage",
+        u64::from(unsafe { (*p_create_info).usage }.as_raw()),
+    );
+    vk::Result::SUCCESS
+}
+
+extern "system" fn get_image_memory_requirements(
+    _device: vk::Device,
+    image: vk::Image,
+    p_memory_requirements: *mut vk::MemoryRequirements,
+) {
+    unsafe {
+        *p_memory_requirements = vk::MemoryRequirements {
+            size: 256,
+            alignment: 1,
+            memory_type_bits: !0,
+        }
+    };
+    record("vkGetImageMemoryRequirements", image.as_raw());
+}
+
+extern "system" fn allocate_memory(
+    _device: vk::Device,
+    _p_allocate_info: *const vk::MemoryAllocateInfo,
+    _p_allocator: *const AllocationCallbacks,
+    p_memory: *mut vk::DeviceMemory,
+) -> vk::Result {
+    unsafe { *p_memory = vk::DeviceMemory::from_raw(MEMORY) };
+    record("vkAllocateMemory", MEMORY);
+    vk::Result::SUCCESS
+}
+
+extern "system" fn bind_image_memory(
+    _device: vk::Device,
+    image: vk::Image,
+    _memory: vk::DeviceMemory,
+    _memory_offset: vk::DeviceSize,
+) -> vk::Result {
+    record("vkBindImageMemory", image.as_raw());
+    vk::Result::SUCCESS
+}
+
+extern "system" fn create_image_view(
+    _device: vk::Device,
+    _p_create_info: *const vk::ImageViewCreateInfo,
+    _p_allocator: *const AllocationCallbacks,
+    p_view: *mut vk::ImageView,
+) -> vk::Result {
+    unsafe { *p_view = vk::ImageView::from_raw(IMAGE_VIEW) };
+    record("vkCreateImageView", IMAGE_VIEW);
+    vk::Result::SUCCESS
+}
+
+extern "system" fn destroy_debug_utils_messenger(
+    _instance: vk::Instance,
+    messenger: vk::DebugUtilsMessengerEXT,
+    _p_allocator: *const AllocationCallbacks,
+) {
+    record("vkDestroyDebugUtilsMessengerEXT", messenger.as_raw());
+}
+
+extern "system" fn destroy_surface(
+    _instance: vk::Instance,
+    surface: vk::SurfaceKHR,
+    _p_allocator: *const AllocationCallbacks,
+) {
+    record("vkDestroySurfaceKHR", surface.as_raw());
+}
+
+// Logs the first code word, which for a valid module is the SPIR-V magic number.
+extern "system" fn create_shader_module(
+    _device: vk::Device,
+    p_create_info: *const vk::ShaderModuleCreateInfo,
+    _p_allocator: *const AllocationCallbacks,
+    p_shader_module: *mut vk::ShaderModule,
+) -> vk::Result {
+    unsafe { *p_shader_module = vk::ShaderModule::from_raw(SHADER_MODULE) };
+    record(
+        "vkCreateShaderModule",
+        u64::from(unsafe { *(*p_create_info).p_code }),
+    );
+    vk::Result::SUCCESS
+}
+
+// Every format can be a depth attachment except D24_UNORM_S8_UINT, which some vendors lack.
+extern "system" fn get_physical_device_format_properties(
+    _physical_device: vk::PhysicalDevice,
+    format: vk::Format,
+    p_format_properties: *mut vk::FormatProperties,
+) {
+    let optimal_tiling_features = if format == vk::Format::D24_UNORM_S8_UINT {
+        vk::FormatFeatureFlags::empty()
+    } else {
+        vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+    };
+    unsafe {
+        *p_format_properties = vk::FormatProperties {
+            optimal_tiling_features,
+            ..Default::default()
+        }
+    };
+}
+
+// Fills a caller's buffer the way every two-call vkGet*/vkEnumerate* entry point does.
+unsafe fn write_array<T: Copy>(items: &[T], p_count: *mut u32, p_items: *mut T) -> vk::Result {
+    if !p_items.is_null() {
+        p_items.copy_from_nonoverlapping(items.as_ptr(), items.len());
+    }
+    *p_count = items.len() as u32;
+    vk::Result::SUCCESS
+}
+
+extern "system" fn get_physical_device_surface_capabilities(
+    _physical_device: vk::PhysicalDevice,
+    _surface: vk::SurfaceKHR,
+    p_surface_capabilities: *mut vk::SurfaceCapabilitiesKHR,
+) -> vk::Result {
+    unsafe {
+        *p_surface_capabilities = vk::SurfaceCapabilitiesKHR {
+            min_image_count: 2,
+            max_image_count: 3,
+            current_extent: vk::Extent2D {
+                width: u32::MAX,
+                height: u32::MAX,
+            },
+            min_image_extent: SURFACE_MIN_EXTENT,
+            max_image_extent: SURFACE_MAX_EXTENT,
+            max_image_array_layers: 1,
+            supported_usage_flags: vk::ImageUsageFlags::COLOR_ATTACHMENT
+                | vk::ImageUsageFlags::TRANSFER_SRC,
+            ..Default::default()
+        }
+    };
+    vk::Result::SUCCESS
+}
+
+extern "system" fn get_physical_device_surface_formats(
+    _physical_device: vk::PhysicalDevice,
+    _surface: vk::SurfaceKHR,
+    p_surface_format_count: *mut u32,
+    p_surface_formats: *mut vk::SurfaceFormatKHR,
+) -> vk::Result {
+    let formats = [vk::SurfaceFormatKHR {
+        format: vk::Format::B8G8R8A8_SRGB,
+        color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+    }];
+    unsafe { write_array(&formats, p_surface_format_count, p_surface_formats) }
+}
+
+extern "system" fn get_physical_device_surface_present_modes(
+    _physical_device: vk::PhysicalDevice,
+    _surface: vk::SurfaceKHR,
+    p_present_mode_count: *mut u32,
+    p_present_modes: *mut vk::PresentModeKHR,
+) -> vk::Result {
+    let present_modes = [vk::PresentModeKHR::FIFO];
+    unsafe { write_array(&present_modes, p_present_mode_count, p_present_modes) }
+}
+
+// Logs the requested image count; the swapchain then holds exactly that many images.
+extern "system" fn create_swapchain(
+    _device: vk::Device,
+    p_create_info: *const vk::SwapchainCreateInfoKHR,
+    _p_allocator: *const AllocationCallbacks,
+    p_swapchain: *mut vk::SwapchainKHR,
+) -> vk::Result {
+    unsafe { *p_swapchain = vk::SwapchainKHR::from_raw(SWAPCHAIN) };
+    record(
+        "vkCreateSwapchainKHR",
+        u64::from(unsafe { (*p_create_info).min_image_count }),
+    );
+    vk::Result::SUCCESS
+}
+
+extern "system" fn get_swapchain_images(
+    _device: vk::Device,
+    _swapchain: vk::SwapchainKHR,
+    p_swapchain_image_count: *mut u32,
+    p_swapchain_images: *mut vk::Image,
+) -> vk::Result {
+    let image_count = CALLS.with(|x| {
+        x.borrow()
+            .iter()
+            .rev()
+            .find(|(name, _)| *name == "vkCreateSwapchainKHR")
+            .map_or(0, |(_, image_count)| *image_count)
+    });
+    let images: Vec<_> = (0..image_count)
+        .map(|x| vk::Image::from_raw(IMAGE + x))
+        .collect();
+    unsafe { write_array(&images, p_swapchain_image_count, p_swapchain_images) }
+}
+
+fn to_void_function(function: *const ()) -> PFN_vkVoidFunction {
+    Some(unsafe {
+        std::mem::transmute::<*const (), unsafe extern "system" fn() -> c_void>(function)
+    })
+}
+
+extern "system" fn get_device_proc_addr(
+    _device: vk::Device,
+    p_name: *const c_char,
+) -> PFN_vkVoidFunction {
+    let function = match unsafe { CStr::from_ptr(p_name) }.to_bytes() {
+        b"vkDestroyDevice" => Some(destroy_device as *const ()),
+        b"vkDeviceWaitIdle" => Some(device_wait_idle as *const ()),
+        b"vkAllocateCommandBuffers" => Some(allocate_command_buffers as *const ()),
+        b"vkFreeCommandBuffers" => Some(free_command_buffers as *const ()),
+        b"vkBeginCommandBuffer" => Some(begin_command_buffer as *const ()),
+        b"vkEndCommandBuffer" => Some(end_command_buffer as *const ()),
+        b"vkCreateFence" => Some(create_fence as *const ()),
+        b"vkQueueSubmit" => Some(queue_submit as *const ()),
+        b"vkWaitForFences" => Some(wait_for_fences as *const ()),
+        b"vkCreateBuffer" => Some(create_buffer as *const ()),
+        b"vkGetBufferMemoryRequirements" => Some(get_buffer_memory_requirements as *const ()),
+        b"vkBindBufferMemory" => Some(bind_buffer_memory as *const ()),
+        b"vkUpdateDescriptorSets" => Some(update_descriptor_sets as *const ()),
+        b"vkCreateImage" => Some(create_image as *const ()),
+        b"vkGetImageMemoryRequirements" => Some(get_image_memory_requirements as *const ()),
+        b"vkAllocateMemory" => Some(allocate_memory as *const ()),
+        b"vkBindImageMemory" => Some(bind_image_memory as *const ()),
+        b"vkCreateImageView" => Some(create_image_view as *const ()),
+        b"vkCreateShaderModule" => Some(create_shader_module as *const ()),
+        b"vkCreateDescriptorPool" => Some(create_descriptor_pool as *const ()),
+        b"vkAllocateDescriptorSets" => Some(allocate_descriptor_sets as *const ()),
+        b"vkCmdBindVertexBuffers" => Some(cmd_bind_vertex_buffers as *const ()),
+        b"vkCmdBindDescriptorSets" => Some(cmd_bind_descriptor_sets as *const ()),
+        b"vkCmdPushConstants" => Some(cmd_push_constants as *const ()),
+        b"vkCmdBindIndexBuffer" => Some(cmd_bind_index_buffer as *const ()),
+        b"vkCmdDraw" => Some(cmd_draw as *const ()),
+        b"vkCmdDrawIndexed" => Some(cmd_draw_indexed as *const ()),
+        b"vkCmdBeginRenderPass" => Some(cmd_begin_render_pass as *const ()),
+        b"vkCmdExecuteCommands" => Some(cmd_execute_commands as *const ()),
+        b"vkCmdEndRenderPass" => Some(cmd_end_render_pass as *const ()),
+        b"vkCreateSwapchainKHR" => Some(create_swapchain as *const ()),
+        b"vkGetSwapchainImagesKHR" => Some(get_swapchain_images as *const ()),
+        name => destroy_stub(name),
+    };
+    function.and_then(to_void_function)
+}
+
+extern "system" fn get_instance_proc_addr(
+    _instance: vk::Instance,
+    p_name: *const c_char,
+) -> PFN_vkVoidFunction {
+    match unsafe { CStr::from_ptr(p_name) }.to_bytes() {
+        b"vkGetInstanceProcAddr" => to_void_function(get_instance_proc_addr as *const ()),
+        b"vkGetDeviceProcAddr" => to_void_function(get_device_proc_addr as *const ()),
+        b"vkDestroySurfaceKHR" => to_void_function(destroy_surface as *const ()),
+        b"vkGetPhysicalDeviceFormatProperties" => {
+            to_void_function(get_physical_device_format_properties as *const ())
+        }
+        b"vkGetPhysicalDeviceSurfaceCapabilitiesKHR" => {
+            to_void_function(get_physical_device_surface_capabilities as *const ())
+        }
+        b"vkGetPhysicalDeviceSurfaceFormatsKHR" => {
+            to_void_function(get_physical_device_surface_formats as *const ())
+        }
+        b"vkGetPhysicalDeviceSurfacePresentModesKHR" => {
+            to_void_function(get_physical_device_surface_present_modes as *const ())
+        }
+        b"vkDestroyDebugUtilsMessengerEXT" => {
+            to_void_function(destroy_debug_utils_messenger as *const ())
+        }
+        _ => None,
+    }
+}
+
+fn static_fn() -> StaticFn {
+    StaticFn {
+        get_instance_proc_addr,
+    }
+}
+
+pub fn entry() -> EntryCustom<()> {
+    EntryCustom::new_custom((), |_, name| {
+        get_instance_proc_addr(vk::Instance::null(), name.as_ptr())
+            .map_or(std::ptr::null(), |x| x as *const c_void)
+    })
+}
+
+pub fn instance() -> Instance {
+    unsafe { Instance::load(&static_fn(), vk::Instance::from_raw(1)) }
+}
+
+pub fn device() -> Device {
+    let instance_fn = InstanceFnV1_0 {
+        get_device_proc_addr,
+        ..InstanceFnV1_0::load(|_| std::ptr::null())
+    };
+    unsafe { Device::load(&instance_fn, vk::Device::from_raw(1)) }
+}
@@ -0,0 +1,146 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+const GPU_BOUND_FRACTION: f32 = 0.9;
+const SPIN_WAIT_MARGIN: Duration = Duration::from_millis(1);
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameStats {
+    pub frame_ms: f32,
+    pub fence_wait_ms: f32,
+    pub gpu_ms: Option<f32>,
+    pub gpu_bound: bool,
+    pub samples: u32,
+}
+
+impl FrameStats {
+    pub fn new(frame_time: Duration, fence_wait: Duration, gpu_ms: Option<f32>) -> FrameStats {
+        let frame_ms = frame_time.as_secs_f32() * 1000.0;
+        let fence_wait_ms = fence_wait.as_secs_f32() * 1000.0;
+        FrameStats {
+            frame_ms,
+            fence_wait_ms,
+            gpu_ms,
+            gpu_bound: is_gpu_bound(frame_ms, gpu_ms),
+            samples: 1,
+        }
+    }
+
+    pub fn average(samples: &[FrameStats]) -> FrameStats {
+        if samples.is_empty() {
+            return FrameStats::default();
+        }
+        let count = samples.len() as f32;
+        let frame_ms = samples.iter().map(|x| x.frame_ms).sum::<f32>() / count;
+        let fence_wait_ms = samples.iter().map(|x| x.fence_wait_ms).sum::<f32>() / count;
+        let gpu_times: Vec<f32> = samples.iter().filter_map(|x| x.gpu_ms).collect();
+        let gpu_ms = if gpu_times.is_empty() {
+            None
+        } else {
+            Some(gpu_times.iter().sum::<f32>() / gpu_times.len() as f32)
+        };
+        FrameStats {
+            frame_ms,
+            fence_wait_ms,
+            gpu_ms,
+            gpu_bound: is_gpu_bound(frame_ms, gpu_ms),
+            samples: samples.len() as u32,
+        }
+    }
+}
+
+// Time blocked on a fence also counts presentation and CPU stalls, so only the GPU's own
+// timestamps decide; without them a frame is never reported as GPU bound.
+pub fn is_gpu_bound(frame_ms: f32, gpu_ms: Option<f32>) -> bool {
+    frame_ms > 0.0 && gpu_ms.is_some_and(|x| x >= frame_ms * GPU_BOUND_FRACTION)
+}
+
+pub fn target_frame_time(max_fps: u32) -> Option<Duration> {
+    if max_fps == 0 {
+        None
+    } else {
+        Some(Duration::from_secs(1) / max_fps)
+    }
+}
+
+pub fn frame_limit_sleep(target_frame_time: Duration, elapsed: Duration) -> Duration {
+    target_frame_time
+        .checked_sub(elapsed)
+        .unwrap_or_else(|| Duration::from_secs(0))
+}
+
+pub fn wait_until(deadline: Instant) {
+    let now = Instant::now();
+    if deadline <= now {
+        return;
+    }
+    let remaining = deadline - now;
+    if remaining > SPIN_WAIT_MARGIN {
+        thread::sleep(remaining - SPIN_WAIT_MARGIN);
+    }
+    while Instant::now() < deadline {
+        thread::yield_now();
+    }
+}
+
+pub fn frame_time_since(last_frame_start: Option<Instant>, now: Instant) -> Duration {
+    match last_frame_start {
+        Some(last_frame_start) => now.duration_since(last_frame_start),
+        None => Duration::from_secs(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_limit_sleep_skips_when_over_budget() {
+        let target = Duration::from_millis(16);
+        assert_eq!(frame_limit_sleep(target, Duration::from_millis(20)), Duration::from_secs(0));
+        assert_eq!(frame_limit_sleep(target, target), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn frame_limit_sleep_returns_remaining_budget() {
+        let target = Duration::from_millis(16);
+        assert_eq!(frame_limit_sleep(target, Duration::from_millis(10)), Duration::from_millis(6));
+    }
+
+    #[test]
+    fn average_counts_and_averages_samples() {
+        let samples = [
+            FrameStats::new(Duration::from_millis(10), Duration::from_millis(2), Some(4.0)),
+            FrameStats::new(Duration::from_millis(20), Duration::from_millis(4), Some(8.0)),
+            FrameStats::new(Duration::from_millis(30), Duration::from_millis(6), None),
+        ];
+        let average = FrameStats::average(&samples);
+        assert_eq!(average.samples, 3);
+        assert!((average.frame_ms - 20.0).abs() < 1e-3);
+        assert!((average.fence_wait_ms - 4.0).abs() < 1e-3);
+        assert_eq!(average.gpu_ms, Some(6.0));
+        assert!(!average.gpu_bound);
+    }
+
+    #[test]
+    fn average_of_no_samples_is_empty() {
+        let average = FrameStats::average(&[]);
+        assert_eq!(average.samples, 0);
+        assert_eq!(average.frame_ms, 0.0);
+    }
+
+    #[test]
+    fn gpu_bound_when_gpu_time_covers_the_frame() {
+        assert!(is_gpu_bound(16.0, Some(15.0)));
+        assert!(!is_gpu_bound(16.0, Some(8.0)));
+        assert!(!is_gpu_bound(16.0, None));
+        assert!(!is_gpu_bound(0.0, Some(0.0)));
+
+        // A long fence wait alone says nothing about the GPU.
+        let stats = FrameStats::new(Duration::from_millis(20), Duration::from_millis(15), None);
+        assert!(!stats.gpu_bound);
+        let stats =
+            FrameStats::new(Duration::from_millis(20), Duration::from_millis(1), Some(19.0));
+        assert!(stats.gpu_bound);
+    }
+}
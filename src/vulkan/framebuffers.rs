@@ -9,13 +9,21 @@ pub fn create_framebuffers(
     device: &Device,
     render_pass: RenderPass,
     image_views: &[ImageView],
+    depth_image_view: Option<ImageView>,
+    msaa_color_image_view: Option<ImageView>,
     swapchain_extent: &Extent2D,
 ) -> Vec<Framebuffer> {
     image_views
         .iter()
         .map(|x| {
-            let attachments = [*x];
-            FramebufferCreateInfo {
+            let attachments: Vec<ImageView> = match msaa_color_image_view {
+                Some(color_view) => vec![Some(color_view), depth_image_view, Some(*x)],
+                None => vec![Some(*x), depth_image_view],
+            }
+            .into_iter()
+            .flatten()
+            .collect();
+            let framebuffer_create_info = FramebufferCreateInfo {
                 s_type: StructureType::FRAMEBUFFER_CREATE_INFO,
                 p_next: std::ptr::null(),
                 flags: FramebufferCreateFlags::empty(),
@@ -25,12 +33,12 @@ pub fn create_framebuffers(
                 width: swapchain_extent.width,
                 height: swapchain_extent.height,
                 layers: 1,
+            };
+            unsafe {
+                device
+                    .create_framebuffer(&framebuffer_create_info, None)
+                    .expect("Failed to create framebuffer")
             }
         })
-        .map(|x| unsafe {
-            device
-                .create_framebuffer(&x, None)
-                .expect("Failed to create framebuffer")
-        })
         .collect()
 }
@@ -0,0 +1,95 @@
+use super::scene::{Aabb, Renderable};
+use super::UniformBufferObject::UniformBufferObject;
+use cgmath::{InnerSpace, Matrix, Matrix4, Vector4};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    pub planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    // Gribb-Hartmann extraction; the projection uses GL style -w..w clip depth.
+    pub fn from_view_projection(view_projection: Matrix4<f32>) -> Frustum {
+        let [x, y, z, w] = [
+            view_projection.row(0),
+            view_projection.row(1),
+            view_projection.row(2),
+            view_projection.row(3),
+        ];
+        Frustum {
+            planes: [w + x, w - x, w + y, w - y, w + z, w - z],
+        }
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            let positive_vertex = Vector4::new(
+                if plane.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+                1.0,
+            );
+            plane.dot(positive_vertex) >= 0.0
+        })
+    }
+}
+
+pub fn cull<'a>(
+    renderables: &'a [Renderable],
+    camera: &UniformBufferObject,
+) -> Vec<&'a Renderable> {
+    let frustum = Frustum::from_view_projection(camera.proj * camera.view);
+    renderables
+        .iter()
+        .filter(|x| match x.bounds {
+            Some(bounds) => frustum.intersects_aabb(&bounds.transform(camera.model * x.transform)),
+            None => true,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{perspective, Deg, Point3};
+
+    fn frustum() -> Frustum {
+        Frustum::from_view_projection(perspective(Deg(90.0), 1.0, 0.1, 10.0))
+    }
+
+    #[test]
+    fn box_inside_frustum_intersects() {
+        let aabb = Aabb::new(Point3::new(-0.5, -0.5, -3.0), Point3::new(0.5, 0.5, -2.0));
+        assert!(frustum().intersects_aabb(&aabb));
+    }
+
+    #[test]
+    fn box_outside_frustum_is_culled() {
+        let beside = Aabb::new(Point3::new(10.0, 10.0, -3.0), Point3::new(11.0, 11.0, -2.0));
+        let behind = Aabb::new(Point3::new(-0.5, -0.5, 1.0), Point3::new(0.5, 0.5, 2.0));
+        let beyond_far = Aabb::new(Point3::new(-0.5, -0.5, -20.0), Point3::new(0.5, 0.5, -11.0));
+        assert!(!frustum().intersects_aabb(&beside));
+        assert!(!frustum().intersects_aabb(&behind));
+        assert!(!frustum().intersects_aabb(&beyond_far));
+    }
+
+    #[test]
+    fn box_straddling_frustum_intersects() {
+        let side = Aabb::new(Point3::new(1.0, -0.5, -2.0), Point3::new(5.0, 0.5, -2.0));
+        let far = Aabb::new(Point3::new(-0.5, -0.5, -12.0), Point3::new(0.5, 0.5, -5.0));
+        assert!(frustum().intersects_aabb(&side));
+        assert!(frustum().intersects_aabb(&far));
+    }
+}
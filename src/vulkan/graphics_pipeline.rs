@@ -1,5 +1,9 @@
-use super::vertex::Vertex;
-use crate::io::file::read_file_to_bytes;
+use super::overlay::{OverlayVertex, OVERLAY_FRAGMENT_SHADER, OVERLAY_VERTEX_SHADER};
+use super::particles::Particle;
+use super::render_pass::RenderTargetFormats;
+use super::shader::{load_shader_module, ShaderSource};
+use super::text::{GlyphVertex, TEXT_FRAGMENT_SHADER, TEXT_VERTEX_SHADER};
+use super::vertex::{Vertex, VertexLayout};
 use ash::version::DeviceV1_0;
 use ash::vk::{
     BlendFactor, BlendOp, ColorComponentFlags, CompareOp, CullModeFlags, Extent2D, FrontFace,
@@ -13,25 +17,357 @@ use ash::vk::{
     PipelineRasterizationStateCreateInfo, PipelineShaderStageCreateFlags,
     PipelineShaderStageCreateInfo, PipelineVertexInputStateCreateFlags,
     PipelineVertexInputStateCreateInfo, PipelineViewportStateCreateFlags,
-    PipelineViewportStateCreateInfo, PolygonMode, PrimitiveTopology, Rect2D, RenderPass,
-    SampleCountFlags, ShaderModule, ShaderModuleCreateFlags, ShaderModuleCreateInfo,
-    ShaderStageFlags, StencilOp, StencilOpState, StructureType, VertexInputAttributeDescription,
-    VertexInputBindingDescription, Viewport, FALSE, DescriptorSetLayout
+    PhysicalDeviceLimits, PipelineViewportStateCreateInfo, PolygonMode, PrimitiveTopology,
+    PushConstantRange, Rect2D, RenderPass, SampleCountFlags, ShaderStageFlags, StencilOp,
+    StencilOpState, StructureType, VertexInputAttributeDescription,
+    VertexInputBindingDescription, Viewport, FALSE, TRUE,
+    DescriptorSetLayout
 };
 use ash::Device;
+use cgmath::Matrix4;
+use log::warn;
 use std::ffi::CString;
 
+// Debug segments are uploaded as unindexed vertex pairs.
+pub const DEBUG_LINE_TOPOLOGY: PrimitiveTopology = PrimitiveTopology::LINE_LIST;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    Opaque,
+    AlphaBlend,
+    Additive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StencilOpConfig {
+    pub compare_op: CompareOp,
+    pub fail_op: StencilOp,
+    pub pass_op: StencilOp,
+    pub depth_fail_op: StencilOp,
+    pub reference: u32,
+    pub compare_mask: u32,
+    pub write_mask: u32,
+}
+
+impl Default for StencilOpConfig {
+    fn default() -> Self {
+        StencilOpConfig {
+            compare_op: CompareOp::ALWAYS,
+            fail_op: StencilOp::KEEP,
+            pass_op: StencilOp::KEEP,
+            depth_fail_op: StencilOp::KEEP,
+            reference: 0,
+            compare_mask: 0xFF,
+            write_mask: 0xFF,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthConfig {
+    pub compare_op: CompareOp,
+    pub clear_value: f32,
+}
+
+impl DepthConfig {
+    pub fn reverse_z() -> DepthConfig {
+        DepthConfig {
+            compare_op: CompareOp::GREATER,
+            clear_value: 0.0,
+        }
+    }
+}
+
+impl Default for DepthConfig {
+    fn default() -> Self {
+        DepthConfig {
+            compare_op: CompareOp::LESS_OR_EQUAL,
+            clear_value: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PipelineConfig {
+    pub blend_mode: BlendMode,
+    pub polygon_mode: PolygonMode,
+    pub cull_mode: CullModeFlags,
+    pub front_face: FrontFace,
+    pub samples: SampleCountFlags,
+    pub stencil: Option<StencilOpConfig>,
+    pub depth: DepthConfig,
+    pub line_width: f32,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        PipelineConfig {
+            blend_mode: BlendMode::Opaque,
+            polygon_mode: PolygonMode::FILL,
+            cull_mode: CullModeFlags::BACK,
+            front_face: FrontFace::CLOCKWISE,
+            samples: SampleCountFlags::TYPE_1,
+            stencil: None,
+            depth: DepthConfig::default(),
+            line_width: 1.0,
+        }
+    }
+}
+
+// Where a pipeline draws: the render pass it is used in, the formats of that pass's attachments
+// and the extent of the fixed viewport.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PipelineTarget {
+    pub render_pass: RenderPass,
+    pub formats: RenderTargetFormats,
+    pub extent: Extent2D,
+}
+
+impl PipelineTarget {
+    // Overlays and fullscreen passes are drawn on top of the scene and never touch depth.
+    pub fn without_depth(&self) -> PipelineTarget {
+        PipelineTarget {
+            formats: RenderTargetFormats {
+                depth: None,
+                ..self.formats
+            },
+            ..*self
+        }
+    }
+}
+
+pub fn find_line_width(
+    requested: f32,
+    is_wide_lines_supported: bool,
+    limits: &PhysicalDeviceLimits,
+) -> f32 {
+    if requested == 1.0 {
+        return 1.0;
+    }
+    if !is_wide_lines_supported {
+        warn!(
+            "Line width {} requires the wideLines feature, falling back to 1.0",
+            requested
+        );
+        return 1.0;
+    }
+    snap_line_width(
+        requested,
+        limits.line_width_range,
+        limits.line_width_granularity,
+    )
+}
+
+pub fn snap_line_width(requested: f32, range: [f32; 2], granularity: f32) -> f32 {
+    let [min, max] = range;
+    let clamped = requested.max(min).min(max);
+    if granularity <= 0.0 {
+        return clamped;
+    }
+    let steps = ((clamped - min) / granularity).round();
+    (min + steps * granularity).min(max)
+}
+
 pub fn create_graphics_pipeline(
     device: &Device,
-    render_pass: RenderPass,
-    swapchain_extent: Extent2D,
-    ubo_set_layout: DescriptorSetLayout
+    pipeline_cache: PipelineCache,
+    target: &PipelineTarget,
+    (ubo_set_layout, material_set_layout): (DescriptorSetLayout, DescriptorSetLayout),
+    shaders: (ShaderSource, ShaderSource),
+    vertex_layout: &VertexLayout,
+    config: &PipelineConfig,
+) -> (Pipeline, PipelineLayout) {
+    create_pipeline(
+        device,
+        pipeline_cache,
+        target,
+        &PipelineDescription {
+            shaders,
+            topology: PrimitiveTopology::TRIANGLE_LIST,
+            binding_descriptions: &vertex_layout.binding_descriptions(),
+            attribute_descriptions: vertex_layout.attribute_descriptions(),
+            layout: (
+                &[ubo_set_layout, material_set_layout],
+                &[transform_push_constant_range()],
+            ),
+        },
+        config,
+    )
+}
+
+// Per-renderable transforms are pushed rather than stored in the frame uniform.
+pub fn transform_push_constant_range() -> PushConstantRange {
+    PushConstantRange {
+        stage_flags: ShaderStageFlags::VERTEX,
+        offset: 0,
+        size: std::mem::size_of::<Matrix4<f32>>() as u32,
+    }
+}
+
+pub fn create_particle_pipeline(
+    device: &Device,
+    pipeline_cache: PipelineCache,
+    target: &PipelineTarget,
+    config: &PipelineConfig,
+) -> (Pipeline, PipelineLayout) {
+    create_pipeline(
+        device,
+        pipeline_cache,
+        target,
+        &PipelineDescription {
+            shaders: (
+                ShaderSource::File("src/shaders/spv/particle-vert.spv"),
+                ShaderSource::File("src/shaders/spv/particle-frag.spv"),
+            ),
+            topology: PrimitiveTopology::POINT_LIST,
+            binding_descriptions: &Particle::get_binding_descriptions(),
+            attribute_descriptions: &Particle::get_attribute_descriptions(),
+            layout: (&[], &[]),
+        },
+        config,
+    )
+}
+
+pub fn create_debug_line_pipeline(
+    device: &Device,
+    pipeline_cache: PipelineCache,
+    target: &PipelineTarget,
+    ubo_set_layout: DescriptorSetLayout,
+    config: &PipelineConfig,
+) -> (Pipeline, PipelineLayout) {
+    create_pipeline(
+        device,
+        pipeline_cache,
+        target,
+        &PipelineDescription {
+            shaders: (
+                ShaderSource::File("src/shaders/spv/debug-vert.spv"),
+                ShaderSource::File("src/shaders/spv/debug-frag.spv"),
+            ),
+            topology: DEBUG_LINE_TOPOLOGY,
+            binding_descriptions: &Vertex::get_binding_descriptions(),
+            attribute_descriptions: &Vertex::get_attribute_descriptions(),
+            layout: (&[ubo_set_layout], &[]),
+        },
+        config,
+    )
+}
+
+pub fn create_post_process_pipeline(
+    device: &Device,
+    pipeline_cache: PipelineCache,
+    target: &PipelineTarget,
+    set_layout: DescriptorSetLayout,
+    shaders: (ShaderSource, ShaderSource),
+    config: &PipelineConfig,
+) -> (Pipeline, PipelineLayout) {
+    let config = PipelineConfig {
+        blend_mode: BlendMode::Opaque,
+        polygon_mode: PolygonMode::FILL,
+        cull_mode: CullModeFlags::NONE,
+        stencil: None,
+        ..*config
+    };
+    create_pipeline(
+        device,
+        pipeline_cache,
+        &target.without_depth(),
+        &PipelineDescription {
+            shaders,
+            topology: PrimitiveTopology::TRIANGLE_LIST,
+            binding_descriptions: &[],
+            attribute_descriptions: &[],
+            layout: (&[set_layout], &[]),
+        },
+        &config,
+    )
+}
+
+pub fn create_text_pipeline(
+    device: &Device,
+    pipeline_cache: PipelineCache,
+    target: &PipelineTarget,
+    set_layout: DescriptorSetLayout,
+    config: &PipelineConfig,
+) -> (Pipeline, PipelineLayout) {
+    let config = PipelineConfig {
+        blend_mode: BlendMode::AlphaBlend,
+        polygon_mode: PolygonMode::FILL,
+        cull_mode: CullModeFlags::NONE,
+        stencil: None,
+        ..*config
+    };
+    create_pipeline(
+        device,
+        pipeline_cache,
+        &target.without_depth(),
+        &PipelineDescription {
+            shaders: (TEXT_VERTEX_SHADER, TEXT_FRAGMENT_SHADER),
+            topology: PrimitiveTopology::TRIANGLE_LIST,
+            binding_descriptions: &GlyphVertex::get_binding_descriptions(),
+            attribute_descriptions: &GlyphVertex::get_attribute_descriptions(),
+            layout: (&[set_layout], &[]),
+        },
+        &config,
+    )
+}
+
+pub fn create_overlay_pipeline(
+    device: &Device,
+    pipeline_cache: PipelineCache,
+    target: &PipelineTarget,
+    config: &PipelineConfig,
 ) -> (Pipeline, PipelineLayout) {
-    let vert_shader = read_file_to_bytes("src/shaders/spv/shader-vert.spv").unwrap();
-    let frag_shader = read_file_to_bytes("src/shaders/spv/shader-frag.spv").unwrap();
+    let config = PipelineConfig {
+        blend_mode: BlendMode::AlphaBlend,
+        polygon_mode: PolygonMode::FILL,
+        cull_mode: CullModeFlags::NONE,
+        stencil: None,
+        ..*config
+    };
+    create_pipeline(
+        device,
+        pipeline_cache,
+        &target.without_depth(),
+        &PipelineDescription {
+            shaders: (OVERLAY_VERTEX_SHADER, OVERLAY_FRAGMENT_SHADER),
+            topology: PrimitiveTopology::TRIANGLE_LIST,
+            binding_descriptions: &OverlayVertex::get_binding_descriptions(),
+            attribute_descriptions: &OverlayVertex::get_attribute_descriptions(),
+            layout: (&[], &[]),
+        },
+        &config,
+    )
+}
 
-    let vert_module = create_shader_module(device, vert_shader);
-    let frag_module = create_shader_module(device, frag_shader);
+// What a pipeline runs and reads: its shader pair, how its vertices are assembled and the set
+// layouts and push constant ranges of its layout.
+struct PipelineDescription<'a> {
+    shaders: (ShaderSource, ShaderSource),
+    topology: PrimitiveTopology,
+    binding_descriptions: &'a [VertexInputBindingDescription],
+    attribute_descriptions: &'a [VertexInputAttributeDescription],
+    layout: (&'a [DescriptorSetLayout], &'a [PushConstantRange]),
+}
+
+fn create_pipeline(
+    device: &Device,
+    pipeline_cache: PipelineCache,
+    target: &PipelineTarget,
+    description: &PipelineDescription,
+    config: &PipelineConfig,
+) -> (Pipeline, PipelineLayout) {
+    let formats = &target.formats;
+    debug_assert!(
+        formats.is_consistent(),
+        "Render target formats {:?} do not describe a color and depth target",
+        formats
+    );
+    let (vert_shader, frag_shader) = description.shaders;
+    let vert_module = load_shader_module(device, vert_shader)
+        .unwrap_or_else(|e| panic!("Failed to load vertex shader: {}", e));
+    let frag_module = load_shader_module(device, frag_shader)
+        .unwrap_or_else(|e| panic!("Failed to load fragment shader: {}", e));
 
     let main_function_name = CString::new("main").unwrap();
 
@@ -56,29 +392,32 @@ pub fn create_graphics_pipeline(
         },
     ];
 
-    let binding_description = Vertex::get_binding_descriptions();
-    let attribute_description = Vertex::get_attribute_descriptions();
-
-    let vertex_input_state_create_info =
-        create_vertex_input_state_create_info(&attribute_description, &binding_description);
-    let vertex_input_assembly_state_info = create_vertex_input_assembly_state_info();
+    let vertex_input_state_create_info = create_vertex_input_state_create_info(
+        description.attribute_descriptions,
+        description.binding_descriptions,
+    );
+    let vertex_input_assembly_state_info =
+        create_vertex_input_assembly_state_info(description.topology);
 
-    let viewports = create_viewport(&swapchain_extent);
-    let scissors = create_scissors(&swapchain_extent);
+    let viewports = create_viewport(&target.extent);
+    let scissors = create_scissors(&target.extent);
 
     let viewport_state_create_info = create_viewport_state_create_info(&viewports, &scissors);
-    let rasterization_state_create_info = create_rasterization_state_create_info();
-    let multisample_state_create_info = create_multisample_state_create_info();
+    let rasterization_state_create_info = create_rasterization_state_create_info(config);
+    let multisample_state_create_info = create_multisample_state_create_info(formats.samples);
 
-    let stencil_state = create_stencil_state();
-
-    let depth_state_create_info = create_depth_state_create_info(&stencil_state);
-    let color_blend_attachment_states = create_color_blend_attachment_states();
+    let mut depth_state_create_info =
+        create_depth_state_create_info(&config.depth, config.stencil.as_ref());
+    if formats.depth.is_none() {
+        depth_state_create_info.depth_test_enable = FALSE;
+        depth_state_create_info.depth_write_enable = FALSE;
+        depth_state_create_info.stencil_test_enable = FALSE;
+    }
+    let color_blend_attachment_states = create_color_blend_attachment_states(config.blend_mode);
 
     let color_blend_state = create_color_blend_state(&color_blend_attachment_states);
 
-    let set_layouts = [ubo_set_layout];
-    let pipeline_layout_create_info = create_pipeline_layout_create_info(&set_layouts);
+    let pipeline_layout_create_info = create_pipeline_layout_create_info(description.layout);
 
     let pipeline_layout = unsafe {
         device
@@ -102,7 +441,7 @@ pub fn create_graphics_pipeline(
         p_color_blend_state: &color_blend_state,
         p_dynamic_state: std::ptr::null(),
         layout: pipeline_layout,
-        render_pass,
+        render_pass: target.render_pass,
         subpass: 0,
         base_pipeline_handle: Pipeline::null(),
         base_pipeline_index: -1,
@@ -110,7 +449,7 @@ pub fn create_graphics_pipeline(
 
     let graphics_pipelines = unsafe {
         device
-            .create_graphics_pipelines(PipelineCache::null(), &graphics_pipeline_create_infos, None)
+            .create_graphics_pipelines(pipeline_cache, &graphics_pipeline_create_infos, None)
             .expect("Failed to create graphics pipelines")
     };
     unsafe {
@@ -121,23 +460,6 @@ pub fn create_graphics_pipeline(
     (graphics_pipelines[0], pipeline_layout)
 }
 
-fn create_shader_module(device: &Device, code: Vec<u8>) -> ShaderModule {
-    #[allow(clippy::cast_ptr_alignment)]
-    let shader_module_create_info = ShaderModuleCreateInfo {
-        s_type: StructureType::SHADER_MODULE_CREATE_INFO,
-        p_next: std::ptr::null(),
-        flags: ShaderModuleCreateFlags::empty(),
-        code_size: code.len(),
-        p_code: code.as_ptr() as *const u32,
-    };
-
-    unsafe {
-        device
-            .create_shader_module(&shader_module_create_info, None)
-            .expect("Failed to create shader module")
-    }
-}
-
 fn create_vertex_input_state_create_info(
     attribute_descriptions: &[VertexInputAttributeDescription],
     binding_description: &[VertexInputBindingDescription],
@@ -153,13 +475,15 @@ fn create_vertex_input_state_create_info(
     }
 }
 
-fn create_vertex_input_assembly_state_info() -> PipelineInputAssemblyStateCreateInfo {
+fn create_vertex_input_assembly_state_info(
+    topology: PrimitiveTopology,
+) -> PipelineInputAssemblyStateCreateInfo {
     PipelineInputAssemblyStateCreateInfo {
         s_type: StructureType::PIPELINE_INPUT_ASSEMBLY_STATE_CREATE_INFO,
         flags: PipelineInputAssemblyStateCreateFlags::empty(),
         p_next: std::ptr::null(),
         primitive_restart_enable: FALSE,
-        topology: PrimitiveTopology::TRIANGLE_LIST,
+        topology,
     }
 }
 
@@ -196,16 +520,18 @@ fn create_viewport_state_create_info(
     }
 }
 
-fn create_rasterization_state_create_info() -> PipelineRasterizationStateCreateInfo {
+pub fn create_rasterization_state_create_info(
+    config: &PipelineConfig,
+) -> PipelineRasterizationStateCreateInfo {
     PipelineRasterizationStateCreateInfo {
         s_type: StructureType::PIPELINE_RASTERIZATION_STATE_CREATE_INFO,
         p_next: std::ptr::null(),
         flags: PipelineRasterizationStateCreateFlags::empty(),
         depth_clamp_enable: FALSE,
-        cull_mode: CullModeFlags::BACK,
-        front_face: FrontFace::CLOCKWISE,
-        line_width: 1.0,
-        polygon_mode: PolygonMode::FILL,
+        cull_mode: config.cull_mode,
+        front_face: config.front_face,
+        line_width: config.line_width,
+        polygon_mode: config.polygon_mode,
         rasterizer_discard_enable: FALSE,
         depth_bias_clamp: 0.0,
         depth_bias_constant_factor: 0.0,
@@ -214,12 +540,14 @@ fn create_rasterization_state_create_info() -> PipelineRasterizationStateCreateI
     }
 }
 
-fn create_multisample_state_create_info() -> PipelineMultisampleStateCreateInfo {
+fn create_multisample_state_create_info(
+    samples: SampleCountFlags,
+) -> PipelineMultisampleStateCreateInfo {
     PipelineMultisampleStateCreateInfo {
         s_type: StructureType::PIPELINE_MULTISAMPLE_STATE_CREATE_INFO,
         flags: PipelineMultisampleStateCreateFlags::empty(),
         p_next: std::ptr::null(),
-        rasterization_samples: SampleCountFlags::TYPE_1,
+        rasterization_samples: samples,
         sample_shading_enable: FALSE,
         min_sample_shading: 0.0,
         p_sample_mask: std::ptr::null(),
@@ -228,46 +556,67 @@ fn create_multisample_state_create_info() -> PipelineMultisampleStateCreateInfo
     }
 }
 
-fn create_stencil_state() -> StencilOpState {
+pub fn create_stencil_state(stencil: &StencilOpConfig) -> StencilOpState {
     StencilOpState {
-        fail_op: StencilOp::KEEP,
-        pass_op: StencilOp::KEEP,
-        depth_fail_op: StencilOp::KEEP,
-        compare_op: CompareOp::ALWAYS,
-        compare_mask: 0,
-        write_mask: 0,
-        reference: 0,
+        fail_op: stencil.fail_op,
+        pass_op: stencil.pass_op,
+        depth_fail_op: stencil.depth_fail_op,
+        compare_op: stencil.compare_op,
+        compare_mask: stencil.compare_mask,
+        write_mask: stencil.write_mask,
+        reference: stencil.reference,
     }
 }
 
-fn create_depth_state_create_info(
-    stencil_state: &StencilOpState,
+pub fn create_depth_state_create_info(
+    depth: &DepthConfig,
+    stencil: Option<&StencilOpConfig>,
 ) -> PipelineDepthStencilStateCreateInfo {
+    let stencil_state = create_stencil_state(stencil.unwrap_or(&StencilOpConfig::default()));
     PipelineDepthStencilStateCreateInfo {
         s_type: StructureType::PIPELINE_DEPTH_STENCIL_STATE_CREATE_INFO,
         p_next: std::ptr::null(),
         flags: PipelineDepthStencilStateCreateFlags::empty(),
-        depth_test_enable: FALSE,
-        depth_write_enable: FALSE,
-        depth_compare_op: CompareOp::LESS_OR_EQUAL,
+        depth_test_enable: TRUE,
+        depth_write_enable: TRUE,
+        depth_compare_op: depth.compare_op,
         depth_bounds_test_enable: FALSE,
-        stencil_test_enable: FALSE,
-        front: *stencil_state,
-        back: *stencil_state,
+        stencil_test_enable: if stencil.is_some() { TRUE } else { FALSE },
+        front: stencil_state,
+        back: stencil_state,
         max_depth_bounds: 1.0,
         min_depth_bounds: 0.0,
     }
 }
 
-fn create_color_blend_attachment_states() -> [PipelineColorBlendAttachmentState; 1] {
+pub fn create_color_blend_attachment_states(
+    blend_mode: BlendMode,
+) -> [PipelineColorBlendAttachmentState; 1] {
+    let (blend_enable, src_color_blend_factor, dst_color_blend_factor, dst_alpha_blend_factor) =
+        match blend_mode {
+            BlendMode::Opaque => (FALSE, BlendFactor::ONE, BlendFactor::ZERO, BlendFactor::ZERO),
+            BlendMode::AlphaBlend => (
+                TRUE,
+                BlendFactor::SRC_ALPHA,
+                BlendFactor::ONE_MINUS_SRC_ALPHA,
+                BlendFactor::ONE_MINUS_SRC_ALPHA,
+            ),
+            BlendMode::Additive => (
+                TRUE,
+                BlendFactor::SRC_ALPHA,
+                BlendFactor::ONE,
+                BlendFactor::ONE,
+            ),
+        };
+
     [PipelineColorBlendAttachmentState {
-        blend_enable: FALSE,
+        blend_enable,
         color_write_mask: ColorComponentFlags::all(),
-        src_color_blend_factor: BlendFactor::ONE,
-        dst_color_blend_factor: BlendFactor::ZERO,
+        src_color_blend_factor,
+        dst_color_blend_factor,
         color_blend_op: BlendOp::ADD,
         src_alpha_blend_factor: BlendFactor::ONE,
-        dst_alpha_blend_factor: BlendFactor::ZERO,
+        dst_alpha_blend_factor,
         alpha_blend_op: BlendOp::ADD,
     }]
 }
@@ -287,14 +636,186 @@ fn create_color_blend_state(
     }
 }
 
-    fn create_pipeline_layout_create_info(set_layouts: &[DescriptorSetLayout; 1]) -> PipelineLayoutCreateInfo {
+fn create_pipeline_layout_create_info(
+    (set_layouts, push_constant_ranges): (&[DescriptorSetLayout], &[PushConstantRange]),
+) -> PipelineLayoutCreateInfo {
     PipelineLayoutCreateInfo {
         s_type: StructureType::PIPELINE_LAYOUT_CREATE_INFO,
         p_next: std::ptr::null(),
         flags: PipelineLayoutCreateFlags::empty(),
         set_layout_count: set_layouts.len() as u32,
         p_set_layouts: set_layouts.as_ptr(),
-        push_constant_range_count: 0,
-        p_push_constant_ranges: std::ptr::null(),
+        push_constant_range_count: push_constant_ranges.len() as u32,
+        p_push_constant_ranges: push_constant_ranges.as_ptr(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::command_pool::create_clear_values;
+    use ash::vk::{ClearColorValue, Format, Handle};
+
+    #[test]
+    fn target_without_depth_keeps_pass_color_and_extent() {
+        let target = PipelineTarget {
+            render_pass: RenderPass::from_raw(1),
+            formats: RenderTargetFormats {
+                color: Format::B8G8R8A8_SRGB,
+                depth: Some(Format::D32_SFLOAT),
+                samples: SampleCountFlags::TYPE_4,
+            },
+            extent: Extent2D {
+                width: 800,
+                height: 600,
+            },
+        };
+        let overlay_target = target.without_depth();
+        assert_eq!(overlay_target.formats.depth, None);
+        assert_eq!(overlay_target.formats.color, target.formats.color);
+        assert_eq!(overlay_target.formats.samples, target.formats.samples);
+        assert_eq!(overlay_target.render_pass, target.render_pass);
+        assert_eq!(overlay_target.extent, target.extent);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "do not describe a color and depth target")]
+    fn mismatched_target_formats_are_caught_in_pipeline_creation() {
+        let target = PipelineTarget {
+            render_pass: RenderPass::from_raw(1),
+            formats: RenderTargetFormats {
+                color: Format::D32_SFLOAT,
+                depth: None,
+                samples: SampleCountFlags::TYPE_1,
+            },
+            extent: Extent2D {
+                width: 800,
+                height: 600,
+            },
+        };
+        create_overlay_pipeline(
+            &crate::vulkan::fake_device::device(),
+            PipelineCache::null(),
+            &target,
+            &PipelineConfig::default(),
+        );
+    }
+
+    #[test]
+    fn opaque_blend_disables_blending() {
+        let states = create_color_blend_attachment_states(BlendMode::Opaque);
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].blend_enable, FALSE);
+        assert_eq!(states[0].color_write_mask, ColorComponentFlags::all());
+    }
+
+    #[test]
+    fn alpha_blend_uses_source_alpha() {
+        let states = create_color_blend_attachment_states(BlendMode::AlphaBlend);
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].blend_enable, TRUE);
+        assert_eq!(states[0].src_color_blend_factor, BlendFactor::SRC_ALPHA);
+        assert_eq!(states[0].dst_color_blend_factor, BlendFactor::ONE_MINUS_SRC_ALPHA);
+        assert_eq!(states[0].color_blend_op, BlendOp::ADD);
+    }
+
+    #[test]
+    fn additive_blend_adds_destination() {
+        let states = create_color_blend_attachment_states(BlendMode::Additive);
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].blend_enable, TRUE);
+        assert_eq!(states[0].src_color_blend_factor, BlendFactor::SRC_ALPHA);
+        assert_eq!(states[0].dst_color_blend_factor, BlendFactor::ONE);
+        assert_eq!(states[0].dst_alpha_blend_factor, BlendFactor::ONE);
+    }
+
+    fn line_limits() -> PhysicalDeviceLimits {
+        PhysicalDeviceLimits {
+            line_width_range: [1.0, 8.0],
+            line_width_granularity: 0.5,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn line_width_falls_back_without_wide_lines() {
+        assert_eq!(find_line_width(4.0, false, &line_limits()), 1.0);
+    }
+
+    #[test]
+    fn line_width_is_clamped_to_device_range() {
+        assert_eq!(find_line_width(4.0, true, &line_limits()), 4.0);
+        assert_eq!(find_line_width(3.2, true, &line_limits()), 3.0);
+        assert_eq!(find_line_width(16.0, true, &line_limits()), 8.0);
+        assert_eq!(find_line_width(0.25, true, &line_limits()), 1.0);
+    }
+
+    #[test]
+    fn debug_line_pipeline_assembles_line_lists() {
+        let input_assembly = create_vertex_input_assembly_state_info(DEBUG_LINE_TOPOLOGY);
+        assert_eq!(input_assembly.topology, PrimitiveTopology::LINE_LIST);
+        assert_eq!(input_assembly.primitive_restart_enable, FALSE);
+    }
+
+    #[test]
+    fn reverse_z_compares_greater_and_clears_to_zero() {
+        let depth = DepthConfig::reverse_z();
+        let depth_state = create_depth_state_create_info(&depth, None);
+        assert_eq!(depth_state.depth_compare_op, CompareOp::GREATER);
+        assert_eq!(depth_state.depth_test_enable, TRUE);
+        assert_eq!(depth_state.depth_write_enable, TRUE);
+
+        let clear_values = create_clear_values(ClearColorValue::default(), depth.clear_value);
+        assert_eq!(unsafe { clear_values[1].depth_stencil.depth }, 0.0);
+    }
+
+    #[test]
+    fn default_depth_compares_less_or_equal_and_clears_to_one() {
+        let depth = DepthConfig::default();
+        let depth_state = create_depth_state_create_info(&depth, None);
+        assert_eq!(depth_state.depth_compare_op, CompareOp::LESS_OR_EQUAL);
+
+        let clear_values = create_clear_values(ClearColorValue::default(), depth.clear_value);
+        assert_eq!(unsafe { clear_values[1].depth_stencil.depth }, 1.0);
+    }
+
+    #[test]
+    fn rasterization_state_uses_configured_front_face() {
+        let default_state = create_rasterization_state_create_info(&PipelineConfig::default());
+        assert_eq!(default_state.front_face, FrontFace::CLOCKWISE);
+
+        let config = PipelineConfig {
+            front_face: FrontFace::COUNTER_CLOCKWISE,
+            ..Default::default()
+        };
+        let state = create_rasterization_state_create_info(&config);
+        assert_eq!(state.front_face, FrontFace::COUNTER_CLOCKWISE);
+        assert_eq!(state.cull_mode, CullModeFlags::BACK);
+    }
+
+    #[test]
+    fn depth_stencil_state_reflects_the_stencil_config() {
+        let stencil = StencilOpConfig {
+            compare_op: CompareOp::EQUAL,
+            fail_op: StencilOp::ZERO,
+            pass_op: StencilOp::REPLACE,
+            depth_fail_op: StencilOp::INCREMENT_AND_CLAMP,
+            reference: 1,
+            compare_mask: 0x0F,
+            write_mask: 0xF0,
+        };
+        let state = create_depth_state_create_info(&DepthConfig::default(), Some(&stencil));
+        assert_eq!(state.stencil_test_enable, TRUE);
+        for face in &[state.front, state.back] {
+            assert_eq!(face.compare_op, CompareOp::EQUAL);
+            assert_eq!(face.fail_op, StencilOp::ZERO);
+            assert_eq!(face.pass_op, StencilOp::REPLACE);
+            assert_eq!(face.depth_fail_op, StencilOp::INCREMENT_AND_CLAMP);
+            assert_eq!((face.reference, face.compare_mask, face.write_mask), (1, 0x0F, 0xF0));
+        }
+
+        let state = create_depth_state_create_info(&DepthConfig::default(), None);
+        assert_eq!(state.stencil_test_enable, FALSE);
     }
 }
@@ -0,0 +1,261 @@
+use super::error::PotatoError;
+use ash::version::DeviceV1_0;
+use ash::vk::{
+    AccessFlags, CommandBuffer, DependencyFlags, Format, Image, ImageAspectFlags, ImageLayout,
+    ImageMemoryBarrier, ImageSubresourceRange, PipelineStageFlags, StructureType,
+    QUEUE_FAMILY_IGNORED,
+};
+use ash::Device;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayoutTransition {
+    pub src_access_mask: AccessFlags,
+    pub dst_access_mask: AccessFlags,
+    pub src_stage: PipelineStageFlags,
+    pub dst_stage: PipelineStageFlags,
+}
+
+pub fn find_layout_transition(
+    old_layout: ImageLayout,
+    new_layout: ImageLayout,
+) -> Result<LayoutTransition, PotatoError> {
+    let (src_access_mask, dst_access_mask, src_stage, dst_stage) = match (old_layout, new_layout) {
+        (ImageLayout::UNDEFINED, ImageLayout::TRANSFER_DST_OPTIMAL) => (
+            AccessFlags::empty(),
+            AccessFlags::TRANSFER_WRITE,
+            PipelineStageFlags::TOP_OF_PIPE,
+            PipelineStageFlags::TRANSFER,
+        ),
+        (ImageLayout::TRANSFER_DST_OPTIMAL, ImageLayout::SHADER_READ_ONLY_OPTIMAL) => (
+            AccessFlags::TRANSFER_WRITE,
+            AccessFlags::SHADER_READ,
+            PipelineStageFlags::TRANSFER,
+            PipelineStageFlags::FRAGMENT_SHADER,
+        ),
+        (ImageLayout::UNDEFINED, ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL) => (
+            AccessFlags::empty(),
+            AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                | AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            PipelineStageFlags::TOP_OF_PIPE,
+            PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+        ),
+        (ImageLayout::UNDEFINED, ImageLayout::COLOR_ATTACHMENT_OPTIMAL) => (
+            AccessFlags::empty(),
+            AccessFlags::COLOR_ATTACHMENT_READ | AccessFlags::COLOR_ATTACHMENT_WRITE,
+            PipelineStageFlags::TOP_OF_PIPE,
+            PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        ),
+        (ImageLayout::COLOR_ATTACHMENT_OPTIMAL, ImageLayout::SHADER_READ_ONLY_OPTIMAL) => (
+            AccessFlags::COLOR_ATTACHMENT_WRITE,
+            AccessFlags::SHADER_READ,
+            PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            PipelineStageFlags::FRAGMENT_SHADER,
+        ),
+        (ImageLayout::SHADER_READ_ONLY_OPTIMAL, ImageLayout::COLOR_ATTACHMENT_OPTIMAL) => (
+            AccessFlags::SHADER_READ,
+            AccessFlags::COLOR_ATTACHMENT_WRITE,
+            PipelineStageFlags::FRAGMENT_SHADER,
+            PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        ),
+        // Swapchain captures are recorded right after the render pass that wrote the image.
+        (ImageLayout::PRESENT_SRC_KHR, ImageLayout::TRANSFER_SRC_OPTIMAL) => (
+            AccessFlags::COLOR_ATTACHMENT_WRITE,
+            AccessFlags::TRANSFER_READ,
+            PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            PipelineStageFlags::TRANSFER,
+        ),
+        (ImageLayout::TRANSFER_SRC_OPTIMAL, ImageLayout::PRESENT_SRC_KHR) => (
+            AccessFlags::TRANSFER_READ,
+            AccessFlags::MEMORY_READ,
+            PipelineStageFlags::TRANSFER,
+            PipelineStageFlags::BOTTOM_OF_PIPE,
+        ),
+        _ => return Err(PotatoError::UnsupportedLayoutTransition(old_layout, new_layout)),
+    };
+
+    Ok(LayoutTransition {
+        src_access_mask,
+        dst_access_mask,
+        src_stage,
+        dst_stage,
+    })
+}
+
+pub fn find_aspect_mask(format: Format) -> ImageAspectFlags {
+    match format {
+        Format::D16_UNORM | Format::X8_D24_UNORM_PACK32 | Format::D32_SFLOAT => {
+            ImageAspectFlags::DEPTH
+        }
+        Format::D16_UNORM_S8_UINT | Format::D24_UNORM_S8_UINT | Format::D32_SFLOAT_S8_UINT => {
+            ImageAspectFlags::DEPTH | ImageAspectFlags::STENCIL
+        }
+        Format::S8_UINT => ImageAspectFlags::STENCIL,
+        _ => ImageAspectFlags::COLOR,
+    }
+}
+
+// The first mip level and array layer under the aspect the format implies; images with more
+// levels or layers widen it with struct update syntax.
+pub fn base_subresource_range(format: Format) -> ImageSubresourceRange {
+    ImageSubresourceRange {
+        aspect_mask: find_aspect_mask(format),
+        base_mip_level: 0,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: 1,
+    }
+}
+
+pub fn transition_image_layout(
+    device: &Device,
+    command_buffer: CommandBuffer,
+    image: Image,
+    subresource_range: ImageSubresourceRange,
+    old_layout: ImageLayout,
+    new_layout: ImageLayout,
+) -> Result<(), PotatoError> {
+    let transition = find_layout_transition(old_layout, new_layout)?;
+
+    let barriers = [ImageMemoryBarrier {
+        s_type: StructureType::IMAGE_MEMORY_BARRIER,
+        p_next: std::ptr::null(),
+        src_access_mask: transition.src_access_mask,
+        dst_access_mask: transition.dst_access_mask,
+        old_layout,
+        new_layout,
+        src_queue_family_index: QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index: QUEUE_FAMILY_IGNORED,
+        image,
+        subresource_range,
+    }];
+
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            transition.src_stage,
+            transition.dst_stage,
+            DependencyFlags::empty(),
+            &[],
+            &[],
+            &barriers,
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_subresource_range_covers_one_level_and_layer_of_the_format_aspect() {
+        let color_range = base_subresource_range(Format::B8G8R8A8_SRGB);
+        assert_eq!(color_range.aspect_mask, ImageAspectFlags::COLOR);
+        assert_eq!((color_range.base_mip_level, color_range.level_count), (0, 1));
+        assert_eq!((color_range.base_array_layer, color_range.layer_count), (0, 1));
+        assert_eq!(
+            base_subresource_range(Format::D24_UNORM_S8_UINT).aspect_mask,
+            ImageAspectFlags::DEPTH | ImageAspectFlags::STENCIL
+        );
+    }
+
+    #[test]
+    fn layout_transitions_use_matching_masks_and_stages() {
+        let cases = [
+            (
+                ImageLayout::UNDEFINED,
+                ImageLayout::TRANSFER_DST_OPTIMAL,
+                AccessFlags::empty(),
+                AccessFlags::TRANSFER_WRITE,
+                PipelineStageFlags::TOP_OF_PIPE,
+                PipelineStageFlags::TRANSFER,
+            ),
+            (
+                ImageLayout::TRANSFER_DST_OPTIMAL,
+                ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                AccessFlags::TRANSFER_WRITE,
+                AccessFlags::SHADER_READ,
+                PipelineStageFlags::TRANSFER,
+                PipelineStageFlags::FRAGMENT_SHADER,
+            ),
+            (
+                ImageLayout::UNDEFINED,
+                ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                AccessFlags::empty(),
+                AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                    | AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                PipelineStageFlags::TOP_OF_PIPE,
+                PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+            ),
+            (
+                ImageLayout::UNDEFINED,
+                ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                AccessFlags::empty(),
+                AccessFlags::COLOR_ATTACHMENT_READ | AccessFlags::COLOR_ATTACHMENT_WRITE,
+                PipelineStageFlags::TOP_OF_PIPE,
+                PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            ),
+            (
+                ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                AccessFlags::COLOR_ATTACHMENT_WRITE,
+                AccessFlags::SHADER_READ,
+                PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                PipelineStageFlags::FRAGMENT_SHADER,
+            ),
+            (
+                ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                AccessFlags::SHADER_READ,
+                AccessFlags::COLOR_ATTACHMENT_WRITE,
+                PipelineStageFlags::FRAGMENT_SHADER,
+                PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            ),
+            (
+                ImageLayout::PRESENT_SRC_KHR,
+                ImageLayout::TRANSFER_SRC_OPTIMAL,
+                AccessFlags::COLOR_ATTACHMENT_WRITE,
+                AccessFlags::TRANSFER_READ,
+                PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                PipelineStageFlags::TRANSFER,
+            ),
+            (
+                ImageLayout::TRANSFER_SRC_OPTIMAL,
+                ImageLayout::PRESENT_SRC_KHR,
+                AccessFlags::TRANSFER_READ,
+                AccessFlags::MEMORY_READ,
+                PipelineStageFlags::TRANSFER,
+                PipelineStageFlags::BOTTOM_OF_PIPE,
+            ),
+        ];
+        for (old_layout, new_layout, src_access_mask, dst_access_mask, src_stage, dst_stage) in
+            cases
+        {
+            assert_eq!(
+                find_layout_transition(old_layout, new_layout).unwrap(),
+                LayoutTransition {
+                    src_access_mask,
+                    dst_access_mask,
+                    src_stage,
+                    dst_stage
+                },
+                "{:?} -> {:?}",
+                old_layout,
+                new_layout
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_layout_transitions_are_rejected() {
+        let result =
+            find_layout_transition(ImageLayout::GENERAL, ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        assert!(matches!(
+            result,
+            Err(PotatoError::UnsupportedLayoutTransition(
+                ImageLayout::GENERAL,
+                ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            ))
+        ));
+    }
+}
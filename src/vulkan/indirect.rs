@@ -0,0 +1,97 @@
+use super::buffer::PotatoBuffer;
+use super::error::PotatoError;
+use super::scene::DrawMode;
+use ash::vk::{
+    BufferUsageFlags, DrawIndexedIndirectCommand, MemoryPropertyFlags,
+    PhysicalDeviceMemoryProperties,
+};
+use ash::Device;
+use std::rc::Rc;
+
+pub fn indirect_command_stride() -> u32 {
+    std::mem::size_of::<DrawIndexedIndirectCommand>() as u32
+}
+
+pub fn check_indirect_draw_count(
+    draw_count: u32,
+    is_multi_draw_enabled: bool,
+) -> Result<(), PotatoError> {
+    if draw_count > 1 && !is_multi_draw_enabled {
+        Err(PotatoError::MissingDeviceFeature(
+            "multiDrawIndirect".to_string(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+pub struct IndirectBuffer {
+    pub buffer: PotatoBuffer,
+    pub draw_count: u32,
+}
+
+impl IndirectBuffer {
+    // Storage usage lets compute shaders fill in the draw arguments.
+    pub fn new(
+        device: &Rc<Device>,
+        device_memory_properties: &PhysicalDeviceMemoryProperties,
+        commands: &[DrawIndexedIndirectCommand],
+    ) -> IndirectBuffer {
+        let buffer = PotatoBuffer::new(
+            device,
+            std::mem::size_of_val(commands).max(indirect_command_stride() as usize) as u64,
+            BufferUsageFlags::INDIRECT_BUFFER | BufferUsageFlags::STORAGE_BUFFER,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+            device_memory_properties,
+        );
+        let mut indirect_buffer = IndirectBuffer {
+            buffer,
+            draw_count: 0,
+        };
+        indirect_buffer.write(commands);
+        indirect_buffer
+    }
+
+    pub fn write(&mut self, commands: &[DrawIndexedIndirectCommand]) {
+        let draw_count = (self.buffer.size / indirect_command_stride() as u64) as usize;
+        let commands = &commands[..commands.len().min(draw_count)];
+        unsafe {
+            let data_ptr = self.buffer.map::<DrawIndexedIndirectCommand>();
+            data_ptr.copy_from_nonoverlapping(commands.as_ptr(), commands.len());
+        }
+        self.buffer.unmap();
+        self.draw_count = commands.len() as u32;
+    }
+
+    pub fn draw_mode(&self) -> DrawMode {
+        DrawMode::IndexedIndirect {
+            buffer: self.buffer.buffer,
+            draw_count: self.draw_count,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stride_matches_the_indirect_command_struct() {
+        assert_eq!(
+            indirect_command_stride() as usize,
+            std::mem::size_of::<DrawIndexedIndirectCommand>()
+        );
+        assert_eq!(indirect_command_stride(), 5 * 4);
+    }
+
+    #[test]
+    fn multi_draw_needs_the_multi_draw_indirect_feature() {
+        assert!(check_indirect_draw_count(1, false).is_ok());
+        assert!(check_indirect_draw_count(4, true).is_ok());
+        assert!(matches!(
+            check_indirect_draw_count(4, false),
+            Err(PotatoError::MissingDeviceFeature(x)) if x == "multiDrawIndirect"
+        ));
+    }
+}
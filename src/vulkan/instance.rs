@@ -1,3 +1,6 @@
+use super::error::PotatoError;
+use super::physical_device::find_missing_extensions;
+use super::portability::{portability_enumeration_extension_name, ENUMERATE_PORTABILITY};
 use super::utilities::{conver_str_vec_to_c_str_ptr_vec, vk_to_string};
 use super::vulk_validation_layers::populate_debug_messenger_create_info;
 #[cfg(feature = "xlib")]
@@ -7,37 +10,81 @@ use ash::extensions::khr::WaylandSurface;
 use ash::extensions::khr::Surface;
 use ash::extensions::ext::DebugUtils;
 use ash::vk::{
-    make_version, ApplicationInfo, DebugUtilsMessengerCreateInfoEXT, InstanceCreateFlags,
+    make_version, version_major, version_minor, version_patch, ApplicationInfo,
+    DebugUtilsMessengerCreateInfoEXT, ExtSwapchainColorspaceFn, InstanceCreateFlags,
     InstanceCreateInfo, StructureType
 };
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 use std::os::raw::c_void;
 use ash::Entry;
 use ash::Instance;
 use ash::version::EntryV1_0;
 use super::constants::VALIDATION;
-use log::{debug};
+use log::{debug, warn};
 
-pub fn create_instance(entry: &Entry) -> Instance {
-    if VALIDATION.is_enable && !check_validation_layer_support(entry) {
-        panic!("Validation layers requested but not supported");
+#[derive(Debug, Clone)]
+pub struct ApplicationConfig {
+    pub app_name: String,
+    pub app_version: u32,
+    pub engine_name: String,
+    pub engine_version: u32,
+    pub api_version: u32,
+}
+
+impl Default for ApplicationConfig {
+    fn default() -> Self {
+        ApplicationConfig {
+            app_name: "Potato".to_string(),
+            app_version: make_version(0, 0, 1),
+            engine_name: "Vulkan API".to_string(),
+            engine_version: make_version(0, 0, 1),
+            api_version: make_version(1, 2, 148),
+        }
     }
+}
 
-    let app_name = CString::new("Potato").unwrap();
-    let engine_name = CString::new("Vulkan API").unwrap();
-    let app_info = ApplicationInfo {
-        s_type: StructureType::APPLICATION_INFO,
-        p_next: std::ptr::null(),
-        p_application_name: app_name.as_ptr(),
-        application_version: make_version(0, 0, 1),
-        p_engine_name: engine_name.as_ptr(),
-        engine_version: make_version(0, 0, 1),
-        api_version: make_version(1, 2, 148),
+pub fn create_instance(
+    entry: &Entry,
+    application_config: &ApplicationConfig,
+    is_lenient_validation: bool,
+    is_validation_requested: bool,
+    is_colorspace_requested: bool,
+    additional_extensions: &[String],
+) -> Result<(Instance, bool, bool, u32), PotatoError> {
+    let is_validation_enabled = if VALIDATION.is_enable && is_validation_requested {
+        resolve_validation(check_validation_layer_support(entry), is_lenient_validation)?
+    } else {
+        false
     };
 
-    let debug_utils_create_info = populate_debug_messenger_create_info();
+    let app_name = CString::new(application_config.app_name.as_str())
+        .expect("Application name must not contain a nul byte");
+    let engine_name = CString::new(application_config.engine_name.as_str())
+        .expect("Engine name must not contain a nul byte");
+    let api_version = find_instance_api_version(entry, application_config.api_version);
+    let app_info =
+        create_application_info(application_config, api_version, &app_name, &engine_name);
 
-    let extension_names = create_extention_names();
+    let debug_utils_create_info = populate_debug_messenger_create_info(std::ptr::null_mut());
+
+    let mut extension_names = create_extention_names();
+    let colorspace_extension = ExtSwapchainColorspaceFn::name();
+    let is_colorspace_enabled = is_colorspace_requested
+        && is_instance_extension_available(entry, &colorspace_extension.to_string_lossy());
+    if is_colorspace_enabled {
+        extension_names.push(colorspace_extension.as_ptr());
+    } else if is_colorspace_requested {
+        warn!("{:?} is not available", colorspace_extension);
+    }
+    let portability_extension = portability_enumeration_extension_name();
+    let is_portability_enabled =
+        is_instance_extension_available(entry, &portability_extension.to_string_lossy());
+    if is_portability_enabled {
+        debug!("Enabling {:?}", portability_extension);
+        extension_names.push(portability_extension.as_ptr());
+    }
+    let additional_extension_names = find_additional_extensions(entry, additional_extensions)?;
+    append_extension_names(&mut extension_names, &additional_extension_names);
 
     let (cstring_vec, enable_layer_names) =
         conver_str_vec_to_c_str_ptr_vec(VALIDATION.required_validation_layers.to_vec());
@@ -45,19 +92,23 @@ pub fn create_instance(entry: &Entry) -> Instance {
     debug!("{:?}", cstring_vec);
     let create_info = InstanceCreateInfo {
         s_type: StructureType::INSTANCE_CREATE_INFO,
-        p_next: if VALIDATION.is_enable {
+        p_next: if is_validation_enabled {
             &debug_utils_create_info as *const DebugUtilsMessengerCreateInfoEXT as *const c_void
         } else {
             std::ptr::null()
         },
-        flags: InstanceCreateFlags::empty(),
+        flags: if is_portability_enabled {
+            ENUMERATE_PORTABILITY
+        } else {
+            InstanceCreateFlags::empty()
+        },
         p_application_info: &app_info,
-        pp_enabled_layer_names: if VALIDATION.is_enable {
+        pp_enabled_layer_names: if is_validation_enabled {
             enable_layer_names.as_ptr()
         } else {
             std::ptr::null()
         },
-        enabled_layer_count: get_enabled_layers_len(),
+        enabled_layer_count: get_enabled_layers_len(is_validation_enabled),
         pp_enabled_extension_names: extension_names.as_ptr(),
         enabled_extension_count: extension_names.len() as u32,
     };
@@ -69,28 +120,144 @@ pub fn create_instance(entry: &Entry) -> Instance {
             .expect("Failed to create instance")
     };
     debug!("Finished creating instance");
-    instance
+    Ok((instance, is_validation_enabled, is_colorspace_enabled, api_version))
 }
 
-fn check_validation_layer_support(entry: &Entry) -> bool {
-    let layer_properties = entry
-        .enumerate_instance_layer_properties()
-        .expect("Failed to enumerate the Instance Layers Properties!");
+fn find_instance_api_version(entry: &Entry, desired_version: u32) -> u32 {
+    let supported_version = entry
+        .try_enumerate_instance_version()
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| make_version(1, 0, 0));
+    let api_version = negotiate_api_version(desired_version, supported_version);
+    if api_version != desired_version {
+        warn!(
+            "Requested Vulkan {}.{}.{} but the loader supports {}.{}.{}, using {}.{}.{}",
+            version_major(desired_version),
+            version_minor(desired_version),
+            version_patch(desired_version),
+            version_major(supported_version),
+            version_minor(supported_version),
+            version_patch(supported_version),
+            version_major(api_version),
+            version_minor(api_version),
+            version_patch(api_version)
+        );
+    }
+    api_version
+}
+
+pub fn negotiate_api_version(desired_version: u32, supported_version: u32) -> u32 {
+    desired_version.min(supported_version)
+}
+
+fn is_instance_extension_available(entry: &Entry, name: &str) -> bool {
+    find_available_instance_extensions(entry).iter().any(|x| x == name)
+}
+
+fn find_available_instance_extensions(entry: &Entry) -> Vec<String> {
+    entry
+        .enumerate_instance_extension_properties()
+        .map(|x| x.iter().map(|y| vk_to_string(&y.extension_name)).collect())
+        .unwrap_or_default()
+}
+
+fn find_additional_extensions(
+    entry: &Entry,
+    additional_extensions: &[String],
+) -> Result<Vec<CString>, PotatoError> {
+    if additional_extensions.is_empty() {
+        return Ok(vec![]);
+    }
+    check_instance_extensions(additional_extensions, &find_available_instance_extensions(entry))?;
+    Ok(additional_extensions
+        .iter()
+        .map(|x| CString::new(x.as_str()).expect("Extension name must not contain a nul byte"))
+        .collect())
+}
+
+// Extensions the engine already enables are skipped so none is requested twice.
+pub fn append_extension_names(extension_names: &mut Vec<*const i8>, additional: &[CString]) {
+    for name in additional {
+        let is_enabled = extension_names
+            .iter()
+            .any(|x| unsafe { CStr::from_ptr(*x) } == name.as_c_str());
+        if !is_enabled {
+            debug!("Enabling {:?}", name);
+            extension_names.push(name.as_ptr());
+        }
+    }
+}
+
+pub fn check_instance_extensions(
+    requested_extensions: &[String],
+    available_extensions: &[String],
+) -> Result<(), PotatoError> {
+    let requested_extensions: Vec<&str> = requested_extensions.iter().map(|x| x.as_str()).collect();
+    let missing_extensions = find_missing_extensions(&requested_extensions, available_extensions);
+    if missing_extensions.is_empty() {
+        Ok(())
+    } else {
+        Err(PotatoError::MissingInstanceExtensions(missing_extensions))
+    }
+}
+
+pub fn create_application_info(
+    application_config: &ApplicationConfig,
+    api_version: u32,
+    app_name: &CString,
+    engine_name: &CString,
+) -> ApplicationInfo {
+    ApplicationInfo {
+        s_type: StructureType::APPLICATION_INFO,
+        p_next: std::ptr::null(),
+        p_application_name: app_name.as_ptr(),
+        application_version: application_config.app_version,
+        p_engine_name: engine_name.as_ptr(),
+        engine_version: application_config.engine_version,
+        api_version,
+    }
+}
+
+fn check_validation_layer_support(entry: &Entry) -> Result<(), PotatoError> {
+    let layer_properties = entry.enumerate_instance_layer_properties()?;
 
     debug!("{:?}", layer_properties);
-    VALIDATION
-        .required_validation_layers
+    let available_layers: Vec<String> = layer_properties
+        .iter()
+        .map(|x| vk_to_string(&x.layer_name))
+        .collect();
+
+    match find_missing_layer(&VALIDATION.required_validation_layers, &available_layers) {
+        Some(layer) => Err(PotatoError::MissingValidationLayer(layer.to_string())),
+        None => Ok(()),
+    }
+}
+
+// Missing layers only fail init when the caller asked for strict validation.
+fn resolve_validation(
+    layer_support: Result<(), PotatoError>,
+    is_lenient_validation: bool,
+) -> Result<bool, PotatoError> {
+    match layer_support {
+        Ok(_) => Ok(true),
+        Err(e) if is_lenient_validation => {
+            warn!("{}, continuing without validation", e);
+            Ok(false)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+pub fn find_missing_layer<'a>(requested: &[&'a str], available: &[String]) -> Option<&'a str> {
+    requested
         .iter()
-        .map(|layers| {
-            layer_properties
-                .iter()
-                .any(|v| vk_to_string(&v.layer_name) == *layers)
-        })
-        .any(|b| b)
+        .find(|layer| !available.iter().any(|x| x == *layer))
+        .copied()
 }
 
-fn get_enabled_layers_len() -> u32 {
-    if VALIDATION.is_enable {
+fn get_enabled_layers_len(is_validation_enabled: bool) -> u32 {
+    if is_validation_enabled {
         VALIDATION.required_validation_layers.iter().len() as u32
     } else {
         0 as u32
@@ -113,4 +280,113 @@ fn create_extention_names() -> Vec<*const i8> {
         XlibSurface::name().as_ptr(),
         DebugUtils::name().as_ptr(),
     ]
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api_version_is_the_lower_of_requested_and_supported() {
+        let vulkan_1_0 = make_version(1, 0, 0);
+        let vulkan_1_2 = make_version(1, 2, 0);
+        assert_eq!(negotiate_api_version(vulkan_1_2, vulkan_1_0), vulkan_1_0);
+        assert_eq!(negotiate_api_version(vulkan_1_0, vulkan_1_2), vulkan_1_0);
+        assert_eq!(negotiate_api_version(vulkan_1_2, vulkan_1_2), vulkan_1_2);
+    }
+
+    #[test]
+    fn find_missing_layer_reports_the_first_absent_layer() {
+        let available = vec![
+            "VK_LAYER_KHRONOS_validation".to_string(),
+            "VK_LAYER_MESA_overlay".to_string(),
+        ];
+        assert_eq!(find_missing_layer(&["VK_LAYER_KHRONOS_validation"], &available), None);
+        assert_eq!(
+            find_missing_layer(
+                &["VK_LAYER_KHRONOS_validation", "VK_LAYER_LUNARG_api_dump"],
+                &available
+            ),
+            Some("VK_LAYER_LUNARG_api_dump")
+        );
+        assert_eq!(
+            find_missing_layer(&["VK_LAYER_KHRONOS_validation"], &[]),
+            Some("VK_LAYER_KHRONOS_validation")
+        );
+    }
+
+    #[test]
+    fn missing_layers_disable_validation_unless_strict() {
+        let missing = || {
+            Err(PotatoError::MissingValidationLayer(
+                "VK_LAYER_KHRONOS_validation".to_string(),
+            ))
+        };
+        assert!(matches!(resolve_validation(Ok(()), true), Ok(true)));
+        assert!(matches!(resolve_validation(missing(), true), Ok(false)));
+        assert!(matches!(
+            resolve_validation(missing(), false),
+            Err(PotatoError::MissingValidationLayer(_))
+        ));
+    }
+
+    #[test]
+    fn application_info_points_at_the_configured_names() {
+        let application_config = ApplicationConfig {
+            app_name: "Potato Test".to_string(),
+            app_version: make_version(1, 2, 3),
+            engine_name: "Potato Engine".to_string(),
+            ..Default::default()
+        };
+        let app_name = CString::new(application_config.app_name.as_str()).unwrap();
+        let engine_name = CString::new(application_config.engine_name.as_str()).unwrap();
+        let api_version = make_version(1, 1, 0);
+
+        let app_info =
+            create_application_info(&application_config, api_version, &app_name, &engine_name);
+        let (application_name, application_engine_name) = unsafe {
+            (CStr::from_ptr(app_info.p_application_name), CStr::from_ptr(app_info.p_engine_name))
+        };
+        assert_eq!(application_name.to_str().unwrap(), "Potato Test");
+        assert_eq!(application_engine_name.to_str().unwrap(), "Potato Engine");
+        assert_eq!(app_info.application_version, make_version(1, 2, 3));
+        assert_eq!(app_info.engine_version, application_config.engine_version);
+        assert_eq!(app_info.api_version, api_version);
+    }
+
+    #[test]
+    fn additional_instance_extensions_must_be_available() {
+        let available = vec![
+            "VK_KHR_surface".to_string(),
+            "VK_KHR_get_physical_device_properties2".to_string(),
+        ];
+        assert!(check_instance_extensions(
+            &["VK_KHR_get_physical_device_properties2".to_string()],
+            &available
+        )
+        .is_ok());
+        match check_instance_extensions(
+            &["VK_KHR_surface".to_string(), "VK_EXT_headless_surface".to_string()],
+            &available,
+        ) {
+            Err(PotatoError::MissingInstanceExtensions(missing)) => {
+                assert_eq!(missing, vec!["VK_EXT_headless_surface".to_string()])
+            }
+            result => panic!("Expected missing instance extensions, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn additional_instance_extensions_are_appended_once() {
+        let additional = vec![
+            CString::new("VK_KHR_surface").unwrap(),
+            CString::new("VK_KHR_get_physical_device_properties2").unwrap(),
+        ];
+        let mut extension_names = vec![Surface::name().as_ptr()];
+        append_extension_names(&mut extension_names, &additional);
+
+        let extension_names: Vec<&CStr> =
+            extension_names.iter().map(|x| unsafe { CStr::from_ptr(*x) }).collect();
+        assert_eq!(extension_names, vec![Surface::name(), additional[1].as_c_str()]);
+    }
+}
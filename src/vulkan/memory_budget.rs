@@ -0,0 +1,102 @@
+use ash::version::InstanceV1_1;
+use ash::vk::{
+    version_minor, ExtMemoryBudgetFn, PhysicalDevice, PhysicalDeviceMemoryBudgetPropertiesEXT,
+    PhysicalDeviceMemoryProperties2, StructureType,
+};
+use ash::Instance;
+use std::ffi::CStr;
+
+pub fn memory_budget_extension_name() -> &'static CStr {
+    ExtMemoryBudgetFn::name()
+}
+
+pub fn is_memory_budget_usable(
+    is_extension_supported: bool,
+    instance_api_version: u32,
+    device_api_version: u32,
+) -> bool {
+    is_extension_supported
+        && version_minor(instance_api_version) >= 1
+        && version_minor(device_api_version) >= 1
+}
+
+pub fn query_memory_budget(
+    instance: &Instance,
+    physical_device: PhysicalDevice,
+    is_memory_budget_enabled: bool,
+) -> Vec<(u64, u64)> {
+    if !is_memory_budget_enabled {
+        return vec![];
+    }
+    let mut memory_budget_properties = PhysicalDeviceMemoryBudgetPropertiesEXT {
+        s_type: StructureType::PHYSICAL_DEVICE_MEMORY_BUDGET_PROPERTIES_EXT,
+        ..Default::default()
+    };
+    let mut memory_properties = PhysicalDeviceMemoryProperties2 {
+        s_type: StructureType::PHYSICAL_DEVICE_MEMORY_PROPERTIES_2,
+        p_next: &mut memory_budget_properties as *mut _ as *mut _,
+        ..Default::default()
+    };
+
+    unsafe {
+        instance.get_physical_device_memory_properties2(physical_device, &mut memory_properties);
+    }
+
+    collect_heap_budgets(
+        memory_properties.memory_properties.memory_heap_count,
+        &memory_budget_properties,
+    )
+}
+
+fn collect_heap_budgets(
+    memory_heap_count: u32,
+    memory_budget_properties: &PhysicalDeviceMemoryBudgetPropertiesEXT,
+) -> Vec<(u64, u64)> {
+    memory_budget_properties
+        .heap_usage
+        .iter()
+        .zip(memory_budget_properties.heap_budget.iter())
+        .take(memory_heap_count as usize)
+        .map(|(used, budget)| (*used, *budget))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::fake_device;
+    use ash::vk::{make_version, Handle};
+
+    #[test]
+    fn unsupported_extension_reports_no_budgets() {
+        let version = make_version(1, 2, 0);
+        let is_enabled = is_memory_budget_usable(false, version, version);
+        assert!(!is_enabled);
+        // The fake instance panics on the properties query, so it must not be reached.
+        let budgets = query_memory_budget(
+            &fake_device::instance(),
+            PhysicalDevice::from_raw(1),
+            is_enabled,
+        );
+        assert!(budgets.is_empty());
+    }
+
+    #[test]
+    fn budget_needs_vulkan_1_1_on_both_sides() {
+        let (vulkan_1_0, vulkan_1_1) = (make_version(1, 0, 0), make_version(1, 1, 0));
+        assert!(is_memory_budget_usable(true, vulkan_1_1, vulkan_1_1));
+        assert!(!is_memory_budget_usable(true, vulkan_1_0, vulkan_1_1));
+        assert!(!is_memory_budget_usable(true, vulkan_1_1, vulkan_1_0));
+    }
+
+    #[test]
+    fn heap_budgets_stop_at_the_heap_count() {
+        let mut memory_budget_properties = PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+        memory_budget_properties.heap_usage[..3].copy_from_slice(&[10, 20, 30]);
+        memory_budget_properties.heap_budget[..3].copy_from_slice(&[100, 200, 300]);
+        assert_eq!(
+            collect_heap_budgets(2, &memory_budget_properties),
+            vec![(10, 100), (20, 200)]
+        );
+    }
+}
@@ -1,6 +1,8 @@
+pub mod builder;
 pub mod vulk_init;
 mod vulk_validation_layers;
 mod queue_family;
+mod render_context;
 mod physical_device;
 mod utilities;
 mod queue;
@@ -14,6 +16,34 @@ mod framebuffers;
 mod command_pool;
 mod instance;
 mod sync_objects;
-mod vertex;
+pub mod vertex;
 mod buffer;
-mod UniformBufferObject;
+pub mod UniformBufferObject;
+mod error;
+pub mod shader;
+mod screenshot;
+mod texture;
+pub mod particles;
+mod timestamp;
+mod debug_draw;
+mod attachment;
+mod image_layout;
+mod frame_stats;
+mod pipeline_cache;
+pub mod offscreen;
+mod submit_batch;
+pub mod scene;
+mod descriptor_allocator;
+mod memory_budget;
+mod pipeline_stats;
+mod portability;
+pub mod color;
+pub mod postprocess;
+pub mod text;
+pub mod overlay;
+pub mod indirect;
+pub mod frustum;
+pub mod render_graph;
+pub mod external_memory;
+#[cfg(test)]
+mod fake_device;
@@ -0,0 +1,250 @@
+use super::attachment::{create_depth_attachment, create_msaa_color_attachment, AttachmentImage};
+use super::render_pass::create_offscreen_render_pass;
+use super::texture::create_texture_descriptor_write;
+use ash::version::DeviceV1_0;
+use ash::vk::{
+    ClearColorValue, ClearDepthStencilValue, ClearValue, CommandBuffer, DescriptorImageInfo,
+    DescriptorSet, Extent2D, Format, Framebuffer, FramebufferCreateFlags, FramebufferCreateInfo,
+    ImageAspectFlags, ImageLayout, ImageUsageFlags, Offset2D, PhysicalDeviceMemoryProperties,
+    Rect2D, RenderPass, RenderPassBeginInfo, SampleCountFlags, Sampler, StructureType,
+    SubpassContents,
+};
+use ash::Device;
+
+pub const OFFSCREEN_COLOR_USAGE: ImageUsageFlags = ImageUsageFlags::from_raw(
+    ImageUsageFlags::COLOR_ATTACHMENT.as_raw() | ImageUsageFlags::SAMPLED.as_raw(),
+);
+
+pub struct OffscreenTarget {
+    pub color: AttachmentImage,
+    pub depth: AttachmentImage,
+    pub resolve: Option<AttachmentImage>,
+    pub render_pass: RenderPass,
+    pub framebuffer: Framebuffer,
+    pub extent: Extent2D,
+}
+
+impl OffscreenTarget {
+    pub fn create(
+        device: &Device,
+        device_memory_properties: &PhysicalDeviceMemoryProperties,
+        extent: Extent2D,
+        format: Format,
+        depth_format: Format,
+        samples: SampleCountFlags,
+    ) -> OffscreenTarget {
+        let is_multisampled = samples != SampleCountFlags::TYPE_1;
+        let color = if is_multisampled {
+            create_msaa_color_attachment(device, device_memory_properties, extent, format, samples)
+        } else {
+            create_sampled_color_attachment(device, device_memory_properties, extent, format)
+        };
+        let depth = create_depth_attachment(
+            device,
+            device_memory_properties,
+            extent,
+            depth_format,
+            samples,
+        );
+        let resolve = if is_multisampled {
+            Some(create_sampled_color_attachment(
+                device,
+                device_memory_properties,
+                extent,
+                format,
+            ))
+        } else {
+            None
+        };
+        let render_pass = create_offscreen_render_pass(
+            device,
+            format,
+            depth_format,
+            samples,
+            resolve.as_ref().map_or(format, |x| x.format),
+        )
+        .expect("Failed to create offscreen render pass");
+
+        let mut attachments = vec![color.view, depth.view];
+        if let Some(resolve) = &resolve {
+            attachments.push(resolve.view);
+        }
+        let framebuffer_create_info = FramebufferCreateInfo {
+            s_type: StructureType::FRAMEBUFFER_CREATE_INFO,
+            p_next: std::ptr::null(),
+            flags: FramebufferCreateFlags::empty(),
+            render_pass,
+            attachment_count: attachments.len() as u32,
+            p_attachments: attachments.as_ptr(),
+            width: extent.width,
+            height: extent.height,
+            layers: 1,
+        };
+        let framebuffer = unsafe {
+            device
+                .create_framebuffer(&framebuffer_create_info, None)
+                .expect("Failed to create offscreen framebuffer")
+        };
+
+        OffscreenTarget {
+            color,
+            depth,
+            resolve,
+            render_pass,
+            framebuffer,
+            extent,
+        }
+    }
+
+    pub fn begin(
+        &self,
+        device: &Device,
+        command_buffer: CommandBuffer,
+        clear_color: [f32; 4],
+        depth_clear_value: f32,
+    ) {
+        let clear_values = [
+            ClearValue {
+                color: ClearColorValue {
+                    float32: clear_color,
+                },
+            },
+            ClearValue {
+                depth_stencil: ClearDepthStencilValue {
+                    depth: depth_clear_value,
+                    stencil: 0,
+                },
+            },
+        ];
+
+        let render_pass_begin_info = RenderPassBeginInfo {
+            s_type: StructureType::RENDER_PASS_BEGIN_INFO,
+            p_next: std::ptr::null(),
+            render_pass: self.render_pass,
+            framebuffer: self.framebuffer,
+            render_area: Rect2D {
+                offset: Offset2D { x: 0, y: 0 },
+                extent: self.extent,
+            },
+            clear_value_count: clear_values.len() as u32,
+            p_clear_values: clear_values.as_ptr(),
+        };
+
+        unsafe {
+            device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                SubpassContents::INLINE,
+            );
+        }
+    }
+
+    pub fn end(&self, device: &Device, command_buffer: CommandBuffer) {
+        unsafe {
+            device.cmd_end_render_pass(command_buffer);
+        }
+    }
+
+    pub fn sampled_image(&self) -> &AttachmentImage {
+        self.resolve.as_ref().unwrap_or(&self.color)
+    }
+
+    pub fn descriptor_image_info(&self, sampler: Sampler) -> DescriptorImageInfo {
+        DescriptorImageInfo {
+            sampler,
+            image_view: self.sampled_image().view,
+            image_layout: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        }
+    }
+
+    pub fn write_descriptor(
+        &self,
+        device: &Device,
+        descriptor_set: DescriptorSet,
+        binding: u32,
+        sampler: Sampler,
+    ) {
+        let descriptor_image_info = [self.descriptor_image_info(sampler)];
+
+        let descriptor_write_sets = [create_texture_descriptor_write(
+            descriptor_set,
+            binding,
+            &descriptor_image_info,
+        )];
+
+        unsafe {
+            device.update_descriptor_sets(&descriptor_write_sets, &[]);
+        }
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        unsafe {
+            device.destroy_framebuffer(self.framebuffer, None);
+            device.destroy_render_pass(self.render_pass, None);
+        }
+        if let Some(resolve) = &self.resolve {
+            resolve.destroy(device);
+        }
+        self.depth.destroy(device);
+        self.color.destroy(device);
+    }
+}
+
+fn create_sampled_color_attachment(
+    device: &Device,
+    device_memory_properties: &PhysicalDeviceMemoryProperties,
+    extent: Extent2D,
+    format: Format,
+) -> AttachmentImage {
+    AttachmentImage::new(
+        device,
+        device_memory_properties,
+        extent,
+        format,
+        SampleCountFlags::TYPE_1,
+        OFFSCREEN_COLOR_USAGE,
+        ImageAspectFlags::COLOR,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::fake_device::{self, IMAGE, IMAGE_VIEW, MEMORY};
+    use ash::vk::{MemoryPropertyFlags, MemoryType};
+
+    #[test]
+    fn offscreen_image_can_be_rendered_to_and_sampled() {
+        let mut device_memory_properties = PhysicalDeviceMemoryProperties {
+            memory_type_count: 1,
+            ..Default::default()
+        };
+        device_memory_properties.memory_types[0] = MemoryType {
+            property_flags: MemoryPropertyFlags::DEVICE_LOCAL,
+            heap_index: 0,
+        };
+        let extent = Extent2D {
+            width: 256,
+            height: 256,
+        };
+        let color = create_sampled_color_attachment(
+            &fake_device::device(),
+            &device_memory_properties,
+            extent,
+            Format::R8G8B8A8_UNORM,
+        );
+
+        let usage = ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::SAMPLED;
+        assert_eq!(
+            fake_device::take_calls(),
+            vec![
+                ("vkCreateImage", u64::from(usage.as_raw())),
+                ("vkGetImageMemoryRequirements", IMAGE),
+                ("vkAllocateMemory", MEMORY),
+                ("vkBindImageMemory", IMAGE),
+                ("vkCreateImageView", IMAGE_VIEW),
+            ]
+        );
+        assert_eq!(color.samples, SampleCountFlags::TYPE_1);
+    }
+}
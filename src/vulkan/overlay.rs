@@ -0,0 +1,225 @@
+use super::buffer::{upload_host_visible, PotatoBuffer};
+use super::shader::ShaderSource;
+use ash::version::DeviceV1_0;
+use ash::vk::{
+    BufferUsageFlags, CommandBuffer, Extent2D, Format, IndexType, PhysicalDeviceMemoryProperties,
+    Pipeline, PipelineBindPoint, PipelineLayout, VertexInputAttributeDescription,
+    VertexInputBindingDescription, VertexInputRate,
+};
+use ash::Device;
+use std::rc::Rc;
+
+pub const OVERLAY_VERTEX_SHADER: ShaderSource =
+    ShaderSource::File("src/shaders/spv/overlay-vert.spv");
+pub const OVERLAY_FRAGMENT_SHADER: ShaderSource =
+    ShaderSource::File("src/shaders/spv/overlay-frag.spv");
+const QUAD_INDICES: [u32; 6] = [0, 1, 2, 2, 3, 0];
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OverlayVertex {
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl OverlayVertex {
+    pub fn get_binding_descriptions() -> [VertexInputBindingDescription; 1] {
+        [VertexInputBindingDescription {
+            binding: 0,
+            stride: std::mem::size_of::<Self>() as u32,
+            input_rate: VertexInputRate::VERTEX,
+        }]
+    }
+
+    pub fn get_attribute_descriptions() -> [VertexInputAttributeDescription; 2] {
+        [
+            VertexInputAttributeDescription {
+                binding: 0,
+                location: 0,
+                format: Format::R32G32_SFLOAT,
+                offset: 0,
+            },
+            VertexInputAttributeDescription {
+                binding: 0,
+                location: 1,
+                format: Format::R32G32B32A32_SFLOAT,
+                offset: std::mem::size_of::<[f32; 2]>() as u32,
+            },
+        ]
+    }
+}
+
+// Vulkan's NDC has y pointing down, so a top-left pixel origin maps without a flip.
+pub fn pixel_to_ndc(x: f32, y: f32, viewport: Extent2D) -> [f32; 2] {
+    [
+        x / viewport.width as f32 * 2.0 - 1.0,
+        y / viewport.height as f32 * 2.0 - 1.0,
+    ]
+}
+
+pub struct Overlay2d {
+    pub viewport: Extent2D,
+    pub index_count: u32,
+    vertices: Vec<OverlayVertex>,
+    indices: Vec<u32>,
+    vertex_buffer: Option<PotatoBuffer>,
+    index_buffer: Option<PotatoBuffer>,
+}
+
+impl Overlay2d {
+    pub fn new() -> Overlay2d {
+        Overlay2d {
+            viewport: Extent2D {
+                width: 1,
+                height: 1,
+            },
+            index_count: 0,
+            vertices: vec![],
+            indices: vec![],
+            vertex_buffer: None,
+            index_buffer: None,
+        }
+    }
+
+    pub fn begin(&mut self, viewport: Extent2D) {
+        self.viewport = viewport;
+        self.vertices.clear();
+        self.indices.clear();
+    }
+
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) {
+        self.quad(
+            [
+                [x, y],
+                [x + width, y],
+                [x + width, y + height],
+                [x, y + height],
+            ],
+            color,
+        );
+    }
+
+    // Lines are drawn as one pixel wide quads so they share the rect pipeline.
+    pub fn line(&mut self, a: [f32; 2], b: [f32; 2], color: [f32; 4]) {
+        let direction = [b[0] - a[0], b[1] - a[1]];
+        let length = (direction[0] * direction[0] + direction[1] * direction[1]).sqrt();
+        if length == 0.0 {
+            return;
+        }
+        let normal = [-direction[1] / length * 0.5, direction[0] / length * 0.5];
+        self.quad(
+            [
+                [a[0] + normal[0], a[1] + normal[1]],
+                [b[0] + normal[0], b[1] + normal[1]],
+                [b[0] - normal[0], b[1] - normal[1]],
+                [a[0] - normal[0], a[1] - normal[1]],
+            ],
+            color,
+        );
+    }
+
+    pub fn vertices(&self) -> &[OverlayVertex] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    fn quad(&mut self, corners: [[f32; 2]; 4], color: [f32; 4]) {
+        let base = self.vertices.len() as u32;
+        let viewport = self.viewport;
+        self.vertices
+            .extend(corners.iter().map(|[x, y]| OverlayVertex {
+                position: pixel_to_ndc(*x, *y, viewport),
+                color,
+            }));
+        self.indices.extend(QUAD_INDICES.iter().map(|i| base + i));
+    }
+
+    pub fn upload(
+        &mut self,
+        device: &Rc<Device>,
+        device_memory_properties: &PhysicalDeviceMemoryProperties,
+    ) {
+        self.index_count = self.indices.len() as u32;
+        if self.indices.is_empty() {
+            return;
+        }
+        upload_host_visible(
+            device,
+            device_memory_properties,
+            &mut self.vertex_buffer,
+            BufferUsageFlags::VERTEX_BUFFER,
+            &self.vertices,
+        );
+        upload_host_visible(
+            device,
+            device_memory_properties,
+            &mut self.index_buffer,
+            BufferUsageFlags::INDEX_BUFFER,
+            &self.indices,
+        );
+    }
+
+    pub fn release(&mut self) {
+        self.vertex_buffer = None;
+        self.index_buffer = None;
+        self.index_count = 0;
+    }
+
+    pub fn record_draw(
+        &self,
+        device: &Device,
+        command_buffer: CommandBuffer,
+        (pipeline, _): (Pipeline, PipelineLayout),
+    ) {
+        let (vertex_buffer, index_buffer) = match (&self.vertex_buffer, &self.index_buffer) {
+            (Some(vertex_buffer), Some(index_buffer)) if self.index_count > 0 => {
+                (vertex_buffer, index_buffer)
+            }
+            _ => return,
+        };
+
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::GRAPHICS, pipeline);
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[vertex_buffer.buffer], &[0]);
+            device.cmd_bind_index_buffer(command_buffer, index_buffer.buffer, 0, IndexType::UINT32);
+            device.cmd_draw_indexed(command_buffer, self.index_count, 1, 0, 0, 0);
+        }
+    }
+}
+
+impl Default for Overlay2d {
+    fn default() -> Self {
+        Overlay2d::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIEWPORT: Extent2D = Extent2D {
+        width: 800,
+        height: 600,
+    };
+
+    #[test]
+    fn pixel_corners_and_center_map_to_ndc() {
+        assert_eq!(pixel_to_ndc(0.0, 0.0, VIEWPORT), [-1.0, -1.0]);
+        assert_eq!(pixel_to_ndc(800.0, 600.0, VIEWPORT), [1.0, 1.0]);
+        assert_eq!(pixel_to_ndc(800.0, 0.0, VIEWPORT), [1.0, -1.0]);
+        assert_eq!(pixel_to_ndc(400.0, 300.0, VIEWPORT), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn rect_emits_a_quad_in_ndc() {
+        let mut overlay = Overlay2d::new();
+        overlay.begin(VIEWPORT);
+        overlay.rect(400.0, 300.0, 400.0, 300.0, [1.0; 4]);
+        let positions: Vec<_> = overlay.vertices().iter().map(|x| x.position).collect();
+        assert_eq!(positions, vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+        assert_eq!(overlay.indices(), &QUAD_INDICES);
+    }
+}
@@ -0,0 +1,407 @@
+use super::buffer::{create_device_local_buffer, StagingBuffer, UploadQueues};
+use super::shader::{load_shader_module, ShaderSource};
+use ash::version::DeviceV1_0;
+use ash::vk::{
+    AccessFlags, Buffer, BufferMemoryBarrier, BufferUsageFlags, CommandBuffer,
+    ComputePipelineCreateInfo, DependencyFlags, DescriptorBufferInfo, DescriptorPool,
+    DescriptorPoolCreateFlags, DescriptorPoolCreateInfo, DescriptorPoolSize, DescriptorSet,
+    DescriptorSetAllocateInfo, DescriptorSetLayout, DescriptorSetLayoutBinding,
+    DescriptorSetLayoutCreateFlags, DescriptorSetLayoutCreateInfo, DescriptorType, DeviceMemory,
+    DeviceSize, Format, PhysicalDeviceMemoryProperties, Pipeline, PipelineBindPoint, PipelineCache,
+    PipelineCreateFlags, PipelineLayout, PipelineLayoutCreateFlags, PipelineLayoutCreateInfo,
+    PipelineShaderStageCreateFlags, PipelineShaderStageCreateInfo, PipelineStageFlags,
+    ShaderStageFlags, StructureType, VertexInputAttributeDescription,
+    VertexInputBindingDescription, VertexInputRate, WriteDescriptorSet, QUEUE_FAMILY_IGNORED,
+    WHOLE_SIZE,
+};
+use ash::Device;
+use memoffset::offset_of;
+use std::ffi::CString;
+
+const WORKGROUP_SIZE: u32 = 256;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Particle {
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl Particle {
+    pub fn get_binding_descriptions() -> [VertexInputBindingDescription; 1] {
+        [VertexInputBindingDescription {
+            binding: 0,
+            stride: std::mem::size_of::<Self>() as u32,
+            input_rate: VertexInputRate::VERTEX,
+        }]
+    }
+
+    pub fn get_attribute_descriptions() -> [VertexInputAttributeDescription; 2] {
+        [
+            VertexInputAttributeDescription {
+                location: 0,
+                binding: 0,
+                format: Format::R32G32_SFLOAT,
+                offset: offset_of!(Self, position) as u32,
+            },
+            VertexInputAttributeDescription {
+                location: 1,
+                binding: 0,
+                format: Format::R32G32B32A32_SFLOAT,
+                offset: offset_of!(Self, color) as u32,
+            },
+        ]
+    }
+}
+
+pub struct ParticleSystem {
+    pub particle_count: u32,
+    pub buffer: Buffer,
+    memory: DeviceMemory,
+    descriptor_set_layout: DescriptorSetLayout,
+    descriptor_pool: DescriptorPool,
+    descriptor_set: DescriptorSet,
+    pipeline_layout: PipelineLayout,
+    pipeline: Pipeline,
+}
+
+impl ParticleSystem {
+    pub fn new(
+        device: &Device,
+        device_memory_properties: &PhysicalDeviceMemoryProperties,
+        upload_queues: &UploadQueues,
+        staging_buffer: &mut StagingBuffer,
+        particles: &[Particle],
+        compute_shader: ShaderSource,
+        pipeline_cache: PipelineCache,
+    ) -> ParticleSystem {
+        let (buffer, memory) = create_device_local_buffer(
+            device,
+            device_memory_properties,
+            upload_queues,
+            staging_buffer,
+            particles,
+            BufferUsageFlags::TRANSFER_DST
+                | BufferUsageFlags::STORAGE_BUFFER
+                | BufferUsageFlags::VERTEX_BUFFER,
+        );
+
+        let descriptor_set_layout = create_particle_descriptor_set_layout(device);
+        let descriptor_pool = create_particle_descriptor_pool(device);
+        let descriptor_set =
+            create_particle_descriptor_set(device, descriptor_pool, descriptor_set_layout, buffer);
+        let (pipeline, pipeline_layout) =
+            create_compute_pipeline(device, pipeline_cache, descriptor_set_layout, compute_shader);
+
+        ParticleSystem {
+            particle_count: particles.len() as u32,
+            buffer,
+            memory,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_set,
+            pipeline_layout,
+            pipeline,
+        }
+    }
+
+    pub fn record_update(&self, device: &Device, command_buffer: CommandBuffer) {
+        unsafe {
+            record_buffer_barrier(
+                device,
+                command_buffer,
+                &create_vertex_to_compute_barrier(self.buffer),
+            );
+            device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::COMPUTE, self.pipeline);
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                PipelineBindPoint::COMPUTE,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor_set],
+                &[],
+            );
+            device.cmd_dispatch(
+                command_buffer,
+                self.particle_count.div_ceil(WORKGROUP_SIZE),
+                1,
+                1,
+            );
+            record_buffer_barrier(
+                device,
+                command_buffer,
+                &create_compute_to_vertex_barrier(self.buffer),
+            );
+        }
+    }
+
+    pub fn record_draw(&self, device: &Device, command_buffer: CommandBuffer, pipeline: Pipeline) {
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::GRAPHICS, pipeline);
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.buffer], &[0]);
+            device.cmd_draw(command_buffer, self.particle_count, 1, 0, 0);
+        }
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+            device.destroy_buffer(self.buffer, None);
+            device.free_memory(self.memory, None);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BufferBarrier {
+    pub src_stage: PipelineStageFlags,
+    pub dst_stage: PipelineStageFlags,
+    pub barrier: BufferMemoryBarrier,
+}
+
+pub fn create_compute_to_vertex_barrier(buffer: Buffer) -> BufferBarrier {
+    BufferBarrier {
+        src_stage: PipelineStageFlags::COMPUTE_SHADER,
+        dst_stage: PipelineStageFlags::VERTEX_INPUT,
+        barrier: create_buffer_barrier(
+            buffer,
+            AccessFlags::SHADER_WRITE,
+            AccessFlags::VERTEX_ATTRIBUTE_READ,
+        ),
+    }
+}
+
+pub fn create_vertex_to_compute_barrier(buffer: Buffer) -> BufferBarrier {
+    BufferBarrier {
+        src_stage: PipelineStageFlags::VERTEX_INPUT,
+        dst_stage: PipelineStageFlags::COMPUTE_SHADER,
+        barrier: create_buffer_barrier(
+            buffer,
+            AccessFlags::VERTEX_ATTRIBUTE_READ,
+            AccessFlags::SHADER_WRITE,
+        ),
+    }
+}
+
+fn record_buffer_barrier(device: &Device, command_buffer: CommandBuffer, barrier: &BufferBarrier) {
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            barrier.src_stage,
+            barrier.dst_stage,
+            DependencyFlags::empty(),
+            &[],
+            &[barrier.barrier],
+            &[],
+        );
+    }
+}
+
+fn create_buffer_barrier(
+    buffer: Buffer,
+    src_access_mask: AccessFlags,
+    dst_access_mask: AccessFlags,
+) -> BufferMemoryBarrier {
+    BufferMemoryBarrier {
+        s_type: StructureType::BUFFER_MEMORY_BARRIER,
+        p_next: std::ptr::null(),
+        src_access_mask,
+        dst_access_mask,
+        src_queue_family_index: QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index: QUEUE_FAMILY_IGNORED,
+        buffer,
+        offset: 0,
+        size: WHOLE_SIZE,
+    }
+}
+
+fn create_particle_descriptor_set_layout(device: &Device) -> DescriptorSetLayout {
+    let layout_bindings = [DescriptorSetLayoutBinding {
+        binding: 0,
+        descriptor_type: DescriptorType::STORAGE_BUFFER,
+        descriptor_count: 1,
+        stage_flags: ShaderStageFlags::COMPUTE,
+        p_immutable_samplers: std::ptr::null(),
+    }];
+
+    let layout_create_info = DescriptorSetLayoutCreateInfo {
+        s_type: StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+        p_next: std::ptr::null(),
+        flags: DescriptorSetLayoutCreateFlags::empty(),
+        binding_count: layout_bindings.len() as u32,
+        p_bindings: layout_bindings.as_ptr(),
+    };
+
+    unsafe {
+        device
+            .create_descriptor_set_layout(&layout_create_info, None)
+            .expect("Failed to create particle descriptor set layout")
+    }
+}
+
+fn create_particle_descriptor_pool(device: &Device) -> DescriptorPool {
+    let pool_sizes = [DescriptorPoolSize {
+        ty: DescriptorType::STORAGE_BUFFER,
+        descriptor_count: 1,
+    }];
+
+    let descriptor_pool_create_info = DescriptorPoolCreateInfo {
+        s_type: StructureType::DESCRIPTOR_POOL_CREATE_INFO,
+        p_next: std::ptr::null(),
+        flags: DescriptorPoolCreateFlags::empty(),
+        max_sets: 1,
+        pool_size_count: pool_sizes.len() as u32,
+        p_pool_sizes: pool_sizes.as_ptr(),
+    };
+
+    unsafe {
+        device
+            .create_descriptor_pool(&descriptor_pool_create_info, None)
+            .expect("Failed to create particle descriptor pool")
+    }
+}
+
+fn create_particle_descriptor_set(
+    device: &Device,
+    descriptor_pool: DescriptorPool,
+    descriptor_set_layout: DescriptorSetLayout,
+    buffer: Buffer,
+) -> DescriptorSet {
+    let layouts = [descriptor_set_layout];
+    let descriptor_set_allocate_info = DescriptorSetAllocateInfo {
+        s_type: StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
+        p_next: std::ptr::null(),
+        descriptor_pool,
+        descriptor_set_count: layouts.len() as u32,
+        p_set_layouts: layouts.as_ptr(),
+    };
+
+    let descriptor_set = unsafe {
+        device
+            .allocate_descriptor_sets(&descriptor_set_allocate_info)
+            .expect("Failed to allocate particle descriptor set")[0]
+    };
+
+    let descriptor_buffer_info = [DescriptorBufferInfo {
+        buffer,
+        offset: 0,
+        range: WHOLE_SIZE as DeviceSize,
+    }];
+
+    let descriptor_write_sets = [WriteDescriptorSet {
+        s_type: StructureType::WRITE_DESCRIPTOR_SET,
+        p_next: std::ptr::null(),
+        dst_set: descriptor_set,
+        dst_binding: 0,
+        dst_array_element: 0,
+        descriptor_count: 1,
+        descriptor_type: DescriptorType::STORAGE_BUFFER,
+        p_image_info: std::ptr::null(),
+        p_buffer_info: descriptor_buffer_info.as_ptr(),
+        p_texel_buffer_view: std::ptr::null(),
+    }];
+
+    unsafe {
+        device.update_descriptor_sets(&descriptor_write_sets, &[]);
+    }
+
+    descriptor_set
+}
+
+fn create_compute_pipeline(
+    device: &Device,
+    pipeline_cache: PipelineCache,
+    descriptor_set_layout: DescriptorSetLayout,
+    compute_shader: ShaderSource,
+) -> (Pipeline, PipelineLayout) {
+    let comp_module = load_shader_module(device, compute_shader)
+        .unwrap_or_else(|e| panic!("Failed to load compute shader: {}", e));
+
+    let set_layouts = [descriptor_set_layout];
+    let pipeline_layout_create_info = PipelineLayoutCreateInfo {
+        s_type: StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+        p_next: std::ptr::null(),
+        flags: PipelineLayoutCreateFlags::empty(),
+        set_layout_count: set_layouts.len() as u32,
+        p_set_layouts: set_layouts.as_ptr(),
+        push_constant_range_count: 0,
+        p_push_constant_ranges: std::ptr::null(),
+    };
+
+    let pipeline_layout = unsafe {
+        device
+            .create_pipeline_layout(&pipeline_layout_create_info, None)
+            .expect("Failed to create compute pipeline layout")
+    };
+
+    let main_function_name = CString::new("main").unwrap();
+    let compute_pipeline_create_infos = [ComputePipelineCreateInfo {
+        s_type: StructureType::COMPUTE_PIPELINE_CREATE_INFO,
+        p_next: std::ptr::null(),
+        flags: PipelineCreateFlags::empty(),
+        stage: PipelineShaderStageCreateInfo {
+            s_type: StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
+            p_next: std::ptr::null(),
+            flags: PipelineShaderStageCreateFlags::empty(),
+            module: comp_module,
+            p_name: main_function_name.as_ptr(),
+            p_specialization_info: std::ptr::null(),
+            stage: ShaderStageFlags::COMPUTE,
+        },
+        layout: pipeline_layout,
+        base_pipeline_handle: Pipeline::null(),
+        base_pipeline_index: -1,
+    }];
+
+    let compute_pipelines = unsafe {
+        device
+            .create_compute_pipelines(pipeline_cache, &compute_pipeline_create_infos, None)
+            .expect("Failed to create compute pipeline")
+    };
+    unsafe {
+        device.destroy_shader_module(comp_module, None);
+    }
+
+    (compute_pipelines[0], pipeline_layout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::vk::Handle;
+
+    #[test]
+    fn particle_barriers_hand_the_buffer_between_compute_and_vertex_input() {
+        let buffer = Buffer::from_raw(1);
+        let cases = [
+            (
+                create_compute_to_vertex_barrier(buffer),
+                AccessFlags::SHADER_WRITE,
+                AccessFlags::VERTEX_ATTRIBUTE_READ,
+                PipelineStageFlags::COMPUTE_SHADER,
+                PipelineStageFlags::VERTEX_INPUT,
+            ),
+            (
+                create_vertex_to_compute_barrier(buffer),
+                AccessFlags::VERTEX_ATTRIBUTE_READ,
+                AccessFlags::SHADER_WRITE,
+                PipelineStageFlags::VERTEX_INPUT,
+                PipelineStageFlags::COMPUTE_SHADER,
+            ),
+        ];
+        for (barrier, src_access_mask, dst_access_mask, src_stage, dst_stage) in cases {
+            assert_eq!(barrier.barrier.src_access_mask, src_access_mask);
+            assert_eq!(barrier.barrier.dst_access_mask, dst_access_mask);
+            assert_eq!(barrier.src_stage, src_stage);
+            assert_eq!(barrier.dst_stage, dst_stage);
+            assert_eq!(barrier.barrier.buffer, buffer);
+            assert_eq!(barrier.barrier.size, WHOLE_SIZE);
+            assert_eq!(barrier.barrier.src_queue_family_index, QUEUE_FAMILY_IGNORED);
+            assert_eq!(barrier.barrier.dst_queue_family_index, QUEUE_FAMILY_IGNORED);
+        }
+    }
+}
@@ -1,33 +1,253 @@
 use super::queue_family::find_graphical_queue_family;
-use super::surface::PotatoSurface;
+use super::surface::{create_surface, PotatoSurface};
 use super::utilities::vk_to_string;
 use super::constants::DEVICE_EXTENSTIONS;
+use super::error::PotatoError;
 use super::swapchain::determine_swapchain_support;
 use ash::version::InstanceV1_0;
 use ash::vk::{
-    version_major, version_minor, version_patch, PhysicalDevice, PhysicalDeviceProperties,
-    PhysicalDeviceType, QueueFlags,
+    version_major, version_minor, version_patch, PhysicalDevice, PhysicalDeviceFeatures,
+    PhysicalDeviceProperties, PhysicalDeviceType, QueueFlags, TRUE,
 };
-use ash::Instance;
-use log::{info,debug};
+use ash::{Entry, Instance};
+use log::{info,debug,warn};
+use std::cmp::Reverse;
 use std::collections::HashSet;
+use winit::window::Window;
 
-pub fn select_physical_device(instance: &Instance, surface: &PotatoSurface) -> PhysicalDevice {
-    let physical_devices = unsafe {
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub device_type: PhysicalDeviceType,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub driver_version: u32,
+    pub max_image_dimension_2d: u32,
+    pub max_image_dimension_3d: u32,
+    pub max_push_constants_size: u32,
+    pub sampler_anisotropy: bool,
+    pub geometry_shader: bool,
+    pub suitability: Option<DeviceSuitability>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceSuitability {
+    pub has_queue_families: bool,
+    pub missing_extensions: Vec<String>,
+    pub has_swapchain_support: bool,
+}
+
+impl DeviceSuitability {
+    pub fn has_extensions(&self) -> bool {
+        self.missing_extensions.is_empty()
+    }
+
+    pub fn is_suitable(&self) -> bool {
+        self.has_queue_families && self.has_extensions() && self.has_swapchain_support
+    }
+
+    pub fn failure_reason(&self) -> Option<String> {
+        if !self.has_queue_families {
+            Some("no queue family supports graphics and presentation".to_string())
+        } else if !self.has_extensions() {
+            Some(format!("missing extensions {}", self.missing_extensions.join(", ")))
+        } else if !self.has_swapchain_support {
+            Some("no surface format or present mode for a swapchain".to_string())
+        } else {
+            None
+        }
+    }
+}
+
+impl DeviceInfo {
+    pub fn new(
+        properties: &PhysicalDeviceProperties,
+        features: &PhysicalDeviceFeatures,
+    ) -> DeviceInfo {
+        DeviceInfo {
+            name: vk_to_string(&properties.device_name),
+            device_type: properties.device_type,
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            driver_version: properties.driver_version,
+            max_image_dimension_2d: properties.limits.max_image_dimension2_d,
+            max_image_dimension_3d: properties.limits.max_image_dimension3_d,
+            max_push_constants_size: properties.limits.max_push_constants_size,
+            sampler_anisotropy: features.sampler_anisotropy == TRUE,
+            geometry_shader: features.geometry_shader == TRUE,
+            suitability: None,
+        }
+    }
+
+    pub fn device_type_name(&self) -> &'static str {
+        find_device_type(self.device_type)
+    }
+
+    pub fn is_software(&self) -> bool {
+        is_software_device(self.device_type, self.vendor_id, self.device_id)
+    }
+
+    pub fn is_suitable(&self) -> bool {
+        self.suitability.as_ref().is_some_and(|x| x.is_suitable())
+    }
+
+    pub fn missing_extensions(&self) -> &[String] {
+        self.suitability
+            .as_ref()
+            .map_or(&[], |x| x.missing_extensions.as_slice())
+    }
+
+    pub fn failure_reason(&self) -> Option<String> {
+        self.suitability.as_ref().and_then(DeviceSuitability::failure_reason)
+    }
+}
+
+const VENDOR_ID_MESA: u32 = 0x10005;
+const DEVICE_ID_LAVAPIPE: u32 = 0;
+const VENDOR_ID_GOOGLE: u32 = 0x1AE0;
+const DEVICE_ID_SWIFTSHADER: u32 = 0xC0DE;
+
+// Mesa's vendor id alone doesn't mean software, only lavapipe (llvmpipe) is.
+pub fn is_software_device(device_type: PhysicalDeviceType, vendor_id: u32, device_id: u32) -> bool {
+    device_type == PhysicalDeviceType::CPU
+        || (vendor_id == VENDOR_ID_MESA && device_id == DEVICE_ID_LAVAPIPE)
+        || (vendor_id == VENDOR_ID_GOOGLE && device_id == DEVICE_ID_SWIFTSHADER)
+}
+
+pub fn query_device_info(instance: &Instance, physical_device: PhysicalDevice) -> DeviceInfo {
+    let (properties, features) = unsafe {
+        (
+            instance.get_physical_device_properties(physical_device),
+            instance.get_physical_device_features(physical_device),
+        )
+    };
+    DeviceInfo::new(&properties, &features)
+}
+
+fn enumerate_physical_devices(instance: &Instance) -> Vec<PhysicalDevice> {
+    unsafe {
         instance
             .enumerate_physical_devices()
             .expect("Failed to enumerate physical devices")
-    };
-    info!("{} GPU device(s) found", physical_devices.len());
+    }
+}
 
-    let selected_device = physical_devices
+// Suitability depends on a surface, so one is created for the window just for the check.
+pub fn list_physical_devices(
+    entry: &Entry,
+    instance: &Instance,
+    window: &Window,
+) -> Vec<DeviceInfo> {
+    let surface = create_surface(entry, instance, window);
+    let device_infos = list_surface_devices(instance, &surface);
+    unsafe {
+        surface
+            .surface_loader
+            .destroy_surface(surface.surface, None);
+    }
+    device_infos
+}
+
+pub fn list_surface_devices(instance: &Instance, surface: &PotatoSurface) -> Vec<DeviceInfo> {
+    query_device_infos(instance, &enumerate_physical_devices(instance), surface)
+}
+
+fn query_device_infos(
+    instance: &Instance,
+    physical_devices: &[PhysicalDevice],
+    surface: &PotatoSurface,
+) -> Vec<DeviceInfo> {
+    physical_devices
         .iter()
-        .find(|x| check_device_compatability(instance, **x, surface));
+        .map(|x| DeviceInfo {
+            suitability: Some(check_device_compatability(instance, *x, surface)),
+            ..query_device_info(instance, *x)
+        })
+        .collect()
+}
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum DevicePreference {
+    FirstSuitable,
+    PreferDiscrete,
+    Index(usize),
+    Name(String),
+}
+
+pub fn select_physical_device(
+    instance: &Instance,
+    surface: &PotatoSurface,
+    preference: &DevicePreference,
+) -> Result<PhysicalDevice, PotatoError> {
+    let physical_devices = enumerate_physical_devices(instance);
+    info!("{} GPU device(s) found", physical_devices.len());
+
+    let candidates = query_device_infos(instance, &physical_devices, surface);
+    candidates.iter().for_each(|x| {
+        info!("Found {} ({})", x.name, x.device_type_name());
+        if let Some(reason) = x.failure_reason() {
+            warn!("Skipping {}, {}", x.name, reason);
+        }
+    });
+
+    let selected_device = physical_devices[choose_device(&candidates, preference)?];
     debug!("{:?}", selected_device);
-    match selected_device {
-        Some(p_physical_device) => *p_physical_device,
-        None => panic!("Failed to find compatable device")
+    Ok(selected_device)
+}
+
+pub fn choose_device(
+    candidates: &[DeviceInfo],
+    preference: &DevicePreference,
+) -> Result<usize, PotatoError> {
+    let first_suitable = || {
+        candidates.iter().position(|x| x.is_suitable()).ok_or_else(|| {
+            match candidates.iter().find(|x| !x.missing_extensions().is_empty()) {
+                Some(candidate) => {
+                    PotatoError::MissingDeviceExtensions(candidate.missing_extensions().to_vec())
+                }
+                None => PotatoError::NoSuitableDevice,
+            }
+        })
+    };
+    let require_suitable = |index: usize| {
+        let candidate = &candidates[index];
+        if candidate.is_suitable() {
+            Ok(index)
+        } else if !candidate.missing_extensions().is_empty() {
+            Err(PotatoError::MissingDeviceExtensions(candidate.missing_extensions().to_vec()))
+        } else {
+            Err(PotatoError::UnsuitableDevice(candidate.name.clone()))
+        }
+    };
+
+    match preference {
+        DevicePreference::FirstSuitable => first_suitable(),
+        DevicePreference::PreferDiscrete => candidates
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.is_suitable())
+            .max_by_key(|(index, x)| (device_type_rank(x.device_type), Reverse(*index)))
+            .map(|(index, _)| index)
+            .map_or_else(first_suitable, Ok),
+        DevicePreference::Index(index) => {
+            if *index < candidates.len() {
+                require_suitable(*index)
+            } else {
+                Err(PotatoError::DeviceNotFound(format!("index {}", index)))
+            }
+        }
+        DevicePreference::Name(name) => match candidates.iter().position(|x| x.name == *name) {
+            Some(index) => require_suitable(index),
+            None => Err(PotatoError::DeviceNotFound(format!("name {}", name))),
+        },
+    }
+}
+
+fn device_type_rank(device_type: PhysicalDeviceType) -> u32 {
+    match device_type {
+        PhysicalDeviceType::DISCRETE_GPU => 2,
+        PhysicalDeviceType::INTEGRATED_GPU => 1,
+        _ => 0,
     }
 }
 
@@ -35,13 +255,18 @@ fn check_device_compatability(
     instance: &Instance,
     physical_device: PhysicalDevice,
     surface: &PotatoSurface,
-) -> bool {
+) -> DeviceSuitability {
     let queue_family_support = is_queue_family_supported(instance, physical_device, surface);
-    let device_extension_support = is_device_extension_supported(instance, physical_device);
-    let swapchain_support = is_swapchain_supported(device_extension_support, physical_device, surface);
+    let missing_extensions = check_device_extension_support(instance, physical_device);
+    let swapchain_support =
+        is_swapchain_supported(missing_extensions.is_empty(), physical_device, surface);
 
-    debug!("{}, {}, {}", queue_family_support, device_extension_support, swapchain_support);
-    queue_family_support && device_extension_support && swapchain_support
+    debug!("{}, {:?}, {}", queue_family_support, missing_extensions, swapchain_support);
+    DeviceSuitability {
+        has_queue_families: queue_family_support,
+        missing_extensions,
+        has_swapchain_support: swapchain_support,
+    }
 }
 
 fn is_queue_family_supported(
@@ -53,7 +278,28 @@ fn is_queue_family_supported(
     queue_family.is_complete()
 }
 
-fn is_device_extension_supported(instance: &Instance, physical_device: PhysicalDevice) -> bool {
+fn check_device_extension_support(
+    instance: &Instance,
+    physical_device: PhysicalDevice,
+) -> Vec<String> {
+    let available_extensions = find_available_extensions(instance, physical_device);
+    find_missing_extensions(&DEVICE_EXTENSTIONS.names, &available_extensions)
+}
+
+pub fn is_device_extension_supported(
+    instance: &Instance,
+    physical_device: PhysicalDevice,
+    extension_name: &str,
+) -> bool {
+    find_available_extensions(instance, physical_device)
+        .iter()
+        .any(|x| x == extension_name)
+}
+
+pub fn find_available_extensions(
+    instance: &Instance,
+    physical_device: PhysicalDevice,
+) -> Vec<String> {
     let available_extensions = unsafe {
         instance
             .enumerate_device_extension_properties(physical_device)
@@ -63,9 +309,24 @@ fn is_device_extension_supported(instance: &Instance, physical_device: PhysicalD
     debug!("Available Extensions");
     available_extensions.iter().for_each(|x| debug!("Name: {}, Version: {}", vk_to_string(&x.extension_name), x.spec_version));
 
-    let required_extensions: HashSet<String> = DEVICE_EXTENSTIONS.names.iter().map(|x| x.to_string()).collect();
+    available_extensions
+        .iter()
+        .map(|x| vk_to_string(&x.extension_name))
+        .collect()
+}
 
-    required_extensions.iter().map(|x| available_extensions.iter().map(|y| vk_to_string(&y.extension_name) == *x).any(|z| z)).any(|a| a)
+pub fn find_missing_extensions(
+    required_extensions: &[&str],
+    available_extensions: &[String],
+) -> Vec<String> {
+    let available_extensions: HashSet<&str> =
+        available_extensions.iter().map(|x| x.as_str()).collect();
+
+    required_extensions
+        .iter()
+        .filter(|x| !available_extensions.contains(*x))
+        .map(|x| x.to_string())
+        .collect()
 }
 
 fn is_swapchain_supported(device_extension_support: bool, physical_device: PhysicalDevice, surface: &PotatoSurface) -> bool{
@@ -82,13 +343,23 @@ pub fn describe_device(instance: &Instance, physical_device: PhysicalDevice) {
     let device_queue_familes =
         unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
 
-    let device_type = find_device_type(&device_properties);
+    let device_type = find_device_type(device_properties.device_type);
     let device_name = vk_to_string(&device_properties.device_name);
 
     info!(
         "Device Name: {}, id: {}, type: {}",
         device_name, device_properties.device_id, device_type
     );
+    if is_software_device(
+        device_properties.device_type,
+        device_properties.vendor_id,
+        device_properties.device_id,
+    ) {
+        warn!(
+            "{} is a software rasterizer, expect different behavior and performance",
+            device_name
+        );
+    }
 
     info!(
         "Supported API Version {}.{}.{}",
@@ -110,12 +381,269 @@ pub fn describe_device(instance: &Instance, physical_device: PhysicalDevice) {
     });
 }
 
-fn find_device_type(properties: &PhysicalDeviceProperties) -> &str {
-    match properties.device_type {
+fn find_device_type(device_type: PhysicalDeviceType) -> &'static str {
+    match device_type {
         PhysicalDeviceType::CPU => "CPU",
         PhysicalDeviceType::INTEGRATED_GPU => "Integrated GPU",
         PhysicalDeviceType::DISCRETE_GPU => "Discrete GPU",
         PhysicalDeviceType::VIRTUAL_GPU => "Virtual GPU",
-        _ => panic!(),
+        _ => "Other",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_device_type_names() {
+        assert_eq!(find_device_type(PhysicalDeviceType::CPU), "CPU");
+        assert_eq!(find_device_type(PhysicalDeviceType::INTEGRATED_GPU), "Integrated GPU");
+        assert_eq!(find_device_type(PhysicalDeviceType::DISCRETE_GPU), "Discrete GPU");
+        assert_eq!(find_device_type(PhysicalDeviceType::VIRTUAL_GPU), "Virtual GPU");
+        assert_eq!(find_device_type(PhysicalDeviceType::OTHER), "Other");
+    }
+
+    #[test]
+    fn device_info_from_properties() {
+        let mut properties = PhysicalDeviceProperties {
+            device_type: PhysicalDeviceType::DISCRETE_GPU,
+            vendor_id: 0x10DE,
+            device_id: 0x1234,
+            ..Default::default()
+        };
+        properties.limits.max_image_dimension2_d = 16384;
+        properties.limits.max_push_constants_size = 256;
+        b"Potato GPU"
+            .iter()
+            .zip(properties.device_name.iter_mut())
+            .for_each(|(byte, x)| *x = *byte as _);
+        let features = PhysicalDeviceFeatures {
+            sampler_anisotropy: TRUE,
+            ..Default::default()
+        };
+
+        let device_info = DeviceInfo::new(&properties, &features);
+        assert_eq!(device_info.name, "Potato GPU");
+        assert_eq!(device_info.device_type_name(), "Discrete GPU");
+        assert_eq!(device_info.max_image_dimension_2d, 16384);
+        assert_eq!(device_info.max_push_constants_size, 256);
+        assert!(device_info.sampler_anisotropy);
+        assert!(!device_info.geometry_shader);
+        assert!(device_info.suitability.is_none());
+        assert!(!device_info.is_suitable());
+    }
+
+    #[test]
+    fn software_devices_by_type_vendor_and_device() {
+        let cases = [
+            (PhysicalDeviceType::CPU, 0x10DE, 0x1234, true),
+            (PhysicalDeviceType::CPU, VENDOR_ID_MESA, DEVICE_ID_LAVAPIPE, true),
+            (PhysicalDeviceType::OTHER, VENDOR_ID_MESA, DEVICE_ID_LAVAPIPE, true),
+            (PhysicalDeviceType::INTEGRATED_GPU, VENDOR_ID_MESA, 0x9A49, false),
+            (PhysicalDeviceType::DISCRETE_GPU, VENDOR_ID_MESA, 0x73BF, false),
+            (PhysicalDeviceType::CPU, VENDOR_ID_GOOGLE, DEVICE_ID_SWIFTSHADER, true),
+            (PhysicalDeviceType::OTHER, VENDOR_ID_GOOGLE, DEVICE_ID_SWIFTSHADER, true),
+            (PhysicalDeviceType::DISCRETE_GPU, VENDOR_ID_GOOGLE, 0x1234, false),
+            (PhysicalDeviceType::DISCRETE_GPU, 0x10DE, 0x1234, false),
+            (PhysicalDeviceType::INTEGRATED_GPU, 0x8086, 0x9A49, false),
+        ];
+        for (device_type, vendor_id, device_id, expected) in cases {
+            assert_eq!(
+                is_software_device(device_type, vendor_id, device_id),
+                expected,
+                "{:?} {:#x} {:#x}",
+                device_type,
+                vendor_id,
+                device_id
+            );
+        }
+    }
+
+    #[test]
+    fn find_missing_extensions_reports_only_absent_names() {
+        let available = vec![
+            "VK_KHR_swapchain".to_string(),
+            "VK_KHR_maintenance1".to_string(),
+        ];
+        assert!(find_missing_extensions(&["VK_KHR_swapchain"], &available).is_empty());
+        assert_eq!(
+            find_missing_extensions(
+                &["VK_KHR_swapchain", "VK_EXT_memory_budget", "VK_KHR_maintenance1"],
+                &available
+            ),
+            vec!["VK_EXT_memory_budget".to_string()]
+        );
+        assert_eq!(
+            find_missing_extensions(&["VK_KHR_swapchain"], &[]),
+            vec!["VK_KHR_swapchain".to_string()]
+        );
+        assert!(find_missing_extensions(&[], &available).is_empty());
+    }
+
+    #[test]
+    fn device_is_suitable_only_with_every_flag_set() {
+        let cases = [
+            (true, vec![], true, true),
+            (false, vec![], true, false),
+            (true, vec!["VK_KHR_swapchain".to_string()], true, false),
+            (true, vec![], false, false),
+        ];
+        for (has_queue_families, missing_extensions, has_swapchain_support, expected) in cases {
+            let suitability = DeviceSuitability {
+                has_queue_families,
+                missing_extensions: missing_extensions.clone(),
+                has_swapchain_support,
+            };
+            assert_eq!(suitability.has_extensions(), missing_extensions.is_empty());
+            assert_eq!(suitability.is_suitable(), expected);
+
+            let device_info = DeviceInfo {
+                suitability: Some(suitability),
+                ..DeviceInfo::new(&Default::default(), &Default::default())
+            };
+            assert_eq!(device_info.is_suitable(), expected);
+            assert_eq!(device_info.missing_extensions(), missing_extensions.as_slice());
+        }
+    }
+
+    #[test]
+    fn failure_reason_names_the_first_failed_check() {
+        let suitability = |has_queue_families, missing_extensions: &[&str], has_swapchain_support| {
+            DeviceSuitability {
+                has_queue_families,
+                missing_extensions: missing_extensions.iter().map(|x| x.to_string()).collect(),
+                has_swapchain_support,
+            }
+            .failure_reason()
+        };
+        assert_eq!(suitability(true, &[], true), None);
+        assert_eq!(
+            suitability(false, &["VK_KHR_swapchain"], false).as_deref(),
+            Some("no queue family supports graphics and presentation")
+        );
+        assert_eq!(
+            suitability(true, &["VK_KHR_swapchain", "VK_KHR_maintenance1"], false).as_deref(),
+            Some("missing extensions VK_KHR_swapchain, VK_KHR_maintenance1")
+        );
+        assert_eq!(
+            suitability(true, &[], false).as_deref(),
+            Some("no surface format or present mode for a swapchain")
+        );
+        let unchecked = DeviceInfo::new(&Default::default(), &Default::default());
+        assert_eq!(unchecked.failure_reason(), None);
+    }
+
+    fn candidate(name: &str, device_type: PhysicalDeviceType, is_suitable: bool) -> DeviceInfo {
+        DeviceInfo {
+            name: name.to_string(),
+            device_type,
+            suitability: Some(DeviceSuitability {
+                has_queue_families: is_suitable,
+                missing_extensions: vec![],
+                has_swapchain_support: true,
+            }),
+            ..DeviceInfo::new(&Default::default(), &Default::default())
+        }
+    }
+
+    #[test]
+    fn choose_device_prefers_discrete_then_integrated_then_other() {
+        let candidates = vec![
+            candidate("cpu", PhysicalDeviceType::CPU, true),
+            candidate("integrated", PhysicalDeviceType::INTEGRATED_GPU, true),
+            candidate("discrete", PhysicalDeviceType::DISCRETE_GPU, true),
+        ];
+        let preference = DevicePreference::PreferDiscrete;
+        assert_eq!(choose_device(&candidates, &preference).unwrap(), 2);
+        assert_eq!(choose_device(&candidates[..2], &preference).unwrap(), 1);
+        assert_eq!(choose_device(&candidates[..1], &preference).unwrap(), 0);
+    }
+
+    #[test]
+    fn choose_device_skips_unsuitable_devices() {
+        let candidates = vec![
+            candidate("discrete", PhysicalDeviceType::DISCRETE_GPU, false),
+            candidate("integrated", PhysicalDeviceType::INTEGRATED_GPU, true),
+        ];
+        let preference = DevicePreference::PreferDiscrete;
+        assert_eq!(choose_device(&candidates, &preference).unwrap(), 1);
+        let preference = DevicePreference::FirstSuitable;
+        assert_eq!(choose_device(&candidates, &preference).unwrap(), 1);
+    }
+
+    #[test]
+    fn choose_device_without_suitable_device() {
+        let candidates = vec![candidate("discrete", PhysicalDeviceType::DISCRETE_GPU, false)];
+        for preference in &[DevicePreference::FirstSuitable, DevicePreference::PreferDiscrete] {
+            assert!(matches!(
+                choose_device(&candidates, preference),
+                Err(PotatoError::NoSuitableDevice)
+            ));
+        }
+        assert!(matches!(
+            choose_device(&[], &DevicePreference::FirstSuitable),
+            Err(PotatoError::NoSuitableDevice)
+        ));
+    }
+
+    fn policy_candidates() -> Vec<DeviceInfo> {
+        let mut missing_swapchain = candidate("virtual", PhysicalDeviceType::VIRTUAL_GPU, true);
+        missing_swapchain.suitability = Some(DeviceSuitability {
+            has_queue_families: true,
+            missing_extensions: vec!["VK_KHR_swapchain".to_string()],
+            has_swapchain_support: false,
+        });
+        vec![
+            candidate("integrated", PhysicalDeviceType::INTEGRATED_GPU, true),
+            candidate("discrete", PhysicalDeviceType::DISCRETE_GPU, true),
+            candidate("broken", PhysicalDeviceType::DISCRETE_GPU, false),
+            missing_swapchain,
+        ]
+    }
+
+    #[test]
+    fn choose_device_by_index() {
+        let candidates = policy_candidates();
+        assert_eq!(choose_device(&candidates, &DevicePreference::Index(0)).unwrap(), 0);
+        assert_eq!(choose_device(&candidates, &DevicePreference::Index(1)).unwrap(), 1);
+        assert!(matches!(
+            choose_device(&candidates, &DevicePreference::Index(4)),
+            Err(PotatoError::DeviceNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn choose_device_by_name() {
+        let candidates = policy_candidates();
+        let preference = DevicePreference::Name("discrete".to_string());
+        assert_eq!(choose_device(&candidates, &preference).unwrap(), 1);
+        let preference = DevicePreference::Name("integrated".to_string());
+        assert_eq!(choose_device(&candidates, &preference).unwrap(), 0);
+        let preference = DevicePreference::Name("missing".to_string());
+        assert!(matches!(
+            choose_device(&candidates, &preference),
+            Err(PotatoError::DeviceNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn choose_device_rejects_requested_unsuitable_device() {
+        let candidates = policy_candidates();
+        for preference in &[
+            DevicePreference::Index(2),
+            DevicePreference::Name("broken".to_string()),
+        ] {
+            match choose_device(&candidates, preference) {
+                Err(PotatoError::UnsuitableDevice(name)) => assert_eq!(name, "broken"),
+                x => panic!("Expected an unsuitable device error, got {:?}", x),
+            }
+        }
+        match choose_device(&candidates, &DevicePreference::Index(3)) {
+            Err(PotatoError::MissingDeviceExtensions(extensions)) => {
+                assert_eq!(extensions, vec!["VK_KHR_swapchain".to_string()])
+            }
+            x => panic!("Expected a missing extensions error, got {:?}", x),
+        }
     }
 }
@@ -0,0 +1,132 @@
+use crate::io::file::{read_file_to_bytes, write_bytes};
+use ash::version::DeviceV1_0;
+use ash::vk::{
+    PhysicalDeviceProperties, PipelineCache, PipelineCacheCreateFlags, PipelineCacheCreateInfo,
+    PipelineCacheHeaderVersion, StructureType, UUID_SIZE,
+};
+use ash::Device;
+use log::{debug, warn};
+use std::convert::TryInto;
+
+const PIPELINE_CACHE_PATH: &str = "pipeline_cache.bin";
+const PIPELINE_CACHE_HEADER_SIZE: usize = 16 + UUID_SIZE;
+
+pub fn create_pipeline_cache(
+    device: &Device,
+    physical_device_properties: &PhysicalDeviceProperties,
+) -> PipelineCache {
+    let initial_data = match read_file_to_bytes(PIPELINE_CACHE_PATH) {
+        Ok(data) if is_cache_compatible(&data, physical_device_properties) => {
+            debug!("Loaded {} bytes of pipeline cache", data.len());
+            data
+        }
+        Ok(_) => {
+            warn!("Ignoring incompatible pipeline cache {}", PIPELINE_CACHE_PATH);
+            vec![]
+        }
+        Err(_) => vec![],
+    };
+
+    create_pipeline_cache_with_data(device, &initial_data).unwrap_or_else(|| {
+        warn!("Pipeline cache data was rejected, starting with an empty cache");
+        create_pipeline_cache_with_data(device, &[]).expect("Failed to create pipeline cache")
+    })
+}
+
+fn create_pipeline_cache_with_data(device: &Device, initial_data: &[u8]) -> Option<PipelineCache> {
+    let pipeline_cache_create_info = PipelineCacheCreateInfo {
+        s_type: StructureType::PIPELINE_CACHE_CREATE_INFO,
+        p_next: std::ptr::null(),
+        flags: PipelineCacheCreateFlags::empty(),
+        initial_data_size: initial_data.len(),
+        p_initial_data: initial_data.as_ptr() as *const std::ffi::c_void,
+    };
+
+    unsafe {
+        device
+            .create_pipeline_cache(&pipeline_cache_create_info, None)
+            .ok()
+    }
+}
+
+pub fn is_cache_compatible(
+    data: &[u8],
+    physical_device_properties: &PhysicalDeviceProperties,
+) -> bool {
+    if data.len() < PIPELINE_CACHE_HEADER_SIZE {
+        return false;
+    }
+
+    let read_u32 = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    read_u32(0) as usize >= PIPELINE_CACHE_HEADER_SIZE
+        && read_u32(4) == PipelineCacheHeaderVersion::ONE.as_raw() as u32
+        && read_u32(8) == physical_device_properties.vendor_id
+        && read_u32(12) == physical_device_properties.device_id
+        && data[16..PIPELINE_CACHE_HEADER_SIZE] == physical_device_properties.pipeline_cache_uuid
+}
+
+pub fn save_pipeline_cache(device: &Device, pipeline_cache: PipelineCache) {
+    let data = match unsafe { device.get_pipeline_cache_data(pipeline_cache) } {
+        Ok(data) => data,
+        Err(e) => {
+            warn!("Failed to read pipeline cache data: {}", e);
+            return;
+        }
+    };
+
+    match write_bytes(PIPELINE_CACHE_PATH, &data) {
+        Ok(()) => debug!("Saved {} bytes of pipeline cache", data.len()),
+        Err(e) => warn!("Failed to write pipeline cache {}: {}", PIPELINE_CACHE_PATH, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties() -> PhysicalDeviceProperties {
+        PhysicalDeviceProperties {
+            vendor_id: 0x10de,
+            device_id: 0x1f08,
+            pipeline_cache_uuid: [7; UUID_SIZE],
+            ..Default::default()
+        }
+    }
+
+    fn cache_blob(properties: &PhysicalDeviceProperties) -> Vec<u8> {
+        let header_version = PipelineCacheHeaderVersion::ONE.as_raw() as u32;
+        let mut data = Vec::new();
+        data.extend_from_slice(&(PIPELINE_CACHE_HEADER_SIZE as u32).to_le_bytes());
+        data.extend_from_slice(&header_version.to_le_bytes());
+        data.extend_from_slice(&properties.vendor_id.to_le_bytes());
+        data.extend_from_slice(&properties.device_id.to_le_bytes());
+        data.extend_from_slice(&properties.pipeline_cache_uuid);
+        data.extend_from_slice(&[1, 2, 3, 4]);
+        data
+    }
+
+    #[test]
+    fn cache_bytes_round_trip_through_disk() {
+        let path = std::env::temp_dir().join(format!("potato-cache-{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+        let data = cache_blob(&properties());
+        write_bytes(path, &data).unwrap();
+
+        let read_back = read_file_to_bytes(path).unwrap();
+        assert_eq!(read_back, data);
+        assert!(is_cache_compatible(&read_back, &properties()));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn corrupt_or_foreign_cache_is_incompatible() {
+        let data = cache_blob(&properties());
+        assert!(!is_cache_compatible(&data[..PIPELINE_CACHE_HEADER_SIZE - 1], &properties()));
+
+        let other_device = PhysicalDeviceProperties {
+            device_id: 0x2204,
+            ..properties()
+        };
+        assert!(!is_cache_compatible(&data, &other_device));
+    }
+}
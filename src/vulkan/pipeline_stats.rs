@@ -0,0 +1,141 @@
+use ash::version::DeviceV1_0;
+use ash::vk::{
+    CommandBuffer, QueryControlFlags, QueryPipelineStatisticFlags, QueryPool, QueryPoolCreateFlags,
+    QueryPoolCreateInfo, QueryResultFlags, QueryType, StructureType,
+};
+use ash::Device;
+
+pub const PIPELINE_STATISTICS: QueryPipelineStatisticFlags = QueryPipelineStatisticFlags::from_raw(
+    QueryPipelineStatisticFlags::INPUT_ASSEMBLY_VERTICES.as_raw()
+        | QueryPipelineStatisticFlags::INPUT_ASSEMBLY_PRIMITIVES.as_raw()
+        | QueryPipelineStatisticFlags::VERTEX_SHADER_INVOCATIONS.as_raw()
+        | QueryPipelineStatisticFlags::CLIPPING_INVOCATIONS.as_raw()
+        | QueryPipelineStatisticFlags::CLIPPING_PRIMITIVES.as_raw()
+        | QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS.as_raw(),
+);
+pub const PIPELINE_STATISTIC_COUNT: usize = 6;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PipelineStats {
+    pub input_assembly_vertices: u64,
+    pub input_assembly_primitives: u64,
+    pub vertex_shader_invocations: u64,
+    pub clipping_invocations: u64,
+    pub clipping_primitives: u64,
+    pub fragment_shader_invocations: u64,
+}
+
+impl PipelineStats {
+    // Results are written in the bit order of PIPELINE_STATISTICS.
+    pub fn from_results(results: &[u64; PIPELINE_STATISTIC_COUNT]) -> PipelineStats {
+        PipelineStats {
+            input_assembly_vertices: results[0],
+            input_assembly_primitives: results[1],
+            vertex_shader_invocations: results[2],
+            clipping_invocations: results[3],
+            clipping_primitives: results[4],
+            fragment_shader_invocations: results[5],
+        }
+    }
+}
+
+pub struct PipelineStatsQuery {
+    pub query_pool: QueryPool,
+    is_written: Vec<bool>,
+}
+
+impl PipelineStatsQuery {
+    pub fn new(device: &Device, frame_count: usize) -> PipelineStatsQuery {
+        let query_pool_create_info = QueryPoolCreateInfo {
+            s_type: StructureType::QUERY_POOL_CREATE_INFO,
+            p_next: std::ptr::null(),
+            flags: QueryPoolCreateFlags::empty(),
+            query_type: QueryType::PIPELINE_STATISTICS,
+            query_count: frame_count as u32,
+            pipeline_statistics: PIPELINE_STATISTICS,
+        };
+
+        let query_pool = unsafe {
+            device
+                .create_query_pool(&query_pool_create_info, None)
+                .expect("Failed to create pipeline statistics query pool")
+        };
+
+        PipelineStatsQuery {
+            query_pool,
+            is_written: vec![false; frame_count],
+        }
+    }
+
+    pub fn record_begin(&self, device: &Device, command_buffer: CommandBuffer, index: usize) {
+        unsafe {
+            device.cmd_reset_query_pool(command_buffer, self.query_pool, index as u32, 1);
+            device.cmd_begin_query(
+                command_buffer,
+                self.query_pool,
+                index as u32,
+                QueryControlFlags::empty(),
+            );
+        }
+    }
+
+    pub fn record_end(&self, device: &Device, command_buffer: CommandBuffer, index: usize) {
+        unsafe {
+            device.cmd_end_query(command_buffer, self.query_pool, index as u32);
+        }
+    }
+
+    pub fn mark_submitted(&mut self, index: usize) {
+        self.is_written[index] = true;
+    }
+
+    pub fn read(&self, device: &Device, index: usize) -> Option<PipelineStats> {
+        if !self.is_written[index] {
+            return None;
+        }
+
+        let mut results = [[0_u64; PIPELINE_STATISTIC_COUNT]; 1];
+        let result = unsafe {
+            device.get_query_pool_results(
+                self.query_pool,
+                index as u32,
+                1,
+                &mut results,
+                QueryResultFlags::TYPE_64,
+            )
+        };
+
+        match result {
+            Ok(_) => Some(PipelineStats::from_results(&results[0])),
+            Err(_) => None,
+        }
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        unsafe {
+            device.destroy_query_pool(self.query_pool, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_map_to_named_counters_in_flag_order() {
+        let stats = PipelineStats::from_results(&[36, 12, 24, 12, 10, 4096]);
+        assert_eq!(
+            stats,
+            PipelineStats {
+                input_assembly_vertices: 36,
+                input_assembly_primitives: 12,
+                vertex_shader_invocations: 24,
+                clipping_invocations: 12,
+                clipping_primitives: 10,
+                fragment_shader_invocations: 4096,
+            }
+        );
+        assert_eq!(PIPELINE_STATISTICS.as_raw().count_ones() as usize, PIPELINE_STATISTIC_COUNT);
+    }
+}
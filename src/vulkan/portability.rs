@@ -0,0 +1,46 @@
+use ash::vk::InstanceCreateFlags;
+use std::ffi::CStr;
+
+// VK_INSTANCE_CREATE_ENUMERATE_PORTABILITY_BIT_KHR, which this version of ash doesn't expose.
+pub const ENUMERATE_PORTABILITY: InstanceCreateFlags = InstanceCreateFlags::from_raw(0x1);
+
+pub fn portability_enumeration_extension_name() -> &'static CStr {
+    CStr::from_bytes_with_nul(b"VK_KHR_portability_enumeration\0").unwrap()
+}
+
+pub fn portability_subset_extension_name() -> &'static CStr {
+    CStr::from_bytes_with_nul(b"VK_KHR_portability_subset\0").unwrap()
+}
+
+pub fn append_portability_subset(extensions: &mut Vec<&CStr>, available: &[String]) {
+    let name = portability_subset_extension_name();
+    let is_available = available.iter().any(|x| *x == *name.to_string_lossy());
+    if is_available && !extensions.contains(&name) {
+        extensions.push(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn portability_subset_is_appended_when_available() {
+        let swapchain = CStr::from_bytes_with_nul(b"VK_KHR_swapchain\0").unwrap();
+        let available =
+            vec!["VK_KHR_swapchain".to_string(), "VK_KHR_portability_subset".to_string()];
+        let mut extensions = vec![swapchain];
+        append_portability_subset(&mut extensions, &available);
+        assert_eq!(extensions, vec![swapchain, portability_subset_extension_name()]);
+
+        append_portability_subset(&mut extensions, &available);
+        assert_eq!(extensions.len(), 2);
+    }
+
+    #[test]
+    fn portability_subset_is_skipped_when_unavailable() {
+        let mut extensions = vec![];
+        append_portability_subset(&mut extensions, &["VK_KHR_swapchain".to_string()]);
+        assert!(extensions.is_empty());
+    }
+}
@@ -0,0 +1,216 @@
+use super::descriptor_allocator::DescriptorAllocator;
+use super::graphics_pipeline::{create_post_process_pipeline, PipelineConfig, PipelineTarget};
+use super::offscreen::OffscreenTarget;
+use super::scene::DrawMode;
+use super::shader::ShaderSource;
+use ash::version::DeviceV1_0;
+use ash::vk::{
+    CommandBuffer, DescriptorImageInfo, DescriptorSet, DescriptorSetLayout,
+    DescriptorSetLayoutBinding, DescriptorSetLayoutCreateFlags, DescriptorSetLayoutCreateInfo,
+    DescriptorType, ImageLayout, ImageView, Pipeline, PipelineBindPoint, PipelineCache,
+    PipelineLayout, Sampler, ShaderStageFlags, StructureType, WriteDescriptorSet,
+};
+use ash::Device;
+
+pub const FULLSCREEN_TRIANGLE_VERTEX_COUNT: u32 = 3;
+pub const FULLSCREEN_VERTEX_SHADER: ShaderSource =
+    ShaderSource::File("src/shaders/spv/fullscreen-vert.spv");
+pub const COPY_FRAGMENT_SHADER: ShaderSource =
+    ShaderSource::File("src/shaders/spv/post-copy-frag.spv");
+
+pub struct PostProcess {
+    pub pipeline: Pipeline,
+    pub pipeline_layout: PipelineLayout,
+    pub descriptor_set_layout: DescriptorSetLayout,
+    pub descriptor_set: DescriptorSet,
+    pub source_view: ImageView,
+    descriptor_allocator: DescriptorAllocator,
+}
+
+impl PostProcess {
+    pub fn new(
+        device: &Device,
+        pipeline_cache: PipelineCache,
+        target: &PipelineTarget,
+        fragment_shader: ShaderSource,
+        source: &OffscreenTarget,
+        sampler: Sampler,
+        config: &PipelineConfig,
+    ) -> PostProcess {
+        let descriptor_set_layout = create_sampled_image_set_layout(device);
+        let mut descriptor_allocator = DescriptorAllocator::new(
+            1,
+            &[
+                (DescriptorType::SAMPLED_IMAGE, 1),
+                (DescriptorType::SAMPLER, 1),
+            ],
+        );
+        let descriptor_set = descriptor_allocator
+            .allocate(device, descriptor_set_layout)
+            .expect("Failed to allocate post-process descriptor set");
+        let source_view = source.sampled_image().view;
+        write_sampled_image_descriptors(device, descriptor_set, source_view, sampler);
+
+        let (pipeline, pipeline_layout) = create_post_process_pipeline(
+            device,
+            pipeline_cache,
+            target,
+            descriptor_set_layout,
+            (FULLSCREEN_VERTEX_SHADER, fragment_shader),
+            config,
+        );
+
+        PostProcess {
+            pipeline,
+            pipeline_layout,
+            descriptor_set_layout,
+            descriptor_set,
+            source_view,
+            descriptor_allocator,
+        }
+    }
+
+    pub fn draw_mode(&self) -> DrawMode {
+        fullscreen_draw_mode()
+    }
+
+    pub fn record(&self, device: &Device, command_buffer: CommandBuffer) {
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::GRAPHICS, self.pipeline);
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor_set],
+                &[],
+            );
+            device.cmd_draw(command_buffer, FULLSCREEN_TRIANGLE_VERTEX_COUNT, 1, 0, 0);
+        }
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+        self.descriptor_allocator.destroy(device);
+    }
+}
+
+// The triangle's positions come from gl_VertexIndex, so nothing is bound.
+pub fn fullscreen_draw_mode() -> DrawMode {
+    DrawMode::NonIndexed {
+        vertex_count: FULLSCREEN_TRIANGLE_VERTEX_COUNT,
+    }
+}
+
+pub fn create_sampled_image_set_layout(device: &Device) -> DescriptorSetLayout {
+    let bindings = [
+        DescriptorSetLayoutBinding {
+            binding: 0,
+            descriptor_type: DescriptorType::SAMPLED_IMAGE,
+            descriptor_count: 1,
+            stage_flags: ShaderStageFlags::FRAGMENT,
+            p_immutable_samplers: std::ptr::null(),
+        },
+        DescriptorSetLayoutBinding {
+            binding: 1,
+            descriptor_type: DescriptorType::SAMPLER,
+            descriptor_count: 1,
+            stage_flags: ShaderStageFlags::FRAGMENT,
+            p_immutable_samplers: std::ptr::null(),
+        },
+    ];
+
+    let layout_create_info = DescriptorSetLayoutCreateInfo {
+        s_type: StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+        p_next: std::ptr::null(),
+        flags: DescriptorSetLayoutCreateFlags::empty(),
+        binding_count: bindings.len() as u32,
+        p_bindings: bindings.as_ptr(),
+    };
+
+    unsafe {
+        device
+            .create_descriptor_set_layout(&layout_create_info, None)
+            .expect("Failed to create sampled image descriptor set layout")
+    }
+}
+
+pub fn write_sampled_image_descriptors(
+    device: &Device,
+    descriptor_set: DescriptorSet,
+    image_view: ImageView,
+    sampler: Sampler,
+) {
+    let image_info = [sampled_image_info(image_view)];
+    let sampler_info = [DescriptorImageInfo {
+        sampler,
+        image_view: ImageView::null(),
+        image_layout: ImageLayout::UNDEFINED,
+    }];
+
+    let descriptor_write_sets = [
+        WriteDescriptorSet {
+            s_type: StructureType::WRITE_DESCRIPTOR_SET,
+            p_next: std::ptr::null(),
+            dst_set: descriptor_set,
+            dst_binding: 0,
+            dst_array_element: 0,
+            descriptor_count: 1,
+            descriptor_type: DescriptorType::SAMPLED_IMAGE,
+            p_image_info: image_info.as_ptr(),
+            p_buffer_info: std::ptr::null(),
+            p_texel_buffer_view: std::ptr::null(),
+        },
+        WriteDescriptorSet {
+            s_type: StructureType::WRITE_DESCRIPTOR_SET,
+            p_next: std::ptr::null(),
+            dst_set: descriptor_set,
+            dst_binding: 1,
+            dst_array_element: 0,
+            descriptor_count: 1,
+            descriptor_type: DescriptorType::SAMPLER,
+            p_image_info: sampler_info.as_ptr(),
+            p_buffer_info: std::ptr::null(),
+            p_texel_buffer_view: std::ptr::null(),
+        },
+    ];
+
+    unsafe {
+        device.update_descriptor_sets(&descriptor_write_sets, &[]);
+    }
+}
+
+fn sampled_image_info(image_view: ImageView) -> DescriptorImageInfo {
+    DescriptorImageInfo {
+        sampler: Sampler::null(),
+        image_view,
+        image_layout: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::vk::Handle;
+
+    #[test]
+    fn fullscreen_triangle_is_a_three_vertex_non_indexed_draw() {
+        assert_eq!(
+            fullscreen_draw_mode(),
+            DrawMode::NonIndexed { vertex_count: 3 }
+        );
+    }
+
+    #[test]
+    fn source_descriptor_samples_the_offscreen_view() {
+        let view = ImageView::from_raw(7);
+        let info = sampled_image_info(view);
+        assert_eq!(info.image_view, view);
+        assert_eq!(info.image_layout, ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        assert_eq!(info.sampler, Sampler::null());
+    }
+}
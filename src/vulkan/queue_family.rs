@@ -1,17 +1,35 @@
 use super::surface::PotatoSurface;
 use ash::version::InstanceV1_0;
-use ash::vk::{PhysicalDevice, QueueFlags};
+use ash::vk::{PhysicalDevice, QueueFamilyProperties, QueueFlags};
 use ash::Instance;
-use log::debug;
+use log::{debug, warn};
 
 pub struct QueueFamily {
     pub graphics_family: Option<usize>,
     pub present_family: Option<usize>,
+    pub transfer_family: Option<usize>,
 }
 
 impl QueueFamily {
     pub fn is_complete(&self) -> bool {
-        self.graphics_family.is_some()
+        self.graphics_family.is_some() && self.present_family.is_some()
+    }
+
+    pub fn is_present_separate(&self) -> bool {
+        self.graphics_family != self.present_family
+    }
+
+    pub fn unique_families(&self) -> Vec<u32> {
+        let mut families: Vec<u32> = self
+            .graphics_family
+            .iter()
+            .chain(self.present_family.iter())
+            .chain(self.transfer_family.iter())
+            .map(|x| *x as u32)
+            .collect();
+        families.sort_unstable();
+        families.dedup();
+        families
     }
 }
 
@@ -31,20 +49,76 @@ pub fn find_graphical_queue_family(
         graphics_family.unwrap()
     );
 
-    let is_present_supported = unsafe {
-        surface.surface_loader.get_physical_device_surface_support(
-            physical_device,
-            graphics_family.unwrap() as u32,
-            surface.surface,
-        )
+    let is_present_supported = |index: usize| unsafe {
+        queue_families[index].queue_count > 0
+            && surface
+                .surface_loader
+                .get_physical_device_surface_support(physical_device, index as u32, surface.surface)
+                .unwrap_or(false)
+    };
+
+    let present_family = graphics_family
+        .filter(|x| is_present_supported(*x))
+        .or_else(|| (0..queue_families.len()).find(|x| is_present_supported(*x)));
+
+    match present_family {
+        Some(present_family) if Some(present_family) != graphics_family => debug!(
+            "Graphics family does not support present, using family {} to present",
+            present_family
+        ),
+        None => warn!("Could not find a queue family that supports surface present"),
+        _ => (),
+    }
+
+    QueueFamily {
+        graphics_family,
+        present_family,
+        transfer_family: find_transfer_family(&queue_families),
+    }
+}
+
+pub fn find_transfer_family(queue_families: &[QueueFamilyProperties]) -> Option<usize> {
+    let is_transfer_only = |x: &QueueFamilyProperties| {
+        x.queue_count > 0
+            && x.queue_flags.contains(QueueFlags::TRANSFER)
+            && !x.queue_flags.contains(QueueFlags::GRAPHICS)
     };
 
-    if queue_families[graphics_family.unwrap()].queue_count > 0 && is_present_supported.unwrap() {
-        QueueFamily {
-            graphics_family,
-            present_family: graphics_family,
+    queue_families
+        .iter()
+        .position(|x| is_transfer_only(x) && !x.queue_flags.contains(QueueFlags::COMPUTE))
+        .or_else(|| queue_families.iter().position(is_transfer_only))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue_family(queue_flags: QueueFlags, queue_count: u32) -> QueueFamilyProperties {
+        QueueFamilyProperties {
+            queue_flags,
+            queue_count,
+            ..Default::default()
         }
-    } else {
-        panic!("Could not find a graphical queue that also supports surface present");
+    }
+
+    #[test]
+    fn transfer_family_prefers_transfer_only_queues() {
+        let queue_families = [
+            queue_family(QueueFlags::GRAPHICS | QueueFlags::COMPUTE | QueueFlags::TRANSFER, 16),
+            queue_family(QueueFlags::COMPUTE | QueueFlags::TRANSFER, 8),
+            queue_family(QueueFlags::TRANSFER, 2),
+        ];
+        assert_eq!(find_transfer_family(&queue_families), Some(2));
+        assert_eq!(find_transfer_family(&queue_families[..2]), Some(1));
+    }
+
+    #[test]
+    fn transfer_family_ignores_graphics_and_empty_families() {
+        let queue_families = [
+            queue_family(QueueFlags::GRAPHICS | QueueFlags::TRANSFER, 16),
+            queue_family(QueueFlags::TRANSFER, 0),
+        ];
+        assert_eq!(find_transfer_family(&queue_families), None);
     }
 }
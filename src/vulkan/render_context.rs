@@ -0,0 +1,410 @@
+use super::attachment::AttachmentImage;
+use super::buffer::PotatoBuffer;
+use super::constants::MAX_FRAMES_IN_FLIGHT;
+use super::descriptor_allocator::DescriptorAllocator;
+use super::frame_stats::FrameStats;
+use super::graphics_pipeline::PipelineTarget;
+use super::render_pass::RenderTargetFormats;
+use super::surface::PotatoSurface;
+use super::swapchain::PotatoSwapChain;
+use super::pipeline_stats::{PipelineStats, PipelineStatsQuery};
+use super::timestamp::GpuTimer;
+use ash::version::DeviceV1_0;
+use ash::vk::{
+    CommandBuffer, CommandPool, DescriptorSet, Extent2D, Fence, Framebuffer,
+    Pipeline, PipelineLayout, RenderPass, Result, Semaphore,
+};
+use ash::Device;
+use cgmath::Matrix4;
+use std::time::Instant;
+
+pub struct RenderContext {
+    pub surface: PotatoSurface,
+    pub swapchain: PotatoSwapChain,
+    pub render_pass: RenderPass,
+    pub render_target_formats: RenderTargetFormats,
+    pub pipeline_layout: PipelineLayout,
+    pub graphics_pipeline: Pipeline,
+    pub particle_pipeline: Option<(Pipeline, PipelineLayout)>,
+    pub debug_line_pipeline: (Pipeline, PipelineLayout),
+    pub text_pipeline: Option<(Pipeline, PipelineLayout)>,
+    pub overlay_pipeline: (Pipeline, PipelineLayout),
+    pub depth_attachment: AttachmentImage,
+    pub msaa_color_attachment: Option<AttachmentImage>,
+    pub swapchain_framebuffers: Vec<Framebuffer>,
+    pub command_buffers: Vec<CommandBuffer>,
+    pub scene_command_buffers: Vec<CommandBuffer>,
+    pub worker_command_pools: Vec<CommandPool>,
+    pub parallel_command_buffers: Vec<CommandBuffer>,
+    pub uniform_buffers: Vec<PotatoBuffer>,
+    pub descriptor_allocator: DescriptorAllocator,
+    pub descriptor_sets: Vec<DescriptorSet>,
+    pub image_available_semaphores: Vec<Semaphore>,
+    pub render_finished_semaphores: Vec<Semaphore>,
+    pub in_flight_fences: Vec<Fence>,
+    pub gpu_timer: Option<GpuTimer>,
+    pub last_gpu_time_ms: f32,
+    pub pipeline_stats_query: Option<PipelineStatsQuery>,
+    pub last_pipeline_stats: Option<PipelineStats>,
+    pub frame_stats: FrameStats,
+    pub projection: Matrix4<f32>,
+    pub last_frame_start: Option<Instant>,
+    pub current_frame: usize,
+    pub is_screenshot_requested: bool,
+    pub is_framebuffer_resized: bool,
+    pub is_paused: bool,
+    pub is_surface_lost: bool,
+    pub pending_resize: PendingResize,
+    pub window_extent: Extent2D,
+}
+
+// Only the latest requested extent is kept, so a burst of resizes recreates once.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PendingResize {
+    extent: Option<Extent2D>,
+}
+
+impl PendingResize {
+    pub fn request(&mut self, extent: Extent2D) {
+        self.extent = Some(extent);
+    }
+
+    pub fn take(&mut self, current_extent: Extent2D) -> Option<Extent2D> {
+        match self.extent.take() {
+            Some(extent) if extent != current_extent => Some(extent),
+            _ => None,
+        }
+    }
+}
+
+pub fn should_pause(extent: Extent2D) -> bool {
+    extent.width == 0 || extent.height == 0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SwapchainRecovery {
+    RecreateSwapchain,
+    RecreateSurface,
+}
+
+pub fn swapchain_recovery(vk_result: Result) -> Option<SwapchainRecovery> {
+    match vk_result {
+        Result::ERROR_OUT_OF_DATE_KHR | Result::SUBOPTIMAL_KHR => {
+            Some(SwapchainRecovery::RecreateSwapchain)
+        }
+        Result::ERROR_SURFACE_LOST_KHR => Some(SwapchainRecovery::RecreateSurface),
+        _ => None,
+    }
+}
+
+impl RenderContext {
+    pub fn request_resize(&mut self, extent: Extent2D) {
+        self.window_extent = extent;
+        self.pending_resize.request(extent);
+    }
+
+    pub fn take_resize(&mut self) -> Option<Extent2D> {
+        self.pending_resize.take(self.swapchain.swapchain_extent)
+    }
+
+    pub fn pipeline_target(&self) -> PipelineTarget {
+        PipelineTarget {
+            render_pass: self.render_pass,
+            formats: self.render_target_formats,
+            extent: self.swapchain.swapchain_extent,
+        }
+    }
+
+    pub fn take_recreate_request(&mut self) -> bool {
+        let is_resized = self.take_resize().is_some();
+        let is_invalidated = std::mem::take(&mut self.is_framebuffer_resized);
+        is_resized || is_invalidated
+    }
+
+    pub fn free_command_buffers(&mut self, device: &Device, command_pool: CommandPool) {
+        unsafe {
+            if !self.command_buffers.is_empty() {
+                device.free_command_buffers(command_pool, &self.command_buffers);
+            }
+            if !self.scene_command_buffers.is_empty() {
+                device.free_command_buffers(command_pool, &self.scene_command_buffers);
+            }
+            self.parallel_command_buffers
+                .iter()
+                .zip(&self.worker_command_pools)
+                .for_each(|(x, worker_command_pool)| {
+                    device.free_command_buffers(*worker_command_pool, &[*x])
+                });
+        }
+        self.command_buffers.clear();
+        self.scene_command_buffers.clear();
+        self.parallel_command_buffers.clear();
+    }
+
+    pub fn cleanup_swapchain(&self, device: &Device) {
+        unsafe {
+            self.swapchain_framebuffers
+                .iter()
+                .for_each(|x| device.destroy_framebuffer(*x, None));
+            device.destroy_pipeline(self.graphics_pipeline, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_pipeline(self.debug_line_pipeline.0, None);
+            device.destroy_pipeline_layout(self.debug_line_pipeline.1, None);
+            device.destroy_pipeline(self.overlay_pipeline.0, None);
+            device.destroy_pipeline_layout(self.overlay_pipeline.1, None);
+            if let Some((particle_pipeline, particle_pipeline_layout)) = self.particle_pipeline {
+                device.destroy_pipeline(particle_pipeline, None);
+                device.destroy_pipeline_layout(particle_pipeline_layout, None);
+            }
+            if let Some((text_pipeline, text_pipeline_layout)) = self.text_pipeline {
+                device.destroy_pipeline(text_pipeline, None);
+                device.destroy_pipeline_layout(text_pipeline_layout, None);
+            }
+            device.destroy_render_pass(self.render_pass, None);
+            self.depth_attachment.destroy(device);
+            if let Some(msaa_color_attachment) = &self.msaa_color_attachment {
+                msaa_color_attachment.destroy(device);
+            }
+            if let Some(gpu_timer) = &self.gpu_timer {
+                gpu_timer.destroy(device);
+            }
+            if let Some(pipeline_stats_query) = &self.pipeline_stats_query {
+                pipeline_stats_query.destroy(device);
+            }
+            self.swapchain
+                .swapchain_image_views
+                .iter()
+                .for_each(|x| device.destroy_image_view(*x, None));
+            self.swapchain
+                .swapchain_loader
+                .destroy_swapchain(self.swapchain.swapchain, None);
+        }
+    }
+
+    // Uniform buffers and their descriptor sets are indexed by frame in flight rather than by
+    // swapchain image, so they are kept when the new swapchain has a different image count.
+    pub fn replace_swapchain(
+        &mut self,
+        device: &Device,
+        create_swapchain: impl FnOnce(&PotatoSurface) -> PotatoSwapChain,
+    ) {
+        self.cleanup_swapchain(device);
+        self.swapchain = create_swapchain(&self.surface);
+    }
+
+    pub fn destroy(mut self, device: &Device, command_pool: CommandPool) {
+        unsafe {
+            for i in 0..MAX_FRAMES_IN_FLIGHT {
+                device.destroy_semaphore(self.image_available_semaphores[i], None);
+                device.destroy_semaphore(self.render_finished_semaphores[i], None);
+                device.destroy_fence(self.in_flight_fences[i], None);
+            }
+            self.free_command_buffers(device, command_pool);
+            self.worker_command_pools
+                .iter()
+                .for_each(|x| device.destroy_command_pool(*x, None));
+            self.cleanup_swapchain(device);
+            self.descriptor_allocator.destroy(device);
+            self.uniform_buffers.clear();
+            self.surface
+                .surface_loader
+                .destroy_surface(self.surface.surface, None);
+        }
+    }
+}
+
+#[cfg(test)]
+impl RenderContext {
+    // A context over fake handles with a double-buffered 800x600 swapchain.
+    pub fn from_raw_parts(device: &std::rc::Rc<Device>, surface: PotatoSurface) -> RenderContext {
+        use ash::vk::{Buffer, DeviceMemory, Format, Handle, Image, ImageView, SampleCountFlags};
+        let attachment = AttachmentImage {
+            image: Image::from_raw(10),
+            memory: DeviceMemory::from_raw(11),
+            view: ImageView::from_raw(12),
+            format: Format::D32_SFLOAT,
+            samples: SampleCountFlags::TYPE_1,
+        };
+        RenderContext {
+            surface,
+            swapchain: PotatoSwapChain::from_raw_parts(device, 20, 2),
+            render_pass: RenderPass::from_raw(2),
+            render_target_formats: RenderTargetFormats {
+                color: Format::B8G8R8A8_SRGB,
+                depth: Some(Format::D32_SFLOAT),
+                samples: SampleCountFlags::TYPE_1,
+            },
+            pipeline_layout: PipelineLayout::from_raw(3),
+            graphics_pipeline: Pipeline::from_raw(4),
+            particle_pipeline: None,
+            debug_line_pipeline: (Pipeline::from_raw(5), PipelineLayout::from_raw(6)),
+            text_pipeline: None,
+            overlay_pipeline: (Pipeline::from_raw(7), PipelineLayout::from_raw(8)),
+            depth_attachment: attachment,
+            msaa_color_attachment: None,
+            swapchain_framebuffers: vec![],
+            command_buffers: vec![],
+            scene_command_buffers: vec![],
+            worker_command_pools: vec![],
+            parallel_command_buffers: vec![],
+            uniform_buffers: (0..MAX_FRAMES_IN_FLIGHT as u64)
+                .map(|x| {
+                    PotatoBuffer::from_raw_parts(
+                        Buffer::from_raw(100 + x),
+                        DeviceMemory::from_raw(200 + x),
+                        64,
+                        device,
+                    )
+                })
+                .collect(),
+            descriptor_allocator: DescriptorAllocator::new(1, &[]),
+            descriptor_sets: (0..MAX_FRAMES_IN_FLIGHT as u64)
+                .map(|x| DescriptorSet::from_raw(300 + x))
+                .collect(),
+            image_available_semaphores: (0..MAX_FRAMES_IN_FLIGHT as u64)
+                .map(|x| Semaphore::from_raw(400 + x))
+                .collect(),
+            render_finished_semaphores: (0..MAX_FRAMES_IN_FLIGHT as u64)
+                .map(|x| Semaphore::from_raw(500 + x))
+                .collect(),
+            in_flight_fences: (0..MAX_FRAMES_IN_FLIGHT as u64)
+                .map(|x| Fence::from_raw(600 + x))
+                .collect(),
+            gpu_timer: None,
+            last_gpu_time_ms: 0.0,
+            pipeline_stats_query: None,
+            last_pipeline_stats: None,
+            frame_stats: FrameStats::default(),
+            projection: Matrix4::from_scale(1.0),
+            last_frame_start: None,
+            current_frame: 0,
+            is_screenshot_requested: false,
+            is_framebuffer_resized: false,
+            is_paused: false,
+            is_surface_lost: false,
+            pending_resize: PendingResize::default(),
+            window_extent: Extent2D {
+                width: 800,
+                height: 600,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::fake_device;
+    use ash::extensions::khr::Surface;
+    use ash::vk::{Handle, SurfaceKHR};
+    use std::rc::Rc;
+
+    fn extent(width: u32, height: u32) -> Extent2D {
+        Extent2D { width, height }
+    }
+
+    #[test]
+    fn pending_resize_coalesces_to_latest_extent() {
+        let mut pending_resize = PendingResize::default();
+        pending_resize.request(extent(640, 480));
+        pending_resize.request(extent(700, 500));
+        pending_resize.request(extent(800, 600));
+        assert_eq!(pending_resize.take(extent(640, 480)), Some(extent(800, 600)));
+        assert_eq!(pending_resize.take(extent(800, 600)), None);
+    }
+
+    #[test]
+    fn pending_resize_ignores_unchanged_extent() {
+        let mut pending_resize = PendingResize::default();
+        pending_resize.request(extent(800, 600));
+        assert_eq!(pending_resize.take(extent(800, 600)), None);
+    }
+
+    #[test]
+    fn pending_resize_drops_requests_back_to_current_extent() {
+        let mut pending_resize = PendingResize::default();
+        pending_resize.request(extent(800, 600));
+        pending_resize.request(extent(640, 480));
+        assert_eq!(pending_resize.take(extent(640, 480)), None);
+    }
+
+    #[test]
+    fn should_pause_on_zero_extent() {
+        assert!(should_pause(extent(0, 600)));
+        assert!(should_pause(extent(800, 0)));
+        assert!(should_pause(extent(0, 0)));
+        assert!(!should_pause(extent(800, 600)));
+    }
+
+    #[test]
+    fn swapchain_recovery_maps_present_results() {
+        assert_eq!(
+            swapchain_recovery(Result::ERROR_OUT_OF_DATE_KHR),
+            Some(SwapchainRecovery::RecreateSwapchain)
+        );
+        assert_eq!(
+            swapchain_recovery(Result::SUBOPTIMAL_KHR),
+            Some(SwapchainRecovery::RecreateSwapchain)
+        );
+        assert_eq!(
+            swapchain_recovery(Result::ERROR_SURFACE_LOST_KHR),
+            Some(SwapchainRecovery::RecreateSurface)
+        );
+        assert_eq!(swapchain_recovery(Result::ERROR_DEVICE_LOST), None);
+    }
+
+    fn render_context(device: &Rc<Device>) -> RenderContext {
+        let surface = PotatoSurface {
+            surface_loader: Surface::new(&fake_device::entry(), &fake_device::instance()),
+            surface: SurfaceKHR::from_raw(1),
+        };
+        RenderContext::from_raw_parts(device, surface)
+    }
+
+    #[test]
+    fn image_count_change_keeps_per_frame_uniforms() {
+        let device = Rc::new(fake_device::device());
+        let mut render_context = render_context(&device);
+        let descriptor_sets = render_context.descriptor_sets.clone();
+
+        // Going from a double- to a triple-buffered swapchain.
+        let triple_buffered = PotatoSwapChain::from_raw_parts(&device, 40, 3);
+        render_context.replace_swapchain(&device, |_| triple_buffered);
+
+        assert_eq!(render_context.swapchain.swapchain_images.len(), 3);
+        assert_eq!(render_context.uniform_buffers.len(), MAX_FRAMES_IN_FLIGHT);
+        assert_eq!(render_context.descriptor_sets, descriptor_sets);
+        let calls = fake_device::take_calls();
+        assert!(calls.contains(&("vkDestroySwapchainKHR", 20)));
+        assert!(!calls.iter().any(|(name, _)| {
+            *name == "vkDestroyBuffer" || *name == "vkDestroyDescriptorPool"
+        }));
+    }
+
+    #[test]
+    fn freeing_before_re_recording_releases_the_last_recording_once() {
+        let device = Rc::new(fake_device::device());
+        let mut render_context = render_context(&device);
+        render_context.command_buffers = (50..52).map(CommandBuffer::from_raw).collect();
+        render_context.scene_command_buffers = vec![CommandBuffer::from_raw(52)];
+        render_context.worker_command_pools = vec![CommandPool::from_raw(60)];
+        render_context.parallel_command_buffers = vec![CommandBuffer::from_raw(53)];
+
+        render_context.free_command_buffers(&device, CommandPool::from_raw(9));
+        render_context.free_command_buffers(&device, CommandPool::from_raw(9));
+
+        assert_eq!(
+            fake_device::take_calls(),
+            vec![
+                ("vkFreeCommandBuffers", 50),
+                ("vkFreeCommandBuffers", 51),
+                ("vkFreeCommandBuffers", 52),
+                ("vkFreeCommandBuffers", 53),
+            ]
+        );
+        assert!(render_context.command_buffers.is_empty());
+        assert!(render_context.scene_command_buffers.is_empty());
+        assert!(render_context.parallel_command_buffers.is_empty());
+        assert_eq!(render_context.worker_command_pools, vec![CommandPool::from_raw(60)]);
+    }
+}
@@ -0,0 +1,247 @@
+use super::error::PotatoError;
+use super::image_layout::{base_subresource_range, find_layout_transition, transition_image_layout};
+use ash::vk::{CommandBuffer, Format, Image, ImageLayout};
+use ash::Device;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GraphImage {
+    pub image: Image,
+    pub format: Format,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphPass {
+    pub name: String,
+    pub reads: Vec<String>,
+    pub writes: Vec<String>,
+}
+
+impl GraphPass {
+    pub fn new(name: &str) -> GraphPass {
+        GraphPass {
+            name: name.to_string(),
+            reads: vec![],
+            writes: vec![],
+        }
+    }
+
+    pub fn read(mut self, image: &str) -> Self {
+        self.reads.push(image.to_string());
+        self
+    }
+
+    pub fn write(mut self, image: &str) -> Self {
+        self.writes.push(image.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageTransition {
+    pub image: String,
+    pub old_layout: ImageLayout,
+    pub new_layout: ImageLayout,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledPass {
+    pub pass: usize,
+    pub transitions: Vec<ImageTransition>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RenderGraph {
+    passes: Vec<GraphPass>,
+}
+
+impl RenderGraph {
+    pub fn new() -> RenderGraph {
+        RenderGraph::default()
+    }
+
+    pub fn pass(mut self, pass: GraphPass) -> Self {
+        self.passes.push(pass);
+        self
+    }
+
+    pub fn passes(&self) -> &[GraphPass] {
+        &self.passes
+    }
+
+    pub fn compile(&self) -> Result<Vec<CompiledPass>, PotatoError> {
+        let mut layouts: HashMap<&str, ImageLayout> = HashMap::new();
+        self.execution_order()?
+            .into_iter()
+            .map(|i| {
+                let pass = &self.passes[i];
+                let reads = pass
+                    .reads
+                    .iter()
+                    .map(|x| (x, ImageLayout::SHADER_READ_ONLY_OPTIMAL));
+                let writes = pass
+                    .writes
+                    .iter()
+                    .map(|x| (x, ImageLayout::COLOR_ATTACHMENT_OPTIMAL));
+                let mut transitions = vec![];
+                for (image, new_layout) in reads.chain(writes) {
+                    let old_layout = layouts
+                        .insert(image, new_layout)
+                        .unwrap_or(ImageLayout::UNDEFINED);
+                    if old_layout != new_layout {
+                        find_layout_transition(old_layout, new_layout)?;
+                        transitions.push(ImageTransition {
+                            image: image.clone(),
+                            old_layout,
+                            new_layout,
+                        });
+                    }
+                }
+                Ok(CompiledPass {
+                    pass: i,
+                    transitions,
+                })
+            })
+            .collect()
+    }
+
+    pub fn record<F: FnMut(&GraphPass, CommandBuffer)>(
+        &self,
+        device: &Device,
+        command_buffer: CommandBuffer,
+        compiled: &[CompiledPass],
+        images: &HashMap<String, GraphImage>,
+        mut record_pass: F,
+    ) -> Result<(), PotatoError> {
+        for compiled_pass in compiled {
+            for transition in &compiled_pass.transitions {
+                let image = images.get(&transition.image).ok_or_else(|| {
+                    PotatoError::InvalidRenderGraph(format!("unknown image {}", transition.image))
+                })?;
+                transition_image_layout(
+                    device,
+                    command_buffer,
+                    image.image,
+                    base_subresource_range(image.format),
+                    transition.old_layout,
+                    transition.new_layout,
+                )?;
+            }
+            record_pass(&self.passes[compiled_pass.pass], command_buffer);
+        }
+        Ok(())
+    }
+
+    fn execution_order(&self) -> Result<Vec<usize>, PotatoError> {
+        let mut order = vec![];
+        let mut remaining: Vec<usize> = (0..self.passes.len()).collect();
+        while !remaining.is_empty() {
+            let is_pending_write = |image: &String| {
+                remaining
+                    .iter()
+                    .any(|x| self.passes[*x].writes.contains(image))
+            };
+            let ready = remaining
+                .iter()
+                .position(|x| {
+                    let pass = &self.passes[*x];
+                    !pass
+                        .reads
+                        .iter()
+                        .any(|image| !pass.writes.contains(image) && is_pending_write(image))
+                })
+                .ok_or_else(|| {
+                    PotatoError::InvalidRenderGraph(format!(
+                        "passes {:?} depend on each other",
+                        remaining
+                            .iter()
+                            .map(|x| self.passes[*x].name.as_str())
+                            .collect::<Vec<_>>()
+                    ))
+                })?;
+            order.push(remaining.remove(ready));
+        }
+        Ok(order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transition(
+        image: &str,
+        old_layout: ImageLayout,
+        new_layout: ImageLayout,
+    ) -> ImageTransition {
+        ImageTransition {
+            image: image.to_string(),
+            old_layout,
+            new_layout,
+        }
+    }
+
+    #[test]
+    fn linear_chain_transitions_outputs_into_inputs() {
+        let graph = RenderGraph::new()
+            .pass(GraphPass::new("post").read("scene").write("swapchain"))
+            .pass(GraphPass::new("shadow").write("shadow"))
+            .pass(GraphPass::new("scene").read("shadow").write("scene"));
+
+        let compiled = graph.compile().unwrap();
+        let order: Vec<_> = compiled
+            .iter()
+            .map(|x| graph.passes()[x.pass].name.as_str())
+            .collect();
+        assert_eq!(order, vec!["shadow", "scene", "post"]);
+        assert_eq!(
+            compiled[0].transitions,
+            vec![transition(
+                "shadow",
+                ImageLayout::UNDEFINED,
+                ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+            )]
+        );
+        assert_eq!(
+            compiled[1].transitions,
+            vec![
+                transition(
+                    "shadow",
+                    ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                    ImageLayout::SHADER_READ_ONLY_OPTIMAL
+                ),
+                transition(
+                    "scene",
+                    ImageLayout::UNDEFINED,
+                    ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+                ),
+            ]
+        );
+        assert_eq!(
+            compiled[2].transitions,
+            vec![
+                transition(
+                    "scene",
+                    ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                    ImageLayout::SHADER_READ_ONLY_OPTIMAL
+                ),
+                transition(
+                    "swapchain",
+                    ImageLayout::UNDEFINED,
+                    ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn cyclic_passes_are_rejected() {
+        let graph = RenderGraph::new()
+            .pass(GraphPass::new("a").read("b").write("a"))
+            .pass(GraphPass::new("b").read("a").write("b"));
+        assert!(matches!(
+            graph.compile(),
+            Err(PotatoError::InvalidRenderGraph(_))
+        ));
+    }
+}
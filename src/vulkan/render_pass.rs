@@ -1,71 +1,512 @@
+use super::attachment::has_stencil_component;
+use super::error::PotatoError;
+use super::image_layout::find_aspect_mask;
 use ash::version::DeviceV1_0;
 use ash::vk::{
     AccessFlags, AttachmentDescription, AttachmentDescriptionFlags, AttachmentLoadOp,
-    AttachmentReference, AttachmentStoreOp, DependencyFlags, Format, ImageLayout,
+    AttachmentReference, AttachmentStoreOp, DependencyFlags, Format, ImageAspectFlags, ImageLayout,
     PipelineBindPoint, PipelineStageFlags, RenderPass, RenderPassCreateFlags, RenderPassCreateInfo,
     SampleCountFlags, StructureType, SubpassDependency, SubpassDescription,
     SubpassDescriptionFlags, SUBPASS_EXTERNAL,
 };
 use ash::Device;
 
-pub fn create_render_pass(device: &Device, surface_format: Format) -> RenderPass {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderTargetFormats {
+    pub color: Format,
+    pub depth: Option<Format>,
+    pub samples: SampleCountFlags,
+}
+
+impl RenderTargetFormats {
+    pub fn is_multisampled(&self) -> bool {
+        self.samples != SampleCountFlags::TYPE_1
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        let is_depth_format =
+            |format: Format| find_aspect_mask(format).contains(ImageAspectFlags::DEPTH);
+        self.color != Format::UNDEFINED
+            && !is_depth_format(self.color)
+            && self.depth.is_none_or(is_depth_format)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SubpassLayout {
+    pub color_attachments: Vec<AttachmentReference>,
+    pub resolve_attachments: Vec<AttachmentReference>,
+    pub input_attachments: Vec<AttachmentReference>,
+    pub depth_stencil_attachment: Option<AttachmentReference>,
+}
+
+impl SubpassLayout {
+    pub fn new() -> SubpassLayout {
+        SubpassLayout::default()
+    }
+
+    pub fn color(mut self, attachment: u32) -> Self {
+        self.color_attachments.push(AttachmentReference {
+            attachment,
+            layout: ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        });
+        self
+    }
+
+    pub fn resolve(mut self, attachment: u32) -> Self {
+        self.resolve_attachments.push(AttachmentReference {
+            attachment,
+            layout: ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        });
+        self
+    }
+
+    pub fn input(mut self, attachment: u32, layout: ImageLayout) -> Self {
+        self.input_attachments
+            .push(AttachmentReference { attachment, layout });
+        self
+    }
+
+    pub fn depth_stencil(mut self, attachment: u32) -> Self {
+        self.depth_stencil_attachment = Some(AttachmentReference {
+            attachment,
+            layout: ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        });
+        self
+    }
+
+    fn description(&self) -> SubpassDescription {
+        debug_assert!(
+            self.resolve_attachments.is_empty()
+                || self.resolve_attachments.len() == self.color_attachments.len(),
+            "Subpass must resolve either none or all of its color attachments"
+        );
+        SubpassDescription {
+            flags: SubpassDescriptionFlags::empty(),
+            pipeline_bind_point: PipelineBindPoint::GRAPHICS,
+            input_attachment_count: self.input_attachments.len() as u32,
+            p_input_attachments: self.input_attachments.as_ptr(),
+            color_attachment_count: self.color_attachments.len() as u32,
+            p_color_attachments: self.color_attachments.as_ptr(),
+            p_resolve_attachments: if self.resolve_attachments.is_empty() {
+                std::ptr::null()
+            } else {
+                self.resolve_attachments.as_ptr()
+            },
+            p_depth_stencil_attachment: self
+                .depth_stencil_attachment
+                .as_ref()
+                .map_or(std::ptr::null(), |x| x),
+            preserve_attachment_count: 0,
+            p_preserve_attachments: std::ptr::null(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RenderPassLayout {
+    pub attachments: Vec<AttachmentDescription>,
+    pub subpasses: Vec<SubpassLayout>,
+    pub dependencies: Vec<SubpassDependency>,
+}
+
+impl RenderPassLayout {
+    pub fn new() -> RenderPassLayout {
+        RenderPassLayout::default()
+    }
+
+    pub fn attachment_count(&self) -> u32 {
+        self.attachments.len() as u32
+    }
+
+    pub fn attachment(mut self, attachment: AttachmentDescription) -> Self {
+        self.attachments.push(attachment);
+        self
+    }
+
+    pub fn subpass(mut self, subpass: SubpassLayout) -> Self {
+        self.subpasses.push(subpass);
+        self
+    }
+
+    pub fn dependency(mut self, dependency: SubpassDependency) -> Self {
+        self.dependencies.push(dependency);
+        self
+    }
+
+    pub fn input_dependency(self, src_subpass: u32, dst_subpass: u32) -> Self {
+        self.dependency(SubpassDependency {
+            src_subpass,
+            dst_subpass,
+            src_stage_mask: PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                | PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            dst_stage_mask: PipelineStageFlags::FRAGMENT_SHADER,
+            src_access_mask: AccessFlags::COLOR_ATTACHMENT_WRITE
+                | AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            dst_access_mask: AccessFlags::INPUT_ATTACHMENT_READ,
+            dependency_flags: DependencyFlags::BY_REGION,
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), PotatoError> {
+        let subpass_count = self.subpasses.len() as u32;
+        let is_known_subpass = |x: u32| x == SUBPASS_EXTERNAL || x < subpass_count;
+        if let Some(dependency) = self
+            .dependencies
+            .iter()
+            .find(|x| !is_known_subpass(x.src_subpass) || !is_known_subpass(x.dst_subpass))
+        {
+            return Err(PotatoError::InvalidSubpassDependency(
+                dependency.src_subpass,
+                dependency.dst_subpass,
+            ));
+        }
+        for subpass in &self.subpasses {
+            let samples_of = |x: &AttachmentReference| {
+                self.attachments
+                    .get(x.attachment as usize)
+                    .map(|x| x.samples)
+            };
+            let color_samples = subpass.color_attachments.first().and_then(samples_of);
+            let depth_samples = subpass
+                .depth_stencil_attachment
+                .as_ref()
+                .and_then(samples_of);
+            if let (Some(color_samples), Some(depth_samples)) = (color_samples, depth_samples) {
+                if color_samples != depth_samples {
+                    return Err(PotatoError::SampleCountMismatch(
+                        color_samples,
+                        depth_samples,
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn create(&self, device: &Device) -> Result<RenderPass, PotatoError> {
+        self.validate()?;
+        let subpasses: Vec<SubpassDescription> = self
+            .subpasses
+            .iter()
+            .map(SubpassLayout::description)
+            .collect();
+
+        let render_pass_create_info = RenderPassCreateInfo {
+            s_type: StructureType::RENDER_PASS_CREATE_INFO,
+            flags: RenderPassCreateFlags::empty(),
+            p_next: std::ptr::null(),
+            attachment_count: self.attachments.len() as u32,
+            p_attachments: self.attachments.as_ptr(),
+            subpass_count: subpasses.len() as u32,
+            p_subpasses: subpasses.as_ptr(),
+            dependency_count: self.dependencies.len() as u32,
+            p_dependencies: self.dependencies.as_ptr(),
+        };
+
+        let render_pass = unsafe { device.create_render_pass(&render_pass_create_info, None)? };
+        Ok(render_pass)
+    }
+}
+
+pub fn create_render_pass(
+    device: &Device,
+    formats: &RenderTargetFormats,
+) -> Result<RenderPass, PotatoError> {
+    create_forward_layout(formats).create(device)
+}
+
+fn create_forward_layout(formats: &RenderTargetFormats) -> RenderPassLayout {
+    let is_multisampled = formats.is_multisampled();
+
     let color_attachment = AttachmentDescription {
         flags: AttachmentDescriptionFlags::empty(),
-        format: surface_format,
-        samples: SampleCountFlags::TYPE_1,
+        format: formats.color,
+        samples: formats.samples,
         load_op: AttachmentLoadOp::CLEAR,
-        store_op: AttachmentStoreOp::STORE,
+        store_op: if is_multisampled {
+            AttachmentStoreOp::DONT_CARE
+        } else {
+            AttachmentStoreOp::STORE
+        },
         stencil_load_op: AttachmentLoadOp::DONT_CARE,
         stencil_store_op: AttachmentStoreOp::DONT_CARE,
         initial_layout: ImageLayout::UNDEFINED,
-        final_layout: ImageLayout::PRESENT_SRC_KHR,
+        final_layout: if is_multisampled {
+            ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        } else {
+            ImageLayout::PRESENT_SRC_KHR
+        },
     };
 
-    let color_attachment_ref = AttachmentReference {
-        attachment: 0,
-        layout: ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-    };
-
-    let subpass = SubpassDescription {
-        flags: SubpassDescriptionFlags::empty(),
-        pipeline_bind_point: PipelineBindPoint::GRAPHICS,
-        input_attachment_count: 0,
-        p_input_attachments: std::ptr::null(),
-        color_attachment_count: 1,
-        p_color_attachments: &color_attachment_ref,
-        p_resolve_attachments: std::ptr::null(),
-        p_depth_stencil_attachment: std::ptr::null(),
-        preserve_attachment_count: 0,
-        p_preserve_attachments: std::ptr::null(),
+    let resolve_attachment = AttachmentDescription {
+        flags: AttachmentDescriptionFlags::empty(),
+        format: formats.color,
+        samples: SampleCountFlags::TYPE_1,
+        load_op: AttachmentLoadOp::DONT_CARE,
+        store_op: AttachmentStoreOp::STORE,
+        stencil_load_op: AttachmentLoadOp::DONT_CARE,
+        stencil_store_op: AttachmentStoreOp::DONT_CARE,
+        initial_layout: ImageLayout::UNDEFINED,
+        final_layout: ImageLayout::PRESENT_SRC_KHR,
     };
 
-    let render_pass_attachments = [color_attachment];
+    let mut layout = RenderPassLayout::new().attachment(color_attachment);
+    let mut subpass = SubpassLayout::new().color(0);
+    if let Some(depth_format) = formats.depth {
+        subpass = subpass.depth_stencil(layout.attachment_count());
+        layout = layout.attachment(create_depth_attachment_description(
+            depth_format,
+            formats.samples,
+        ));
+    }
+    if is_multisampled {
+        subpass = subpass.resolve(layout.attachment_count());
+        layout = layout.attachment(resolve_attachment);
+    }
 
-    let subpass_dependencies = [SubpassDependency {
+    layout.subpass(subpass).dependency(SubpassDependency {
         src_subpass: SUBPASS_EXTERNAL,
         dst_subpass: 0,
-        src_stage_mask: PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-        dst_stage_mask: PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        src_stage_mask: PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+            | PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+        dst_stage_mask: PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+            | PipelineStageFlags::EARLY_FRAGMENT_TESTS,
         src_access_mask: AccessFlags::empty(),
-        dst_access_mask: AccessFlags::COLOR_ATTACHMENT_WRITE,
+        dst_access_mask: AccessFlags::COLOR_ATTACHMENT_WRITE
+            | AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
         dependency_flags: DependencyFlags::empty(),
-    }];
-
-    let render_pass_create_info = RenderPassCreateInfo {
-        s_type: StructureType::RENDER_PASS_CREATE_INFO,
-        flags: RenderPassCreateFlags::empty(),
-        p_next: std::ptr::null(),
-        attachment_count: render_pass_attachments.len() as u32,
-        p_attachments: render_pass_attachments.as_ptr(),
-        subpass_count: 1,
-        p_subpasses: &subpass,
-        dependency_count: subpass_dependencies.len() as u32,
-        p_dependencies: subpass_dependencies.as_ptr(),
+    })
+}
+
+pub fn find_stencil_load_op(depth_format: Format) -> AttachmentLoadOp {
+    if has_stencil_component(depth_format) {
+        AttachmentLoadOp::CLEAR
+    } else {
+        AttachmentLoadOp::DONT_CARE
+    }
+}
+
+fn create_depth_attachment_description(
+    depth_format: Format,
+    samples: SampleCountFlags,
+) -> AttachmentDescription {
+    AttachmentDescription {
+        flags: AttachmentDescriptionFlags::empty(),
+        format: depth_format,
+        samples,
+        load_op: AttachmentLoadOp::CLEAR,
+        store_op: AttachmentStoreOp::DONT_CARE,
+        stencil_load_op: find_stencil_load_op(depth_format),
+        stencil_store_op: AttachmentStoreOp::DONT_CARE,
+        initial_layout: ImageLayout::UNDEFINED,
+        final_layout: ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+    }
+}
+
+pub fn create_offscreen_render_pass(
+    device: &Device,
+    color_format: Format,
+    depth_format: Format,
+    samples: SampleCountFlags,
+    resolve_format: Format,
+) -> Result<RenderPass, PotatoError> {
+    create_offscreen_layout(color_format, depth_format, samples, resolve_format)?.create(device)
+}
+
+fn create_offscreen_layout(
+    color_format: Format,
+    depth_format: Format,
+    samples: SampleCountFlags,
+    resolve_format: Format,
+) -> Result<RenderPassLayout, PotatoError> {
+    let is_multisampled = samples != SampleCountFlags::TYPE_1;
+    if is_multisampled && resolve_format != color_format {
+        return Err(PotatoError::ResolveFormatMismatch(color_format, resolve_format));
+    }
+
+    let color_attachment = AttachmentDescription {
+        flags: AttachmentDescriptionFlags::empty(),
+        format: color_format,
+        samples,
+        load_op: AttachmentLoadOp::CLEAR,
+        store_op: if is_multisampled {
+            AttachmentStoreOp::DONT_CARE
+        } else {
+            AttachmentStoreOp::STORE
+        },
+        stencil_load_op: AttachmentLoadOp::DONT_CARE,
+        stencil_store_op: AttachmentStoreOp::DONT_CARE,
+        initial_layout: ImageLayout::UNDEFINED,
+        final_layout: if is_multisampled {
+            ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        } else {
+            ImageLayout::SHADER_READ_ONLY_OPTIMAL
+        },
     };
 
-    unsafe {
-        device
-            .create_render_pass(&render_pass_create_info, None)
-            .expect("Failed to create render pass")
+    let resolve_attachment = AttachmentDescription {
+        flags: AttachmentDescriptionFlags::empty(),
+        format: resolve_format,
+        samples: SampleCountFlags::TYPE_1,
+        load_op: AttachmentLoadOp::DONT_CARE,
+        store_op: AttachmentStoreOp::STORE,
+        stencil_load_op: AttachmentLoadOp::DONT_CARE,
+        stencil_store_op: AttachmentStoreOp::DONT_CARE,
+        initial_layout: ImageLayout::UNDEFINED,
+        final_layout: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    };
+
+    let mut layout = RenderPassLayout::new()
+        .attachment(color_attachment)
+        .attachment(create_depth_attachment_description(depth_format, samples));
+    let mut subpass = SubpassLayout::new().color(0).depth_stencil(1);
+    if is_multisampled {
+        subpass = subpass.resolve(layout.attachment_count());
+        layout = layout.attachment(resolve_attachment);
+    }
+
+    Ok(layout
+        .subpass(subpass)
+        .dependency(SubpassDependency {
+            src_subpass: SUBPASS_EXTERNAL,
+            dst_subpass: 0,
+            src_stage_mask: PipelineStageFlags::FRAGMENT_SHADER,
+            dst_stage_mask: PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            src_access_mask: AccessFlags::SHADER_READ,
+            dst_access_mask: AccessFlags::COLOR_ATTACHMENT_WRITE,
+            dependency_flags: DependencyFlags::BY_REGION,
+        })
+        .dependency(SubpassDependency {
+            src_subpass: 0,
+            dst_subpass: SUBPASS_EXTERNAL,
+            src_stage_mask: PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            dst_stage_mask: PipelineStageFlags::FRAGMENT_SHADER,
+            src_access_mask: AccessFlags::COLOR_ATTACHMENT_WRITE,
+            dst_access_mask: AccessFlags::SHADER_READ,
+            dependency_flags: DependencyFlags::BY_REGION,
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deferred_layout() -> RenderPassLayout {
+        RenderPassLayout::new()
+            .subpass(SubpassLayout::new().color(0).depth_stencil(1))
+            .subpass(
+                SubpassLayout::new()
+                    .input(0, ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                    .color(2),
+            )
+            .input_dependency(0, 1)
+    }
+
+    #[test]
+    fn input_dependency_orders_attachment_writes_before_input_reads() {
+        let layout = deferred_layout();
+        assert!(layout.validate().is_ok());
+
+        let dependency = layout.dependencies[0];
+        assert_eq!((dependency.src_subpass, dependency.dst_subpass), (0, 1));
+        assert_eq!(
+            dependency.src_stage_mask,
+            PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | PipelineStageFlags::LATE_FRAGMENT_TESTS
+        );
+        assert_eq!(dependency.dst_stage_mask, PipelineStageFlags::FRAGMENT_SHADER);
+        assert_eq!(
+            dependency.src_access_mask,
+            AccessFlags::COLOR_ATTACHMENT_WRITE | AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+        );
+        assert_eq!(
+            dependency.dst_access_mask,
+            AccessFlags::INPUT_ATTACHMENT_READ
+        );
+        assert_eq!(dependency.dependency_flags, DependencyFlags::BY_REGION);
+    }
+
+    #[test]
+    fn input_attachment_reaches_the_subpass_description() {
+        let layout = deferred_layout();
+        let description = layout.subpasses[1].description();
+        assert_eq!(description.input_attachment_count, 1);
+        let input_attachment = unsafe { *description.p_input_attachments };
+        assert_eq!(input_attachment.attachment, 0);
+        assert_eq!(input_attachment.layout, ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        assert_eq!(layout.subpasses[0].description().input_attachment_count, 0);
+    }
+
+    #[test]
+    fn dependency_on_unknown_subpass_is_rejected() {
+        let layout = deferred_layout().input_dependency(1, 2);
+        assert!(matches!(
+            layout.validate(),
+            Err(PotatoError::InvalidSubpassDependency(1, 2))
+        ));
+    }
+
+    #[test]
+    fn multisampled_color_and_depth_share_the_sample_count() {
+        let layout = create_forward_layout(&RenderTargetFormats {
+            color: Format::B8G8R8A8_SRGB,
+            depth: Some(Format::D32_SFLOAT),
+            samples: SampleCountFlags::TYPE_4,
+        });
+        assert!(layout.validate().is_ok());
+        assert_eq!(layout.attachments[0].samples, SampleCountFlags::TYPE_4);
+        assert_eq!(layout.attachments[1].samples, SampleCountFlags::TYPE_4);
+        assert_eq!(layout.attachments[2].samples, SampleCountFlags::TYPE_1);
+    }
+
+    #[test]
+    fn depth_sample_count_mismatch_is_rejected() {
+        let mut layout = create_forward_layout(&RenderTargetFormats {
+            color: Format::B8G8R8A8_SRGB,
+            depth: Some(Format::D32_SFLOAT),
+            samples: SampleCountFlags::TYPE_4,
+        });
+        layout.attachments[1].samples = SampleCountFlags::TYPE_1;
+        assert!(matches!(
+            layout.validate(),
+            Err(PotatoError::SampleCountMismatch(
+                SampleCountFlags::TYPE_4,
+                SampleCountFlags::TYPE_1
+            ))
+        ));
+    }
+
+    #[test]
+    fn offscreen_resolve_reference_points_at_the_single_sample_image() {
+        let layout = create_offscreen_layout(
+            Format::R8G8B8A8_UNORM,
+            Format::D32_SFLOAT,
+            SampleCountFlags::TYPE_4,
+            Format::R8G8B8A8_UNORM,
+        )
+        .unwrap();
+        let resolve_attachments = &layout.subpasses[0].resolve_attachments;
+        assert_eq!(resolve_attachments.len(), 1);
+        let resolve_attachment = layout.attachments[resolve_attachments[0].attachment as usize];
+        assert_eq!(resolve_attachment.samples, SampleCountFlags::TYPE_1);
+        assert_eq!(resolve_attachment.format, Format::R8G8B8A8_UNORM);
+        assert_eq!(resolve_attachment.final_layout, ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+    }
+
+    #[test]
+    fn offscreen_resolve_format_must_match_color() {
+        let layout = create_offscreen_layout(
+            Format::R8G8B8A8_UNORM,
+            Format::D32_SFLOAT,
+            SampleCountFlags::TYPE_4,
+            Format::B8G8R8A8_UNORM,
+        );
+        assert!(matches!(
+            layout,
+            Err(PotatoError::ResolveFormatMismatch(
+                Format::R8G8B8A8_UNORM,
+                Format::B8G8R8A8_UNORM
+            ))
+        ));
     }
 }
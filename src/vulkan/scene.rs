@@ -0,0 +1,289 @@
+use super::buffer::BufferUploader;
+use super::vertex::{create_combined_buffer, create_index_buffer, create_vertex_buffer, Vertex};
+use ash::version::DeviceV1_0;
+use ash::vk::{Buffer, BufferUsageFlags, DescriptorSet, DeviceMemory, DeviceSize};
+use ash::Device;
+use cgmath::{EuclideanSpace, Matrix4, Point3, SquareMatrix, Transform};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DrawMode {
+    Indexed { index_count: u32 },
+    NonIndexed { vertex_count: u32 },
+    IndexedIndirect { buffer: Buffer, draw_count: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mesh {
+    pub vertex_buffer: Buffer,
+    pub vertex_buffer_memory: DeviceMemory,
+    pub index_buffer: Buffer,
+    pub index_buffer_memory: DeviceMemory,
+    pub index_offset: DeviceSize,
+    pub draw_mode: DrawMode,
+}
+
+impl Mesh {
+    pub fn is_indexed(&self) -> bool {
+        matches!(
+            self.draw_mode,
+            DrawMode::Indexed { .. } | DrawMode::IndexedIndirect { .. }
+        )
+    }
+
+    pub fn is_combined(&self) -> bool {
+        self.is_indexed() && self.index_buffer == self.vertex_buffer
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        unsafe {
+            if self.is_indexed() && !self.is_combined() {
+                device.destroy_buffer(self.index_buffer, None);
+                device.free_memory(self.index_buffer_memory, None);
+            }
+            device.destroy_buffer(self.vertex_buffer, None);
+            device.free_memory(self.vertex_buffer_memory, None);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Point3<f32>, max: Point3<f32>) -> Aabb {
+        Aabb { min, max }
+    }
+
+    pub fn from_vertices(vertices: &[Vertex]) -> Option<Aabb> {
+        let points: Vec<Point3<f32>> = vertices
+            .iter()
+            .map(|x| Point3::from_vec(x.position()))
+            .collect();
+        Aabb::from_points(&points)
+    }
+
+    pub fn from_points(points: &[Point3<f32>]) -> Option<Aabb> {
+        let first = *points.first()?;
+        Some(points.iter().fold(Aabb::new(first, first), |aabb, x| Aabb {
+            min: Point3::new(aabb.min.x.min(x.x), aabb.min.y.min(x.y), aabb.min.z.min(x.z)),
+            max: Point3::new(aabb.max.x.max(x.x), aabb.max.y.max(x.y), aabb.max.z.max(x.z)),
+        }))
+    }
+
+    pub fn corners(&self) -> [Point3<f32>; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            Point3::new(min.x, min.y, min.z),
+            Point3::new(max.x, min.y, min.z),
+            Point3::new(min.x, max.y, min.z),
+            Point3::new(max.x, max.y, min.z),
+            Point3::new(min.x, min.y, max.z),
+            Point3::new(max.x, min.y, max.z),
+            Point3::new(min.x, max.y, max.z),
+            Point3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    pub fn transform(&self, transform: Matrix4<f32>) -> Aabb {
+        let corners: Vec<Point3<f32>> = self
+            .corners()
+            .iter()
+            .map(|x| transform.transform_point(*x))
+            .collect();
+        Aabb::from_points(&corners).unwrap_or(*self)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Renderable {
+    pub mesh: Mesh,
+    pub transform: Matrix4<f32>,
+    pub descriptor_set: Option<DescriptorSet>,
+    pub bounds: Option<Aabb>,
+}
+
+impl Renderable {
+    pub fn new(mesh: Mesh) -> Renderable {
+        Renderable {
+            mesh,
+            transform: Matrix4::identity(),
+            descriptor_set: None,
+            bounds: None,
+        }
+    }
+
+    pub fn bounds(mut self, bounds: Aabb) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
+}
+
+// Renderables drawing `old` switch to the buffers of `new` but keep their material, transform
+// and indirect arguments.
+pub fn replace_mesh(renderables: &mut [Renderable], old: &Mesh, new: Mesh, bounds: Option<Aabb>) {
+    renderables
+        .iter_mut()
+        .filter(|x| x.mesh.vertex_buffer == old.vertex_buffer)
+        .for_each(|renderable| {
+            let draw_mode = match renderable.mesh.draw_mode {
+                DrawMode::IndexedIndirect { .. } if new.is_indexed() => renderable.mesh.draw_mode,
+                _ => new.draw_mode,
+            };
+            renderable.mesh = Mesh { draw_mode, ..new };
+            renderable.bounds = bounds;
+        });
+}
+
+pub fn create_mesh(uploader: &mut BufferUploader, vertices: &[Vertex], indices: &[u32]) -> Mesh {
+    let (vertex_buffer, vertex_buffer_memory) = create_vertex_buffer(
+        uploader,
+        vertices,
+        BufferUsageFlags::TRANSFER_DST | BufferUsageFlags::VERTEX_BUFFER,
+    );
+    if indices.is_empty() {
+        return Mesh {
+            vertex_buffer,
+            vertex_buffer_memory,
+            index_buffer: Buffer::null(),
+            index_buffer_memory: DeviceMemory::null(),
+            index_offset: 0,
+            draw_mode: DrawMode::NonIndexed {
+                vertex_count: vertices.len() as u32,
+            },
+        };
+    }
+    let (index_buffer, index_buffer_memory) = create_index_buffer(
+        uploader,
+        indices,
+        BufferUsageFlags::TRANSFER_DST | BufferUsageFlags::INDEX_BUFFER,
+    );
+
+    Mesh {
+        vertex_buffer,
+        vertex_buffer_memory,
+        index_buffer,
+        index_buffer_memory,
+        index_offset: 0,
+        draw_mode: DrawMode::Indexed {
+            index_count: indices.len() as u32,
+        },
+    }
+}
+
+pub fn create_combined_mesh(
+    uploader: &mut BufferUploader,
+    vertices: &[Vertex],
+    indices: &[u32],
+) -> Mesh {
+    if indices.is_empty() {
+        return create_mesh(uploader, vertices, indices);
+    }
+    let (buffer, memory, index_offset) = create_combined_buffer(uploader, vertices, indices);
+
+    Mesh {
+        vertex_buffer: buffer,
+        vertex_buffer_memory: memory,
+        index_buffer: buffer,
+        index_buffer_memory: memory,
+        index_offset,
+        draw_mode: DrawMode::Indexed {
+            index_count: indices.len() as u32,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::vertex::combined_index_offset;
+    use ash::vk::Handle;
+
+    fn mesh(handle: u64, index_count: u32) -> Mesh {
+        Mesh {
+            vertex_buffer: Buffer::from_raw(handle),
+            vertex_buffer_memory: DeviceMemory::from_raw(handle),
+            index_buffer: Buffer::from_raw(handle + 1),
+            index_buffer_memory: DeviceMemory::from_raw(handle + 1),
+            index_offset: 0,
+            draw_mode: DrawMode::Indexed { index_count },
+        }
+    }
+
+    #[test]
+    fn replace_mesh_updates_the_index_count() {
+        let (old, other, new) = (mesh(1, 36), mesh(3, 12), mesh(5, 6));
+        let material = DescriptorSet::from_raw(7);
+        let mut renderables = vec![
+            Renderable {
+                descriptor_set: Some(material),
+                ..Renderable::new(old)
+            },
+            Renderable::new(other),
+        ];
+
+        replace_mesh(&mut renderables, &old, new, None);
+        assert_eq!(renderables[0].mesh, new);
+        assert_eq!(renderables[0].mesh.draw_mode, DrawMode::Indexed { index_count: 6 });
+        assert_eq!(renderables[0].descriptor_set, Some(material));
+        assert_eq!(renderables[1].mesh, other);
+    }
+
+    #[test]
+    fn replace_mesh_keeps_indirect_draws() {
+        let (old, new) = (mesh(1, 36), mesh(5, 6));
+        let indirect = DrawMode::IndexedIndirect {
+            buffer: Buffer::from_raw(9),
+            draw_count: 1,
+        };
+        let mut renderables = vec![Renderable::new(Mesh {
+            draw_mode: indirect,
+            ..old
+        })];
+
+        replace_mesh(&mut renderables, &old, new, None);
+        assert_eq!(renderables[0].mesh.vertex_buffer, new.vertex_buffer);
+        assert_eq!(renderables[0].mesh.index_buffer, new.index_buffer);
+        assert_eq!(renderables[0].mesh.draw_mode, indirect);
+    }
+
+    fn combined_mesh(handle: u64, vertex_count: usize, index_count: u32) -> Mesh {
+        let vertex_data_size = vertex_count * std::mem::size_of::<Vertex>();
+        Mesh {
+            index_buffer: Buffer::from_raw(handle),
+            index_buffer_memory: DeviceMemory::from_raw(handle),
+            index_offset: combined_index_offset(vertex_data_size as DeviceSize),
+            ..mesh(handle, index_count)
+        }
+    }
+
+    #[test]
+    fn replace_mesh_moves_to_the_new_combined_offset() {
+        let (old, new) = (combined_mesh(1, 24, 36), combined_mesh(5, 3, 3));
+        let indirect = DrawMode::IndexedIndirect {
+            buffer: Buffer::from_raw(9),
+            draw_count: 1,
+        };
+        let mut renderables = vec![
+            Renderable::new(old),
+            Renderable::new(Mesh {
+                draw_mode: indirect,
+                ..old
+            }),
+        ];
+
+        replace_mesh(&mut renderables, &old, new, None);
+        for renderable in &renderables {
+            assert!(renderable.mesh.is_combined());
+            assert_eq!(renderable.mesh.vertex_buffer, new.vertex_buffer);
+            assert_eq!(
+                renderable.mesh.index_offset,
+                3 * std::mem::size_of::<Vertex>() as DeviceSize
+            );
+        }
+        assert_eq!(renderables[0].mesh.draw_mode, DrawMode::Indexed { index_count: 3 });
+        assert_eq!(renderables[1].mesh.draw_mode, indirect);
+    }
+}
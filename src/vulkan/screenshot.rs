@@ -0,0 +1,206 @@
+use super::buffer::create_buffer;
+use super::image_layout::{base_subresource_range, transition_image_layout};
+use super::swapchain::PotatoSwapChain;
+use ash::version::DeviceV1_0;
+use ash::vk::{
+    AccessFlags, Buffer, BufferImageCopy, BufferMemoryBarrier, BufferUsageFlags, CommandBuffer,
+    DependencyFlags, DeviceMemory, DeviceSize, Extent2D, Extent3D, Format, Image,
+    ImageAspectFlags, ImageLayout, ImageSubresourceLayers, MemoryMapFlags, MemoryPropertyFlags,
+    Offset3D, PhysicalDeviceMemoryProperties, PipelineStageFlags, StructureType,
+    QUEUE_FAMILY_IGNORED,
+};
+use ash::Device;
+
+pub struct ScreenshotCapture {
+    pub extent: Extent2D,
+    image: Image,
+    format: Format,
+    readback_buffer: Buffer,
+    readback_buffer_memory: DeviceMemory,
+    buffer_size: DeviceSize,
+}
+
+impl ScreenshotCapture {
+    pub fn new(
+        device: &Device,
+        device_memory_properties: &PhysicalDeviceMemoryProperties,
+        swapchain: &PotatoSwapChain,
+        image_index: usize,
+    ) -> ScreenshotCapture {
+        let extent = swapchain.swapchain_extent;
+        let buffer_size = (extent.width * extent.height * 4) as DeviceSize;
+        let (readback_buffer, readback_buffer_memory) = create_buffer(
+            device,
+            buffer_size,
+            BufferUsageFlags::TRANSFER_DST,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+            MemoryPropertyFlags::HOST_CACHED,
+            device_memory_properties,
+        );
+
+        ScreenshotCapture {
+            extent,
+            image: swapchain.swapchain_images[image_index],
+            format: swapchain.swapchain_format,
+            readback_buffer,
+            readback_buffer_memory,
+            buffer_size,
+        }
+    }
+
+    // Must be recorded after the render pass and before the image is handed to present.
+    pub fn record_copy(&self, device: &Device, command_buffer: CommandBuffer) {
+        transition_image_layout(
+            device,
+            command_buffer,
+            self.image,
+            base_subresource_range(self.format),
+            ImageLayout::PRESENT_SRC_KHR,
+            ImageLayout::TRANSFER_SRC_OPTIMAL,
+        )
+        .expect("Failed to transition swapchain image for capture");
+
+        let copy_regions = [BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: ImageSubresourceLayers {
+                aspect_mask: ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: Offset3D { x: 0, y: 0, z: 0 },
+            image_extent: Extent3D {
+                width: self.extent.width,
+                height: self.extent.height,
+                depth: 1,
+            },
+        }];
+        unsafe {
+            device.cmd_copy_image_to_buffer(
+                command_buffer,
+                self.image,
+                ImageLayout::TRANSFER_SRC_OPTIMAL,
+                self.readback_buffer,
+                &copy_regions,
+            );
+            // The fence only orders execution, the copy still has to be made visible to the host.
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                PipelineStageFlags::TRANSFER,
+                PipelineStageFlags::HOST,
+                DependencyFlags::empty(),
+                &[],
+                &[create_host_read_barrier(self.readback_buffer, self.buffer_size)],
+                &[],
+            );
+        }
+
+        transition_image_layout(
+            device,
+            command_buffer,
+            self.image,
+            base_subresource_range(self.format),
+            ImageLayout::TRANSFER_SRC_OPTIMAL,
+            ImageLayout::PRESENT_SRC_KHR,
+        )
+        .expect("Failed to transition swapchain image for present");
+    }
+
+    // Only valid once the command buffer holding the copy has finished executing.
+    pub fn read_pixels(&self, device: &Device) -> Vec<u8> {
+        let mut pixels = vec![0_u8; self.buffer_size as usize];
+        unsafe {
+            let data_ptr = device
+                .map_memory(
+                    self.readback_buffer_memory,
+                    0,
+                    self.buffer_size,
+                    MemoryMapFlags::empty(),
+                )
+                .expect("Failed to map memory") as *const u8;
+            pixels
+                .as_mut_ptr()
+                .copy_from_nonoverlapping(data_ptr, pixels.len());
+            device.unmap_memory(self.readback_buffer_memory);
+        }
+
+        if is_bgra_format(self.format) {
+            bgra_to_rgba(&mut pixels);
+        }
+
+        pixels
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        unsafe {
+            device.destroy_buffer(self.readback_buffer, None);
+            device.free_memory(self.readback_buffer_memory, None);
+        }
+    }
+}
+
+pub fn create_host_read_barrier(buffer: Buffer, size: DeviceSize) -> BufferMemoryBarrier {
+    BufferMemoryBarrier {
+        s_type: StructureType::BUFFER_MEMORY_BARRIER,
+        p_next: std::ptr::null(),
+        src_access_mask: AccessFlags::TRANSFER_WRITE,
+        dst_access_mask: AccessFlags::HOST_READ,
+        src_queue_family_index: QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index: QUEUE_FAMILY_IGNORED,
+        buffer,
+        offset: 0,
+        size,
+    }
+}
+
+pub fn is_supported_screenshot_format(format: Format) -> bool {
+    is_bgra_format(format)
+        || format == Format::R8G8B8A8_SRGB
+        || format == Format::R8G8B8A8_UNORM
+}
+
+fn is_bgra_format(format: Format) -> bool {
+    format == Format::B8G8R8A8_SRGB || format == Format::B8G8R8A8_UNORM
+}
+
+pub fn bgra_to_rgba(pixels: &mut [u8]) {
+    pixels.chunks_exact_mut(4).for_each(|x| x.swap(0, 2));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::vk::Handle;
+
+    #[test]
+    fn readback_barrier_makes_the_copy_visible_to_the_host() {
+        let barrier = create_host_read_barrier(Buffer::from_raw(1), 256);
+        assert_eq!(barrier.src_access_mask, AccessFlags::TRANSFER_WRITE);
+        assert_eq!(barrier.dst_access_mask, AccessFlags::HOST_READ);
+        assert_eq!(barrier.buffer, Buffer::from_raw(1));
+        assert_eq!((barrier.offset, barrier.size), (0, 256));
+    }
+
+    #[test]
+    fn bgra_to_rgba_swaps_red_and_blue() {
+        let mut pixels = [1, 2, 3, 4, 10, 20, 30, 40];
+        bgra_to_rgba(&mut pixels);
+        assert_eq!(pixels, [3, 2, 1, 4, 30, 20, 10, 40]);
+    }
+
+    #[test]
+    fn bgra_to_rgba_ignores_trailing_partial_pixel() {
+        let mut pixels = [1, 2, 3, 4, 5, 6];
+        bgra_to_rgba(&mut pixels);
+        assert_eq!(pixels, [3, 2, 1, 4, 5, 6]);
+    }
+
+    #[test]
+    fn supported_screenshot_formats() {
+        assert!(is_supported_screenshot_format(Format::B8G8R8A8_SRGB));
+        assert!(is_supported_screenshot_format(Format::R8G8B8A8_UNORM));
+        assert!(!is_supported_screenshot_format(Format::R16G16B16A16_SFLOAT));
+    }
+}
@@ -0,0 +1,279 @@
+use super::error::PotatoError;
+use crate::io::file::{read_file_to_bytes, read_file_to_str};
+use ash::version::DeviceV1_0;
+use ash::vk::{
+    ShaderModule, ShaderModuleCreateFlags, ShaderModuleCreateInfo, ShaderStageFlags, StructureType,
+};
+use ash::Device;
+use std::path::Path;
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShaderSource {
+    File(&'static str),
+    Bytes(&'static [u8]),
+    Glsl(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShadingMode {
+    Smooth,
+    Flat,
+    Lit,
+}
+
+impl ShadingMode {
+    pub fn fragment_shader(self) -> ShaderSource {
+        match self {
+            ShadingMode::Smooth => ShaderSource::File("src/shaders/spv/shader-frag.spv"),
+            ShadingMode::Flat => ShaderSource::File("src/shaders/spv/shader-frag-flat.spv"),
+            ShadingMode::Lit => ShaderSource::File("src/shaders/spv/shader-frag-lit.spv"),
+        }
+    }
+}
+
+pub fn load_shader_module(
+    device: &Device,
+    source: ShaderSource,
+) -> Result<ShaderModule, PotatoError> {
+    let code = match source {
+        ShaderSource::File(path) => validate_spirv(
+            &read_file_to_bytes(path).map_err(|e| PotatoError::ShaderRead(path.to_string(), e))?,
+        )?,
+        ShaderSource::Bytes(bytes) => validate_spirv(bytes)?,
+        ShaderSource::Glsl(path) => {
+            let glsl = read_file_to_str(path)
+                .map_err(|e| PotatoError::ShaderRead(path.to_string(), e))?;
+            compile_glsl(&glsl, path, shader_stage_from_path(path)?)?
+        }
+    };
+    Ok(create_shader_module(device, &code))
+}
+
+pub fn shader_stage_from_path(path: &str) -> Result<ShaderStageFlags, PotatoError> {
+    match Path::new(path).extension().and_then(|x| x.to_str()) {
+        Some("vert") => Ok(ShaderStageFlags::VERTEX),
+        Some("frag") => Ok(ShaderStageFlags::FRAGMENT),
+        Some("comp") => Ok(ShaderStageFlags::COMPUTE),
+        _ => Err(PotatoError::ShaderCompile(format!(
+            "cannot detect the shader stage of {}",
+            path
+        ))),
+    }
+}
+
+#[cfg(feature = "shaderc")]
+pub fn compile_glsl(
+    source: &str,
+    file_name: &str,
+    stage: ShaderStageFlags,
+) -> Result<Vec<u32>, PotatoError> {
+    let kind = match stage {
+        ShaderStageFlags::VERTEX => shaderc::ShaderKind::Vertex,
+        ShaderStageFlags::FRAGMENT => shaderc::ShaderKind::Fragment,
+        ShaderStageFlags::COMPUTE => shaderc::ShaderKind::Compute,
+        _ => {
+            return Err(PotatoError::ShaderCompile(format!(
+                "unsupported shader stage {:?}",
+                stage
+            )))
+        }
+    };
+    let mut compiler = shaderc::Compiler::new().ok_or_else(|| {
+        PotatoError::ShaderCompile("failed to initialize the shaderc compiler".to_string())
+    })?;
+    let artifact = compiler
+        .compile_into_spirv(source, kind, file_name, "main", None)
+        .map_err(|e| PotatoError::ShaderCompile(e.to_string()))?;
+    Ok(artifact.as_binary().to_vec())
+}
+
+#[cfg(not(feature = "shaderc"))]
+pub fn compile_glsl(
+    _source: &str,
+    file_name: &str,
+    _stage: ShaderStageFlags,
+) -> Result<Vec<u32>, PotatoError> {
+    Err(PotatoError::ShaderCompile(format!(
+        "cannot compile {}, potato was built without the shaderc feature",
+        file_name
+    )))
+}
+
+// Copies into u32 words since a byte buffer has no alignment guarantee. A module written with
+// the other endianness is swapped word by word into host order.
+pub fn validate_spirv(bytes: &[u8]) -> Result<Vec<u32>, PotatoError> {
+    if bytes.is_empty() || !bytes.len().is_multiple_of(4) {
+        return Err(PotatoError::InvalidSpirv(format!(
+            "length {} is not a non-zero multiple of 4",
+            bytes.len()
+        )));
+    }
+
+    let words: Vec<u32> = bytes
+        .chunks_exact(4)
+        .map(|x| u32::from_ne_bytes([x[0], x[1], x[2], x[3]]))
+        .collect();
+    match words[0] {
+        SPIRV_MAGIC => Ok(words),
+        magic if magic.swap_bytes() == SPIRV_MAGIC => {
+            Ok(words.into_iter().map(u32::swap_bytes).collect())
+        }
+        magic => Err(PotatoError::InvalidSpirv(format!(
+            "bad magic number {:#010x}",
+            magic
+        ))),
+    }
+}
+
+pub fn create_shader_module(device: &Device, code: &[u32]) -> ShaderModule {
+    let shader_module_create_info = ShaderModuleCreateInfo {
+        s_type: StructureType::SHADER_MODULE_CREATE_INFO,
+        p_next: std::ptr::null(),
+        flags: ShaderModuleCreateFlags::empty(),
+        code_size: std::mem::size_of_val(code),
+        p_code: code.as_ptr(),
+    };
+
+    unsafe {
+        device
+            .create_shader_module(&shader_module_create_info, None)
+            .expect("Failed to create shader module")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::fake_device::{self, SHADER_MODULE};
+    use ash::vk::Handle;
+
+    fn spirv_bytes(magic: u32) -> Vec<u8> {
+        [magic, 0x0001_0000, 0, 1, 0]
+            .iter()
+            .flat_map(|x| x.to_ne_bytes().to_vec())
+            .collect()
+    }
+
+    fn is_invalid_spirv(bytes: &[u8]) -> bool {
+        matches!(validate_spirv(bytes), Err(PotatoError::InvalidSpirv(_)))
+    }
+
+    #[test]
+    fn validate_spirv_accepts_valid_module() {
+        let bytes = spirv_bytes(SPIRV_MAGIC);
+        let words = validate_spirv(&bytes).unwrap();
+        assert_eq!(words.len(), 5);
+        assert_eq!(words[0], SPIRV_MAGIC);
+    }
+
+    #[test]
+    fn validate_spirv_accepts_unaligned_buffer() {
+        let mut bytes = vec![0_u8];
+        bytes.extend(spirv_bytes(SPIRV_MAGIC));
+        assert!(validate_spirv(&bytes[1..]).is_ok());
+    }
+
+    #[test]
+    fn validate_spirv_rejects_bad_magic() {
+        let bytes = spirv_bytes(0xdead_beef);
+        assert!(is_invalid_spirv(&bytes));
+    }
+
+    #[test]
+    fn validate_spirv_rejects_length_not_multiple_of_4() {
+        let mut bytes = spirv_bytes(SPIRV_MAGIC);
+        bytes.pop();
+        assert!(is_invalid_spirv(&bytes));
+        assert!(is_invalid_spirv(&[]));
+    }
+
+    #[test]
+    fn validate_spirv_swaps_byte_swapped_module_to_host_order() {
+        let host_order = validate_spirv(&spirv_bytes(SPIRV_MAGIC)).unwrap();
+        let swapped_bytes: Vec<u8> = host_order
+            .iter()
+            .flat_map(|x| x.swap_bytes().to_ne_bytes())
+            .collect();
+        assert_eq!(validate_spirv(&swapped_bytes).unwrap(), host_order);
+    }
+
+    #[test]
+    fn each_shading_mode_selects_its_own_fragment_shader() {
+        let modes = [
+            (ShadingMode::Smooth, "src/shaders/spv/shader-frag.spv"),
+            (ShadingMode::Flat, "src/shaders/spv/shader-frag-flat.spv"),
+            (ShadingMode::Lit, "src/shaders/spv/shader-frag-lit.spv"),
+        ];
+        for (mode, path) in &modes {
+            assert_eq!(mode.fragment_shader(), ShaderSource::File(path));
+            assert!(validate_spirv(&read_file_to_bytes(path).unwrap()).is_ok());
+        }
+    }
+
+    #[test]
+    fn shader_stage_is_detected_from_the_extension() {
+        let stage = |path| shader_stage_from_path(path).ok();
+        assert_eq!(stage("shaders/shader.vert"), Some(ShaderStageFlags::VERTEX));
+        assert_eq!(stage("shaders/shader.frag"), Some(ShaderStageFlags::FRAGMENT));
+        assert_eq!(stage("shaders/particle.comp"), Some(ShaderStageFlags::COMPUTE));
+        assert!(matches!(
+            shader_stage_from_path("shaders/shader.glsl"),
+            Err(PotatoError::ShaderCompile(_))
+        ));
+    }
+
+    const TRIVIAL_VERTEX_SHADER: &str = "#version 450
+void main() {
+    gl_Position = vec4(0.0, 0.0, 0.0, 1.0);
+}
+";
+
+    #[test]
+    #[cfg(feature = "shaderc")]
+    fn glsl_compiles_to_spirv() {
+        let code =
+            compile_glsl(TRIVIAL_VERTEX_SHADER, "trivial.vert", ShaderStageFlags::VERTEX).unwrap();
+        assert_eq!(code[0], SPIRV_MAGIC);
+    }
+
+    #[test]
+    #[cfg(feature = "shaderc")]
+    fn glsl_compile_errors_name_the_line() {
+        let source = TRIVIAL_VERTEX_SHADER.replace("vec4(0.0", "vec4(undeclared");
+        match compile_glsl(&source, "broken.vert", ShaderStageFlags::VERTEX) {
+            Err(PotatoError::ShaderCompile(message)) => assert!(message.contains("broken.vert:3")),
+            result => panic!("expected a compile error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "shaderc"))]
+    fn glsl_needs_the_shaderc_feature() {
+        assert!(matches!(
+            compile_glsl(TRIVIAL_VERTEX_SHADER, "trivial.vert", ShaderStageFlags::VERTEX),
+            Err(PotatoError::ShaderCompile(_))
+        ));
+    }
+
+    // Magic, version 1.0, generator, bound and schema of an empty little-endian module.
+    static EMBEDDED_MODULE: [u8; 20] = [
+        0x03, 0x02, 0x23, 0x07, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn shader_module_is_created_from_embedded_bytes() {
+        let device = fake_device::device();
+        let shader_module = load_shader_module(&device, ShaderSource::Bytes(&EMBEDDED_MODULE));
+        assert_eq!(
+            shader_module.ok(),
+            Some(ShaderModule::from_raw(SHADER_MODULE))
+        );
+        assert_eq!(
+            fake_device::take_calls(),
+            vec![("vkCreateShaderModule", u64::from(SPIRV_MAGIC))]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use super::error::PotatoError;
+use ash::version::DeviceV1_0;
+use ash::vk::{
+    CommandBuffer, Fence, PipelineStageFlags, Queue, Semaphore, StructureType, SubmitInfo,
+};
+use ash::prelude::VkResult;
+use ash::Device;
+
+#[derive(Debug, Clone, Default)]
+pub struct SubmitEntry {
+    pub command_buffers: Vec<CommandBuffer>,
+    pub wait_semaphores: Vec<Semaphore>,
+    pub wait_stages: Vec<PipelineStageFlags>,
+    pub signal_semaphores: Vec<Semaphore>,
+}
+
+impl SubmitEntry {
+    pub fn new(command_buffers: &[CommandBuffer]) -> SubmitEntry {
+        SubmitEntry {
+            command_buffers: command_buffers.to_vec(),
+            ..SubmitEntry::default()
+        }
+    }
+
+    pub fn wait(mut self, semaphore: Semaphore, stage: PipelineStageFlags) -> Self {
+        self.wait_semaphores.push(semaphore);
+        self.wait_stages.push(stage);
+        self
+    }
+
+    pub fn signal(mut self, semaphore: Semaphore) -> Self {
+        self.signal_semaphores.push(semaphore);
+        self
+    }
+
+    fn submit_info(&self) -> SubmitInfo {
+        SubmitInfo {
+            s_type: StructureType::SUBMIT_INFO,
+            p_next: std::ptr::null(),
+            wait_semaphore_count: self.wait_semaphores.len() as u32,
+            p_wait_semaphores: self.wait_semaphores.as_ptr(),
+            p_wait_dst_stage_mask: self.wait_stages.as_ptr(),
+            command_buffer_count: self.command_buffers.len() as u32,
+            p_command_buffers: self.command_buffers.as_ptr(),
+            signal_semaphore_count: self.signal_semaphores.len() as u32,
+            p_signal_semaphores: self.signal_semaphores.as_ptr(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SubmitBatch {
+    entries: Vec<SubmitEntry>,
+}
+
+impl SubmitBatch {
+    pub fn new() -> SubmitBatch {
+        SubmitBatch::default()
+    }
+
+    pub fn add(mut self, entry: SubmitEntry) -> Self {
+        self.entries.push(entry);
+        self
+    }
+
+    pub fn submit_infos(&self) -> Vec<SubmitInfo> {
+        self.entries.iter().map(SubmitEntry::submit_info).collect()
+    }
+
+    pub fn submit(&self, device: &Device, queue: Queue, fence: Fence) -> Result<(), PotatoError> {
+        submit_checked(device, queue, &self.submit_infos(), fence)
+    }
+}
+
+pub fn submit_checked(
+    device: &Device,
+    queue: Queue,
+    submit_infos: &[SubmitInfo],
+    fence: Fence,
+) -> Result<(), PotatoError> {
+    map_submit_result(unsafe { device.queue_submit(queue, submit_infos, fence) })
+}
+
+fn map_submit_result(result: VkResult<()>) -> Result<(), PotatoError> {
+    result.map_err(PotatoError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::vk::{self, Handle};
+
+    #[test]
+    fn submit_errors_map_to_potato_errors() {
+        assert!(map_submit_result(Ok(())).is_ok());
+        assert!(matches!(
+            map_submit_result(Err(vk::Result::ERROR_DEVICE_LOST)),
+            Err(PotatoError::DeviceLost)
+        ));
+        assert!(matches!(
+            map_submit_result(Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY)),
+            Err(PotatoError::OutOfMemory(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY))
+        ));
+    }
+
+    #[test]
+    fn batch_maps_each_entry_to_a_submit_info() {
+        let command_buffers = [CommandBuffer::from_raw(1), CommandBuffer::from_raw(2)];
+        let batch = SubmitBatch::new()
+            .add(
+                SubmitEntry::new(&command_buffers)
+                    .wait(Semaphore::from_raw(3), PipelineStageFlags::TRANSFER)
+                    .signal(Semaphore::from_raw(4)),
+            )
+            .add(SubmitEntry::new(&command_buffers[..1]));
+        let submit_infos = batch.submit_infos();
+        assert_eq!(submit_infos.len(), 2);
+        assert_eq!(submit_infos[0].command_buffer_count, 2);
+        assert_eq!(submit_infos[0].wait_semaphore_count, 1);
+        assert_eq!(submit_infos[0].signal_semaphore_count, 1);
+        assert_eq!(submit_infos[1].command_buffer_count, 1);
+        assert_eq!(submit_infos[1].wait_semaphore_count, 0);
+    }
+}
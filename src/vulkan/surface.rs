@@ -15,7 +15,6 @@ pub struct PotatoSurface {
     pub surface: SurfaceKHR,
 }
 //TODO make this support multiple platforms
-//TODO support multiple windows
 pub fn create_surface(entry: &Entry, instance: &Instance, window: &WinitWindow) -> PotatoSurface {
     let surface = unsafe { create_platform_surface(entry, instance, window) };
 
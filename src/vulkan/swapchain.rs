@@ -1,3 +1,4 @@
+use super::error::PotatoError;
 use super::queue_family::QueueFamily;
 use super::surface::PotatoSurface;
 use ash::extensions::khr::Swapchain;
@@ -9,6 +10,7 @@ use ash::vk::{
     ImageViewCreateFlags, ImageViewType, ImageSubresourceRange, ComponentSwizzle, ComponentMapping, ImageAspectFlags
 };
 use ash::{Device, Instance};
+use log::{debug, warn};
 use num::clamp;
 
 pub struct PotatoSwapChain {
@@ -18,6 +20,69 @@ pub struct PotatoSwapChain {
     pub swapchain_format: Format,
     pub swapchain_extent: Extent2D,
     pub swapchain_image_views: Vec<ImageView>,
+    pub swapchain_image_usage: ImageUsageFlags,
+}
+
+#[cfg(test)]
+impl PotatoSwapChain {
+    // Images and views are numbered right after the swapchain handle.
+    pub fn from_raw_parts(device: &Device, handle: u64, image_count: u64) -> PotatoSwapChain {
+        use ash::vk::Handle;
+        PotatoSwapChain {
+            swapchain_loader: Swapchain::new(&super::fake_device::instance(), device),
+            swapchain: SwapchainKHR::from_raw(handle),
+            swapchain_images: (0..image_count).map(|x| Image::from_raw(handle + 1 + x)).collect(),
+            swapchain_format: Format::B8G8R8A8_SRGB,
+            swapchain_extent: Extent2D {
+                width: 800,
+                height: 600,
+            },
+            swapchain_image_views: (0..image_count)
+                .map(|x| ImageView::from_raw(handle + 1 + image_count + x))
+                .collect(),
+            swapchain_image_usage: ImageUsageFlags::COLOR_ATTACHMENT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HdrColorSpace {
+    Hdr10,
+    ExtendedSrgbLinear,
+}
+
+impl HdrColorSpace {
+    pub fn candidates(self) -> &'static [(Format, ColorSpaceKHR)] {
+        match self {
+            HdrColorSpace::Hdr10 => &[
+                (Format::A2B10G10R10_UNORM_PACK32, ColorSpaceKHR::HDR10_ST2084_EXT),
+                (Format::A2R10G10B10_UNORM_PACK32, ColorSpaceKHR::HDR10_ST2084_EXT),
+            ],
+            HdrColorSpace::ExtendedSrgbLinear => &[(
+                Format::R16G16B16A16_SFLOAT,
+                ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+            )],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SwapchainConfig {
+    pub present_mode: PresentModeKHR,
+    pub is_srgb: bool,
+    pub hdr: Option<HdrColorSpace>,
+    pub extra_usage: ImageUsageFlags,
+}
+
+impl Default for SwapchainConfig {
+    fn default() -> Self {
+        SwapchainConfig {
+            present_mode: PresentModeKHR::MAILBOX,
+            is_srgb: true,
+            hdr: None,
+            extra_usage: ImageUsageFlags::empty(),
+        }
+    }
 }
 
 pub struct SwapChainSupportDetail {
@@ -31,22 +96,39 @@ pub fn create_swapchain(
     device: &Device,
     physical_device: PhysicalDevice,
     surface: &PotatoSurface,
-    _queue_family: &QueueFamily,
+    queue_family: &QueueFamily,
+    config: &SwapchainConfig,
+    window_extent: Extent2D,
 ) -> PotatoSwapChain {
     let swapchain_support = determine_swapchain_support(physical_device, surface);
 
-    let surface_format = choose_swapchain_format(&swapchain_support.formats);
-    let present_mode = choose_swapchain_present_mode(&swapchain_support.present_modes);
-    let extent = choose_swapchain_extent(&swapchain_support.capabilities);
+    let surface_format =
+        choose_swapchain_format(&swapchain_support.formats, config.is_srgb, config.hdr);
+    debug!("Using surface format {:?}", surface_format);
+    let present_mode =
+        choose_swapchain_present_mode(&swapchain_support.present_modes, config.present_mode);
+    debug!(
+        "Requested present mode {:?}, using {:?}",
+        config.present_mode, present_mode
+    );
+    let extent = choose_swapchain_extent(&swapchain_support.capabilities, window_extent);
+    debug!("Window extent {:?}, using swapchain extent {:?}", window_extent, extent);
 
-    let image_count = if swapchain_support.capabilities.max_image_count > 0 {
-        swapchain_support.capabilities.max_image_count
-    } else {
-        swapchain_support.capabilities.min_image_count + 1
-    };
+    let image_count = choose_swapchain_image_count(&swapchain_support.capabilities);
+    debug!("Requesting {} swapchain images", image_count);
 
-    let (image_sharing_mode, queue_family_index_count, queue_family_indices) =
-        (SharingMode::EXCLUSIVE, 0, vec![]);
+    let image_usage =
+        choose_swapchain_image_usage(&swapchain_support.capabilities, config.extra_usage)
+            .expect("Failed to choose swapchain image usage");
+
+    let (image_sharing_mode, queue_family_indices) = choose_swapchain_sharing_mode(
+        queue_family.graphics_family.unwrap() as u32,
+        queue_family.present_family.unwrap() as u32,
+    );
+    debug!(
+        "Using {:?} swapchain sharing across families {:?}",
+        image_sharing_mode, queue_family_indices
+    );
 
     let swapchain_create_info = SwapchainCreateInfoKHR {
         s_type: StructureType::SWAPCHAIN_CREATE_INFO_KHR,
@@ -57,10 +139,10 @@ pub fn create_swapchain(
         image_color_space: surface_format.color_space,
         image_format: surface_format.format,
         image_extent: extent,
-        image_usage: ImageUsageFlags::COLOR_ATTACHMENT,
+        image_usage,
         image_sharing_mode,
         p_queue_family_indices: queue_family_indices.as_ptr(),
-        queue_family_index_count,
+        queue_family_index_count: queue_family_indices.len() as u32,
         pre_transform: swapchain_support.capabilities.current_transform,
         composite_alpha: CompositeAlphaFlagsKHR::OPAQUE,
         present_mode,
@@ -91,6 +173,18 @@ pub fn create_swapchain(
         swapchain_extent: extent,
         swapchain_images,
         swapchain_image_views,
+        swapchain_image_usage: image_usage,
+    }
+}
+
+pub fn choose_swapchain_sharing_mode(
+    graphics_family: u32,
+    present_family: u32,
+) -> (SharingMode, Vec<u32>) {
+    if graphics_family == present_family {
+        (SharingMode::EXCLUSIVE, vec![])
+    } else {
+        (SharingMode::CONCURRENT, vec![graphics_family, present_family])
     }
 }
 
@@ -119,35 +213,103 @@ pub fn determine_swapchain_support(
     }
 }
 
-fn choose_swapchain_format(available_foramts: &[SurfaceFormatKHR]) -> SurfaceFormatKHR {
+pub fn choose_swapchain_format(
+    available_foramts: &[SurfaceFormatKHR],
+    is_srgb: bool,
+    hdr: Option<HdrColorSpace>,
+) -> SurfaceFormatKHR {
+    if let Some(hdr) = hdr {
+        match find_hdr_format(available_foramts, hdr) {
+            Some(hdr_format) => return hdr_format,
+            None => warn!("Surface does not support {:?}, falling back to sRGB", hdr),
+        }
+    }
+
+    let preferred_format = SurfaceFormatKHR {
+        format: if is_srgb {
+            Format::B8G8R8A8_SRGB
+        } else {
+            Format::B8G8R8A8_UNORM
+        },
+        color_space: ColorSpaceKHR::SRGB_NONLINEAR,
+    };
+
+    if available_foramts.len() == 1 && available_foramts[0].format == Format::UNDEFINED {
+        return preferred_format;
+    }
+
     *available_foramts
         .iter()
         .find(|x| {
-            x.format == Format::B8G8R8A8_SRGB && x.color_space == ColorSpaceKHR::SRGB_NONLINEAR
+            x.format == preferred_format.format && x.color_space == preferred_format.color_space
         })
         .unwrap_or_else(|| available_foramts.first().unwrap())
 }
 
-fn choose_swapchain_present_mode(available_present_modes: &[PresentModeKHR]) -> PresentModeKHR {
+pub fn find_hdr_format(
+    available_foramts: &[SurfaceFormatKHR],
+    hdr: HdrColorSpace,
+) -> Option<SurfaceFormatKHR> {
+    hdr.candidates().iter().find_map(|(format, color_space)| {
+        available_foramts
+            .iter()
+            .find(|x| x.format == *format && x.color_space == *color_space)
+            .copied()
+    })
+}
+
+pub fn choose_swapchain_present_mode(
+    available_present_modes: &[PresentModeKHR],
+    preferred_present_mode: PresentModeKHR,
+) -> PresentModeKHR {
     available_present_modes
         .iter()
-        .find(|x| **x == PresentModeKHR::MAILBOX)
+        .find(|x| **x == preferred_present_mode)
         .unwrap_or(&PresentModeKHR::FIFO)
         .to_owned()
 }
 
-fn choose_swapchain_extent(capabilities: &SurfaceCapabilitiesKHR) -> Extent2D {
+pub fn choose_swapchain_image_usage(
+    capabilities: &SurfaceCapabilitiesKHR,
+    extra_usage: ImageUsageFlags,
+) -> Result<ImageUsageFlags, PotatoError> {
+    let supported = capabilities.supported_usage_flags;
+    if !supported.contains(extra_usage) {
+        return Err(PotatoError::UnsupportedSwapchainUsage(extra_usage & !supported));
+    }
+
+    let image_usage = ImageUsageFlags::COLOR_ATTACHMENT | extra_usage;
+    if supported.contains(ImageUsageFlags::TRANSFER_SRC) {
+        Ok(image_usage | ImageUsageFlags::TRANSFER_SRC)
+    } else {
+        Ok(image_usage)
+    }
+}
+
+pub fn choose_swapchain_image_count(capabilities: &SurfaceCapabilitiesKHR) -> u32 {
+    let image_count = capabilities.min_image_count + 1;
+    if capabilities.max_image_count > 0 {
+        image_count.min(capabilities.max_image_count)
+    } else {
+        image_count
+    }
+}
+
+pub fn choose_swapchain_extent(
+    capabilities: &SurfaceCapabilitiesKHR,
+    window_extent: Extent2D,
+) -> Extent2D {
     if capabilities.current_extent.width != u32::max_value() {
         capabilities.current_extent
     } else {
         Extent2D {
             width: clamp(
-                800,
+                window_extent.width,
                 capabilities.min_image_extent.width,
                 capabilities.max_image_extent.width,
             ),
             height: clamp(
-                600,
+                window_extent.height,
                 capabilities.min_image_extent.height,
                 capabilities.max_image_extent.height,
             ),
@@ -185,4 +347,232 @@ fn create_image_view(surface_format: Format, image: Image, device: &Device) -> I
     unsafe {
         device.create_image_view(&image_view_create_info, None).expect("Failed to create image view")
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::fake_device::{self, SURFACE_MAX_EXTENT, SURFACE_MIN_EXTENT};
+    use ash::extensions::khr::Surface;
+    use ash::vk::{Handle, SurfaceKHR};
+
+    #[test]
+    fn present_mode_prefers_requested_mode() {
+        let available = [PresentModeKHR::FIFO, PresentModeKHR::MAILBOX];
+        assert_eq!(
+            choose_swapchain_present_mode(&available, PresentModeKHR::MAILBOX),
+            PresentModeKHR::MAILBOX
+        );
+    }
+
+    #[test]
+    fn present_mode_falls_back_to_fifo() {
+        let available = [PresentModeKHR::FIFO, PresentModeKHR::IMMEDIATE];
+        assert_eq!(
+            choose_swapchain_present_mode(&available, PresentModeKHR::MAILBOX),
+            PresentModeKHR::FIFO
+        );
+    }
+
+    fn extent(width: u32, height: u32) -> Extent2D {
+        Extent2D { width, height }
+    }
+
+    fn bounded_capabilities(current_extent: Extent2D) -> SurfaceCapabilitiesKHR {
+        SurfaceCapabilitiesKHR {
+            current_extent,
+            min_image_extent: extent(100, 100),
+            max_image_extent: extent(1920, 1080),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn swapchain_extent_uses_current_extent_when_defined() {
+        let capabilities = bounded_capabilities(extent(800, 600));
+        assert_eq!(choose_swapchain_extent(&capabilities, extent(1024, 768)), extent(800, 600));
+    }
+
+    #[test]
+    fn swapchain_extent_clamps_window_when_undefined() {
+        let capabilities = bounded_capabilities(extent(u32::MAX, u32::MAX));
+        assert_eq!(choose_swapchain_extent(&capabilities, extent(1024, 768)), extent(1024, 768));
+        assert_eq!(choose_swapchain_extent(&capabilities, extent(50, 4000)), extent(100, 1080));
+        assert_eq!(choose_swapchain_extent(&capabilities, extent(4000, 10)), extent(1920, 100));
+    }
+
+    #[test]
+    fn created_swapchain_takes_the_window_size_within_the_surface_bounds() {
+        let instance = fake_device::instance();
+        let device = fake_device::device();
+        let surface = PotatoSurface {
+            surface_loader: Surface::new(&fake_device::entry(), &instance),
+            surface: SurfaceKHR::from_raw(1),
+        };
+        let queue_family = QueueFamily {
+            graphics_family: Some(0),
+            present_family: Some(0),
+            transfer_family: None,
+        };
+        let create = |window_extent| {
+            let swapchain = create_swapchain(
+                &instance,
+                &device,
+                PhysicalDevice::from_raw(1),
+                &surface,
+                &queue_family,
+                &SwapchainConfig::default(),
+                window_extent,
+            );
+            (swapchain.swapchain_extent, swapchain.swapchain_images.len())
+        };
+
+        assert_eq!(create(extent(1024, 768)), (extent(1024, 768), 3));
+        assert_eq!(
+            create(extent(4000, 10)).0,
+            extent(SURFACE_MAX_EXTENT.width, SURFACE_MIN_EXTENT.height)
+        );
+        assert_eq!(
+            create(extent(50, 4000)).0,
+            extent(SURFACE_MIN_EXTENT.width, SURFACE_MAX_EXTENT.height)
+        );
+    }
+
+    #[test]
+    fn image_count_requests_one_more_than_minimum() {
+        let capabilities = SurfaceCapabilitiesKHR {
+            min_image_count: 2,
+            max_image_count: 8,
+            ..Default::default()
+        };
+        assert_eq!(choose_swapchain_image_count(&capabilities), 3);
+    }
+
+    #[test]
+    fn image_count_is_clamped_to_maximum() {
+        let capabilities = SurfaceCapabilitiesKHR {
+            min_image_count: 3,
+            max_image_count: 3,
+            ..Default::default()
+        };
+        assert_eq!(choose_swapchain_image_count(&capabilities), 3);
+    }
+
+    #[test]
+    fn image_count_is_unbounded_when_maximum_is_zero() {
+        let capabilities = SurfaceCapabilitiesKHR {
+            min_image_count: 3,
+            max_image_count: 0,
+            ..Default::default()
+        };
+        assert_eq!(choose_swapchain_image_count(&capabilities), 4);
+    }
+
+    fn surface_format(format: Format, color_space: ColorSpaceKHR) -> SurfaceFormatKHR {
+        SurfaceFormatKHR { format, color_space }
+    }
+
+    #[test]
+    fn swapchain_format_prefers_srgb_nonlinear_bgra() {
+        let available = [
+            surface_format(Format::R8G8B8A8_UNORM, ColorSpaceKHR::SRGB_NONLINEAR),
+            surface_format(Format::B8G8R8A8_UNORM, ColorSpaceKHR::SRGB_NONLINEAR),
+            surface_format(Format::B8G8R8A8_SRGB, ColorSpaceKHR::SRGB_NONLINEAR),
+        ];
+        assert_eq!(
+            choose_swapchain_format(&available, true, None),
+            surface_format(Format::B8G8R8A8_SRGB, ColorSpaceKHR::SRGB_NONLINEAR)
+        );
+    }
+
+    #[test]
+    fn swapchain_format_falls_back_to_first() {
+        let available = [
+            surface_format(Format::R8G8B8A8_UNORM, ColorSpaceKHR::SRGB_NONLINEAR),
+            surface_format(Format::B8G8R8A8_SRGB, ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT),
+        ];
+        assert_eq!(choose_swapchain_format(&available, true, None), available[0]);
+    }
+
+    #[test]
+    fn swapchain_format_uses_unorm_when_linear_is_requested() {
+        let available = [
+            surface_format(Format::B8G8R8A8_SRGB, ColorSpaceKHR::SRGB_NONLINEAR),
+            surface_format(Format::B8G8R8A8_UNORM, ColorSpaceKHR::SRGB_NONLINEAR),
+        ];
+        assert_eq!(choose_swapchain_format(&available, false, None), available[1]);
+        let undefined = [surface_format(Format::UNDEFINED, ColorSpaceKHR::SRGB_NONLINEAR)];
+        assert_eq!(choose_swapchain_format(&undefined, false, None), available[1]);
+    }
+
+    #[test]
+    fn swapchain_format_prefers_available_hdr_format() {
+        let available = [
+            surface_format(Format::B8G8R8A8_SRGB, ColorSpaceKHR::SRGB_NONLINEAR),
+            surface_format(Format::A2R10G10B10_UNORM_PACK32, ColorSpaceKHR::HDR10_ST2084_EXT),
+            surface_format(Format::R16G16B16A16_SFLOAT, ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT),
+        ];
+        assert_eq!(
+            choose_swapchain_format(&available, true, Some(HdrColorSpace::Hdr10)),
+            available[1]
+        );
+        assert_eq!(
+            choose_swapchain_format(&available, true, Some(HdrColorSpace::ExtendedSrgbLinear)),
+            available[2]
+        );
+    }
+
+    #[test]
+    fn swapchain_format_falls_back_to_srgb_without_hdr_support() {
+        let available = [
+            surface_format(Format::B8G8R8A8_SRGB, ColorSpaceKHR::SRGB_NONLINEAR),
+            surface_format(Format::A2B10G10R10_UNORM_PACK32, ColorSpaceKHR::SRGB_NONLINEAR),
+        ];
+        assert_eq!(find_hdr_format(&available, HdrColorSpace::Hdr10), None);
+        assert_eq!(
+            choose_swapchain_format(&available, true, Some(HdrColorSpace::Hdr10)),
+            available[0]
+        );
+    }
+
+    #[test]
+    fn sharing_mode_is_exclusive_for_a_single_family() {
+        assert_eq!(choose_swapchain_sharing_mode(0, 0), (SharingMode::EXCLUSIVE, vec![]));
+    }
+
+    #[test]
+    fn sharing_mode_is_concurrent_across_families() {
+        assert_eq!(choose_swapchain_sharing_mode(0, 2), (SharingMode::CONCURRENT, vec![0, 2]));
+    }
+
+    #[test]
+    fn image_usage_adds_transfer_src_when_supported() {
+        let capabilities = SurfaceCapabilitiesKHR {
+            supported_usage_flags: ImageUsageFlags::COLOR_ATTACHMENT
+                | ImageUsageFlags::TRANSFER_SRC,
+            ..Default::default()
+        };
+        assert_eq!(
+            choose_swapchain_image_usage(&capabilities, ImageUsageFlags::empty()).unwrap(),
+            ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::TRANSFER_SRC
+        );
+    }
+
+    #[test]
+    fn image_usage_rejects_transfer_src_when_unsupported() {
+        let capabilities = SurfaceCapabilitiesKHR {
+            supported_usage_flags: ImageUsageFlags::COLOR_ATTACHMENT,
+            ..Default::default()
+        };
+        assert_eq!(
+            choose_swapchain_image_usage(&capabilities, ImageUsageFlags::empty()).unwrap(),
+            ImageUsageFlags::COLOR_ATTACHMENT
+        );
+        match choose_swapchain_image_usage(&capabilities, ImageUsageFlags::TRANSFER_SRC) {
+            Err(PotatoError::UnsupportedSwapchainUsage(usage)) => {
+                assert_eq!(usage, ImageUsageFlags::TRANSFER_SRC)
+            }
+            result => panic!("Expected an unsupported usage error, got {:?}", result),
+        }
+    }
+}
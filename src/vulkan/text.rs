@@ -0,0 +1,290 @@
+use super::buffer::{upload_host_visible, PotatoBuffer};
+use super::descriptor_allocator::DescriptorAllocator;
+use super::postprocess::{create_sampled_image_set_layout, write_sampled_image_descriptors};
+use super::shader::ShaderSource;
+use super::texture::Texture;
+use ash::version::DeviceV1_0;
+use ash::vk::{
+    BufferUsageFlags, CommandBuffer, DescriptorSet, DescriptorSetLayout, DescriptorType, Format,
+    IndexType, PhysicalDeviceMemoryProperties, Pipeline, PipelineBindPoint, PipelineLayout,
+    Sampler, VertexInputAttributeDescription, VertexInputBindingDescription, VertexInputRate,
+};
+use ash::Device;
+use std::rc::Rc;
+
+pub const TEXT_VERTEX_SHADER: ShaderSource = ShaderSource::File("src/shaders/spv/text-vert.spv");
+pub const TEXT_FRAGMENT_SHADER: ShaderSource = ShaderSource::File("src/shaders/spv/text-frag.spv");
+const GLYPH_INDICES: [u32; 6] = [0, 1, 2, 2, 3, 0];
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+}
+
+impl GlyphVertex {
+    pub fn get_binding_descriptions() -> [VertexInputBindingDescription; 1] {
+        [VertexInputBindingDescription {
+            binding: 0,
+            stride: std::mem::size_of::<Self>() as u32,
+            input_rate: VertexInputRate::VERTEX,
+        }]
+    }
+
+    pub fn get_attribute_descriptions() -> [VertexInputAttributeDescription; 2] {
+        [
+            VertexInputAttributeDescription {
+                binding: 0,
+                location: 0,
+                format: Format::R32G32_SFLOAT,
+                offset: 0,
+            },
+            VertexInputAttributeDescription {
+                binding: 0,
+                location: 1,
+                format: Format::R32G32_SFLOAT,
+                offset: std::mem::size_of::<[f32; 2]>() as u32,
+            },
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BitmapFont {
+    pub columns: u32,
+    pub rows: u32,
+    pub first_char: char,
+    pub fallback_char: char,
+    pub glyph_size: [f32; 2],
+}
+
+impl BitmapFont {
+    pub fn new(columns: u32, rows: u32, first_char: char, glyph_size: [f32; 2]) -> BitmapFont {
+        BitmapFont {
+            columns,
+            rows,
+            first_char,
+            fallback_char: '?',
+            glyph_size,
+        }
+    }
+
+    pub fn fallback_char(mut self, fallback_char: char) -> Self {
+        self.fallback_char = fallback_char;
+        self
+    }
+
+    pub fn glyph_count(&self) -> u32 {
+        self.columns * self.rows
+    }
+
+    pub fn glyph_index(&self, c: char) -> Option<u32> {
+        (c as u32)
+            .checked_sub(self.first_char as u32)
+            .filter(|x| *x < self.glyph_count())
+    }
+
+    pub fn glyph_uv(&self, c: char) -> ([f32; 2], [f32; 2]) {
+        let index = self
+            .glyph_index(c)
+            .or_else(|| self.glyph_index(self.fallback_char))
+            .unwrap_or(0);
+        let cell = [1.0 / self.columns as f32, 1.0 / self.rows as f32];
+        let min = [
+            (index % self.columns) as f32 * cell[0],
+            (index / self.columns) as f32 * cell[1],
+        ];
+        (min, [min[0] + cell[0], min[1] + cell[1]])
+    }
+}
+
+pub fn build_text_quads(
+    font: &BitmapFont,
+    text: &str,
+    x: f32,
+    y: f32,
+) -> (Vec<GlyphVertex>, Vec<u32>) {
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    let [width, height] = font.glyph_size;
+    let mut pen = [x, y];
+    for c in text.chars() {
+        if c == '\n' {
+            pen = [x, pen[1] + height];
+            continue;
+        }
+        let (uv_min, uv_max) = font.glyph_uv(c);
+        let base = vertices.len() as u32;
+        vertices.extend_from_slice(&[
+            GlyphVertex {
+                position: pen,
+                uv: uv_min,
+            },
+            GlyphVertex {
+                position: [pen[0] + width, pen[1]],
+                uv: [uv_max[0], uv_min[1]],
+            },
+            GlyphVertex {
+                position: [pen[0] + width, pen[1] + height],
+                uv: uv_max,
+            },
+            GlyphVertex {
+                position: [pen[0], pen[1] + height],
+                uv: [uv_min[0], uv_max[1]],
+            },
+        ]);
+        indices.extend(GLYPH_INDICES.iter().map(|i| base + i));
+        pen[0] += width;
+    }
+    (vertices, indices)
+}
+
+pub struct TextRenderer {
+    pub font: BitmapFont,
+    pub descriptor_set_layout: DescriptorSetLayout,
+    pub descriptor_set: DescriptorSet,
+    pub index_count: u32,
+    texture: Texture,
+    descriptor_allocator: DescriptorAllocator,
+    vertices: Vec<GlyphVertex>,
+    indices: Vec<u32>,
+    vertex_buffer: Option<PotatoBuffer>,
+    index_buffer: Option<PotatoBuffer>,
+}
+
+impl TextRenderer {
+    pub fn new(
+        device: &Device,
+        font: BitmapFont,
+        texture: Texture,
+        sampler: Sampler,
+    ) -> TextRenderer {
+        let descriptor_set_layout = create_sampled_image_set_layout(device);
+        let mut descriptor_allocator = DescriptorAllocator::new(
+            1,
+            &[
+                (DescriptorType::SAMPLED_IMAGE, 1),
+                (DescriptorType::SAMPLER, 1),
+            ],
+        );
+        let descriptor_set = descriptor_allocator
+            .allocate(device, descriptor_set_layout)
+            .expect("Failed to allocate text descriptor set");
+        write_sampled_image_descriptors(device, descriptor_set, texture.view, sampler);
+
+        TextRenderer {
+            font,
+            descriptor_set_layout,
+            descriptor_set,
+            index_count: 0,
+            texture,
+            descriptor_allocator,
+            vertices: vec![],
+            indices: vec![],
+            vertex_buffer: None,
+            index_buffer: None,
+        }
+    }
+
+    pub fn append(&mut self, text: &str, x: f32, y: f32) {
+        let (vertices, indices) = build_text_quads(&self.font, text, x, y);
+        let base = self.vertices.len() as u32;
+        self.vertices.extend(vertices);
+        self.indices.extend(indices.iter().map(|i| base + i));
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+        self.index_count = 0;
+    }
+
+    pub fn upload(
+        &mut self,
+        device: &Rc<Device>,
+        device_memory_properties: &PhysicalDeviceMemoryProperties,
+    ) {
+        self.index_count = self.indices.len() as u32;
+        if self.indices.is_empty() {
+            return;
+        }
+        upload_host_visible(
+            device,
+            device_memory_properties,
+            &mut self.vertex_buffer,
+            BufferUsageFlags::VERTEX_BUFFER,
+            &self.vertices,
+        );
+        upload_host_visible(
+            device,
+            device_memory_properties,
+            &mut self.index_buffer,
+            BufferUsageFlags::INDEX_BUFFER,
+            &self.indices,
+        );
+    }
+
+    pub fn record_draw(
+        &self,
+        device: &Device,
+        command_buffer: CommandBuffer,
+        (pipeline, pipeline_layout): (Pipeline, PipelineLayout),
+    ) {
+        let (vertex_buffer, index_buffer) = match (&self.vertex_buffer, &self.index_buffer) {
+            (Some(vertex_buffer), Some(index_buffer)) if self.index_count > 0 => {
+                (vertex_buffer, index_buffer)
+            }
+            _ => return,
+        };
+
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, PipelineBindPoint::GRAPHICS, pipeline);
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                PipelineBindPoint::GRAPHICS,
+                pipeline_layout,
+                0,
+                &[self.descriptor_set],
+                &[],
+            );
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[vertex_buffer.buffer], &[0]);
+            device.cmd_bind_index_buffer(command_buffer, index_buffer.buffer, 0, IndexType::UINT32);
+            device.cmd_draw_indexed(command_buffer, self.index_count, 1, 0, 0, 0);
+        }
+    }
+
+    pub fn destroy(mut self, device: &Device) {
+        self.vertex_buffer = None;
+        self.index_buffer = None;
+        unsafe {
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+        self.descriptor_allocator.destroy(device);
+        self.texture.destroy(device);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn font() -> BitmapFont {
+        BitmapFont::new(16, 6, ' ', [8.0, 16.0])
+    }
+
+    #[test]
+    fn text_quads_emit_one_quad_per_glyph() {
+        let (vertices, indices) = build_text_quads(&font(), "abc", 0.0, 0.0);
+        assert_eq!(vertices.len(), 3 * 4);
+        assert_eq!(indices.len(), 3 * 6);
+        assert_eq!(&indices[6..12], &[4, 5, 6, 6, 7, 4]);
+    }
+
+    #[test]
+    fn text_quads_advance_by_glyph_width() {
+        let (vertices, _) = build_text_quads(&font(), "ab\nc", 10.0, 20.0);
+        let origins: Vec<_> = vertices.chunks_exact(4).map(|x| x[0].position).collect();
+        assert_eq!(origins, vec![[10.0, 20.0], [18.0, 20.0], [10.0, 36.0]]);
+    }
+}
@@ -0,0 +1,785 @@
+use super::buffer::{find_memory_type, single_time_commands, StagingBuffer, UploadQueues};
+use super::error::PotatoError;
+use super::image_layout::{base_subresource_range, transition_image_layout};
+use crate::io::file::{read_file_to_bytes, read_png};
+use ash::version::{DeviceV1_0, InstanceV1_0};
+use ash::vk::{
+    BorderColor, BufferImageCopy, CompareOp, ComponentMapping, ComponentSwizzle,
+    DescriptorImageInfo, DescriptorSet, DescriptorSetLayout, DescriptorSetLayoutBinding,
+    DescriptorSetLayoutCreateFlags, DescriptorSetLayoutCreateInfo, DescriptorType, DeviceMemory,
+    DeviceSize, Extent3D, Filter, Format, FormatFeatureFlags, Image, ImageAspectFlags,
+    ImageCreateFlags, ImageCreateInfo, ImageLayout, ImageSubresourceLayers, ImageSubresourceRange,
+    ImageTiling, ImageType, ImageUsageFlags, ImageView, ImageViewCreateFlags, ImageViewCreateInfo,
+    ImageViewType, MemoryAllocateInfo, MemoryPropertyFlags, Offset3D, PhysicalDevice,
+    PhysicalDeviceFeatures, PhysicalDeviceLimits, PhysicalDeviceMemoryProperties,
+    SampleCountFlags, Sampler, SamplerAddressMode, SamplerCreateFlags, SamplerCreateInfo,
+    SamplerMipmapMode, ShaderStageFlags, SharingMode, StructureType, WriteDescriptorSet, FALSE,
+    LOD_CLAMP_NONE, TRUE,
+};
+use ash::{Device, Instance};
+use log::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SamplerPreset {
+    Nearest,
+    Linear,
+    Anisotropic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerSettings {
+    pub mag_filter: Filter,
+    pub min_filter: Filter,
+    pub mipmap_mode: SamplerMipmapMode,
+    pub address_mode: SamplerAddressMode,
+    pub max_lod: f32,
+    pub is_anisotropic: bool,
+}
+
+impl SamplerPreset {
+    pub fn settings(self) -> SamplerSettings {
+        match self {
+            SamplerPreset::Nearest => SamplerSettings {
+                mag_filter: Filter::NEAREST,
+                min_filter: Filter::NEAREST,
+                mipmap_mode: SamplerMipmapMode::NEAREST,
+                address_mode: SamplerAddressMode::CLAMP_TO_EDGE,
+                max_lod: 0.0,
+                is_anisotropic: false,
+            },
+            SamplerPreset::Linear => SamplerSettings {
+                mag_filter: Filter::LINEAR,
+                min_filter: Filter::LINEAR,
+                mipmap_mode: SamplerMipmapMode::LINEAR,
+                address_mode: SamplerAddressMode::REPEAT,
+                max_lod: LOD_CLAMP_NONE,
+                is_anisotropic: false,
+            },
+            SamplerPreset::Anisotropic => SamplerSettings {
+                is_anisotropic: true,
+                ..SamplerPreset::Linear.settings()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerOptions {
+    pub max_anisotropy: Option<f32>,
+    pub mip_lod_bias: f32,
+    pub min_lod: f32,
+    pub max_lod: Option<f32>,
+}
+
+impl Default for SamplerOptions {
+    fn default() -> Self {
+        SamplerOptions {
+            max_anisotropy: None,
+            mip_lod_bias: 0.0,
+            min_lod: 0.0,
+            max_lod: None,
+        }
+    }
+}
+
+impl SamplerOptions {
+    pub fn new() -> SamplerOptions {
+        SamplerOptions::default()
+    }
+
+    pub fn max_anisotropy(mut self, max_anisotropy: f32) -> Self {
+        self.max_anisotropy = Some(max_anisotropy);
+        self
+    }
+
+    pub fn mip_lod_bias(mut self, mip_lod_bias: f32) -> Self {
+        self.mip_lod_bias = mip_lod_bias;
+        self
+    }
+
+    pub fn lod_range(mut self, min_lod: f32, max_lod: f32) -> Self {
+        self.min_lod = min_lod;
+        self.max_lod = Some(max_lod);
+        self
+    }
+
+    pub fn clamp_to_limits(self, limits: &PhysicalDeviceLimits) -> SamplerOptions {
+        let min_lod = self.min_lod.max(0.0);
+        SamplerOptions {
+            max_anisotropy: self
+                .max_anisotropy
+                .map(|x| clamp_anisotropy(x, limits.max_sampler_anisotropy)),
+            mip_lod_bias: self
+                .mip_lod_bias
+                .max(-limits.max_sampler_lod_bias)
+                .min(limits.max_sampler_lod_bias),
+            min_lod,
+            max_lod: self.max_lod.map(|x| x.max(min_lod)),
+        }
+    }
+}
+
+// Material sets sit at set 1 next to the frame set, whose uniform buffer keeps binding 0.
+pub const MATERIAL_TEXTURE_BINDING: u32 = 1;
+
+pub fn create_material_set_layout(device: &Device) -> DescriptorSetLayout {
+    let material_layout_bindings = [DescriptorSetLayoutBinding {
+        binding: MATERIAL_TEXTURE_BINDING,
+        descriptor_type: DescriptorType::COMBINED_IMAGE_SAMPLER,
+        descriptor_count: 1,
+        stage_flags: ShaderStageFlags::FRAGMENT,
+        p_immutable_samplers: std::ptr::null(),
+    }];
+
+    let material_layout_create_info = DescriptorSetLayoutCreateInfo {
+        s_type: StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+        p_next: std::ptr::null(),
+        flags: DescriptorSetLayoutCreateFlags::empty(),
+        binding_count: material_layout_bindings.len() as u32,
+        p_bindings: material_layout_bindings.as_ptr(),
+    };
+
+    unsafe {
+        device
+            .create_descriptor_set_layout(&material_layout_create_info, None)
+            .expect("Failed to create material descriptor set layout")
+    }
+}
+
+pub fn create_texture_descriptor_write(
+    descriptor_set: DescriptorSet,
+    binding: u32,
+    image_info: &[DescriptorImageInfo],
+) -> WriteDescriptorSet {
+    WriteDescriptorSet {
+        s_type: StructureType::WRITE_DESCRIPTOR_SET,
+        p_next: std::ptr::null(),
+        dst_set: descriptor_set,
+        dst_binding: binding,
+        dst_array_element: 0,
+        descriptor_count: image_info.len() as u32,
+        descriptor_type: DescriptorType::COMBINED_IMAGE_SAMPLER,
+        p_image_info: image_info.as_ptr(),
+        p_buffer_info: std::ptr::null(),
+        p_texel_buffer_view: std::ptr::null(),
+    }
+}
+
+pub fn write_texture_descriptor(
+    device: &Device,
+    descriptor_set: DescriptorSet,
+    binding: u32,
+    image_view: ImageView,
+    sampler: Sampler,
+) {
+    let image_info = [DescriptorImageInfo {
+        sampler,
+        image_view,
+        image_layout: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    }];
+    let descriptor_write_sets = [create_texture_descriptor_write(
+        descriptor_set,
+        binding,
+        &image_info,
+    )];
+    unsafe {
+        device.update_descriptor_sets(&descriptor_write_sets, &[]);
+    }
+}
+
+pub fn create_texture_sampler(
+    device: &Device,
+    enabled_features: &PhysicalDeviceFeatures,
+    limits: &PhysicalDeviceLimits,
+    preset: SamplerPreset,
+    options: &SamplerOptions,
+) -> Sampler {
+    let settings = preset.settings();
+    let options = options.clamp_to_limits(limits);
+    let max_anisotropy = find_max_anisotropy(
+        settings.is_anisotropic && enabled_features.sampler_anisotropy == TRUE,
+        options.max_anisotropy,
+        limits.max_sampler_anisotropy,
+    );
+
+    let sampler_create_info = SamplerCreateInfo {
+        s_type: StructureType::SAMPLER_CREATE_INFO,
+        p_next: std::ptr::null(),
+        flags: SamplerCreateFlags::empty(),
+        mag_filter: settings.mag_filter,
+        min_filter: settings.min_filter,
+        mipmap_mode: settings.mipmap_mode,
+        address_mode_u: settings.address_mode,
+        address_mode_v: settings.address_mode,
+        address_mode_w: settings.address_mode,
+        mip_lod_bias: options.mip_lod_bias,
+        anisotropy_enable: if max_anisotropy.is_some() { TRUE } else { FALSE },
+        max_anisotropy: max_anisotropy.unwrap_or(1.0),
+        compare_enable: FALSE,
+        compare_op: CompareOp::ALWAYS,
+        min_lod: options.min_lod,
+        max_lod: options.max_lod.unwrap_or(settings.max_lod).max(options.min_lod),
+        border_color: BorderColor::INT_OPAQUE_BLACK,
+        unnormalized_coordinates: FALSE,
+    };
+
+    unsafe {
+        device
+            .create_sampler(&sampler_create_info, None)
+            .expect("Failed to create texture sampler")
+    }
+}
+
+pub fn clamp_anisotropy(requested: f32, device_max: f32) -> f32 {
+    requested.max(1.0).min(device_max)
+}
+
+// None means anisotropic filtering stays disabled.
+pub fn find_max_anisotropy(
+    is_supported: bool,
+    requested: Option<f32>,
+    device_max: f32,
+) -> Option<f32> {
+    if is_supported {
+        Some(clamp_anisotropy(requested.unwrap_or(device_max), device_max))
+    } else {
+        None
+    }
+}
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const KTX2_LEVEL_INDEX_OFFSET: usize = 80;
+const DDS_MAGIC: [u8; 4] = *b"DDS ";
+const DDS_HEADER_END: usize = 128;
+const DDS_DX10_HEADER_END: usize = 148;
+
+pub struct MipLevel {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+pub struct TextureData {
+    pub format: Format,
+    pub levels: Vec<MipLevel>,
+}
+
+pub struct Texture {
+    pub image: Image,
+    pub memory: DeviceMemory,
+    pub view: ImageView,
+    pub format: Format,
+    pub mip_levels: u32,
+}
+
+impl Texture {
+    pub fn destroy(&self, device: &Device) {
+        unsafe {
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+            device.free_memory(self.memory, None);
+        }
+    }
+}
+
+pub fn block_size(format: Format) -> (u32, u32, u32) {
+    match format {
+        Format::BC1_RGB_UNORM_BLOCK
+        | Format::BC1_RGB_SRGB_BLOCK
+        | Format::BC1_RGBA_UNORM_BLOCK
+        | Format::BC1_RGBA_SRGB_BLOCK
+        | Format::BC4_UNORM_BLOCK
+        | Format::BC4_SNORM_BLOCK => (4, 4, 8),
+        Format::BC2_UNORM_BLOCK
+        | Format::BC2_SRGB_BLOCK
+        | Format::BC3_UNORM_BLOCK
+        | Format::BC3_SRGB_BLOCK
+        | Format::BC5_UNORM_BLOCK
+        | Format::BC5_SNORM_BLOCK
+        | Format::BC6H_UFLOAT_BLOCK
+        | Format::BC6H_SFLOAT_BLOCK
+        | Format::BC7_UNORM_BLOCK
+        | Format::BC7_SRGB_BLOCK
+        | Format::ASTC_4X4_UNORM_BLOCK
+        | Format::ASTC_4X4_SRGB_BLOCK => (4, 4, 16),
+        Format::ASTC_6X6_UNORM_BLOCK | Format::ASTC_6X6_SRGB_BLOCK => (6, 6, 16),
+        Format::ASTC_8X8_UNORM_BLOCK | Format::ASTC_8X8_SRGB_BLOCK => (8, 8, 16),
+        _ => (1, 1, 4),
+    }
+}
+
+pub fn mip_level_size(format: Format, width: u32, height: u32) -> DeviceSize {
+    let (block_width, block_height, block_bytes) = block_size(format);
+    let blocks_wide = width.max(1).div_ceil(block_width) as DeviceSize;
+    let blocks_high = height.max(1).div_ceil(block_height) as DeviceSize;
+    blocks_wide * blocks_high * block_bytes as DeviceSize
+}
+
+pub fn is_format_sampleable(
+    instance: &Instance,
+    physical_device: PhysicalDevice,
+    format: Format,
+) -> bool {
+    let format_properties =
+        unsafe { instance.get_physical_device_format_properties(physical_device, format) };
+    format_properties
+        .optimal_tiling_features
+        .contains(FormatFeatureFlags::SAMPLED_IMAGE)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, PotatoError> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|x| u32::from_le_bytes([x[0], x[1], x[2], x[3]]))
+        .ok_or_else(|| PotatoError::InvalidTexture(format!("truncated at byte {}", offset)))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, PotatoError> {
+    Ok(read_u32(bytes, offset)? as u64 | (read_u32(bytes, offset + 4)? as u64) << 32)
+}
+
+fn read_level(bytes: &[u8], offset: usize, size: usize) -> Result<Vec<u8>, PotatoError> {
+    bytes
+        .get(offset..offset + size)
+        .map(|x| x.to_vec())
+        .ok_or_else(|| PotatoError::InvalidTexture(format!("truncated at byte {}", offset)))
+}
+
+pub fn parse_ktx2(bytes: &[u8]) -> Result<TextureData, PotatoError> {
+    if !bytes.starts_with(&KTX2_IDENTIFIER) {
+        return Err(PotatoError::InvalidTexture("missing KTX2 identifier".to_string()));
+    }
+    let format = Format::from_raw(read_u32(bytes, 12)? as i32);
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?;
+    let depth = read_u32(bytes, 28)?;
+    let layer_count = read_u32(bytes, 32)?;
+    let face_count = read_u32(bytes, 36)?;
+    let level_count = read_u32(bytes, 40)?.max(1);
+    let supercompression_scheme = read_u32(bytes, 44)?;
+    if depth > 1 || layer_count > 1 || face_count != 1 {
+        return Err(PotatoError::InvalidTexture(
+            "only single 2D KTX2 images are supported".to_string(),
+        ));
+    }
+    if supercompression_scheme != 0 {
+        return Err(PotatoError::InvalidTexture(
+            "supercompressed KTX2 files are not supported".to_string(),
+        ));
+    }
+
+    let levels = (0..level_count)
+        .map(|level| {
+            let index_offset = KTX2_LEVEL_INDEX_OFFSET + level as usize * 24;
+            let byte_offset = read_u64(bytes, index_offset)? as usize;
+            let byte_length = read_u64(bytes, index_offset + 8)? as usize;
+            Ok(MipLevel {
+                width: (width >> level).max(1),
+                height: (height >> level).max(1),
+                data: read_level(bytes, byte_offset, byte_length)?,
+            })
+        })
+        .collect::<Result<Vec<MipLevel>, PotatoError>>()?;
+
+    Ok(TextureData { format, levels })
+}
+
+pub fn parse_dds(bytes: &[u8]) -> Result<TextureData, PotatoError> {
+    if !bytes.starts_with(&DDS_MAGIC) {
+        return Err(PotatoError::InvalidTexture("missing DDS magic".to_string()));
+    }
+    let height = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 16)?;
+    let level_count = read_u32(bytes, 28)?.max(1);
+    let four_cc = read_level(bytes, 84, 4)?;
+    let (format, data_offset) = match four_cc.as_slice() {
+        b"DXT1" => (Format::BC1_RGBA_UNORM_BLOCK, DDS_HEADER_END),
+        b"DXT3" => (Format::BC2_UNORM_BLOCK, DDS_HEADER_END),
+        b"DXT5" => (Format::BC3_UNORM_BLOCK, DDS_HEADER_END),
+        b"ATI2" | b"BC5U" => (Format::BC5_UNORM_BLOCK, DDS_HEADER_END),
+        b"DX10" => (find_dxgi_format(read_u32(bytes, 128)?)?, DDS_DX10_HEADER_END),
+        _ => {
+            return Err(PotatoError::InvalidTexture(format!(
+                "unsupported DDS FourCC {:?}",
+                String::from_utf8_lossy(&four_cc)
+            )))
+        }
+    };
+
+    let mut offset = data_offset;
+    let levels = (0..level_count)
+        .map(|level| {
+            let level_width = (width >> level).max(1);
+            let level_height = (height >> level).max(1);
+            let size = mip_level_size(format, level_width, level_height) as usize;
+            let data = read_level(bytes, offset, size)?;
+            offset += size;
+            Ok(MipLevel {
+                width: level_width,
+                height: level_height,
+                data,
+            })
+        })
+        .collect::<Result<Vec<MipLevel>, PotatoError>>()?;
+
+    Ok(TextureData { format, levels })
+}
+
+fn find_dxgi_format(dxgi_format: u32) -> Result<Format, PotatoError> {
+    match dxgi_format {
+        71 => Ok(Format::BC1_RGBA_UNORM_BLOCK),
+        72 => Ok(Format::BC1_RGBA_SRGB_BLOCK),
+        74 => Ok(Format::BC2_UNORM_BLOCK),
+        75 => Ok(Format::BC2_SRGB_BLOCK),
+        77 => Ok(Format::BC3_UNORM_BLOCK),
+        78 => Ok(Format::BC3_SRGB_BLOCK),
+        80 => Ok(Format::BC4_UNORM_BLOCK),
+        83 => Ok(Format::BC5_UNORM_BLOCK),
+        95 => Ok(Format::BC6H_UFLOAT_BLOCK),
+        96 => Ok(Format::BC6H_SFLOAT_BLOCK),
+        98 => Ok(Format::BC7_UNORM_BLOCK),
+        99 => Ok(Format::BC7_SRGB_BLOCK),
+        _ => Err(PotatoError::InvalidTexture(format!(
+            "unsupported DXGI format {}",
+            dxgi_format
+        ))),
+    }
+}
+
+pub fn read_compressed_texture(path: &str) -> Result<TextureData, PotatoError> {
+    let bytes =
+        read_file_to_bytes(path).map_err(|e| PotatoError::TextureRead(path.to_string(), e))?;
+    if bytes.starts_with(&DDS_MAGIC) {
+        parse_dds(&bytes)
+    } else {
+        parse_ktx2(&bytes)
+    }
+}
+
+pub fn read_png_texture(path: &str) -> Result<TextureData, PotatoError> {
+    let (width, height, rgba) =
+        read_png(path).map_err(|e| PotatoError::TextureRead(path.to_string(), e))?;
+    Ok(TextureData {
+        format: Format::R8G8B8A8_SRGB,
+        levels: vec![MipLevel {
+            width,
+            height,
+            data: rgba,
+        }],
+    })
+}
+
+//TODO Reduce number of arguments
+pub fn load_texture(
+    instance: &Instance,
+    device: &Device,
+    physical_device: PhysicalDevice,
+    upload_queues: &UploadQueues,
+    staging_buffer: &mut StagingBuffer,
+    path: &str,
+    fallback_png_path: &str,
+) -> Result<Texture, PotatoError> {
+    let compressed = read_compressed_texture(path)?;
+    let texture_data = if is_format_sampleable(instance, physical_device, compressed.format) {
+        compressed
+    } else {
+        warn!(
+            "{:?} is not sampleable on this device, falling back to {}",
+            compressed.format, fallback_png_path
+        );
+        read_png_texture(fallback_png_path)?
+    };
+
+    let device_memory_properties =
+        unsafe { instance.get_physical_device_memory_properties(physical_device) };
+    Ok(create_texture(
+        device,
+        &device_memory_properties,
+        upload_queues,
+        staging_buffer,
+        &texture_data,
+    ))
+}
+
+pub fn create_texture(
+    device: &Device,
+    device_memory_properties: &PhysicalDeviceMemoryProperties,
+    upload_queues: &UploadQueues,
+    staging_buffer: &mut StagingBuffer,
+    texture_data: &TextureData,
+) -> Texture {
+    let format = texture_data.format;
+    let mip_levels = texture_data.levels.len() as u32;
+    let base_level = &texture_data.levels[0];
+
+    let image_create_info = ImageCreateInfo {
+        s_type: StructureType::IMAGE_CREATE_INFO,
+        p_next: std::ptr::null(),
+        flags: ImageCreateFlags::empty(),
+        image_type: ImageType::TYPE_2D,
+        format,
+        extent: Extent3D {
+            width: base_level.width,
+            height: base_level.height,
+            depth: 1,
+        },
+        mip_levels,
+        array_layers: 1,
+        samples: SampleCountFlags::TYPE_1,
+        tiling: ImageTiling::OPTIMAL,
+        usage: ImageUsageFlags::TRANSFER_DST | ImageUsageFlags::SAMPLED,
+        sharing_mode: SharingMode::EXCLUSIVE,
+        queue_family_index_count: 0,
+        p_queue_family_indices: std::ptr::null(),
+        initial_layout: ImageLayout::UNDEFINED,
+    };
+
+    let image = unsafe {
+        device
+            .create_image(&image_create_info, None)
+            .expect("Failed to create texture image")
+    };
+
+    let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
+    let allocate_info = MemoryAllocateInfo {
+        s_type: StructureType::MEMORY_ALLOCATE_INFO,
+        p_next: std::ptr::null(),
+        allocation_size: mem_requirements.size,
+        memory_type_index: find_memory_type(
+            mem_requirements.memory_type_bits,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+            MemoryPropertyFlags::empty(),
+            device_memory_properties,
+        )
+        .expect("Failed to find texture image memory type"),
+    };
+
+    let memory = unsafe {
+        device
+            .allocate_memory(&allocate_info, None)
+            .expect("Failed to allocate texture image memory")
+    };
+
+    unsafe {
+        device
+            .bind_image_memory(image, memory, 0)
+            .expect("Failed to bind texture image memory");
+    }
+
+    let mut pixels = vec![];
+    let mut copy_regions = vec![];
+    texture_data.levels.iter().enumerate().for_each(|(i, x)| {
+        copy_regions.push(BufferImageCopy {
+            buffer_offset: pixels.len() as DeviceSize,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: ImageSubresourceLayers {
+                aspect_mask: ImageAspectFlags::COLOR,
+                mip_level: i as u32,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: Offset3D { x: 0, y: 0, z: 0 },
+            image_extent: Extent3D {
+                width: x.width,
+                height: x.height,
+                depth: 1,
+            },
+        });
+        pixels.extend_from_slice(&x.data);
+    });
+    staging_buffer.upload(device, device_memory_properties, &pixels);
+
+    let mip_range = ImageSubresourceRange {
+        level_count: mip_levels,
+        ..base_subresource_range(format)
+    };
+    single_time_commands(
+        device,
+        upload_queues.graphics_command_pool,
+        upload_queues.graphics_queue,
+        |command_buffer| {
+            transition_image_layout(
+                device,
+                command_buffer,
+                image,
+                mip_range,
+                ImageLayout::UNDEFINED,
+                ImageLayout::TRANSFER_DST_OPTIMAL,
+            )
+            .expect("Failed to transition texture for upload");
+            unsafe {
+                device.cmd_copy_buffer_to_image(
+                    command_buffer,
+                    staging_buffer.buffer,
+                    image,
+                    ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &copy_regions,
+                );
+            }
+            transition_image_layout(
+                device,
+                command_buffer,
+                image,
+                mip_range,
+                ImageLayout::TRANSFER_DST_OPTIMAL,
+                ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            )
+            .expect("Failed to transition texture for sampling");
+        },
+    );
+
+    let image_view_create_info = ImageViewCreateInfo {
+        s_type: StructureType::IMAGE_VIEW_CREATE_INFO,
+        p_next: std::ptr::null(),
+        flags: ImageViewCreateFlags::empty(),
+        view_type: ImageViewType::TYPE_2D,
+        format,
+        components: ComponentMapping {
+            r: ComponentSwizzle::IDENTITY,
+            g: ComponentSwizzle::IDENTITY,
+            b: ComponentSwizzle::IDENTITY,
+            a: ComponentSwizzle::IDENTITY,
+        },
+        subresource_range: ImageSubresourceRange {
+            aspect_mask: ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: mip_levels,
+            base_array_layer: 0,
+            layer_count: 1,
+        },
+        image,
+    };
+
+    let view = unsafe {
+        device
+            .create_image_view(&image_view_create_info, None)
+            .expect("Failed to create texture image view")
+    };
+
+    Texture {
+        image,
+        memory,
+        view,
+        format,
+        mip_levels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anisotropy_is_disabled_when_unsupported() {
+        assert_eq!(find_max_anisotropy(false, Some(8.0), 16.0), None);
+        assert_eq!(find_max_anisotropy(false, None, 16.0), None);
+    }
+
+    #[test]
+    fn anisotropy_is_clamped_to_device_max() {
+        assert_eq!(find_max_anisotropy(true, Some(32.0), 16.0), Some(16.0));
+        assert_eq!(find_max_anisotropy(true, Some(4.0), 16.0), Some(4.0));
+        assert_eq!(find_max_anisotropy(true, None, 16.0), Some(16.0));
+        assert_eq!(clamp_anisotropy(0.5, 16.0), 1.0);
+    }
+
+    #[test]
+    fn mip_level_size_of_uncompressed_format() {
+        assert_eq!(mip_level_size(Format::R8G8B8A8_SRGB, 5, 3), 5 * 3 * 4);
+        assert_eq!(mip_level_size(Format::R8G8B8A8_SRGB, 0, 0), 4);
+    }
+
+    #[test]
+    fn bc7_rounds_partial_blocks_up() {
+        assert_eq!(block_size(Format::BC7_SRGB_BLOCK), (4, 4, 16));
+        assert_eq!(mip_level_size(Format::BC7_SRGB_BLOCK, 4, 4), 16);
+        assert_eq!(mip_level_size(Format::BC7_SRGB_BLOCK, 5, 3), 2 * 16);
+        assert_eq!(mip_level_size(Format::BC7_SRGB_BLOCK, 13, 9), 4 * 3 * 16);
+        assert_eq!(mip_level_size(Format::BC7_SRGB_BLOCK, 1, 1), 16);
+    }
+
+    #[test]
+    fn nearest_preset_samples_base_level_only() {
+        let settings = SamplerPreset::Nearest.settings();
+        assert_eq!(settings.mag_filter, Filter::NEAREST);
+        assert_eq!(settings.min_filter, Filter::NEAREST);
+        assert_eq!(settings.address_mode, SamplerAddressMode::CLAMP_TO_EDGE);
+        assert_eq!(settings.max_lod, 0.0);
+        assert!(!settings.is_anisotropic);
+    }
+
+    #[test]
+    fn anisotropic_preset_extends_linear_preset() {
+        let linear = SamplerPreset::Linear.settings();
+        assert_eq!(linear.mipmap_mode, SamplerMipmapMode::LINEAR);
+        assert_eq!(linear.max_lod, LOD_CLAMP_NONE);
+        assert!(!linear.is_anisotropic);
+        assert_eq!(
+            SamplerPreset::Anisotropic.settings(),
+            SamplerSettings {
+                is_anisotropic: true,
+                ..linear
+            }
+        );
+    }
+
+    #[test]
+    fn texture_descriptor_write_targets_material_binding() {
+        let image_info = [DescriptorImageInfo {
+            sampler: Sampler::null(),
+            image_view: ImageView::null(),
+            image_layout: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        }];
+        let descriptor_set = DescriptorSet::null();
+        let write =
+            create_texture_descriptor_write(descriptor_set, MATERIAL_TEXTURE_BINDING, &image_info);
+        assert_eq!(write.dst_set, descriptor_set);
+        assert_eq!(write.dst_binding, MATERIAL_TEXTURE_BINDING);
+        assert_eq!(write.dst_array_element, 0);
+        assert_eq!(write.descriptor_count, 1);
+        assert_eq!(write.descriptor_type, DescriptorType::COMBINED_IMAGE_SAMPLER);
+        assert_eq!(write.p_image_info, image_info.as_ptr());
+        assert!(write.p_buffer_info.is_null());
+    }
+
+    #[test]
+    fn sampler_options_clamp_to_device_limits() {
+        let limits = PhysicalDeviceLimits {
+            max_sampler_anisotropy: 16.0,
+            max_sampler_lod_bias: 4.0,
+            ..Default::default()
+        };
+        let options = SamplerOptions::new()
+            .max_anisotropy(64.0)
+            .mip_lod_bias(15.0)
+            .lod_range(2.0, 10.0)
+            .clamp_to_limits(&limits);
+        assert_eq!(options.max_anisotropy, Some(16.0));
+        assert_eq!(options.mip_lod_bias, 4.0);
+        assert_eq!(options.min_lod, 2.0);
+        assert_eq!(options.max_lod, Some(10.0));
+
+        let options = SamplerOptions::new()
+            .mip_lod_bias(-15.0)
+            .clamp_to_limits(&limits);
+        assert_eq!(options.mip_lod_bias, -4.0);
+    }
+
+    #[test]
+    fn sampler_options_raise_max_lod_to_min_lod() {
+        let options = SamplerOptions::new()
+            .lod_range(3.0, 1.0)
+            .clamp_to_limits(&PhysicalDeviceLimits::default());
+        assert_eq!(options.min_lod, 3.0);
+        assert_eq!(options.max_lod, Some(3.0));
+
+        let options = SamplerOptions::new()
+            .lod_range(-2.0, -1.0)
+            .clamp_to_limits(&PhysicalDeviceLimits::default());
+        assert_eq!(options.min_lod, 0.0);
+        assert_eq!(options.max_lod, Some(0.0));
+    }
+}
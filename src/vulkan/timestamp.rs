@@ -0,0 +1,137 @@
+use ash::version::DeviceV1_0;
+use ash::vk::{
+    CommandBuffer, PipelineStageFlags, QueryPipelineStatisticFlags, QueryPool,
+    QueryPoolCreateFlags, QueryPoolCreateInfo, QueryResultFlags, QueryType, StructureType,
+};
+use ash::Device;
+
+pub struct GpuTimer {
+    pub query_pool: QueryPool,
+    timestamp_period: f32,
+    valid_bits: u32,
+    is_written: Vec<bool>,
+}
+
+impl GpuTimer {
+    pub fn new(
+        device: &Device,
+        frame_count: usize,
+        timestamp_period: f32,
+        valid_bits: u32,
+    ) -> Option<GpuTimer> {
+        if valid_bits == 0 || timestamp_period <= 0.0 {
+            return None;
+        }
+
+        let query_pool_create_info = QueryPoolCreateInfo {
+            s_type: StructureType::QUERY_POOL_CREATE_INFO,
+            p_next: std::ptr::null(),
+            flags: QueryPoolCreateFlags::empty(),
+            query_type: QueryType::TIMESTAMP,
+            query_count: frame_count as u32 * 2,
+            pipeline_statistics: QueryPipelineStatisticFlags::empty(),
+        };
+
+        let query_pool = unsafe {
+            device
+                .create_query_pool(&query_pool_create_info, None)
+                .expect("Failed to create timestamp query pool")
+        };
+
+        Some(GpuTimer {
+            query_pool,
+            timestamp_period,
+            valid_bits,
+            is_written: vec![false; frame_count],
+        })
+    }
+
+    pub fn record_start(&self, device: &Device, command_buffer: CommandBuffer, index: usize) {
+        unsafe {
+            device.cmd_reset_query_pool(command_buffer, self.query_pool, index as u32 * 2, 2);
+            device.cmd_write_timestamp(
+                command_buffer,
+                PipelineStageFlags::TOP_OF_PIPE,
+                self.query_pool,
+                index as u32 * 2,
+            );
+        }
+    }
+
+    pub fn record_end(&self, device: &Device, command_buffer: CommandBuffer, index: usize) {
+        unsafe {
+            device.cmd_write_timestamp(
+                command_buffer,
+                PipelineStageFlags::BOTTOM_OF_PIPE,
+                self.query_pool,
+                index as u32 * 2 + 1,
+            );
+        }
+    }
+
+    pub fn mark_submitted(&mut self, index: usize) {
+        self.is_written[index] = true;
+    }
+
+    pub fn read_ms(&self, device: &Device, index: usize) -> Option<f32> {
+        if !self.is_written[index] {
+            return None;
+        }
+
+        let mut timestamps = [0_u64; 2];
+        let result = unsafe {
+            device.get_query_pool_results(
+                self.query_pool,
+                index as u32 * 2,
+                2,
+                &mut timestamps,
+                QueryResultFlags::TYPE_64,
+            )
+        };
+
+        match result {
+            Ok(_) => Some(ticks_to_ms(
+                timestamps[0],
+                timestamps[1],
+                self.timestamp_period,
+                self.valid_bits,
+            )),
+            Err(_) => None,
+        }
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        unsafe {
+            device.destroy_query_pool(self.query_pool, None);
+        }
+    }
+}
+
+pub fn ticks_to_ms(start: u64, end: u64, timestamp_period: f32, valid_bits: u32) -> f32 {
+    let mask = if valid_bits >= 64 {
+        u64::MAX
+    } else {
+        (1_u64 << valid_bits) - 1
+    };
+    let ticks = end.wrapping_sub(start) & mask;
+
+    (ticks as f64 * timestamp_period as f64 / 1_000_000.0) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_are_scaled_by_the_timestamp_period() {
+        assert_eq!(ticks_to_ms(0, 1_000_000, 1.0, 64), 1.0);
+        assert_eq!(ticks_to_ms(500, 2_000_500, 2.5, 64), 5.0);
+        assert!((ticks_to_ms(0, 192_000, 52.083, 64) - 10.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn ticks_wrap_within_the_valid_bits() {
+        let max = (1_u64 << 36) - 1;
+        assert_eq!(ticks_to_ms(max - 499_999, 500_000, 1.0, 36), 1.0);
+    }
+}
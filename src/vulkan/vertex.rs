@@ -1,21 +1,92 @@
-use super::buffer::{copy_buffer, create_buffer};
-use super::constants::{VERTICES_DATA,INDICES_DATA};
-use ash::version::DeviceV1_0;
-use ash::version::InstanceV1_0;
+use super::buffer::BufferUploader;
 use ash::vk::{
-    Buffer, BufferUsageFlags, CommandPool, DeviceMemory, DeviceSize, Format, MemoryMapFlags,
-    MemoryPropertyFlags, PhysicalDevice, Queue, VertexInputAttributeDescription,
+    Buffer, BufferUsageFlags, DeviceMemory, DeviceSize, Format, VertexInputAttributeDescription,
     VertexInputBindingDescription, VertexInputRate,
 };
-use ash::Device;
-use ash::Instance;
+use cgmath::{InnerSpace, Vector3};
 use memoffset::offset_of;
 
+#[derive(Debug, Clone)]
+pub struct VertexLayout {
+    pub binding: u32,
+    pub input_rate: VertexInputRate,
+    pub stride: u32,
+    attributes: Vec<VertexInputAttributeDescription>,
+}
+
+impl VertexLayout {
+    pub fn new() -> VertexLayout {
+        VertexLayout {
+            binding: 0,
+            input_rate: VertexInputRate::VERTEX,
+            stride: 0,
+            attributes: vec![],
+        }
+    }
+
+    pub fn attribute(self, location: u32, format: Format) -> Self {
+        let offset = self.stride;
+        self.attribute_at(location, format, offset)
+    }
+
+    pub fn attribute_at(mut self, location: u32, format: Format, offset: u32) -> Self {
+        self.stride = self.stride.max(offset + format_size(format));
+        self.attributes.push(VertexInputAttributeDescription {
+            location,
+            binding: self.binding,
+            format,
+            offset,
+        });
+        self
+    }
+
+    pub fn per_instance(mut self) -> Self {
+        self.input_rate = VertexInputRate::INSTANCE;
+        self
+    }
+
+    pub fn binding_descriptions(&self) -> [VertexInputBindingDescription; 1] {
+        [VertexInputBindingDescription {
+            binding: self.binding,
+            stride: self.stride,
+            input_rate: self.input_rate,
+        }]
+    }
+
+    pub fn attribute_descriptions(&self) -> &[VertexInputAttributeDescription] {
+        &self.attributes
+    }
+}
+
+impl Default for VertexLayout {
+    fn default() -> Self {
+        VertexLayout::new()
+    }
+}
+
+pub fn format_size(format: Format) -> u32 {
+    match format {
+        Format::R8_UNORM | Format::R8_UINT => 1,
+        Format::R8G8_UNORM | Format::R16_SFLOAT | Format::R16_UINT => 2,
+        Format::R8G8B8A8_UNORM
+        | Format::R8G8B8A8_UINT
+        | Format::R16G16_SFLOAT
+        | Format::R32_SFLOAT
+        | Format::R32_UINT
+        | Format::R32_SINT => 4,
+        Format::R16G16B16A16_SFLOAT | Format::R32G32_SFLOAT | Format::R32G32_UINT => 8,
+        Format::R32G32B32_SFLOAT | Format::R32G32B32_UINT => 12,
+        Format::R32G32B32A32_SFLOAT | Format::R32G32B32A32_UINT => 16,
+        _ => panic!("Unsupported vertex attribute format {:?}", format),
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Clone)]
 pub struct Vertex {
     pub pos: [f32; 2],
     pub color: [f32; 3],
+    pub normal: [f32; 3],
 }
 
 impl Vertex {
@@ -27,7 +98,7 @@ impl Vertex {
         }]
     }
 
-    pub fn get_attribute_descriptions() -> [VertexInputAttributeDescription; 2] {
+    pub fn get_attribute_descriptions() -> [VertexInputAttributeDescription; 3] {
         [
             VertexInputAttributeDescription {
                 location: 0,
@@ -41,124 +112,175 @@ impl Vertex {
                 format: Format::R32G32B32_SFLOAT,
                 offset: offset_of!(Self, color) as u32,
             },
+            VertexInputAttributeDescription {
+                location: 2,
+                binding: 0,
+                format: Format::R32G32B32_SFLOAT,
+                offset: offset_of!(Self, normal) as u32,
+            },
         ]
     }
-}
 
-//TODO undo duplicated logic
-pub fn create_vertex_buffer(
-    instance: &Instance,
-    device: &Device,
-    physical_device: PhysicalDevice,
-    command_pool: CommandPool,
-    submit_queue: Queue,
-    buffer_usage_flags: BufferUsageFlags,
-) -> (Buffer, DeviceMemory) {
-    let buffer_size = std::mem::size_of_val(&VERTICES_DATA) as DeviceSize;
-    let device_memory_properties =
-        unsafe { instance.get_physical_device_memory_properties(physical_device) };
-
-    let (staging_buffer, staging_buffer_memory) = create_buffer(
-        device,
-        buffer_size,
-        BufferUsageFlags::TRANSFER_SRC,
-        MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
-        &device_memory_properties,
-    );
-    unsafe {
-        let data_ptr = device
-            .map_memory(
-                staging_buffer_memory,
-                0,
-                buffer_size,
-                MemoryMapFlags::empty(),
-            )
-            .expect("Failed to map memory") as *mut Vertex;
-
-        data_ptr.copy_from_nonoverlapping(VERTICES_DATA.as_ptr(), VERTICES_DATA.len());
-
-        device.unmap_memory(staging_buffer_memory);
-    }
-
-    let (vertex_buffer, vertex_buffer_memory) = create_buffer(
-        device,
-        buffer_size,
-        buffer_usage_flags,
-        MemoryPropertyFlags::DEVICE_LOCAL,
-        &device_memory_properties,
-    );
+    pub fn layout() -> VertexLayout {
+        VertexLayout::new()
+            .attribute_at(0, Format::R32G32_SFLOAT, offset_of!(Self, pos) as u32)
+            .attribute_at(1, Format::R32G32B32_SFLOAT, offset_of!(Self, color) as u32)
+            .attribute_at(2, Format::R32G32B32_SFLOAT, offset_of!(Self, normal) as u32)
+    }
 
-    copy_buffer(
-        device,
-        submit_queue,
-        command_pool,
-        staging_buffer,
-        vertex_buffer,
-        buffer_size,
-    );
+    // Matches shader.vert, which places the 2D position on the z = 0 plane.
+    pub fn position(&self) -> Vector3<f32> {
+        Vector3::new(self.pos[0], self.pos[1], 0.0)
+    }
+}
 
-    unsafe {
-        device.destroy_buffer(staging_buffer, None);
-        device.free_memory(staging_buffer_memory, None);
+pub fn compute_face_normal(a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>) -> Vector3<f32> {
+    let normal = (b - a).cross(c - a);
+    if normal.magnitude2() == 0.0 {
+        Vector3::unit_z()
+    } else {
+        normal.normalize()
     }
+}
 
-    (vertex_buffer, vertex_buffer_memory)
+pub fn compute_vertex_normals(vertices: &mut [Vertex], indices: &[u32]) {
+    let mut normals = vec![Vector3::new(0.0, 0.0, 0.0); vertices.len()];
+    indices.chunks_exact(3).for_each(|triangle| {
+        let (a, b, c) = (
+            triangle[0] as usize,
+            triangle[1] as usize,
+            triangle[2] as usize,
+        );
+        let face_normal = compute_face_normal(
+            vertices[a].position(),
+            vertices[b].position(),
+            vertices[c].position(),
+        );
+        normals[a] += face_normal;
+        normals[b] += face_normal;
+        normals[c] += face_normal;
+    });
+
+    vertices.iter_mut().zip(normals).for_each(|(vertex, normal)| {
+        let normal = if normal.magnitude2() == 0.0 {
+            Vector3::unit_z()
+        } else {
+            normal.normalize()
+        };
+        vertex.normal = normal.into();
+    });
+}
+
+pub fn create_vertex_buffer(
+    uploader: &mut BufferUploader,
+    vertices: &[Vertex],
+    buffer_usage_flags: BufferUsageFlags,
+) -> (Buffer, DeviceMemory) {
+    uploader.create_device_local_buffer(vertices, buffer_usage_flags)
 }
 
 pub fn create_index_buffer(
-    instance: &Instance,
-    device: &Device,
-    physical_device: PhysicalDevice,
-    command_pool: CommandPool,
-    submit_queue: Queue,
+    uploader: &mut BufferUploader,
+    indices: &[u32],
     buffer_usage_flags: BufferUsageFlags,
 ) -> (Buffer, DeviceMemory) {
-    let buffer_size = std::mem::size_of_val(&INDICES_DATA) as DeviceSize;
-    let device_memory_properties =
-        unsafe { instance.get_physical_device_memory_properties(physical_device) };
-
-    let (staging_buffer, staging_buffer_memory) = create_buffer(
-        device,
-        buffer_size,
-        BufferUsageFlags::TRANSFER_SRC,
-        MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
-        &device_memory_properties,
-    );
+    uploader.create_device_local_buffer(indices, buffer_usage_flags)
+}
+
+pub fn combined_index_offset(vertex_data_size: DeviceSize) -> DeviceSize {
+    let alignment = std::mem::size_of::<u32>() as DeviceSize;
+    vertex_data_size.div_ceil(alignment) * alignment
+}
+
+pub fn create_combined_buffer(
+    uploader: &mut BufferUploader,
+    vertices: &[Vertex],
+    indices: &[u32],
+) -> (Buffer, DeviceMemory, DeviceSize) {
+    let vertex_data_size = std::mem::size_of_val(vertices);
+    let index_offset = combined_index_offset(vertex_data_size as DeviceSize);
+    let mut data = vec![0_u8; index_offset as usize + std::mem::size_of_val(indices)];
     unsafe {
-        let data_ptr = device
-            .map_memory(
-                staging_buffer_memory,
-                0,
-                buffer_size,
-                MemoryMapFlags::empty(),
-            )
-            .expect("Failed to map memory") as *mut u32;
-
-        data_ptr.copy_from_nonoverlapping(INDICES_DATA.as_ptr(), INDICES_DATA.len());
-
-        device.unmap_memory(staging_buffer_memory);
-    }
-
-    let (vertex_buffer, vertex_buffer_memory) = create_buffer(
-        device,
-        buffer_size,
-        buffer_usage_flags,
-        MemoryPropertyFlags::DEVICE_LOCAL,
-        &device_memory_properties,
-    );
+        std::ptr::copy_nonoverlapping(
+            vertices.as_ptr() as *const u8,
+            data.as_mut_ptr(),
+            vertex_data_size,
+        );
+        std::ptr::copy_nonoverlapping(
+            indices.as_ptr() as *const u8,
+            data.as_mut_ptr().add(index_offset as usize),
+            std::mem::size_of_val(indices),
+        );
+    }
 
-    copy_buffer(
-        device,
-        submit_queue,
-        command_pool,
-        staging_buffer,
-        vertex_buffer,
-        buffer_size,
+    let (buffer, memory) = uploader.create_device_local_buffer(
+        &data,
+        BufferUsageFlags::TRANSFER_DST
+            | BufferUsageFlags::VERTEX_BUFFER
+            | BufferUsageFlags::INDEX_BUFFER,
     );
-    unsafe {
-        device.destroy_buffer(staging_buffer, None);
-        device.free_memory(staging_buffer_memory, None);
+    (buffer, memory, index_offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(x: f32, y: f32) -> Vertex {
+        Vertex {
+            pos: [x, y],
+            color: [1.0, 1.0, 1.0],
+            normal: [0.0, 0.0, 0.0],
+        }
+    }
+
+    #[test]
+    fn ccw_triangle_faces_positive_z() {
+        let normal = compute_face_normal(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+        assert_eq!(normal, Vector3::unit_z());
+    }
+
+    #[test]
+    fn vertex_normals_follow_winding() {
+        let mut vertices = vec![vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(0.0, 1.0)];
+        compute_vertex_normals(&mut vertices, &[0, 1, 2]);
+        assert!(vertices.iter().all(|x| x.normal == [0.0, 0.0, 1.0]));
+        compute_vertex_normals(&mut vertices, &[0, 2, 1]);
+        assert!(vertices.iter().all(|x| x.normal == [0.0, 0.0, -1.0]));
+    }
+
+    #[test]
+    fn layout_matches_vertex_struct() {
+        let layout = Vertex::layout();
+        let offsets: Vec<_> = layout
+            .attribute_descriptions()
+            .iter()
+            .map(|x| x.offset as usize)
+            .collect();
+        assert_eq!(
+            offsets,
+            vec![
+                offset_of!(Vertex, pos),
+                offset_of!(Vertex, color),
+                offset_of!(Vertex, normal),
+            ]
+        );
+        assert_eq!(layout.stride as usize, std::mem::size_of::<Vertex>());
+        let describe = |x: &VertexInputAttributeDescription| (x.location, x.format, x.offset);
+        assert!(Vertex::get_attribute_descriptions()
+            .iter()
+            .map(describe)
+            .eq(layout.attribute_descriptions().iter().map(describe)));
     }
 
-    (vertex_buffer, vertex_buffer_memory)
+    #[test]
+    fn combined_index_offset_is_u32_aligned() {
+        assert_eq!(combined_index_offset(0), 0);
+        assert_eq!(combined_index_offset(32), 32);
+        assert_eq!(combined_index_offset(33), 36);
+    }
 }
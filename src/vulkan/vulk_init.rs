@@ -1,278 +1,985 @@
-use super::command_pool::{create_command_buffers, create_command_pool};
-use super::constants::{INDICES_DATA, MAX_FRAMES_IN_FLIGHT, VALIDATION, VERTICES_DATA};
+use super::attachment::{
+    choose_sample_count, create_depth_attachment, create_msaa_color_attachment,
+    decode_sample_counts, find_depth_format, find_supported_sample_counts, AttachmentImage,
+};
+use super::buffer::{
+    single_time_commands, BufferUploader, PotatoBuffer, StagingBuffer, UploadQueues,
+};
+use super::builder::VulkanApiObjectsBuilder;
+use super::color::{resolve_clear_color, ClearColorSpace};
+use super::command_pool::{
+    create_clear_values, create_command_buffers, create_command_pool,
+    create_command_pool_for_family, record_frame_command_buffer, record_parallel,
+    record_renderable, record_scene_command_buffer, FrameRecording, RecordChunk, SceneDraws,
+};
+use super::constants::{
+    BENCHMARK_EXTENT, BENCHMARK_FORMAT, DESCRIPTOR_SETS_PER_POOL, INDICES_DATA,
+    MAX_FRAMES_IN_FLIGHT, VERTICES_DATA,
+};
+use super::descriptor_allocator::DescriptorAllocator;
+use super::debug_draw::DebugLines;
 use super::device::create_logical_device;
+use super::error::PotatoError;
+use super::frame_stats::{
+    frame_limit_sleep, frame_time_since, target_frame_time, wait_until, FrameStats,
+};
 use super::framebuffers::create_framebuffers;
-use super::graphics_pipeline::create_graphics_pipeline;
+use super::frustum::cull;
+use super::graphics_pipeline::{
+    create_debug_line_pipeline, create_graphics_pipeline, create_overlay_pipeline,
+    create_particle_pipeline, create_text_pipeline, find_line_width, PipelineConfig, PipelineTarget,
+};
 use super::instance::create_instance;
-use super::physical_device::{describe_device, select_physical_device};
+use super::indirect::{check_indirect_draw_count, IndirectBuffer};
+use super::offscreen::OffscreenTarget;
+use super::overlay::Overlay2d;
+use super::external_memory::{
+    external_memory_extension_names, is_external_memory_usable, ExportableBuffer,
+};
+use super::memory_budget::{
+    is_memory_budget_usable, memory_budget_extension_name, query_memory_budget,
+};
+use super::particles::ParticleSystem;
+use super::pipeline_stats::{PipelineStats, PipelineStatsQuery, PIPELINE_STATISTICS};
+use super::scene::{create_combined_mesh, create_mesh, replace_mesh, Aabb, Mesh, Renderable};
+use super::physical_device::{
+    describe_device, is_device_extension_supported, list_physical_devices, list_surface_devices,
+    query_device_info, select_physical_device, DeviceInfo,
+};
+use super::pipeline_cache::{create_pipeline_cache, save_pipeline_cache};
+use super::postprocess::PostProcess;
 use super::queue_family::QueueFamily;
-use super::render_pass::create_render_pass;
+use super::render_context::{
+    should_pause, swapchain_recovery, PendingResize, RenderContext,
+    SwapchainRecovery,
+};
+use super::render_pass::{create_render_pass, RenderTargetFormats};
+use super::screenshot::{is_supported_screenshot_format, ScreenshotCapture};
+use super::shader::ShaderSource;
+use super::submit_batch::{SubmitBatch, SubmitEntry};
 use super::surface::{create_surface, PotatoSurface};
-use super::swapchain::{create_swapchain, PotatoSwapChain};
+use super::swapchain::{
+    choose_swapchain_image_usage, create_swapchain, determine_swapchain_support, PotatoSwapChain,
+    SwapchainConfig,
+};
 use super::sync_objects::create_sync_objects;
-use super::vertex::{create_index_buffer, create_vertex_buffer};
-use super::vulk_validation_layers::setup_debug_utils;
+use super::text::{BitmapFont, TextRenderer};
+use super::texture::{
+    create_material_set_layout, create_texture_sampler, load_texture, write_texture_descriptor,
+    SamplerOptions, SamplerPreset, Texture, MATERIAL_TEXTURE_BINDING,
+};
+use super::timestamp::GpuTimer;
+use super::vertex::{compute_vertex_normals, Vertex, VertexLayout};
+use super::vulk_validation_layers::{set_debug_name, setup_debug_utils, ValidationLog};
 use super::UniformBufferObject::{
-    create_descriptor_pool, create_descriptor_set_layout, create_descriptor_sets,
-    create_uniform_buffers, update_uniform_buffer,
+    create_descriptor_set_layout, create_descriptor_sets,
+    create_projection, create_uniform_buffers, update_uniform_buffer, UniformBufferObject,
 };
 use ash::extensions::ext::DebugUtils;
+use ash::extensions::khr::Surface;
 use ash::version::{DeviceV1_0, InstanceV1_0};
 use ash::vk::{
-    Buffer, BufferUsageFlags, CommandBuffer, CommandPool, DebugUtilsMessengerEXT, DescriptorPool,
-    DescriptorSet, DescriptorSetLayout, DeviceMemory, Fence, Framebuffer, PhysicalDevice, Pipeline,
-    PipelineLayout, PipelineStageFlags, PresentInfoKHR, Queue, RenderPass, Result, Semaphore,
-    StructureType, SubmitInfo,
+    BufferUsageFlags, CommandBuffer, CommandPool, DebugUtilsMessengerEXT, DescriptorSet,
+    DescriptorSetLayout, DescriptorType, DeviceSize, DrawIndexedIndirectCommand, Extent2D, Fence,
+    Format, Handle, ImageUsageFlags, ImageView, PhysicalDevice, PhysicalDeviceFeatures,
+    PipelineBindPoint, PipelineCache, PipelineStageFlags, PresentInfoKHR,
+    QueryPipelineStatisticFlags, Queue, QueueFlags, RenderPass, SampleCountFlags, Sampler,
+    StructureType, SurfaceKHR, TRUE,
 };
 use ash::Device;
 use ash::Entry;
 use ash::Instance;
-use log::debug;
+use cgmath::Matrix4;
+use crate::io::file::write_png;
+use crate::windowing::input::{Action, InputMap};
+use crate::windowing::window::{CursorState, DisplayMode, PotatoWindow};
+use log::{debug, error, info, warn};
 use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use winit::{
-    dpi::LogicalSize,
-    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    dpi::{LogicalSize, PhysicalSize},
+    event::{ElementState, Event, KeyboardInput, WindowEvent},
     event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget},
-    window::{Window, WindowBuilder, WindowId},
+    window::{Fullscreen, Window, WindowBuilder, WindowId},
 };
 
 pub struct VulkanApiObjects {
-    windows: HashMap<WindowId, Window>,
-    _entry: Entry,
+    windows: HashMap<WindowId, PotatoWindow>,
+    render_contexts: HashMap<WindowId, RenderContext>,
+    entry: Entry,
     instance: Instance,
-    surface: PotatoSurface,
     queue_family: QueueFamily,
     debug_utils_loader: DebugUtils,
     debug_messenger: DebugUtilsMessengerEXT,
+    is_debug_utils_enabled: bool,
+    validation_log: Arc<ValidationLog>,
     physical_device: PhysicalDevice,
-    device: Device,
+    device: Rc<Device>,
     graphics_queue: Queue,
-    swapchain: PotatoSwapChain,
-    pipeline_layout: PipelineLayout,
-    render_pass: RenderPass,
-    graphics_pipeline: Pipeline,
-    swapchain_framebuffers: Vec<Framebuffer>,
+    present_queue: Queue,
+    swapchain_config: SwapchainConfig,
+    pipeline_config: PipelineConfig,
+    clear_color: [f32; 4],
+    clear_color_space: ClearColorSpace,
+    shaders: (ShaderSource, ShaderSource),
+    vertex_layout: VertexLayout,
+    pipeline_cache: PipelineCache,
+    depth_format: Format,
     command_pool: CommandPool,
-    command_buffers: Vec<CommandBuffer>,
-    image_available_semaphores: Vec<Semaphore>,
-    render_finished_semaphores: Vec<Semaphore>,
-    in_flight_fences: Vec<Fence>,
-    current_frame: usize,
-    vertex_buffer: Buffer,
-    vertex_buffer_memory: DeviceMemory,
-    index_buffer: Buffer,
-    index_buffer_memory: DeviceMemory,
-    uniform_buffers: Vec<Buffer>,
-    uniform_buffers_memory: Vec<DeviceMemory>,
+    upload_queues: UploadQueues,
+    meshes: Vec<Mesh>,
+    renderables: Vec<Renderable>,
+    indirect_buffers: Vec<IndirectBuffer>,
+    staging_buffer: StagingBuffer,
+    particle_system: Option<ParticleSystem>,
+    debug_lines: DebugLines,
+    overlay: Overlay2d,
+    text_renderer: Option<TextRenderer>,
+    uniform: Option<UniformBufferObject>,
+    is_demo_rotation: bool,
+    is_animated: bool,
+    is_projection_from_extent: bool,
+    is_frustum_culling_enabled: bool,
     ubo_layout: DescriptorSetLayout,
-    descriptor_pool: DescriptorPool,
-    descriptor_sets: Vec<DescriptorSet>,
+    material_layout: DescriptorSetLayout,
+    material_allocator: DescriptorAllocator,
+    material_textures: Vec<Texture>,
+    texture_sampler: Sampler,
+    samplers: HashMap<SamplerPreset, Sampler>,
+    sampler_options: SamplerOptions,
+    enabled_features: PhysicalDeviceFeatures,
+    input_map: InputMap,
+    cursor: CursorState,
+    parallel_chunks: Vec<RecordChunk>,
+    target_frame_time: Option<Duration>,
+    non_coherent_atom_size: DeviceSize,
+    is_memory_budget_enabled: bool,
+    is_pipeline_stats_enabled: bool,
+    is_external_memory_enabled: bool,
+    shutdown_state: ShutdownState,
 }
 
 impl VulkanApiObjects {
-    pub fn init(event_loop: &EventLoop<()>) -> VulkanApiObjects {
+    pub fn builder() -> VulkanApiObjectsBuilder {
+        VulkanApiObjectsBuilder::new()
+    }
+
+    pub fn init(
+        event_loop: &EventLoop<()>,
+        builder: VulkanApiObjectsBuilder,
+    ) -> std::result::Result<VulkanApiObjects, PotatoError> {
         debug!("Init window");
         let window = VulkanApiObjects::init_window(&event_loop, "origin");
         debug!("Init entry");
         let entry = Entry::new().unwrap();
         debug!("Init instance");
-        let instance = create_instance(&entry);
+        let (instance, is_validation_enabled, is_colorspace_enabled, api_version) = create_instance(
+            &entry,
+            &builder.application_config,
+            builder.is_lenient_validation,
+            builder.is_validation_requested,
+            builder.swapchain_config.hdr.is_some(),
+            &builder.instance_extensions,
+        )?;
+        let mut swapchain_config = builder.swapchain_config;
+        if !is_colorspace_enabled {
+            swapchain_config.hdr = None;
+        }
         debug!("Init debug utils");
-        let (debug_utils_loader, debug_messenger) = setup_debug_utils(&entry, &instance);
+        let validation_log = Arc::new(ValidationLog::new(builder.validation_log_capacity));
+        let (debug_utils_loader, debug_messenger) =
+            setup_debug_utils(&entry, &instance, &validation_log, is_validation_enabled);
         debug!("Init surface");
         let potato_surface = create_surface(&entry, &instance, &window);
+        let mut init_guard = InitGuard {
+            instance: instance.clone(),
+            surface: (potato_surface.surface_loader.clone(), potato_surface.surface),
+            debug_utils: (debug_utils_loader.clone(), debug_messenger),
+            device: None,
+        };
         debug!("Init physical device");
-        let physical_device = select_physical_device(&instance, &potato_surface);
-        let physical_device_memory_properties =
-            unsafe { instance.get_physical_device_memory_properties(physical_device) };
+        let physical_device =
+            select_physical_device(&instance, &potato_surface, &builder.device_preference)?;
         describe_device(&instance, physical_device);
+        choose_swapchain_image_usage(
+            &determine_swapchain_support(physical_device, &potato_surface).capabilities,
+            swapchain_config.extra_usage,
+        )?;
+        let physical_device_properties =
+            unsafe { instance.get_physical_device_properties(physical_device) };
+        let mut pipeline_config = builder.pipeline_config;
+        let sample_count = choose_sample_count(
+            find_supported_sample_counts(&physical_device_properties.limits),
+            pipeline_config.samples,
+        );
+        if sample_count != pipeline_config.samples {
+            warn!(
+                "{:?} samples are not supported for color and depth, falling back to {:?}",
+                pipeline_config.samples, sample_count
+            );
+            pipeline_config.samples = sample_count;
+        }
+        let supported_features =
+            unsafe { instance.get_physical_device_features(physical_device) };
+        pipeline_config.line_width = find_line_width(
+            pipeline_config.line_width,
+            supported_features.wide_lines == TRUE,
+            &physical_device_properties.limits,
+        );
+        let depth_format =
+            find_depth_format(&instance, physical_device, pipeline_config.stencil.is_some());
+        debug!("Using depth format {:?}", depth_format);
+
+        let is_memory_budget_enabled = is_memory_budget_usable(
+            is_device_extension_supported(
+                &instance,
+                physical_device,
+                &memory_budget_extension_name().to_string_lossy(),
+            ),
+            api_version,
+            physical_device_properties.api_version,
+        );
+        let mut optional_extensions = if is_memory_budget_enabled {
+            vec![memory_budget_extension_name()]
+        } else {
+            info!("VK_EXT_memory_budget is unavailable, memory budgets won't be reported");
+            vec![]
+        };
+        let is_external_memory_enabled = builder.is_external_memory_requested
+            && is_external_memory_usable(&instance, physical_device, api_version);
+        if is_external_memory_enabled {
+            optional_extensions.extend_from_slice(&external_memory_extension_names());
+        } else if builder.is_external_memory_requested {
+            warn!("External memory is unavailable, exportable buffers can't be created");
+        }
 
         debug!("Init logical device");
-        let (logical_device, queue_family) =
-            create_logical_device(&instance, physical_device, &potato_surface);
-        debug!("Init swapchain");
-        let swapchain = create_swapchain(
+        let (logical_device, queue_family, enabled_features) = create_logical_device(
             &instance,
-            &logical_device,
             physical_device,
             &potato_surface,
-            &queue_family,
-        );
+            &pipeline_config,
+            &optional_extensions,
+        )?;
+        let logical_device = Rc::new(logical_device);
+        init_guard.device = Some(Rc::clone(&logical_device));
+        if builder.particles.is_some() {
+            let queue_families =
+                unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+            if !queue_families[queue_family.graphics_family.unwrap()]
+                .queue_flags
+                .contains(QueueFlags::COMPUTE)
+            {
+                return Err(PotatoError::MissingDeviceFeature(
+                    "compute on the graphics queue".to_string(),
+                ));
+            }
+        }
         debug!("Init graphics queue");
         let graphics_queue = unsafe {
             logical_device.get_device_queue(queue_family.graphics_family.unwrap() as u32, 0)
         };
-        debug!("Init render pass");
-        let render_pass = create_render_pass(&logical_device, swapchain.swapchain_format);
+        let present_queue = if queue_family.is_present_separate() {
+            debug!("Init present queue");
+            unsafe {
+                logical_device.get_device_queue(queue_family.present_family.unwrap() as u32, 0)
+            }
+        } else {
+            graphics_queue
+        };
         debug!("Init descriptor layout");
         let ubo_layout = create_descriptor_set_layout(&logical_device);
-        debug!("Init graphics pipeline");
-        let (graphics_pipeline, pipeline_layout) = create_graphics_pipeline(
-            &logical_device,
-            render_pass,
-            swapchain.swapchain_extent,
-            ubo_layout,
-        );
-        debug!("Init framebuffers");
-        let swapchain_framebuffers = create_framebuffers(
-            &logical_device,
-            render_pass,
-            &swapchain.swapchain_image_views,
-            &swapchain.swapchain_extent,
-        );
+        let material_layout = create_material_set_layout(&logical_device);
         debug!("Init command pool");
         let command_pool = create_command_pool(&logical_device, &queue_family);
-        debug!("Init vertex buffer");
-        let (vertex_buffer, vertex_buffer_memory) = create_vertex_buffer(
-            &instance,
-            &logical_device,
-            physical_device,
-            command_pool,
-            graphics_queue,
-            BufferUsageFlags::TRANSFER_DST | BufferUsageFlags::VERTEX_BUFFER,
-        );
-        debug!("Init index buffer");
-        let (index_buffer, index_buffer_memory) = create_index_buffer(
-            &instance,
-            &logical_device,
-            physical_device,
-            command_pool,
-            graphics_queue,
-            BufferUsageFlags::TRANSFER_DST | BufferUsageFlags::INDEX_BUFFER,
-        );
-        debug!("Init ubo buffer");
-        let (uniform_buffers, uniform_buffers_memory) = create_uniform_buffers(
-            &logical_device,
-            &physical_device_memory_properties,
-            swapchain.swapchain_images.len(),
-        );
-        debug!("Init descriptor pool");
-        let descriptor_pool =
-            create_descriptor_pool(&logical_device, swapchain.swapchain_images.len());
-        debug!("Init descriptor sets");
-        let descriptor_sets = create_descriptor_sets(
-            &logical_device,
-            descriptor_pool,
-            ubo_layout,
-            &uniform_buffers,
-            swapchain.swapchain_images.len(),
+        let graphics_family = queue_family.graphics_family.unwrap() as u32;
+        let upload_queues = match queue_family.transfer_family {
+            Some(transfer_family) => {
+                debug!("Init transfer queue from family {}", transfer_family);
+                UploadQueues {
+                    transfer_queue: unsafe {
+                        logical_device.get_device_queue(transfer_family as u32, 0)
+                    },
+                    transfer_command_pool: create_command_pool_for_family(
+                        &logical_device,
+                        transfer_family as u32,
+                    ),
+                    transfer_family: transfer_family as u32,
+                    graphics_queue,
+                    graphics_command_pool: command_pool,
+                    graphics_family,
+                }
+            }
+            None => UploadQueues {
+                transfer_queue: graphics_queue,
+                transfer_command_pool: command_pool,
+                transfer_family: graphics_family,
+                graphics_queue,
+                graphics_command_pool: command_pool,
+                graphics_family,
+            },
+        };
+        let mut staging_buffer = StagingBuffer::new();
+        debug!("Init default mesh");
+        let mut vertices = VERTICES_DATA.to_vec();
+        compute_vertex_normals(&mut vertices, &INDICES_DATA);
+        let mesh = create_mesh(
+            &mut BufferUploader::new(
+                &instance,
+                &logical_device,
+                physical_device,
+                &upload_queues,
+                &mut staging_buffer,
+            ),
+            &vertices,
+            &INDICES_DATA,
         );
-        debug!("Init command buffers");
-        let command_buffers = create_command_buffers(
+        debug!("Init pipeline cache");
+        let pipeline_cache = create_pipeline_cache(&logical_device, &physical_device_properties);
+        let particle_system = match builder.particles {
+            Some(particles) => {
+                debug!("Init particle system");
+                let physical_device_memory_properties =
+                    unsafe { instance.get_physical_device_memory_properties(physical_device) };
+                Some(ParticleSystem::new(
+                    &logical_device,
+                    &physical_device_memory_properties,
+                    &upload_queues,
+                    &mut staging_buffer,
+                    &particles,
+                    builder.particle_shader,
+                    pipeline_cache,
+                ))
+            }
+            None => None,
+        };
+        debug!("Init texture sampler");
+        let texture_sampler = create_texture_sampler(
             &logical_device,
-            command_pool,
-            graphics_pipeline,
-            &swapchain_framebuffers,
-            render_pass,
-            swapchain.swapchain_extent,
-            vertex_buffer,
-            index_buffer,
-            pipeline_layout,
-            &descriptor_sets,
+            &enabled_features,
+            &physical_device_properties.limits,
+            builder.sampler_preset,
+            &builder.sampler_options,
         );
-        debug!("Init sync objects");
-        let sync_objects = create_sync_objects(&logical_device);
-
-        let mut windows = HashMap::new();
-        windows.insert(window.id(), window);
 
-        VulkanApiObjects {
-            windows,
-            _entry: entry,
+        let mut vulkan_api_objects = VulkanApiObjects {
+            windows: HashMap::new(),
+            render_contexts: HashMap::new(),
+            entry,
             instance,
-            surface: potato_surface,
             queue_family,
             debug_utils_loader,
             debug_messenger,
+            is_debug_utils_enabled: is_validation_enabled,
+            validation_log,
             physical_device,
             device: logical_device,
             graphics_queue,
+            present_queue,
+            swapchain_config,
+            pipeline_config,
+            clear_color: builder.clear_color,
+            clear_color_space: builder.clear_color_space,
+            shaders: builder.shaders,
+            vertex_layout: builder.vertex_layout,
+            pipeline_cache,
+            depth_format,
+            command_pool,
+            upload_queues,
+            meshes: vec![mesh],
+            renderables: vec![Renderable {
+                bounds: Aabb::from_vertices(&vertices),
+                ..Renderable::new(mesh)
+            }],
+            indirect_buffers: vec![],
+            staging_buffer,
+            particle_system,
+            debug_lines: DebugLines::new(),
+            overlay: Overlay2d::new(),
+            text_renderer: None,
+            uniform: None,
+            is_demo_rotation: true,
+            is_animated: builder.is_animated,
+            is_projection_from_extent: true,
+            is_frustum_culling_enabled: false,
+            ubo_layout,
+            material_layout,
+            material_allocator: DescriptorAllocator::new(
+                DESCRIPTOR_SETS_PER_POOL,
+                &[(DescriptorType::COMBINED_IMAGE_SAMPLER, 1)],
+            ),
+            material_textures: vec![],
+            texture_sampler,
+            samplers: HashMap::new(),
+            sampler_options: builder.sampler_options,
+            enabled_features,
+            input_map: builder.input_map,
+            cursor: builder.cursor,
+            parallel_chunks: vec![],
+            target_frame_time: target_frame_time(builder.max_fps),
+            non_coherent_atom_size: physical_device_properties.limits.non_coherent_atom_size,
+            is_memory_budget_enabled,
+            is_pipeline_stats_enabled: enabled_features.pipeline_statistics_query == TRUE,
+            is_external_memory_enabled,
+            shutdown_state: ShutdownState::default(),
+        };
+        // VulkanApiObjects now owns everything but the surface, which its render context takes.
+        init_guard.device = None;
+        std::mem::forget(init_guard);
+        vulkan_api_objects.set_mesh_debug_names(&mesh);
+        if let Err(e) = vulkan_api_objects.init_demo_scene(builder.texture) {
+            unsafe {
+                potato_surface
+                    .surface_loader
+                    .destroy_surface(potato_surface.surface, None);
+            }
+            return Err(e);
+        }
+
+        debug!("Init render context");
+        let render_context =
+            vulkan_api_objects.create_render_context(potato_surface, window_extent(&window));
+        vulkan_api_objects
+            .render_contexts
+            .insert(window.id(), render_context);
+        vulkan_api_objects
+            .windows
+            .insert(window.id(), PotatoWindow::new(window, builder.cursor));
+        if let Some((path, fallback_png_path, font)) = builder.font {
+            debug!("Init font");
+            vulkan_api_objects.load_font(&path, &fallback_png_path, font)?;
+        }
+
+        Ok(vulkan_api_objects)
+    }
+
+    fn init_demo_scene(
+        &mut self,
+        texture: Option<(String, String)>,
+    ) -> std::result::Result<(), PotatoError> {
+        // The demo mesh is drawn indirectly so the indirect path is exercised every frame.
+        let indirect_buffer = self.create_indirect_buffer(&[DrawIndexedIndirectCommand {
+            index_count: INDICES_DATA.len() as u32,
+            instance_count: 1,
+            first_index: 0,
+            vertex_offset: 0,
+            first_instance: 0,
+        }])?;
+        self.renderables[0].mesh.draw_mode = indirect_buffer.draw_mode();
+        self.indirect_buffers.push(indirect_buffer);
+        if let Some((path, fallback_png_path)) = texture {
+            debug!("Init demo material");
+            let texture = self.load_texture(&path, &fallback_png_path)?;
+            let texture_view = texture.view;
+            self.material_textures.push(texture);
+            let material = self.create_material(texture_view, self.texture_sampler)?;
+            self.renderables[0].descriptor_set = Some(material);
+        }
+        Ok(())
+    }
+
+    fn create_render_context(
+        &self,
+        surface: PotatoSurface,
+        window_extent: Extent2D,
+    ) -> RenderContext {
+        let swapchain = create_swapchain(
+            &self.instance,
+            &self.device,
+            self.physical_device,
+            &surface,
+            &self.queue_family,
+            &self.swapchain_config,
+            window_extent,
+        );
+        let render_target_formats = self.render_target_formats(&swapchain);
+        let render_pass = self.create_context_render_pass(&render_target_formats);
+        let (depth_attachment, msaa_color_attachment) = self.create_attachments(&swapchain);
+        let target = PipelineTarget {
+            render_pass,
+            formats: render_target_formats,
+            extent: swapchain.swapchain_extent,
+        };
+        let (graphics_pipeline, pipeline_layout) = create_graphics_pipeline(
+            &self.device,
+            self.pipeline_cache,
+            &target,
+            (self.ubo_layout, self.material_layout),
+            self.shaders,
+            &self.vertex_layout,
+            &self.pipeline_config,
+        );
+        let particle_pipeline = self.particle_system.as_ref().map(|_| {
+            create_particle_pipeline(
+                &self.device,
+                self.pipeline_cache,
+                &target,
+                &self.pipeline_config,
+            )
+        });
+        let debug_line_pipeline = create_debug_line_pipeline(
+            &self.device,
+            self.pipeline_cache,
+            &target,
+            self.ubo_layout,
+            &self.pipeline_config,
+        );
+        let overlay_pipeline = create_overlay_pipeline(
+            &self.device,
+            self.pipeline_cache,
+            &target,
+            &self.pipeline_config,
+        );
+        let text_pipeline = self.text_renderer.as_ref().map(|text_renderer| {
+            create_text_pipeline(
+                &self.device,
+                self.pipeline_cache,
+                &target,
+                text_renderer.descriptor_set_layout,
+                &self.pipeline_config,
+            )
+        });
+        let swapchain_framebuffers = create_framebuffers(
+            &self.device,
+            render_pass,
+            &swapchain.swapchain_image_views,
+            Some(depth_attachment.view),
+            msaa_color_attachment.as_ref().map(|x| x.view),
+            &swapchain.swapchain_extent,
+        );
+        let (uniform_buffers, descriptor_allocator, descriptor_sets) =
+            self.create_uniform_resources(MAX_FRAMES_IN_FLIGHT);
+        let projection = create_projection(swapchain.swapchain_extent);
+        let sync_objects = create_sync_objects(&self.device);
+        let gpu_timer = self.create_gpu_timer();
+        let pipeline_stats_query = self.create_pipeline_stats_query();
+
+        let mut render_context = RenderContext {
+            surface,
             swapchain,
-            pipeline_layout,
             render_pass,
+            render_target_formats,
+            pipeline_layout,
             graphics_pipeline,
+            particle_pipeline,
+            debug_line_pipeline,
+            text_pipeline,
+            overlay_pipeline,
+            depth_attachment,
+            msaa_color_attachment,
             swapchain_framebuffers,
-            command_pool,
-            command_buffers,
+            command_buffers: vec![],
+            scene_command_buffers: vec![],
+            worker_command_pools: vec![],
+            parallel_command_buffers: vec![],
+            uniform_buffers,
+            descriptor_allocator,
+            descriptor_sets,
             image_available_semaphores: sync_objects.image_available_semaphores,
             render_finished_semaphores: sync_objects.render_finished_semaphores,
             in_flight_fences: sync_objects.inflight_fences,
+            gpu_timer,
+            last_gpu_time_ms: 0.0,
+            pipeline_stats_query,
+            last_pipeline_stats: None,
+            frame_stats: FrameStats::default(),
+            projection,
+            last_frame_start: None,
             current_frame: 0,
-            vertex_buffer,
-            vertex_buffer_memory,
-            index_buffer,
-            index_buffer_memory,
-            uniform_buffers,
-            uniform_buffers_memory,
-            ubo_layout,
-            descriptor_pool,
-            descriptor_sets,
+            is_screenshot_requested: false,
+            is_framebuffer_resized: false,
+            is_paused: false,
+            is_surface_lost: false,
+            pending_resize: PendingResize::default(),
+            window_extent,
+        };
+        self.set_context_debug_names(&render_context);
+        self.record_context_command_buffers(&mut render_context);
+        render_context
+    }
+
+    fn debug_utils(&self) -> Option<&DebugUtils> {
+        if self.is_debug_utils_enabled {
+            Some(&self.debug_utils_loader)
+        } else {
+            None
         }
     }
 
-    pub fn draw(&mut self, delta_time: f32) {
-        let wait_fences = [self.in_flight_fences[self.current_frame]];
-        let (image_index, _is_sub_optimal) = unsafe {
-            self.device
-                .wait_for_fences(&wait_fences, true, std::u64::MAX)
-                .expect("Failed to wait for Fence!");
-
-            let result = self.swapchain.swapchain_loader.acquire_next_image(
-                self.swapchain.swapchain,
-                std::u64::MAX,
-                self.image_available_semaphores[self.current_frame],
-                Fence::null(),
-            );
-            match result {
-                Ok(image_index) => image_index,
-                Err(vk_result) => match vk_result {
-                    Result::ERROR_OUT_OF_DATE_KHR => {
-                        self.recreate_swapchain();
-                        return;
-                    }
-                    _ => panic!("Failed to acquire swap chain image"),
-                },
-            }
+    fn set_debug_name<T: Handle>(&self, object: T, name: &str) {
+        if let Err(e) = set_debug_name(self.debug_utils(), &self.device, object, name) {
+            warn!("Failed to name {}: {}", name, e);
+        }
+    }
+
+    fn set_mesh_debug_names(&self, mesh: &Mesh) {
+        if mesh.is_combined() {
+            self.set_debug_name(mesh.vertex_buffer, "Vertex/Index Buffer");
+            return;
+        }
+        self.set_debug_name(mesh.vertex_buffer, "Vertex Buffer");
+        if mesh.is_indexed() {
+            self.set_debug_name(mesh.index_buffer, "Index Buffer");
+        }
+    }
+
+    fn set_context_debug_names(&self, render_context: &RenderContext) {
+        render_context
+            .swapchain
+            .swapchain_images
+            .iter()
+            .enumerate()
+            .for_each(|(i, x)| self.set_debug_name(*x, &format!("Swapchain Image {}", i)));
+        self.set_debug_name(render_context.graphics_pipeline, "Graphics Pipeline");
+        self.set_debug_name(render_context.debug_line_pipeline.0, "Debug Line Pipeline");
+        self.set_debug_name(render_context.overlay_pipeline.0, "Overlay Pipeline");
+        if let Some((particle_pipeline, _)) = render_context.particle_pipeline {
+            self.set_debug_name(particle_pipeline, "Particle Pipeline");
+        }
+        if let Some((text_pipeline, _)) = render_context.text_pipeline {
+            self.set_debug_name(text_pipeline, "Text Pipeline");
+        }
+        render_context
+            .uniform_buffers
+            .iter()
+            .enumerate()
+            .for_each(|(i, x)| self.set_debug_name(x.buffer, &format!("Uniform Buffer {}", i)));
+    }
+
+    fn render_target_formats(&self, swapchain: &PotatoSwapChain) -> RenderTargetFormats {
+        RenderTargetFormats {
+            color: swapchain.swapchain_format,
+            depth: Some(self.depth_format),
+            samples: self.pipeline_config.samples,
+        }
+    }
+
+    fn create_context_render_pass(&self, formats: &RenderTargetFormats) -> RenderPass {
+        create_render_pass(&self.device, formats).expect("Failed to create render pass")
+    }
+
+    fn create_attachments(
+        &self,
+        swapchain: &PotatoSwapChain,
+    ) -> (AttachmentImage, Option<AttachmentImage>) {
+        let physical_device_memory_properties = unsafe {
+            self.instance
+                .get_physical_device_memory_properties(self.physical_device)
+        };
+        let depth_attachment = create_depth_attachment(
+            &self.device,
+            &physical_device_memory_properties,
+            swapchain.swapchain_extent,
+            self.depth_format,
+            self.pipeline_config.samples,
+        );
+        let msaa_color_attachment = if self.pipeline_config.samples != SampleCountFlags::TYPE_1 {
+            Some(create_msaa_color_attachment(
+                &self.device,
+                &physical_device_memory_properties,
+                swapchain.swapchain_extent,
+                swapchain.swapchain_format,
+                self.pipeline_config.samples,
+            ))
+        } else {
+            None
         };
+        (depth_attachment, msaa_color_attachment)
+    }
 
-        update_uniform_buffer(
-            &self.swapchain,
+    // Timestamp slots follow the frame in flight, whose fence guards their command buffer.
+    fn create_gpu_timer(&self) -> Option<GpuTimer> {
+        let limits = unsafe {
+            self.instance
+                .get_physical_device_properties(self.physical_device)
+                .limits
+        };
+        let queue_families = unsafe {
+            self.instance
+                .get_physical_device_queue_family_properties(self.physical_device)
+        };
+        let valid_bits =
+            queue_families[self.queue_family.graphics_family.unwrap()].timestamp_valid_bits;
+
+        let gpu_timer =
+            GpuTimer::new(&self.device, MAX_FRAMES_IN_FLIGHT, limits.timestamp_period, valid_bits);
+        if gpu_timer.is_none() {
+            warn!("Graphics queue doesn't support timestamps, GPU frame timing is disabled");
+        }
+        gpu_timer
+    }
+
+    pub fn create_exportable_buffer(
+        &self,
+        size: DeviceSize,
+        usage: BufferUsageFlags,
+    ) -> std::result::Result<ExportableBuffer, PotatoError> {
+        if !self.is_external_memory_enabled {
+            return Err(PotatoError::MissingDeviceExtensions(
+                external_memory_extension_names()
+                    .iter()
+                    .map(|x| x.to_string_lossy().to_string())
+                    .collect(),
+            ));
+        }
+        let physical_device_memory_properties = unsafe {
+            self.instance
+                .get_physical_device_memory_properties(self.physical_device)
+        };
+        ExportableBuffer::new(
+            &self.instance,
             &self.device,
-            image_index as usize,
-            delta_time,
-            &self.uniform_buffers_memory,
+            size,
+            usage,
+            &physical_device_memory_properties,
+        )
+    }
+
+    fn create_uniform_resources(
+        &self,
+        frames_in_flight: usize,
+    ) -> (Vec<PotatoBuffer>, DescriptorAllocator, Vec<DescriptorSet>) {
+        let physical_device_memory_properties = unsafe {
+            self.instance
+                .get_physical_device_memory_properties(self.physical_device)
+        };
+        let uniform_buffers = create_uniform_buffers(
+            &self.device,
+            &physical_device_memory_properties,
+            frames_in_flight,
+        );
+        let mut descriptor_allocator = DescriptorAllocator::new(
+            DESCRIPTOR_SETS_PER_POOL,
+            &[(DescriptorType::UNIFORM_BUFFER, 1)],
+        );
+        let descriptor_sets = create_descriptor_sets(
+            &self.device,
+            &mut descriptor_allocator,
+            self.ubo_layout,
+            &uniform_buffers,
+        );
+        (uniform_buffers, descriptor_allocator, descriptor_sets)
+    }
+
+    fn create_pipeline_stats_query(&self) -> Option<PipelineStatsQuery> {
+        if self.is_pipeline_stats_enabled {
+            Some(PipelineStatsQuery::new(&self.device, MAX_FRAMES_IN_FLIGHT))
+        } else {
+            None
+        }
+    }
+
+    fn pipeline_statistics(&self) -> QueryPipelineStatisticFlags {
+        if self.is_pipeline_stats_enabled {
+            PIPELINE_STATISTICS
+        } else {
+            QueryPipelineStatisticFlags::empty()
+        }
+    }
+
+    fn add_window(&mut self, window: Window) {
+        let surface = create_surface(&self.entry, &self.instance, &window);
+        let is_present_supported = unsafe {
+            surface
+                .surface_loader
+                .get_physical_device_surface_support(
+                    self.physical_device,
+                    self.queue_family.graphics_family.unwrap() as u32,
+                    surface.surface,
+                )
+                .unwrap_or(false)
+        };
+
+        let extent = window_extent(&window);
+        let mut render_contexts = std::mem::take(&mut self.render_contexts);
+        add_render_context(
+            &mut render_contexts,
+            window.id(),
+            surface,
+            is_present_supported,
+            |x| self.create_render_context(x, extent),
         );
+        self.render_contexts = render_contexts;
+        self.windows
+            .insert(window.id(), PotatoWindow::new(window, self.cursor));
+    }
 
-        let wait_semaphores = [self.image_available_semaphores[self.current_frame]];
-        let wait_stages = [PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
-        let signal_semaphores = [self.render_finished_semaphores[self.current_frame]];
+    fn remove_window(&mut self, window_id: WindowId) -> ControlFlow {
+        remove_render_context(
+            &mut self.render_contexts,
+            &mut self.windows,
+            &window_id,
+            &self.device,
+            self.command_pool,
+        )
+    }
 
-        let submit_infos = [SubmitInfo {
-            s_type: StructureType::SUBMIT_INFO,
-            p_next: std::ptr::null(),
-            wait_semaphore_count: wait_semaphores.len() as u32,
-            p_wait_semaphores: wait_semaphores.as_ptr(),
-            p_wait_dst_stage_mask: wait_stages.as_ptr(),
-            command_buffer_count: 1,
-            p_command_buffers: &self.command_buffers[image_index as usize],
-            signal_semaphore_count: signal_semaphores.len() as u32,
-            p_signal_semaphores: signal_semaphores.as_ptr(),
-        }];
+    pub fn draw(
+        &mut self,
+        window_id: WindowId,
+        delta_time: f32,
+    ) -> std::result::Result<(), PotatoError> {
+        self.shutdown_state.ensure_running()?;
+        let mut render_context = match self.render_contexts.remove(&window_id) {
+            Some(render_context) => render_context,
+            None => return Ok(()),
+        };
+        let window_extent = self.windows.get(&window_id).map(|x| window_extent(&x.window));
+        let result = match window_extent {
+            Some(extent) if should_pause(extent) => {
+                if !render_context.is_paused {
+                    debug!("Pausing rendering for minimized window {:?}", window_id);
+                    render_context.is_paused = true;
+                }
+                Ok(())
+            }
+            _ => self.draw_context(&mut render_context, delta_time),
+        };
+        if render_context.is_surface_lost {
+            if let Some(window) = self.windows.get(&window_id) {
+                render_context = self.recreate_surface(render_context, &window.window);
+            }
+        }
+        self.render_contexts.insert(window_id, render_context);
+        result
+    }
+
+    fn recreate_surface(&self, render_context: RenderContext, window: &Window) -> RenderContext {
+        warn!("Surface for window {:?} was lost, recreating it", window.id());
         unsafe {
             self.device
-                .reset_fences(&wait_fences)
-                .expect("Failed to reset Fence!");
+                .device_wait_idle()
+                .expect("Failed to wait on device")
+        };
+        render_context.destroy(&self.device, self.command_pool);
+        let surface = create_surface(&self.entry, &self.instance, window);
+        self.create_render_context(surface, window_extent(window))
+    }
 
-            self.device
-                .queue_submit(
-                    self.graphics_queue,
-                    &submit_infos,
-                    self.in_flight_fences[self.current_frame],
+    fn draw_context(
+        &self,
+        render_context: &mut RenderContext,
+        delta_time: f32,
+    ) -> std::result::Result<(), PotatoError> {
+        let frame_start = Instant::now();
+        let wait_fences = [render_context.in_flight_fences[render_context.current_frame]];
+        unsafe { self.device.wait_for_fences(&wait_fences, true, u64::MAX)? };
+        let fence_wait = frame_start.elapsed();
+
+        let result = match start_frame(&mut SwapchainFrameStart {
+            vulkan_api_objects: self,
+            render_context,
+        }) {
+            Some(result) => result,
+            None => return Ok(()),
+        };
+        let frame_time = frame_time_since(render_context.last_frame_start, frame_start);
+        render_context.last_frame_start = Some(frame_start);
+        let (image_index, is_sub_optimal) = match result {
+            Ok(image_index) => image_index,
+            Err(vk_result) => match swapchain_recovery(vk_result) {
+                Some(SwapchainRecovery::RecreateSwapchain) => {
+                    self.recreate_swapchain(render_context);
+                    return Ok(());
+                }
+                Some(SwapchainRecovery::RecreateSurface) => {
+                    render_context.is_surface_lost = true;
+                    return Ok(());
+                }
+                None => return Err(vk_result.into()),
+            },
+        };
+        if is_sub_optimal {
+            render_context.is_framebuffer_resized = true;
+        }
+
+        let gpu_time_ms = render_context
+            .gpu_timer
+            .as_ref()
+            .and_then(|x| x.read_ms(&self.device, render_context.current_frame));
+        if let Some(gpu_time_ms) = gpu_time_ms {
+            render_context.last_gpu_time_ms = gpu_time_ms;
+        }
+        render_context.frame_stats = FrameStats::new(frame_time, fence_wait, gpu_time_ms);
+        if let Some(pipeline_stats_query) = &render_context.pipeline_stats_query {
+            if let Some(stats) =
+                pipeline_stats_query.read(&self.device, render_context.current_frame)
+            {
+                render_context.last_pipeline_stats = Some(stats);
+            }
+        }
+
+        let ubo = self.camera(render_context.projection).unwrap_or_else(|| {
+            UniformBufferObject::demo_rotation(
+                render_context.projection,
+                delta_time,
+                self.is_animated,
+            )
+        });
+        update_uniform_buffer(
+            &ubo,
+            render_context.current_frame,
+            &render_context.uniform_buffers,
+            self.non_coherent_atom_size,
+        );
+        if self.is_frustum_culling_enabled {
+            self.record_culled_scene(render_context, &ubo);
+        }
+
+        let screenshot = if std::mem::take(&mut render_context.is_screenshot_requested) {
+            let physical_device_memory_properties = unsafe {
+                self.instance
+                    .get_physical_device_memory_properties(self.physical_device)
+            };
+            Some(ScreenshotCapture::new(
+                &self.device,
+                &physical_device_memory_properties,
+                &render_context.swapchain,
+                image_index as usize,
+            ))
+        } else {
+            None
+        };
+
+        let command_buffer = render_context.command_buffers[render_context.current_frame];
+        self.record_frame(
+            render_context,
+            command_buffer,
+            image_index as usize,
+            screenshot.as_ref(),
+        );
+
+        let signal_semaphores =
+            [render_context.render_finished_semaphores[render_context.current_frame]];
+
+        let submit_batch = SubmitBatch::new().add(
+            SubmitEntry::new(&[command_buffer])
+                .wait(
+                    render_context.image_available_semaphores[render_context.current_frame],
+                    PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
                 )
-                .expect("Failed to execute queue submit.");
+                .signal(signal_semaphores[0]),
+        );
+        unsafe {
+            self.device.reset_fences(&wait_fences)?;
+        }
+        submit_batch.submit(
+            &self.device,
+            self.graphics_queue,
+            render_context.in_flight_fences[render_context.current_frame],
+        )?;
+        if let Some(gpu_timer) = &mut render_context.gpu_timer {
+            gpu_timer.mark_submitted(render_context.current_frame);
+        }
+        if let Some(pipeline_stats_query) = &mut render_context.pipeline_stats_query {
+            pipeline_stats_query.mark_submitted(render_context.current_frame);
         }
 
-        let swapchains = [self.swapchain.swapchain];
+        let swapchains = [render_context.swapchain.swapchain];
 
         let present_info = PresentInfoKHR {
             s_type: StructureType::PRESENT_INFO_KHR,
@@ -286,113 +993,1031 @@ impl VulkanApiObjects {
         };
 
         let result = unsafe {
-            self.swapchain
+            render_context
+                .swapchain
                 .swapchain_loader
-                .queue_present(self.graphics_queue, &present_info)
+                .queue_present(self.present_queue, &present_info)
         };
 
+        if let Some(screenshot) = screenshot {
+            unsafe {
+                self.device.wait_for_fences(&wait_fences, true, u64::MAX)?;
+            }
+            save_screenshot(&screenshot, &self.device);
+            screenshot.destroy(&self.device);
+        }
+
         let is_resized = match result {
             Ok(_) => false,
-            Err(vk_result) => match vk_result {
-                Result::ERROR_OUT_OF_DATE_KHR | Result::SUBOPTIMAL_KHR => true,
-                _ => panic!("Failed to execute queue present"),
+            Err(vk_result) => match swapchain_recovery(vk_result) {
+                Some(SwapchainRecovery::RecreateSwapchain) => true,
+                Some(SwapchainRecovery::RecreateSurface) => {
+                    render_context.is_surface_lost = true;
+                    false
+                }
+                None => return Err(vk_result.into()),
             },
         };
 
         if is_resized {
-            self.recreate_swapchain();
+            render_context.is_framebuffer_resized = true;
         }
 
-        self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+        render_context.current_frame = (render_context.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+        Ok(())
     }
 
-    fn recreate_swapchain(&mut self) {
+    fn recreate_swapchain(&self, render_context: &mut RenderContext) {
+        let capabilities =
+            determine_swapchain_support(self.physical_device, &render_context.surface).capabilities;
+        render_context.is_paused = should_pause(capabilities.current_extent);
+        if render_context.is_paused {
+            return;
+        }
+
         unsafe {
             self.device
                 .device_wait_idle()
                 .expect("Failed to wait on device")
         };
-        self.cleanup_swapchain();
-
-        self.swapchain = create_swapchain(
-            &self.instance,
+        let window_extent = render_context.window_extent;
+        render_context.replace_swapchain(&self.device, |surface| {
+            create_swapchain(
+                &self.instance,
+                &self.device,
+                self.physical_device,
+                surface,
+                &self.queue_family,
+                &self.swapchain_config,
+                window_extent,
+            )
+        });
+        render_context.projection = create_projection(render_context.swapchain.swapchain_extent);
+        render_context.render_target_formats =
+            self.render_target_formats(&render_context.swapchain);
+        render_context.render_pass =
+            self.create_context_render_pass(&render_context.render_target_formats);
+        let (depth_attachment, msaa_color_attachment) =
+            self.create_attachments(&render_context.swapchain);
+        render_context.depth_attachment = depth_attachment;
+        render_context.msaa_color_attachment = msaa_color_attachment;
+        let (graphics_pipeline, pipeline_layout) = create_graphics_pipeline(
             &self.device,
-            self.physical_device,
-            &self.surface,
-            &self.queue_family,
+            self.pipeline_cache,
+            &render_context.pipeline_target(),
+            (self.ubo_layout, self.material_layout),
+            self.shaders,
+            &self.vertex_layout,
+            &self.pipeline_config,
         );
-        self.render_pass = create_render_pass(&self.device, self.swapchain.swapchain_format);
-        let (graphics_pipeline, pipeline_layout) = create_graphics_pipeline(
+        render_context.graphics_pipeline = graphics_pipeline;
+        render_context.pipeline_layout = pipeline_layout;
+        render_context.debug_line_pipeline = create_debug_line_pipeline(
             &self.device,
-            self.render_pass,
-            self.swapchain.swapchain_extent,
+            self.pipeline_cache,
+            &render_context.pipeline_target(),
             self.ubo_layout,
+            &self.pipeline_config,
         );
-        self.graphics_pipeline = graphics_pipeline;
-        self.pipeline_layout = pipeline_layout;
-        self.swapchain_framebuffers = create_framebuffers(
+        render_context.overlay_pipeline = create_overlay_pipeline(
             &self.device,
-            self.render_pass,
-            &self.swapchain.swapchain_image_views,
-            &self.swapchain.swapchain_extent,
+            self.pipeline_cache,
+            &render_context.pipeline_target(),
+            &self.pipeline_config,
         );
-        self.command_buffers = create_command_buffers(
+        render_context.gpu_timer = self.create_gpu_timer();
+        render_context.pipeline_stats_query = self.create_pipeline_stats_query();
+        render_context.particle_pipeline = self.particle_system.as_ref().map(|_| {
+            create_particle_pipeline(
+                &self.device,
+                self.pipeline_cache,
+                &render_context.pipeline_target(),
+                &self.pipeline_config,
+            )
+        });
+        render_context.text_pipeline = self.text_renderer.as_ref().map(|text_renderer| {
+            create_text_pipeline(
+                &self.device,
+                self.pipeline_cache,
+                &render_context.pipeline_target(),
+                text_renderer.descriptor_set_layout,
+                &self.pipeline_config,
+            )
+        });
+        render_context.swapchain_framebuffers = create_framebuffers(
             &self.device,
-            self.command_pool,
-            graphics_pipeline,
-            &self.swapchain_framebuffers,
-            self.render_pass,
-            self.swapchain.swapchain_extent,
-            self.vertex_buffer,
-            self.index_buffer,
-            self.pipeline_layout,
-            &self.descriptor_sets,
+            render_context.render_pass,
+            &render_context.swapchain.swapchain_image_views,
+            Some(render_context.depth_attachment.view),
+            render_context.msaa_color_attachment.as_ref().map(|x| x.view),
+            &render_context.swapchain.swapchain_extent,
         );
+        self.set_context_debug_names(render_context);
+        self.record_context_command_buffers(render_context);
     }
 
-    fn cleanup_swapchain(&self) {
-        unsafe {
-            self.device
-                .free_command_buffers(self.command_pool, &self.command_buffers);
-            self.swapchain_framebuffers
-                .iter()
-                .for_each(|x| self.device.destroy_framebuffer(*x, None));
-            self.device.destroy_pipeline(self.graphics_pipeline, None);
-            self.device
-                .destroy_pipeline_layout(self.pipeline_layout, None);
-            self.device.destroy_render_pass(self.render_pass, None);
-            self.swapchain
-                .swapchain_image_views
-                .iter()
-                .for_each(|x| self.device.destroy_image_view(*x, None));
-            self.swapchain
-                .swapchain_loader
-                .destroy_swapchain(self.swapchain.swapchain, None);
+    pub fn record_command_buffers(&mut self) {
+        let mut render_contexts = std::mem::take(&mut self.render_contexts);
+        render_contexts
+            .values_mut()
+            .for_each(|x| self.record_context_command_buffers(x));
+        self.render_contexts = render_contexts;
+    }
+
+    // Only the given window's context is touched, other windows keep their command buffers.
+    pub fn record_window_command_buffers(&mut self, window_id: WindowId) {
+        if let Some(mut render_context) = self.render_contexts.remove(&window_id) {
+            self.record_context_command_buffers(&mut render_context);
+            self.render_contexts.insert(window_id, render_context);
         }
     }
 
-    fn init_window(event_loop: &EventLoopWindowTarget<()>, name: &str) -> Window {
-        WindowBuilder::new()
+    pub fn resize_window(&mut self, window_id: WindowId, width: u32, height: u32) {
+        if let Some(mut render_context) = self.render_contexts.remove(&window_id) {
+            render_context.request_resize(Extent2D { width, height });
+            self.render_contexts.insert(window_id, render_context);
+        }
+    }
+
+    fn record_frame(
+        &self,
+        render_context: &RenderContext,
+        command_buffer: CommandBuffer,
+        image_index: usize,
+        screenshot: Option<&ScreenshotCapture>,
+    ) {
+        let mut secondary_command_buffers =
+            vec![render_context.scene_command_buffers[render_context.current_frame]];
+        secondary_command_buffers.extend_from_slice(&render_context.parallel_command_buffers);
+        let clear_values = create_clear_values(
+            resolve_clear_color(
+                self.clear_color,
+                self.clear_color_space,
+                render_context.swapchain.swapchain_format,
+            ),
+            self.pipeline_config.depth.clear_value,
+        );
+        let frame = FrameRecording {
+            frame_index: render_context.current_frame,
+            render_pass: render_context.render_pass,
+            framebuffer: render_context.swapchain_framebuffers[image_index],
+            surface_extent: render_context.swapchain.swapchain_extent,
+            clear_values: &clear_values,
+            particle_system: self
+                .particle_system
+                .as_ref()
+                .filter(|_| render_context.particle_pipeline.is_some()),
+            gpu_timer: render_context.gpu_timer.as_ref(),
+            pipeline_stats_query: render_context.pipeline_stats_query.as_ref(),
+            secondary_command_buffers: &secondary_command_buffers,
+            screenshot,
+        };
+        record_frame_command_buffer(&self.device, command_buffer, &frame, self.debug_utils());
+    }
+
+    fn record_context_command_buffers(&self, render_context: &mut RenderContext) {
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .expect("Failed to wait on device");
+        }
+        render_context.free_command_buffers(&self.device, self.command_pool);
+        while render_context.worker_command_pools.len() < self.parallel_chunks.len() {
+            render_context.worker_command_pools.push(create_command_pool_for_family(
+                &self.device,
+                self.queue_family.graphics_family.unwrap() as u32,
+            ));
+        }
+        render_context.parallel_command_buffers = record_parallel(
+            &self.device,
+            &render_context.worker_command_pools,
+            render_context.render_pass,
+            &self.parallel_chunks,
+            self.pipeline_statistics(),
+        );
+        let (command_buffers, scene_command_buffers) = create_command_buffers(
+            &self.device,
+            self.command_pool,
+            render_context.render_pass,
+            &render_context.descriptor_sets,
+            &self.scene_draws(render_context, &self.renderables),
+            self.pipeline_statistics(),
+        );
+        render_context.command_buffers = command_buffers;
+        render_context.scene_command_buffers = scene_command_buffers;
+    }
+
+    // The scene buffer of the current frame is no longer in flight once its fence was waited on,
+    // so it is re-recorded against the uniform uploaded for this frame.
+    fn record_culled_scene(&self, render_context: &RenderContext, camera: &UniformBufferObject) {
+        let visible_renderables: Vec<Renderable> =
+            cull(&self.renderables, camera).into_iter().copied().collect();
+        record_scene_command_buffer(
+            &self.device,
+            render_context.scene_command_buffers[render_context.current_frame],
+            render_context.render_pass,
+            &self.scene_draws(render_context, &visible_renderables),
+            render_context.descriptor_sets[render_context.current_frame],
+            self.pipeline_statistics(),
+        );
+    }
+
+    fn scene_draws<'a>(
+        &'a self,
+        render_context: &'a RenderContext,
+        renderables: &'a [Renderable],
+    ) -> SceneDraws<'a> {
+        SceneDraws {
+            graphics_pipeline: (render_context.graphics_pipeline, render_context.pipeline_layout),
+            renderables,
+            particles: self
+                .particle_system
+                .as_ref()
+                .zip(render_context.particle_pipeline)
+                .map(|(particle_system, (particle_pipeline, _))| {
+                    (particle_system, particle_pipeline)
+                }),
+            debug_lines: (&self.debug_lines, render_context.debug_line_pipeline),
+            overlay: (&self.overlay, render_context.overlay_pipeline),
+            text: self.text_renderer.as_ref().zip(render_context.text_pipeline),
+        }
+    }
+
+    pub fn create_offscreen_target(
+        &self,
+        width: u32,
+        height: u32,
+        format: Format,
+    ) -> OffscreenTarget {
+        let physical_device_memory_properties = unsafe {
+            self.instance
+                .get_physical_device_memory_properties(self.physical_device)
+        };
+        OffscreenTarget::create(
+            &self.device,
+            &physical_device_memory_properties,
+            Extent2D { width, height },
+            format,
+            self.depth_format,
+            self.pipeline_config.samples,
+        )
+    }
+
+    pub fn create_post_process(
+        &self,
+        window_id: WindowId,
+        fragment_shader: ShaderSource,
+        source: &OffscreenTarget,
+    ) -> Option<PostProcess> {
+        self.render_contexts.get(&window_id).map(|render_context| {
+            PostProcess::new(
+                &self.device,
+                self.pipeline_cache,
+                &render_context.pipeline_target(),
+                fragment_shader,
+                source,
+                self.texture_sampler,
+                &self.pipeline_config,
+            )
+        })
+    }
+
+    pub fn destroy_post_process(&self, post_process: PostProcess) {
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .expect("Failed to wait on device");
+        }
+        post_process.destroy(&self.device);
+    }
+
+    pub fn destroy_offscreen_target(&self, offscreen_target: OffscreenTarget) {
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .expect("Failed to wait on device");
+        }
+        offscreen_target.destroy(&self.device);
+    }
+
+    pub fn load_texture(
+        &mut self,
+        path: &str,
+        fallback_png_path: &str,
+    ) -> std::result::Result<Texture, PotatoError> {
+        load_texture(
+            &self.instance,
+            &self.device,
+            self.physical_device,
+            &self.upload_queues,
+            &mut self.staging_buffer,
+            path,
+            fallback_png_path,
+        )
+    }
+
+    pub fn sampler(&mut self, preset: SamplerPreset) -> Sampler {
+        if let Some(sampler) = self.samplers.get(&preset) {
+            return *sampler;
+        }
+        let limits = unsafe {
+            self.instance
+                .get_physical_device_properties(self.physical_device)
+                .limits
+        };
+        let sampler = create_texture_sampler(
+            &self.device,
+            &self.enabled_features,
+            &limits,
+            preset,
+            &self.sampler_options,
+        );
+        self.set_debug_name(sampler, &format!("{:?} Sampler", preset));
+        self.samplers.insert(preset, sampler);
+        sampler
+    }
+
+    pub fn create_sampler(&self, preset: SamplerPreset, options: &SamplerOptions) -> Sampler {
+        let limits = unsafe {
+            self.instance
+                .get_physical_device_properties(self.physical_device)
+                .limits
+        };
+        create_texture_sampler(&self.device, &self.enabled_features, &limits, preset, options)
+    }
+
+    pub fn destroy_sampler(&self, sampler: Sampler) {
+        unsafe {
+            self.device.destroy_sampler(sampler, None);
+        }
+    }
+
+    pub fn create_material(
+        &mut self,
+        image_view: ImageView,
+        sampler: Sampler,
+    ) -> std::result::Result<DescriptorSet, PotatoError> {
+        let descriptor_set = self
+            .material_allocator
+            .allocate(&self.device, self.material_layout)?;
+        write_texture_descriptor(
+            &self.device,
+            descriptor_set,
+            MATERIAL_TEXTURE_BINDING,
+            image_view,
+            sampler,
+        );
+        Ok(descriptor_set)
+    }
+
+    pub fn update_descriptor_texture(
+        &mut self,
+        set_index: usize,
+        image_view: ImageView,
+        sampler: Sampler,
+    ) -> std::result::Result<(), PotatoError> {
+        let descriptor_set = self
+            .renderables
+            .get(set_index)
+            .and_then(|x| x.descriptor_set)
+            .ok_or_else(|| {
+                PotatoError::InvalidTexture(format!(
+                    "renderable {} has no material descriptor set",
+                    set_index
+                ))
+            })?;
+        // Recorded frames still reference the set, so they must finish before it changes.
+        unsafe { self.device.device_wait_idle()? };
+        write_texture_descriptor(
+            &self.device,
+            descriptor_set,
+            MATERIAL_TEXTURE_BINDING,
+            image_view,
+            sampler,
+        );
+        self.record_command_buffers();
+        Ok(())
+    }
+
+    pub fn destroy_texture(&self, texture: Texture) {
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .expect("Failed to wait on device");
+        }
+        texture.destroy(&self.device);
+    }
+
+    pub fn record_parallel(&mut self, chunks: Vec<RecordChunk>) {
+        self.parallel_chunks = chunks;
+        self.record_command_buffers();
+    }
+
+    fn buffer_uploader(&mut self) -> BufferUploader<'_> {
+        BufferUploader::new(
+            &self.instance,
+            &self.device,
+            self.physical_device,
+            &self.upload_queues,
+            &mut self.staging_buffer,
+        )
+    }
+
+    pub fn create_mesh(&mut self, vertices: &[Vertex], indices: &[u32]) -> Mesh {
+        let mesh = create_mesh(&mut self.buffer_uploader(), vertices, indices);
+        self.set_mesh_debug_names(&mesh);
+        self.meshes.push(mesh);
+        mesh
+    }
+
+    pub fn create_combined_mesh(&mut self, vertices: &[Vertex], indices: &[u32]) -> Mesh {
+        let mesh = create_combined_mesh(&mut self.buffer_uploader(), vertices, indices);
+        self.set_mesh_debug_names(&mesh);
+        self.meshes.push(mesh);
+        mesh
+    }
+
+    // Indirect draws keep their arguments buffer, so rewrite it if the index count changed. A
+    // combined mesh is rebuilt combined, so its renderables pick up the new index offset.
+    pub fn update_mesh(
+        &mut self,
+        mesh: Mesh,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> std::result::Result<Mesh, PotatoError> {
+        self.shutdown_state.ensure_running()?;
+        unsafe { self.device.device_wait_idle()? };
+        let new_mesh = if mesh.is_combined() {
+            self.create_combined_mesh(vertices, indices)
+        } else {
+            self.create_mesh(vertices, indices)
+        };
+        replace_mesh(&mut self.renderables, &mesh, new_mesh, Aabb::from_vertices(vertices));
+        if let Some(index) = self
+            .meshes
+            .iter()
+            .position(|x| x.vertex_buffer == mesh.vertex_buffer)
+        {
+            self.meshes.remove(index).destroy(&self.device);
+        }
+        self.record_command_buffers();
+        Ok(new_mesh)
+    }
+
+    pub fn add_renderable(&mut self, renderable: Renderable) {
+        self.renderables.push(renderable);
+        self.record_command_buffers();
+    }
+
+    pub fn set_renderables(&mut self, renderables: Vec<Renderable>) {
+        self.renderables = renderables;
+        self.record_command_buffers();
+    }
+
+    pub fn renderables(&self) -> &[Renderable] {
+        &self.renderables
+    }
+
+    pub fn draw_lines(&mut self, segments: &[(Vertex, Vertex)]) {
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .expect("Failed to wait on device")
+        };
+        let physical_device_memory_properties = unsafe {
+            self.instance
+                .get_physical_device_memory_properties(self.physical_device)
+        };
+        self.debug_lines
+            .upload(&self.device, &physical_device_memory_properties, segments);
+        self.record_command_buffers();
+    }
+
+    pub fn create_indirect_buffer(
+        &self,
+        commands: &[DrawIndexedIndirectCommand],
+    ) -> std::result::Result<IndirectBuffer, PotatoError> {
+        check_indirect_draw_count(
+            commands.len() as u32,
+            self.enabled_features.multi_draw_indirect == TRUE,
+        )?;
+        let physical_device_memory_properties = unsafe {
+            self.instance
+                .get_physical_device_memory_properties(self.physical_device)
+        };
+        Ok(IndirectBuffer::new(
+            &self.device,
+            &physical_device_memory_properties,
+            commands,
+        ))
+    }
+
+    pub fn begin_2d(&mut self, window_id: WindowId) {
+        match self.render_contexts.get(&window_id) {
+            Some(render_context) => self.overlay.begin(render_context.swapchain.swapchain_extent),
+            None => warn!("Unknown window {:?}, ignoring begin_2d", window_id),
+        }
+    }
+
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) {
+        self.overlay.rect(x, y, width, height, color);
+    }
+
+    pub fn line(&mut self, a: [f32; 2], b: [f32; 2], color: [f32; 4]) {
+        self.overlay.line(a, b, color);
+    }
+
+    pub fn end_2d(&mut self) {
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .expect("Failed to wait on device")
+        };
+        let physical_device_memory_properties = unsafe {
+            self.instance
+                .get_physical_device_memory_properties(self.physical_device)
+        };
+        self.overlay.upload(&self.device, &physical_device_memory_properties);
+        self.record_command_buffers();
+    }
+
+    pub fn load_font(
+        &mut self,
+        path: &str,
+        fallback_png_path: &str,
+        font: BitmapFont,
+    ) -> std::result::Result<(), PotatoError> {
+        let texture = self.load_texture(path, fallback_png_path)?;
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .expect("Failed to wait on device");
+        }
+        self.destroy_text_pipelines();
+        if let Some(text_renderer) = self.text_renderer.take() {
+            text_renderer.destroy(&self.device);
+        }
+        let text_renderer = TextRenderer::new(&self.device, font, texture, self.texture_sampler);
+        for render_context in self.render_contexts.values_mut() {
+            render_context.text_pipeline = Some(create_text_pipeline(
+                &self.device,
+                self.pipeline_cache,
+                &render_context.pipeline_target(),
+                text_renderer.descriptor_set_layout,
+                &self.pipeline_config,
+            ));
+        }
+        self.text_renderer = Some(text_renderer);
+        self.record_command_buffers();
+        Ok(())
+    }
+
+    pub fn draw_text(&mut self, text: &str, x: f32, y: f32) {
+        if self.text_renderer.is_none() {
+            warn!("No font loaded, ignoring draw_text");
+            return;
+        }
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .expect("Failed to wait on device")
+        };
+        let physical_device_memory_properties = unsafe {
+            self.instance
+                .get_physical_device_memory_properties(self.physical_device)
+        };
+        if let Some(text_renderer) = &mut self.text_renderer {
+            text_renderer.append(text, x, y);
+            text_renderer.upload(&self.device, &physical_device_memory_properties);
+        }
+        self.record_command_buffers();
+    }
+
+    pub fn clear_text(&mut self) {
+        if let Some(text_renderer) = &mut self.text_renderer {
+            text_renderer.clear();
+            self.record_command_buffers();
+        }
+    }
+
+    fn destroy_text_pipelines(&mut self) {
+        for render_context in self.render_contexts.values_mut() {
+            if let Some((pipeline, pipeline_layout)) = render_context.text_pipeline.take() {
+                unsafe {
+                    self.device.destroy_pipeline(pipeline, None);
+                    self.device.destroy_pipeline_layout(pipeline_layout, None);
+                }
+            }
+        }
+    }
+
+    pub fn set_uniform(&mut self, ubo: UniformBufferObject) {
+        self.uniform = Some(ubo);
+        self.is_demo_rotation = false;
+    }
+
+    fn camera(&self, projection: Matrix4<f32>) -> Option<UniformBufferObject> {
+        match self.uniform {
+            Some(ubo) if !self.is_demo_rotation => Some(UniformBufferObject {
+                proj: if self.is_projection_from_extent {
+                    projection
+                } else {
+                    ubo.proj
+                },
+                ..ubo
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn cull(&self, camera: &UniformBufferObject) -> Vec<&Renderable> {
+        cull(&self.renderables, camera)
+    }
+
+    pub fn set_frustum_culling(&mut self, is_enabled: bool) {
+        self.is_frustum_culling_enabled = is_enabled;
+        self.record_command_buffers();
+    }
+
+    pub fn set_clear_color(&mut self, clear_color: [f32; 4]) {
+        self.clear_color = clear_color;
+    }
+
+    pub fn clear_color(&self) -> [f32; 4] {
+        self.clear_color
+    }
+
+    pub fn set_demo_rotation(&mut self, is_enabled: bool) {
+        self.is_demo_rotation = is_enabled;
+    }
+
+    pub fn set_animated(&mut self, is_animated: bool) {
+        self.is_animated = is_animated;
+    }
+
+    pub fn set_projection_from_extent(&mut self, is_enabled: bool) {
+        self.is_projection_from_extent = is_enabled;
+    }
+
+    pub fn recent_validation_messages(&self) -> Vec<String> {
+        self.validation_log.recent()
+    }
+
+    pub fn last_gpu_time_ms(&self, window_id: WindowId) -> Option<f32> {
+        self.render_contexts
+            .get(&window_id)
+            .filter(|x| x.gpu_timer.is_some())
+            .map(|x| x.last_gpu_time_ms)
+    }
+
+    pub fn pipeline_stats(&self, window_id: WindowId) -> Option<PipelineStats> {
+        self.render_contexts
+            .get(&window_id)
+            .and_then(|x| x.last_pipeline_stats)
+    }
+
+    // Validation is chosen when the instance is built, so callers opt out with benchmark_mode().
+    pub fn benchmark(&self, frames: u32) -> Result<FrameStats, PotatoError> {
+        if self.is_debug_utils_enabled {
+            return Err(PotatoError::BenchmarkWithValidation);
+        }
+
+        let extent = self
+            .render_contexts
+            .values()
+            .next()
+            .map_or(BENCHMARK_EXTENT, |x| x.swapchain.swapchain_extent);
+        let offscreen_target =
+            self.create_offscreen_target(extent.width, extent.height, BENCHMARK_FORMAT);
+        let formats = RenderTargetFormats {
+            color: BENCHMARK_FORMAT,
+            depth: Some(self.depth_format),
+            samples: self.pipeline_config.samples,
+        };
+        let (pipeline, pipeline_layout) = create_graphics_pipeline(
+            &self.device,
+            self.pipeline_cache,
+            &PipelineTarget {
+                render_pass: offscreen_target.render_pass,
+                formats,
+                extent,
+            },
+            (self.ubo_layout, self.material_layout),
+            self.shaders,
+            &self.vertex_layout,
+            &self.pipeline_config,
+        );
+        let (uniform_buffers, descriptor_allocator, descriptor_sets) =
+            self.create_uniform_resources(1);
+        update_uniform_buffer(
+            &UniformBufferObject::demo_rotation(create_projection(extent), 0.0, self.is_animated),
+            0,
+            &uniform_buffers,
+            self.non_coherent_atom_size,
+        );
+
+        let mut gpu_timer = self.create_gpu_timer();
+        let samples: Vec<FrameStats> = (0..frames)
+            .map(|_| {
+                let frame_start = Instant::now();
+                single_time_commands(
+                    &self.device,
+                    self.command_pool,
+                    self.graphics_queue,
+                    |command_buffer| {
+                        if let Some(gpu_timer) = &gpu_timer {
+                            gpu_timer.record_start(&self.device, command_buffer, 0);
+                        }
+                        offscreen_target.begin(
+                            &self.device,
+                            command_buffer,
+                            self.clear_color,
+                            self.pipeline_config.depth.clear_value,
+                        );
+                        unsafe {
+                            self.device.cmd_bind_pipeline(
+                                command_buffer,
+                                PipelineBindPoint::GRAPHICS,
+                                pipeline,
+                            );
+                        }
+                        for renderable in &self.renderables {
+                            record_renderable(
+                                &self.device,
+                                command_buffer,
+                                renderable,
+                                pipeline_layout,
+                                descriptor_sets[0],
+                            );
+                        }
+                        offscreen_target.end(&self.device, command_buffer);
+                        if let Some(gpu_timer) = &gpu_timer {
+                            gpu_timer.record_end(&self.device, command_buffer, 0);
+                        }
+                    },
+                );
+                let wait_start = Instant::now();
+                unsafe {
+                    self.device
+                        .device_wait_idle()
+                        .expect("Failed to wait on device");
+                }
+                let gpu_time_ms = gpu_timer.as_mut().and_then(|x| {
+                    x.mark_submitted(0);
+                    x.read_ms(&self.device, 0)
+                });
+                FrameStats::new(frame_start.elapsed(), wait_start.elapsed(), gpu_time_ms)
+            })
+            .collect();
+
+        if let Some(gpu_timer) = &gpu_timer {
+            gpu_timer.destroy(&self.device);
+        }
+        unsafe {
+            self.device.destroy_pipeline(pipeline, None);
+            self.device.destroy_pipeline_layout(pipeline_layout, None);
+        }
+        descriptor_allocator.destroy(&self.device);
+        drop(uniform_buffers);
+        offscreen_target.destroy(&self.device);
+        Ok(FrameStats::average(&samples))
+    }
+
+    pub fn frame_stats(&self, window_id: WindowId) -> Option<FrameStats> {
+        self.render_contexts.get(&window_id).map(|x| x.frame_stats)
+    }
+
+    pub fn shutdown(&mut self) {
+        if !self.shutdown_state.begin() {
+            return;
+        }
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .expect("Failed to wait on device");
+            for (.., render_context) in self.render_contexts.drain() {
+                render_context.destroy(&self.device, self.command_pool);
+            }
+            if let Some(particle_system) = &self.particle_system {
+                particle_system.destroy(&self.device);
+            }
+            self.debug_lines.release();
+            self.overlay.release();
+            self.indirect_buffers.clear();
+            if let Some(text_renderer) = self.text_renderer.take() {
+                text_renderer.destroy(&self.device);
+            }
+            save_pipeline_cache(&self.device, self.pipeline_cache);
+            self.device.destroy_pipeline_cache(self.pipeline_cache, None);
+            self.device.destroy_sampler(self.texture_sampler, None);
+            for (.., sampler) in self.samplers.drain() {
+                self.device.destroy_sampler(sampler, None);
+            }
+            self.device
+                .destroy_descriptor_set_layout(self.ubo_layout, None);
+            for texture in self.material_textures.drain(..) {
+                texture.destroy(&self.device);
+            }
+            self.material_allocator.destroy(&self.device);
+            self.device
+                .destroy_descriptor_set_layout(self.material_layout, None);
+            self.staging_buffer.destroy(&self.device);
+            for mesh in self.meshes.drain(..) {
+                mesh.destroy(&self.device);
+            }
+            if self.upload_queues.is_dedicated_transfer() {
+                self.device
+                    .destroy_command_pool(self.upload_queues.transfer_command_pool, None);
+            }
+            self.device.destroy_command_pool(self.command_pool, None);
+            destroy_device(&self.device);
+            destroy_debug_messenger(&self.debug_utils_loader, self.debug_messenger);
+            self.instance.destroy_instance(None);
+        }
+    }
+
+    pub fn swapchain_extent(&self, window_id: WindowId) -> Option<(u32, u32)> {
+        self.render_contexts.get(&window_id).map(|x| {
+            let extent = x.swapchain.swapchain_extent;
+            (extent.width, extent.height)
+        })
+    }
+
+    pub fn command_buffers(&self, window_id: WindowId) -> Option<Vec<CommandBuffer>> {
+        self.render_contexts.get(&window_id).map(|x| {
+            let mut command_buffers = x.command_buffers.clone();
+            command_buffers.extend_from_slice(&x.scene_command_buffers);
+            command_buffers
+        })
+    }
+
+    pub fn swapchain_image_count(&self, window_id: WindowId) -> Option<usize> {
+        self.render_contexts
+            .get(&window_id)
+            .map(|x| x.swapchain.swapchain_images.len())
+    }
+
+    pub fn memory_budget(&self) -> Vec<(u64, u64)> {
+        query_memory_budget(
+            &self.instance,
+            self.physical_device,
+            self.is_memory_budget_enabled,
+        )
+    }
+
+    pub fn supported_sample_counts(&self) -> Vec<SampleCountFlags> {
+        let limits = unsafe {
+            self.instance
+                .get_physical_device_properties(self.physical_device)
+                .limits
+        };
+        decode_sample_counts(find_supported_sample_counts(&limits))
+    }
+
+    pub fn device_info(&self) -> DeviceInfo {
+        query_device_info(&self.instance, self.physical_device)
+    }
+
+    pub fn list_physical_devices(
+        entry: &Entry,
+        instance: &Instance,
+        window: &Window,
+    ) -> Vec<DeviceInfo> {
+        list_physical_devices(entry, instance, window)
+    }
+
+    pub fn available_devices(&self, window_id: WindowId) -> Option<Vec<DeviceInfo>> {
+        self.render_contexts
+            .get(&window_id)
+            .map(|x| list_surface_devices(&self.instance, &x.surface))
+    }
+
+    // The copy is recorded into the next frame, before that frame's image is presented.
+    fn take_screenshot(&mut self, window_id: WindowId) {
+        let render_context = match self.render_contexts.get_mut(&window_id) {
+            Some(render_context) => render_context,
+            None => return,
+        };
+
+        let swapchain = &render_context.swapchain;
+        if !swapchain
+            .swapchain_image_usage
+            .contains(ImageUsageFlags::TRANSFER_SRC)
+            || !is_supported_screenshot_format(swapchain.swapchain_format)
+        {
+            warn!(
+                "Swapchain format {:?} with usage {:?} can't be captured",
+                swapchain.swapchain_format, swapchain.swapchain_image_usage
+            );
+            return;
+        }
+        render_context.is_screenshot_requested = true;
+    }
+
+    fn toggle_fullscreen(&mut self, window_id: WindowId) {
+        let potato_window = match self.windows.get_mut(&window_id) {
+            Some(potato_window) => potato_window,
+            None => return,
+        };
+
+        let display_mode = potato_window.display_mode.toggled();
+        match display_mode {
+            DisplayMode::Fullscreen => {
+                let monitor = potato_window
+                    .window
+                    .current_monitor()
+                    .or_else(|| potato_window.window.primary_monitor());
+                if monitor.is_none() {
+                    warn!("No monitor found for window {:?}, staying windowed", window_id);
+                    return;
+                }
+                potato_window
+                    .window
+                    .set_fullscreen(Some(Fullscreen::Borderless(monitor)));
+            }
+            DisplayMode::Windowed => potato_window.window.set_fullscreen(None),
+        }
+        potato_window.display_mode = display_mode;
+        if let Some(render_context) = self.render_contexts.get_mut(&window_id) {
+            render_context.is_framebuffer_resized = true;
+        }
+    }
+
+    pub fn set_cursor_visible(&mut self, window_id: WindowId, is_visible: bool) {
+        if let Some(potato_window) = self.windows.get_mut(&window_id) {
+            potato_window.set_cursor_visible(is_visible);
+        }
+    }
+
+    pub fn set_cursor_grabbed(&mut self, window_id: WindowId, is_grabbed: bool) {
+        if let Some(potato_window) = self.windows.get_mut(&window_id) {
+            potato_window.set_cursor_grabbed(is_grabbed);
+        }
+    }
+
+    fn toggle_cursor_grab(&mut self, window_id: WindowId) {
+        if let Some(potato_window) = self.windows.get_mut(&window_id) {
+            let cursor = potato_window.cursor.toggled_grab();
+            debug!("Setting cursor for {:?} to {:?}", window_id, cursor);
+            potato_window.set_cursor(cursor);
+        }
+    }
+
+    fn init_window(event_loop: &EventLoopWindowTarget<()>, name: &str) -> Window {
+        WindowBuilder::new()
             .with_title(name)
             .with_inner_size(LogicalSize::new(800, 600))
             .build(event_loop)
             .expect("Failed to create window.")
     }
 
+    pub fn run(builder: VulkanApiObjectsBuilder) -> std::result::Result<(), PotatoError> {
+        debug!("Init event_loop");
+        let event_loop = EventLoop::new();
+        let vulkan_api_objects = VulkanApiObjects::init(&event_loop, builder)?;
+        debug!("Starting event loop");
+        vulkan_api_objects.init_event_loop(event_loop);
+        Ok(())
+    }
+
     pub fn init_event_loop(mut self, event_loop: EventLoop<()>) {
         let time = std::time::Instant::now();
         let mut delta_frame = 0;
+        let mut frame_start = Instant::now();
         event_loop.run(move |event, event_loop, control_flow| {
-            *control_flow = ControlFlow::Wait;
+            *control_flow = control_flow_for(self.windows.len());
 
             match event {
                 Event::WindowEvent { event, window_id } => {
+                    if let WindowEvent::Resized(size) = event {
+                        if let Some(render_context) = self.render_contexts.get_mut(&window_id) {
+                            render_context.request_resize(Extent2D {
+                                width: size.width,
+                                height: size.height,
+                            });
+                        }
+                    }
+
+                    if let WindowEvent::ScaleFactorChanged { scale_factor, .. } = &event {
+                        debug!("Window {:?} scale factor is now {}", window_id, scale_factor);
+                        // Keep the logical size and let the physical size follow the new scale.
+                        if let (Some(potato_window), Some(render_context)) = (
+                            self.windows.get_mut(&window_id),
+                            self.render_contexts.get_mut(&window_id),
+                        ) {
+                            let logical_size = potato_window
+                                .window
+                                .inner_size()
+                                .to_logical(potato_window.scale_factor);
+                            potato_window.scale_factor = *scale_factor;
+                            render_context
+                                .request_resize(physical_extent(logical_size, *scale_factor));
+                        }
+                    }
+
                     if let WindowEvent::CloseRequested = event {
                         println!("Window {:?} has received the signal to close", window_id);
-                        self.windows.remove(&window_id);
-                        if self.windows.is_empty() {
-                            *control_flow = ControlFlow::Exit;
-                        }
+                        *control_flow = self.remove_window(window_id);
                     }
 
                     if let WindowEvent::KeyboardInput {
@@ -406,72 +2031,471 @@ impl VulkanApiObjects {
                         ..
                     } = event
                     {
-                        //TODO abstract keyboard input logic
-                        if state == ElementState::Released
-                            && virtual_keycode == Some(VirtualKeyCode::N)
-                            && !is_synthetic
-                        {
-                            let window = VulkanApiObjects::init_window(event_loop, "spawn");
-                            self.windows.insert(window.id(), window);
+                        let action = match virtual_keycode {
+                            Some(key) if state == ElementState::Released && !is_synthetic => {
+                                self.input_map.action(key)
+                            }
+                            _ => None,
+                        };
+
+                        match action {
+                            Some(Action::SpawnWindow) => {
+                                let window = VulkanApiObjects::init_window(event_loop, "spawn");
+                                self.add_window(window);
+                            }
+                            Some(Action::Screenshot) => self.take_screenshot(window_id),
+                            Some(Action::ToggleFullscreen) => self.toggle_fullscreen(window_id),
+                            Some(Action::ToggleCursorGrab) => self.toggle_cursor_grab(window_id),
+                            None => (),
                         }
                     }
                 }
                 Event::MainEventsCleared => {
-                    for (.., window) in self.windows.iter() {
-                        window.request_redraw();
+                    if let Some(target_frame_time) = self.target_frame_time {
+                        let elapsed = frame_start.elapsed();
+                        wait_until(Instant::now() + frame_limit_sleep(target_frame_time, elapsed));
+                        frame_start = Instant::now();
+                    }
+                    for (.., potato_window) in self.windows.iter() {
+                        potato_window.window.request_redraw();
                     }
                 }
-                Event::RedrawRequested(_window_id) => {
+                Event::RedrawRequested(window_id) => {
                     let delta_time = delta_frame as f32 / 1_000_000.0 as f32;
-                    self.draw(delta_time);
+                    if let Err(e) = self.draw(window_id, delta_time) {
+                        error!("Failed to draw window {:?}: {}", window_id, e);
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
 
                     delta_frame = time.elapsed().subsec_micros();
                 }
-                Event::LoopDestroyed => {
-                    unsafe {
-                        self.device
-                            .device_wait_idle()
-                            .expect("Failed to wait device idle!")
-                    };
-                }
+                Event::LoopDestroyed => self.shutdown(),
                 _ => (),
             }
         })
     }
 }
 
+// The steps that open a frame, kept behind a trait so their order can be tested without a
+// swapchain.
+trait FrameStart {
+    fn is_paused(&self) -> bool;
+    fn take_recreate_request(&mut self) -> bool;
+    fn recreate_swapchain(&mut self);
+    fn acquire_next_image(&mut self) -> std::result::Result<(u32, bool), ash::vk::Result>;
+}
+
+struct SwapchainFrameStart<'a> {
+    vulkan_api_objects: &'a VulkanApiObjects,
+    render_context: &'a mut RenderContext,
+}
+
+impl FrameStart for SwapchainFrameStart<'_> {
+    fn is_paused(&self) -> bool {
+        self.render_context.is_paused
+    }
+
+    fn take_recreate_request(&mut self) -> bool {
+        self.render_context.take_recreate_request()
+    }
+
+    fn recreate_swapchain(&mut self) {
+        self.vulkan_api_objects
+            .recreate_swapchain(self.render_context);
+    }
+
+    fn acquire_next_image(&mut self) -> std::result::Result<(u32, bool), ash::vk::Result> {
+        let render_context = &self.render_context;
+        unsafe {
+            render_context
+                .swapchain
+                .swapchain_loader
+                .acquire_next_image(
+                    render_context.swapchain.swapchain,
+                    u64::MAX,
+                    render_context.image_available_semaphores[render_context.current_frame],
+                    Fence::null(),
+                )
+        }
+    }
+}
+
+// A pending resize is handled before acquiring, so an image is never drawn with command buffers
+// recorded for the old swapchain. Returns None while the window stays paused.
+fn start_frame(
+    frame: &mut impl FrameStart,
+) -> Option<std::result::Result<(u32, bool), ash::vk::Result>> {
+    let is_recreate_requested = frame.take_recreate_request();
+    if frame.is_paused() || is_recreate_requested {
+        debug!("Rebuilding swapchain after resize or pause");
+        frame.recreate_swapchain();
+        if frame.is_paused() {
+            return None;
+        }
+    }
+    Some(frame.acquire_next_image())
+}
+
+fn control_flow_for(open_windows: usize) -> ControlFlow {
+    if open_windows == 0 {
+        ControlFlow::Exit
+    } else {
+        ControlFlow::Wait
+    }
+}
+
+pub fn physical_extent(logical_size: LogicalSize<f64>, scale_factor: f64) -> Extent2D {
+    let size: PhysicalSize<u32> = logical_size.to_physical(scale_factor);
+    Extent2D {
+        width: size.width,
+        height: size.height,
+    }
+}
+
+fn window_extent(window: &Window) -> Extent2D {
+    let size = window.inner_size();
+    Extent2D {
+        width: size.width,
+        height: size.height,
+    }
+}
+
+// Every window gets its own context, except one the graphics queue can't present to, whose
+// surface is released instead.
+fn add_render_context<K: Eq + Hash + Debug>(
+    render_contexts: &mut HashMap<K, RenderContext>,
+    window_id: K,
+    surface: PotatoSurface,
+    is_present_supported: bool,
+    create_render_context: impl FnOnce(PotatoSurface) -> RenderContext,
+) {
+    if is_present_supported {
+        render_contexts.insert(window_id, create_render_context(surface));
+    } else {
+        error!(
+            "Window {:?} can't be presented to from the graphics queue",
+            window_id
+        );
+        unsafe {
+            surface
+                .surface_loader
+                .destroy_surface(surface.surface, None)
+        };
+    }
+}
+
+// A closed window's GPU resources are destroyed right away rather than at shutdown, and the
+// event loop exits once the last window is gone.
+fn remove_render_context<K: Eq + Hash, W>(
+    render_contexts: &mut HashMap<K, RenderContext>,
+    windows: &mut HashMap<K, W>,
+    window_id: &K,
+    device: &Device,
+    command_pool: CommandPool,
+) -> ControlFlow {
+    if let Some(render_context) = render_contexts.remove(window_id) {
+        unsafe {
+            device.device_wait_idle().expect("Failed to wait on device");
+        }
+        render_context.destroy(device, command_pool);
+    }
+    windows.remove(window_id);
+    control_flow_for(windows.len())
+}
+
 impl Drop for VulkanApiObjects {
     fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn save_screenshot(screenshot: &ScreenshotCapture, device: &Device) {
+    let pixels = screenshot.read_pixels(device);
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time is before the unix epoch")
+        .as_millis();
+    let path = format!("screenshot-{}.png", timestamp);
+    match write_png(
+        &path,
+        screenshot.extent.width,
+        screenshot.extent.height,
+        &pixels,
+    ) {
+        Ok(_) => info!("Saved screenshot to {}", path),
+        Err(e) => error!("Failed to save screenshot to {}: {}", path, e),
+    }
+}
+
+// Drop runs shutdown too, so only the first call releases anything; later calls that would
+// touch the destroyed device are refused.
+#[derive(Debug, Default)]
+struct ShutdownState {
+    is_shut_down: bool,
+}
+
+impl ShutdownState {
+    fn begin(&mut self) -> bool {
+        !std::mem::replace(&mut self.is_shut_down, true)
+    }
+
+    fn ensure_running(&self) -> std::result::Result<(), PotatoError> {
+        if self.is_shut_down {
+            Err(PotatoError::ShutDown)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// Every PotatoBuffer holds a clone of the device and frees itself through it on drop, so all of
+// them must be gone before the device is.
+fn destroy_device(device: &Rc<Device>) {
+    assert_eq!(
+        Rc::strong_count(device),
+        1,
+        "Buffers still hold the device at shutdown"
+    );
+    unsafe { device.destroy_device(None) };
+}
+
+// setup_debug_utils leaves the messenger null when validation is off.
+fn destroy_debug_messenger(
+    debug_utils_loader: &DebugUtils,
+    debug_messenger: DebugUtilsMessengerEXT,
+) {
+    if debug_messenger != DebugUtilsMessengerEXT::null() {
+        unsafe { debug_utils_loader.destroy_debug_utils_messenger(debug_messenger, None) };
+    }
+}
+
+// Destroys the device and instance level objects when init bails out before VulkanApiObjects
+// owns them.
+struct InitGuard {
+    instance: Instance,
+    surface: (Surface, SurfaceKHR),
+    debug_utils: (DebugUtils, DebugUtilsMessengerEXT),
+    device: Option<Rc<Device>>,
+}
+
+impl Drop for InitGuard {
+    fn drop(&mut self) {
+        let (surface_loader, surface) = &self.surface;
+        let (debug_utils_loader, debug_messenger) = &self.debug_utils;
         unsafe {
-            for i in 0..MAX_FRAMES_IN_FLIGHT {
-                self.device
-                    .destroy_semaphore(self.image_available_semaphores[i], None);
-                self.device
-                    .destroy_semaphore(self.render_finished_semaphores[i], None);
-                self.device.destroy_fence(self.in_flight_fences[i], None);
-            }
-            self.cleanup_swapchain();
-            self.device
-                .destroy_descriptor_set_layout(self.ubo_layout, None);
-            self.uniform_buffers.iter().enumerate().for_each(|(i, _)| {
-                self.device.destroy_buffer(self.uniform_buffers[i], None);
-                self.device
-                    .free_memory(self.uniform_buffers_memory[i], None);
-            });
-            self.device.destroy_buffer(self.index_buffer, None);
-            self.device.free_memory(self.index_buffer_memory, None);
-            self.device.destroy_buffer(self.vertex_buffer, None);
-            self.device.free_memory(self.vertex_buffer_memory, None);
-            self.device.destroy_command_pool(self.command_pool, None);
-            self.device.destroy_device(None);
-            self.surface
-                .surface_loader
-                .destroy_surface(self.surface.surface, None);
-            if VALIDATION.is_enable {
-                self.debug_utils_loader
-                    .destroy_debug_utils_messenger(self.debug_messenger, None);
+            if let Some(device) = &self.device {
+                device.destroy_device(None);
             }
+            surface_loader.destroy_surface(*surface, None);
+            destroy_debug_messenger(debug_utils_loader, *debug_messenger);
             self.instance.destroy_instance(None);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::fake_device;
+    use ash::vk::Handle;
+
+    #[test]
+    fn physical_extent_rounds_scaled_size() {
+        assert_eq!(
+            physical_extent(LogicalSize::new(101.0, 67.0), 1.5),
+            Extent2D {
+                width: 152,
+                height: 101,
+            }
+        );
+        assert_eq!(
+            physical_extent(LogicalSize::new(800.0, 600.0), 1.0),
+            Extent2D {
+                width: 800,
+                height: 600,
+            }
+        );
+    }
+
+    // Records the order of the frame start steps; a recreate pauses or unpauses the window.
+    #[derive(Default)]
+    struct RecordingFrameStart {
+        is_recreate_requested: bool,
+        is_paused: bool,
+        is_paused_after_recreate: bool,
+        calls: Vec<&'static str>,
+    }
+
+    impl FrameStart for RecordingFrameStart {
+        fn is_paused(&self) -> bool {
+            self.is_paused
+        }
+
+        fn take_recreate_request(&mut self) -> bool {
+            std::mem::take(&mut self.is_recreate_requested)
+        }
+
+        fn recreate_swapchain(&mut self) {
+            self.is_paused = self.is_paused_after_recreate;
+            self.calls.push("recreate");
+        }
+
+        fn acquire_next_image(&mut self) -> std::result::Result<(u32, bool), ash::vk::Result> {
+            self.calls.push("acquire");
+            Ok((0, false))
+        }
+    }
+
+    #[test]
+    fn pending_resize_recreates_the_swapchain_before_acquiring() {
+        let mut frame = RecordingFrameStart {
+            is_recreate_requested: true,
+            ..Default::default()
+        };
+        assert_eq!(start_frame(&mut frame), Some(Ok((0, false))));
+        assert_eq!(frame.calls, vec!["recreate", "acquire"]);
+
+        frame.calls.clear();
+        start_frame(&mut frame);
+        assert_eq!(frame.calls, vec!["acquire"]);
+    }
+
+    #[test]
+    fn paused_window_skips_acquiring_until_it_is_restored() {
+        let mut frame = RecordingFrameStart {
+            is_paused: true,
+            is_paused_after_recreate: true,
+            ..Default::default()
+        };
+        assert_eq!(start_frame(&mut frame), None);
+        assert_eq!(frame.calls, vec!["recreate"]);
+
+        frame.is_paused_after_recreate = false;
+        frame.calls.clear();
+        assert!(start_frame(&mut frame).is_some());
+        assert_eq!(frame.calls, vec!["recreate", "acquire"]);
+    }
+
+    #[test]
+    fn shutdown_releases_only_once() {
+        let mut shutdown_state = ShutdownState::default();
+        assert!(shutdown_state.ensure_running().is_ok());
+        assert!(shutdown_state.begin());
+        // The Drop that follows an explicit shutdown finds nothing left to release.
+        assert!(!shutdown_state.begin());
+        assert!(matches!(shutdown_state.ensure_running(), Err(PotatoError::ShutDown)));
+    }
+
+    #[test]
+    fn device_is_destroyed_after_its_buffers() {
+        let device = Rc::new(fake_device::device());
+        let buffer = PotatoBuffer::from_raw_parts(
+            ash::vk::Buffer::from_raw(2),
+            ash::vk::DeviceMemory::from_raw(3),
+            64,
+            &device,
+        );
+        drop(buffer);
+        destroy_device(&device);
+        assert_eq!(
+            fake_device::take_calls(),
+            vec![("vkDestroyBuffer", 2), ("vkFreeMemory", 3), ("vkDestroyDevice", 1)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Buffers still hold the device at shutdown")]
+    fn device_outlived_by_a_buffer_is_not_destroyed() {
+        let device = Rc::new(fake_device::device());
+        let _buffer = PotatoBuffer::from_raw_parts(
+            ash::vk::Buffer::from_raw(2),
+            ash::vk::DeviceMemory::from_raw(3),
+            64,
+            &device,
+        );
+        destroy_device(&device);
+    }
+
+    #[test]
+    fn debug_messenger_is_destroyed_only_when_created() {
+        let debug_utils_loader = DebugUtils::new(&fake_device::entry(), &fake_device::instance());
+        destroy_debug_messenger(&debug_utils_loader, DebugUtilsMessengerEXT::null());
+        assert!(fake_device::take_calls().is_empty());
+
+        destroy_debug_messenger(&debug_utils_loader, DebugUtilsMessengerEXT::from_raw(5));
+        assert_eq!(
+            fake_device::take_calls(),
+            vec![("vkDestroyDebugUtilsMessengerEXT", 5)]
+        );
+    }
+
+    fn surface(handle: u64) -> PotatoSurface {
+        PotatoSurface {
+            surface_loader: Surface::new(&fake_device::entry(), &fake_device::instance()),
+            surface: SurfaceKHR::from_raw(handle),
+        }
+    }
+
+    #[test]
+    fn each_window_gets_its_own_render_context() {
+        let device = Rc::new(fake_device::device());
+        let mut render_contexts = HashMap::new();
+        for window_id in [1, 2] {
+            add_render_context(
+                &mut render_contexts,
+                window_id,
+                surface(window_id),
+                true,
+                |x| RenderContext::from_raw_parts(&device, x),
+            );
+        }
+        add_render_context(&mut render_contexts, 3, surface(3), false, |x| {
+            RenderContext::from_raw_parts(&device, x)
+        });
+
+        assert_eq!(render_contexts.len(), 2);
+        assert_eq!(render_contexts[&1].surface.surface, SurfaceKHR::from_raw(1));
+        assert_eq!(render_contexts[&2].surface.surface, SurfaceKHR::from_raw(2));
+        assert_eq!(fake_device::take_calls(), vec![("vkDestroySurfaceKHR", 3)]);
+    }
+
+    #[test]
+    fn closing_the_last_window_destroys_its_context_and_exits() {
+        let device = Rc::new(fake_device::device());
+        let mut render_contexts = HashMap::new();
+        let mut windows = HashMap::new();
+        for window_id in [1, 2] {
+            render_contexts.insert(
+                window_id,
+                RenderContext::from_raw_parts(&device, surface(window_id)),
+            );
+            windows.insert(window_id, ());
+        }
+        let command_pool = CommandPool::from_raw(1);
+        let mut close = |window_id| {
+            remove_render_context(
+                &mut render_contexts,
+                &mut windows,
+                &window_id,
+                &device,
+                command_pool,
+            )
+        };
+
+        assert_eq!(close(2), ControlFlow::Wait);
+        let calls = fake_device::take_calls();
+        assert_eq!(calls.first(), Some(&("vkDeviceWaitIdle", 1)));
+        assert!(calls.contains(&("vkDestroyFence", 600)));
+        assert!(calls.contains(&("vkDestroySwapchainKHR", 20)));
+        assert_eq!(calls.last(), Some(&("vkDestroySurfaceKHR", 2)));
+
+        assert_eq!(close(1), ControlFlow::Exit);
+        assert_eq!(
+            fake_device::take_calls().last(),
+            Some(&("vkDestroySurfaceKHR", 1))
+        );
+        assert_eq!(close(1), ControlFlow::Exit);
+        assert!(fake_device::take_calls().is_empty());
+    }
+}
@@ -1,20 +1,53 @@
+use super::error::PotatoError;
 use ash::extensions::ext::DebugUtils;
 use ash::vk;
 use ash::vk::{
     Bool32, DebugUtilsMessageSeverityFlagsEXT, DebugUtilsMessageTypeFlagsEXT,
-    DebugUtilsMessengerCallbackDataEXT, DebugUtilsMessengerEXT,
+    CommandBuffer, DebugUtilsLabelEXT, DebugUtilsMessengerCallbackDataEXT, DebugUtilsMessengerEXT,
+    DebugUtilsObjectNameInfoEXT, Handle, StructureType,
 };
-use ash::{Entry, Instance};
+use ash::{Device, Entry, Instance};
 use log::debug;
-use std::ffi::CStr;
+use std::collections::VecDeque;
+use std::ffi::{CStr, CString};
 use std::os::raw::c_void;
-use super::constants::VALIDATION;
+use std::sync::{Arc, Mutex};
+
+pub struct ValidationLog {
+    capacity: usize,
+    messages: Mutex<VecDeque<String>>,
+}
+
+impl ValidationLog {
+    pub fn new(capacity: usize) -> ValidationLog {
+        ValidationLog {
+            capacity,
+            messages: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn push(&self, message: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut messages = self.messages.lock().unwrap_or_else(|e| e.into_inner());
+        while messages.len() >= self.capacity {
+            messages.pop_front();
+        }
+        messages.push_back(message);
+    }
+
+    pub fn recent(&self) -> Vec<String> {
+        let messages = self.messages.lock().unwrap_or_else(|e| e.into_inner());
+        messages.iter().cloned().collect()
+    }
+}
 
 unsafe extern "system" fn vulkan_debug_utils_callback(
     message_severity: DebugUtilsMessageSeverityFlagsEXT,
     message_type: DebugUtilsMessageTypeFlagsEXT,
     p_callback_data: *const DebugUtilsMessengerCallbackDataEXT,
-    _p_user_data: *mut c_void,
+    p_user_data: *mut c_void,
 ) -> Bool32 {
     let severity = match message_severity {
         DebugUtilsMessageSeverityFlagsEXT::VERBOSE => "[Verbose]",
@@ -32,19 +65,33 @@ unsafe extern "system" fn vulkan_debug_utils_callback(
     let message = CStr::from_ptr((*p_callback_data).p_message);
     debug!("{}, {}, {:?}", severity, types, message);
 
+    if !p_user_data.is_null() {
+        let validation_log = &*(p_user_data as *const ValidationLog);
+        validation_log.push(format!(
+            "{}, {}, {}",
+            severity,
+            types,
+            message.to_string_lossy()
+        ));
+    }
+
     ash::vk::FALSE
 }
 
 pub fn setup_debug_utils(
     entry: &Entry,
     instance: &Instance,
+    validation_log: &Arc<ValidationLog>,
+    is_validation_enabled: bool,
 ) -> (DebugUtils, DebugUtilsMessengerEXT) {
     let debug_util_loader = DebugUtils::new(entry, instance);
 
-    if !VALIDATION.is_enable {
+    if !is_validation_enabled {
         (debug_util_loader, DebugUtilsMessengerEXT::null())
     } else {
-        let messenger_ci = populate_debug_messenger_create_info();
+        let messenger_ci = populate_debug_messenger_create_info(
+            Arc::as_ptr(validation_log) as *mut ValidationLog as *mut c_void,
+        );
 
         let utils_messenger = unsafe {
             debug_util_loader
@@ -55,7 +102,61 @@ pub fn setup_debug_utils(
     }
 }
 
-pub fn populate_debug_messenger_create_info() -> vk::DebugUtilsMessengerCreateInfoEXT {
+pub fn set_debug_name<T: Handle>(
+    debug_utils: Option<&DebugUtils>,
+    device: &Device,
+    object: T,
+    name: &str,
+) -> Result<(), PotatoError> {
+    let debug_utils = match debug_utils {
+        Some(debug_utils) => debug_utils,
+        None => return Ok(()),
+    };
+
+    let object_name = CString::new(name).expect("Debug name must not contain a nul byte");
+    let name_info = DebugUtilsObjectNameInfoEXT {
+        s_type: StructureType::DEBUG_UTILS_OBJECT_NAME_INFO_EXT,
+        p_next: std::ptr::null(),
+        object_type: T::TYPE,
+        object_handle: object.as_raw(),
+        p_object_name: object_name.as_ptr(),
+    };
+
+    unsafe { debug_utils.debug_utils_set_object_name(device.handle(), &name_info)? };
+    Ok(())
+}
+
+pub fn create_debug_label(name: &CStr, color: [f32; 4]) -> DebugUtilsLabelEXT {
+    DebugUtilsLabelEXT {
+        s_type: StructureType::DEBUG_UTILS_LABEL_EXT,
+        p_next: std::ptr::null(),
+        p_label_name: name.as_ptr(),
+        color,
+    }
+}
+
+pub fn begin_label(
+    debug_utils: Option<&DebugUtils>,
+    command_buffer: CommandBuffer,
+    name: &str,
+    color: [f32; 4],
+) {
+    if let Some(debug_utils) = debug_utils {
+        let label_name = CString::new(name).expect("Label name must not contain a nul byte");
+        let label = create_debug_label(&label_name, color);
+        unsafe { debug_utils.cmd_begin_debug_utils_label(command_buffer, &label) };
+    }
+}
+
+pub fn end_label(debug_utils: Option<&DebugUtils>, command_buffer: CommandBuffer) {
+    if let Some(debug_utils) = debug_utils {
+        unsafe { debug_utils.cmd_end_debug_utils_label(command_buffer) };
+    }
+}
+
+pub fn populate_debug_messenger_create_info(
+    p_user_data: *mut c_void,
+) -> vk::DebugUtilsMessengerCreateInfoEXT {
     vk::DebugUtilsMessengerCreateInfoEXT {
         s_type: vk::StructureType::DEBUG_UTILS_MESSENGER_CREATE_INFO_EXT,
         p_next: std::ptr::null(),
@@ -69,6 +170,52 @@ pub fn populate_debug_messenger_create_info() -> vk::DebugUtilsMessengerCreateIn
             | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
             | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
         pfn_user_callback: Some(vulkan_debug_utils_callback),
-        p_user_data: std::ptr::null_mut(),
+        p_user_data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::fake_device;
+    use ash::vk::Buffer;
+
+    #[test]
+    fn validation_log_evicts_oldest_past_capacity() {
+        let log = ValidationLog::new(2);
+        log.push("first".to_string());
+        log.push("second".to_string());
+        log.push("third".to_string());
+        assert_eq!(log.recent(), vec!["second".to_string(), "third".to_string()]);
+    }
+
+    #[test]
+    fn validation_log_with_no_capacity_keeps_nothing() {
+        let log = ValidationLog::new(0);
+        log.push("dropped".to_string());
+        assert!(log.recent().is_empty());
+    }
+
+    #[test]
+    fn set_debug_name_is_a_no_op_without_debug_utils() {
+        let device = fake_device::device();
+        assert!(set_debug_name(None, &device, Buffer::from_raw(1), "Vertex Buffer").is_ok());
+        assert!(fake_device::take_calls().is_empty());
+    }
+
+    #[test]
+    fn debug_label_carries_its_name_and_color() {
+        let name = CString::new("Geometry Pass").unwrap();
+        let label = create_debug_label(&name, [0.2, 0.4, 0.6, 1.0]);
+        assert_eq!(label.s_type, StructureType::DEBUG_UTILS_LABEL_EXT);
+        assert_eq!(unsafe { CStr::from_ptr(label.p_label_name) }, name.as_c_str());
+        assert_eq!(label.color, [0.2, 0.4, 0.6, 1.0]);
+    }
+
+    #[test]
+    fn labels_are_skipped_without_debug_utils() {
+        begin_label(None, CommandBuffer::from_raw(1), "Geometry Pass", [1.0; 4]);
+        end_label(None, CommandBuffer::from_raw(1));
+        assert!(fake_device::take_calls().is_empty());
     }
 }
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use winit::event::VirtualKeyCode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    SpawnWindow,
+    Screenshot,
+    ToggleFullscreen,
+    ToggleCursorGrab,
+}
+
+#[derive(Debug, Clone)]
+pub struct InputMap {
+    bindings: HashMap<VirtualKeyCode, Action>,
+}
+
+impl InputMap {
+    pub fn new() -> InputMap {
+        InputMap {
+            bindings: HashMap::new(),
+        }
+    }
+
+    pub fn bind(mut self, key: VirtualKeyCode, action: Action) -> Self {
+        self.bindings.insert(key, action);
+        self
+    }
+
+    pub fn unbind(mut self, key: VirtualKeyCode) -> Self {
+        self.bindings.remove(&key);
+        self
+    }
+
+    pub fn action(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.bindings.get(&key).copied()
+    }
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        InputMap::new()
+            .bind(VirtualKeyCode::N, Action::SpawnWindow)
+            .bind(VirtualKeyCode::F11, Action::ToggleFullscreen)
+            .bind(VirtualKeyCode::F12, Action::Screenshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn action_returns_bound_action() {
+        let input_map = InputMap::new().bind(VirtualKeyCode::G, Action::ToggleCursorGrab);
+        assert_eq!(input_map.action(VirtualKeyCode::G), Some(Action::ToggleCursorGrab));
+    }
+
+    #[test]
+    fn action_is_none_for_unbound_key() {
+        let input_map = InputMap::default().unbind(VirtualKeyCode::N);
+        assert_eq!(input_map.action(VirtualKeyCode::N), None);
+        assert_eq!(input_map.action(VirtualKeyCode::Q), None);
+        assert_eq!(input_map.action(VirtualKeyCode::F11), Some(Action::ToggleFullscreen));
+    }
+}
@@ -0,0 +1,121 @@
+use log::warn;
+use winit::window::Window;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayMode {
+    Windowed,
+    Fullscreen,
+}
+
+impl DisplayMode {
+    pub fn toggled(self) -> DisplayMode {
+        match self {
+            DisplayMode::Windowed => DisplayMode::Fullscreen,
+            DisplayMode::Fullscreen => DisplayMode::Windowed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorState {
+    pub is_visible: bool,
+    pub is_grabbed: bool,
+}
+
+impl CursorState {
+    pub fn toggled_grab(self) -> CursorState {
+        CursorState {
+            is_visible: self.is_grabbed,
+            is_grabbed: !self.is_grabbed,
+        }
+    }
+
+    // A grab the platform refused leaves the cursor as it was.
+    pub fn with_grab(self, is_grabbed: bool, is_applied: bool) -> CursorState {
+        CursorState {
+            is_grabbed: if is_applied { is_grabbed } else { self.is_grabbed },
+            ..self
+        }
+    }
+}
+
+impl Default for CursorState {
+    fn default() -> Self {
+        CursorState {
+            is_visible: true,
+            is_grabbed: false,
+        }
+    }
+}
+
+pub struct PotatoWindow {
+    pub window: Window,
+    pub display_mode: DisplayMode,
+    pub cursor: CursorState,
+    pub scale_factor: f64,
+}
+
+impl PotatoWindow {
+    pub fn new(window: Window, cursor: CursorState) -> PotatoWindow {
+        let scale_factor = window.scale_factor();
+        let mut potato_window = PotatoWindow {
+            window,
+            display_mode: DisplayMode::Windowed,
+            cursor: CursorState::default(),
+            scale_factor,
+        };
+        potato_window.set_cursor(cursor);
+        potato_window
+    }
+
+    pub fn set_cursor(&mut self, cursor: CursorState) {
+        self.set_cursor_grabbed(cursor.is_grabbed);
+        self.set_cursor_visible(cursor.is_visible);
+    }
+
+    pub fn set_cursor_visible(&mut self, is_visible: bool) {
+        self.window.set_cursor_visible(is_visible);
+        self.cursor.is_visible = is_visible;
+    }
+
+    pub fn set_cursor_grabbed(&mut self, is_grabbed: bool) {
+        let result = self.window.set_cursor_grab(is_grabbed);
+        if let Err(e) = &result {
+            warn!("Failed to set cursor grab to {}: {}", is_grabbed, e);
+        }
+        self.cursor = self.cursor.with_grab(is_grabbed, result.is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_mode_toggle_round_trips() {
+        assert_eq!(DisplayMode::Windowed.toggled(), DisplayMode::Fullscreen);
+        assert_eq!(DisplayMode::Fullscreen.toggled(), DisplayMode::Windowed);
+        assert_eq!(DisplayMode::Windowed.toggled().toggled(), DisplayMode::Windowed);
+    }
+
+    #[test]
+    fn toggling_grab_hides_the_cursor_and_toggling_back_restores_it() {
+        let grabbed = CursorState::default().toggled_grab();
+        assert_eq!(
+            grabbed,
+            CursorState {
+                is_visible: false,
+                is_grabbed: true,
+            }
+        );
+        assert_eq!(grabbed.toggled_grab(), CursorState::default());
+    }
+
+    #[test]
+    fn refused_grab_keeps_the_previous_state() {
+        let cursor = CursorState::default();
+        assert!(!cursor.with_grab(true, false).is_grabbed);
+        assert!(cursor.with_grab(true, true).is_grabbed);
+        assert_eq!(cursor.with_grab(true, false).is_visible, cursor.is_visible);
+    }
+}